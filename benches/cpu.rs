@@ -0,0 +1,49 @@
+extern crate criterion;
+extern crate gameboy;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use gameboy::*;
+
+const STEPS: u64 = 10_000;
+
+/* Tight loop mixing ALU, loads, (HL) access and jumps */
+fn gen_runtime() -> Runtime<mbc::RomOnly> {
+    let code = vec![
+        0x21, 0x00, 0xC0, // LD HL, 0xC000
+        0x3E, 0x00, // LD A, 0x00
+        0x3C, // INC A
+        0x77, // LD (HL), A
+        0x86, // ADD A, (HL)
+        0xCB, 0x37, // SWAP A
+        0x05, // DEC B
+        0x20, 0xF8, // JR NZ, -8
+        0xC3, 0x03, 0x00, // JP 0x0003
+    ];
+    let mut rom = vec![0; 1 << 15];
+    for (i, byte) in code.into_iter().enumerate() {
+        rom[i] = byte;
+    }
+    let mut runtime = Runtime::new(mbc::RomOnly::new(rom));
+    runtime.state.mmu.disable_bootrom();
+    runtime
+}
+
+fn cpu_loop(c: &mut Criterion) {
+    let mut runtime = gen_runtime();
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(STEPS));
+    group.bench_function("synthetic loop", |b| {
+        b.iter(|| {
+            for _ in 0..STEPS {
+                runtime.step();
+            }
+            runtime.reset_cycles();
+            runtime.state.apu.left_samples().clear();
+            runtime.state.apu.right_samples().clear();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cpu_loop);
+criterion_main!(benches);
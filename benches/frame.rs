@@ -0,0 +1,32 @@
+extern crate criterion;
+extern crate gameboy;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gameboy::*;
+
+const ROM: &[u8] = include_bytes!("../rom/cpu-inst-test/06-ld r,r.gb");
+
+fn gen_runtime() -> Runtime<mbc::MBC1> {
+    let mut runtime = Runtime::new(mbc::MBC1::new(ROM.to_vec()));
+    runtime.state.mmu.disable_bootrom();
+    runtime.cpu.PC.set(0x100);
+    runtime
+}
+
+fn full_frame(c: &mut Criterion) {
+    let mut runtime = gen_runtime();
+    c.bench_function("full frame", |b| {
+        b.iter(|| {
+            while runtime.cpu_cycles() < CPU_CYCLES_PER_FRAME {
+                runtime.step();
+            }
+            runtime.reset_cycles();
+            // Nobody plays the samples, don't let buffers grow.
+            runtime.state.apu.left_samples().clear();
+            runtime.state.apu.right_samples().clear();
+        })
+    });
+}
+
+criterion_group!(benches, full_frame);
+criterion_main!(benches);
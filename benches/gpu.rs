@@ -0,0 +1,46 @@
+extern crate criterion;
+extern crate gameboy;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gameboy::*;
+
+fn gen() -> (MMU<mbc::MBC1>, GPU) {
+    let mut mmu = MMU::new(mbc::MBC1::new(vec![0; 1 << 21]));
+    let gpu = GPU::new(&mut mmu);
+
+    // Non-empty tiles, background, window and sprites all enabled.
+    for (i, byte) in mmu.vram.iter_mut().enumerate() {
+        *byte = (i * 7) as u8;
+    }
+    for (i, byte) in mmu.oam.iter_mut().enumerate() {
+        *byte = (i * 13) as u8;
+    }
+    mmu.write(ioregs::LCDC, 0b1111_0011);
+    mmu.write(ioregs::WY, 72);
+    mmu.write(ioregs::WX, 87);
+    (mmu, gpu)
+}
+
+/* Renders single scanline. VBLANK lines are skipped over, they are cheap anyway. */
+fn render_scanline(mmu: &mut MMU<mbc::MBC1>, gpu: &mut GPU) {
+    while GPU::MODE(mmu) == GPUMode::VBLANK {
+        gpu.step(mmu);
+    }
+    // OAM search
+    gpu.step(mmu);
+    while GPU::MODE(mmu) == GPUMode::LCD_TRANSFER {
+        gpu.step(mmu);
+    }
+    // HBLANK
+    gpu.step(mmu);
+}
+
+fn scanline(c: &mut Criterion) {
+    let (mut mmu, mut gpu) = gen();
+    c.bench_function("gpu scanline", |b| {
+        b.iter(|| render_scanline(&mut mmu, &mut gpu))
+    });
+}
+
+criterion_group!(benches, scanline);
+criterion_main!(benches);
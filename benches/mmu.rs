@@ -0,0 +1,57 @@
+extern crate criterion;
+extern crate gameboy;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use gameboy::*;
+
+/* One address from every region of the memory map */
+const ADDRESSES: [Addr; 9] = [
+    0x0150, 0x4000, 0x8800, 0xA000, 0xC000, 0xE100, 0xFE10, 0xFF40, 0xFF90,
+];
+
+fn gen_mmu() -> MMU<mbc::MBC1> {
+    let mut mmu = MMU::new(mbc::MBC1::new(vec![0; 1 << 21]));
+    mmu.disable_bootrom();
+    mmu
+}
+
+fn mmu_read(c: &mut Criterion) {
+    let mut mmu = gen_mmu();
+    c.bench_function("mmu read all regions", |b| {
+        b.iter(|| {
+            let mut acc = 0u8;
+            for addr in ADDRESSES.iter() {
+                acc ^= mmu.read(black_box(*addr));
+            }
+            acc
+        })
+    });
+}
+
+fn mmu_read_bootrom(c: &mut Criterion) {
+    let mut mmu = MMU::new(mbc::MBC1::new(vec![0; 1 << 21]));
+    c.bench_function("mmu read bootrom", |b| {
+        b.iter(|| {
+            let mut acc = 0u8;
+            for addr in 0..BOOSTRAP_SIZE as Addr {
+                acc ^= mmu.read(black_box(addr));
+            }
+            acc
+        })
+    });
+}
+
+fn mmu_write(c: &mut Criterion) {
+    let mut mmu = gen_mmu();
+    c.bench_function("mmu write RAM regions", |b| {
+        b.iter(|| {
+            // Skip ROM, writes there are mapper commands.
+            for addr in ADDRESSES.iter().skip(2) {
+                mmu.write(black_box(*addr), black_box(0x42));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, mmu_read, mmu_read_bootrom, mmu_write);
+criterion_main!(benches);
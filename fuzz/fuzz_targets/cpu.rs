@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+/* Random instruction stream at 0x100, must run without panics and keep CPU invariants */
+fuzz_target!(|data: &[u8]| {
+    gameboy_fuzz::check_invariants(data);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+/* Registers, then instructions from reference interpreter's subset, executed by both and compared */
+fuzz_target!(|data: &[u8]| {
+    gameboy_fuzz::check_differential(data);
+});
@@ -0,0 +1,104 @@
+extern crate gameboy;
+
+pub mod reference;
+pub use reference::Reference;
+
+use gameboy::*;
+
+/* Instructions run for one input, enough for loops to matter while keeping runs fast */
+const STEPS: usize = 1000;
+/* Longest instruction(CALL) takes 6 cycles, interrupt dispatch 5 more, waking from HALT 1 */
+const MAX_STEP_CYCLES: u64 = 12;
+/* First bytes of differential input: A, F, B, C, D, E, H, L, SP */
+const REGISTERS_SIZE: usize = 10;
+
+/*
+ * Flat test bus with code at 0x100, registers and IO as DMG boot ROM leaves them. Cart is all RAM, so code can
+ * modify itself. Doctor is on, so illegal opcodes lock CPU up like on hardware instead of panicking.
+ */
+pub fn gen(code: &[u8]) -> Runtime<mbc::RamOnly> {
+    let mut runtime = Runtime::with_code(0x100, &code[..code.len().min(0x8000 - 0x100)]);
+    runtime.skip_bootrom(HardwareModel::DMG);
+    runtime.state.doctor = Some(Doctor::new());
+    runtime
+}
+
+/* Instructions which always go on with the next one, unless interrupt is taken */
+fn straight(text: &str) -> bool {
+    let jumps = ["JP", "JR", "CALL", "RET", "RST", "HALT", "STOP"];
+    !jumps.iter().any(|jump| text.starts_with(jump))
+}
+
+/*
+ * Runs data as code, checking after each step that:
+ * - it took at least one and at most MAX_STEP_CYCLES cycles,
+ * - only upper nibble of F is used,
+ * - PC moved right past the instruction, for ones which don't jump, when interrupts couldn't be taken and CPU
+ *   wasn't halted or stopped.
+ * Panics, including ones from the emulator itself, are what fuzzer reports.
+ */
+pub fn check_invariants(data: &[u8]) {
+    let mut runtime = gen(data);
+    // HALT bug: right after HALT exited without interrupt being taken, next byte is read twice
+    let mut after_halt = false;
+    for _ in 0..STEPS {
+        let pc = runtime.cpu.PC.val();
+        let bytes = (0..3).map(|i| runtime.state.mmu.read(pc.wrapping_add(i))).collect::<Vec<u8>>();
+        let (halted, ime, cycles) = (runtime.cpu.HALT || runtime.cpu.STOP, runtime.cpu.IME, runtime.cpu_cycles());
+        runtime.step();
+
+        let taken = runtime.cpu_cycles() - cycles;
+        assert!((1..=MAX_STEP_CYCLES).contains(&taken), "step at 0x{:04X} took {} cycles", pc, taken);
+        assert_eq!(runtime.cpu.F() & 0x0F, 0, "lower nibble of F set at 0x{:04X}", pc);
+        if runtime.state.doctor.as_ref().is_some_and(|doctor| doctor.locked()) {
+            return;
+        }
+        let text = disassemble(pc, &bytes);
+        let skip = halted || ime || after_halt;
+        after_halt = halted || text.as_ref().is_some_and(|(text, _)| text == "HALT");
+        if skip {
+            continue;
+        }
+        if let Some((text, size)) = text {
+            if straight(&text) {
+                let next = pc.wrapping_add(size as u16);
+                assert_eq!(runtime.cpu.PC.val(), next, "PC after {} at 0x{:04X}", text, pc);
+            }
+        }
+    }
+}
+
+/*
+ * First REGISTERS_SIZE bytes set up registers, rest is filtered down to instructions Reference knows. Both run
+ * them one by one, registers and cycles have to agree after each.
+ */
+pub fn check_differential(data: &[u8]) {
+    if data.len() < REGISTERS_SIZE {
+        return;
+    }
+    let mut reference = Reference::from_bytes(&data[..REGISTERS_SIZE]);
+    let mut program = Vec::new();
+    let mut rest = &data[REGISTERS_SIZE..];
+    while !rest.is_empty() && program.len() < STEPS {
+        match Reference::size(rest) {
+            Some(size) => {
+                program.extend_from_slice(&rest[..size]);
+                rest = &rest[size..];
+            }
+            None => rest = &rest[1..],
+        }
+    }
+
+    let mut runtime = gen(&program);
+    reference.load(&mut runtime.cpu);
+    let mut offset = 0;
+    while offset < program.len() {
+        let text = disassemble(reference.pc, &program[offset..]).map(|(text, _)| text).unwrap_or_default();
+        let cycles = runtime.cpu_cycles();
+        let expected = reference.step(&program[offset..]).expect("instruction outside reference subset");
+        runtime.step();
+        assert_eq!(Reference::of(&runtime.cpu), reference, "registers after {}", text);
+        assert_eq!(runtime.cpu_cycles() - cycles, expected, "cycles of {}", text);
+        offset = (reference.pc - 0x100) as usize;
+    }
+}
@@ -0,0 +1,316 @@
+use gameboy::CPU;
+
+const Z: u8 = 1 << 7;
+const N: u8 = 1 << 6;
+const H: u8 = 1 << 5;
+const C: u8 = 1 << 4;
+
+/* Register index used in opcodes which means (HL), memory isn't part of the subset */
+const HL_INDIRECT: u8 = 6;
+
+/*
+ * Plain interpreter of documented instructions working on registers only: loads between registers and of
+ * immediates, 8-bit ALU, INC/DEC, 16-bit INC/DEC/ADD HL, rotates of A, DAA, CPL, SCF, CCF and all CB ones on
+ * registers. Written straight from the opcode tables, without sharing anything with the emulator's CPU.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reference {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl Reference {
+    /* Registers from A, F, B, C, D, E, H, L, SP(little endian) bytes, PC at 0x100 */
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            a: bytes[0],
+            f: bytes[1] & 0xF0,
+            b: bytes[2],
+            c: bytes[3],
+            d: bytes[4],
+            e: bytes[5],
+            h: bytes[6],
+            l: bytes[7],
+            sp: u16::from_le_bytes([bytes[8], bytes[9]]),
+            pc: 0x100,
+        }
+    }
+
+    pub fn of(cpu: &CPU) -> Self {
+        Self {
+            a: cpu.A,
+            f: cpu.F(),
+            b: cpu.BC.up(),
+            c: cpu.BC.low(),
+            d: cpu.DE.up(),
+            e: cpu.DE.low(),
+            h: cpu.HL.up(),
+            l: cpu.HL.low(),
+            sp: cpu.SP,
+            pc: cpu.PC.val(),
+        }
+    }
+
+    /* Puts registers into emulator's CPU */
+    pub fn load(&self, cpu: &mut CPU) {
+        cpu.A = self.a;
+        cpu.set_F(self.f);
+        cpu.BC.set(u16::from_be_bytes([self.b, self.c]));
+        cpu.DE.set(u16::from_be_bytes([self.d, self.e]));
+        cpu.HL.set(u16::from_be_bytes([self.h, self.l]));
+        cpu.SP = self.sp;
+        cpu.PC.set(self.pc);
+    }
+
+    /* Size of instruction bytes start with, None when it's outside the subset or operands are missing */
+    pub fn size(bytes: &[u8]) -> Option<usize> {
+        let mut scratch = Reference::from_bytes(&[0; 10]);
+        scratch.step(bytes)?;
+        Some((scratch.pc - 0x100) as usize)
+    }
+
+    /* Executes instruction bytes start with, returns its cycles. None, with nothing changed, outside the subset. */
+    pub fn step(&mut self, bytes: &[u8]) -> Option<u64> {
+        let op = *bytes.first()?;
+        let imm8 = || bytes.get(1).copied();
+        let imm16 = || Some(u16::from_le_bytes([*bytes.get(1)?, *bytes.get(2)?]));
+        let dst = (op >> 3) & 0x7;
+        let src = op & 0x7;
+        let (size, cycles) = match op {
+            0x00 => (1, 1),
+            0x01 | 0x11 | 0x21 | 0x31 => {
+                let value = imm16()?;
+                self.set_pair(op >> 4, value);
+                (3, 3)
+            }
+            0x03 | 0x13 | 0x23 | 0x33 => {
+                self.set_pair(op >> 4, self.pair(op >> 4).wrapping_add(1));
+                (1, 2)
+            }
+            0x0B | 0x1B | 0x2B | 0x3B => {
+                self.set_pair(op >> 4, self.pair(op >> 4).wrapping_sub(1));
+                (1, 2)
+            }
+            0x09 | 0x19 | 0x29 | 0x39 => {
+                let (hl, value) = (self.pair(2), self.pair(op >> 4));
+                let (sum, carry) = hl.overflowing_add(value);
+                let half = (hl & 0xFFF) + (value & 0xFFF) > 0xFFF;
+                self.flags(self.f & Z != 0, false, half, carry);
+                self.set_pair(2, sum);
+                (1, 2)
+            }
+            _ if op < 0x40 && src == 4 && dst != HL_INDIRECT => {
+                let value = self.reg(dst).wrapping_add(1);
+                self.flags(value == 0, false, value & 0xF == 0, self.f & C != 0);
+                self.set_reg(dst, value);
+                (1, 1)
+            }
+            _ if op < 0x40 && src == 5 && dst != HL_INDIRECT => {
+                let value = self.reg(dst).wrapping_sub(1);
+                self.flags(value == 0, true, value & 0xF == 0xF, self.f & C != 0);
+                self.set_reg(dst, value);
+                (1, 1)
+            }
+            _ if op < 0x40 && src == 6 && dst != HL_INDIRECT => {
+                let value = imm8()?;
+                self.set_reg(dst, value);
+                (2, 2)
+            }
+            0x07 | 0x0F | 0x17 | 0x1F => {
+                // Same as CB ones on A, except Z is always cleared
+                self.a = self.rotate(dst, self.a);
+                self.f &= !Z;
+                (1, 1)
+            }
+            0x27 => {
+                self.daa();
+                (1, 1)
+            }
+            0x2F => {
+                self.a = !self.a;
+                self.f |= N | H;
+                (1, 1)
+            }
+            0x37 => {
+                self.flags(self.f & Z != 0, false, false, true);
+                (1, 1)
+            }
+            0x3F => {
+                self.flags(self.f & Z != 0, false, false, self.f & C == 0);
+                (1, 1)
+            }
+            0x40..=0x7F if dst != HL_INDIRECT && src != HL_INDIRECT => {
+                self.set_reg(dst, self.reg(src));
+                (1, 1)
+            }
+            0x80..=0xBF if src != HL_INDIRECT => {
+                self.alu(dst, self.reg(src));
+                (1, 1)
+            }
+            _ if op >= 0xC0 && src == 6 => {
+                let value = imm8()?;
+                self.alu(dst, value);
+                (2, 2)
+            }
+            0xCB => {
+                let cb = imm8()?;
+                let reg = cb & 0x7;
+                let bit = (cb >> 3) & 0x7;
+                if reg == HL_INDIRECT {
+                    return None;
+                }
+                let value = self.reg(reg);
+                match cb >> 6 {
+                    0 => {
+                        let value = self.rotate(bit, value);
+                        self.set_reg(reg, value);
+                    }
+                    1 => self.flags(value & (1 << bit) == 0, false, true, self.f & C != 0),
+                    2 => self.set_reg(reg, value & !(1 << bit)),
+                    _ => self.set_reg(reg, value | (1 << bit)),
+                }
+                (2, 2)
+            }
+            _ => return None,
+        };
+        self.pc = self.pc.wrapping_add(size);
+        Some(cycles)
+    }
+
+    /* B, C, D, E, H, L, (HL), A */
+    fn reg(&self, idx: u8) -> u8 {
+        match idx {
+            0 => self.b,
+            1 => self.c,
+            2 => self.d,
+            3 => self.e,
+            4 => self.h,
+            5 => self.l,
+            7 => self.a,
+            _ => unreachable!("(HL) isn't a register"),
+        }
+    }
+
+    fn set_reg(&mut self, idx: u8, value: u8) {
+        match idx {
+            0 => self.b = value,
+            1 => self.c = value,
+            2 => self.d = value,
+            3 => self.e = value,
+            4 => self.h = value,
+            5 => self.l = value,
+            7 => self.a = value,
+            _ => unreachable!("(HL) isn't a register"),
+        }
+    }
+
+    /* BC, DE, HL, SP */
+    fn pair(&self, idx: u8) -> u16 {
+        match idx {
+            0 => u16::from_be_bytes([self.b, self.c]),
+            1 => u16::from_be_bytes([self.d, self.e]),
+            2 => u16::from_be_bytes([self.h, self.l]),
+            _ => self.sp,
+        }
+    }
+
+    fn set_pair(&mut self, idx: u8, value: u16) {
+        let [up, low] = value.to_be_bytes();
+        match idx {
+            0 => (self.b, self.c) = (up, low),
+            1 => (self.d, self.e) = (up, low),
+            2 => (self.h, self.l) = (up, low),
+            _ => self.sp = value,
+        }
+    }
+
+    fn flags(&mut self, z: bool, n: bool, h: bool, c: bool) {
+        self.f = (z as u8) << 7 | (n as u8) << 6 | (h as u8) << 5 | (c as u8) << 4;
+    }
+
+    /* ADD, ADC, SUB, SBC, AND, XOR, OR, CP */
+    fn alu(&mut self, kind: u8, value: u8) {
+        let carry = (self.f & C != 0) as u8;
+        let a = self.a;
+        match kind {
+            0 | 1 => {
+                let carry = if kind == 1 { carry } else { 0 };
+                let sum = a as u16 + value as u16 + carry as u16;
+                self.a = sum as u8;
+                self.flags(self.a == 0, false, (a & 0xF) + (value & 0xF) + carry > 0xF, sum > 0xFF);
+            }
+            2 | 3 | 7 => {
+                let carry = if kind == 3 { carry } else { 0 };
+                let diff = a.wrapping_sub(value).wrapping_sub(carry);
+                let half = (a & 0xF) < (value & 0xF) + carry;
+                let borrow = (a as u16) < value as u16 + carry as u16;
+                self.flags(diff == 0, true, half, borrow);
+                if kind != 7 {
+                    self.a = diff;
+                }
+            }
+            4 => {
+                self.a &= value;
+                self.flags(self.a == 0, false, true, false);
+            }
+            5 => {
+                self.a ^= value;
+                self.flags(self.a == 0, false, false, false);
+            }
+            _ => {
+                self.a |= value;
+                self.flags(self.a == 0, false, false, false);
+            }
+        }
+    }
+
+    /* RLC, RRC, RL, RR, SLA, SRA, SWAP, SRL, setting flags from result */
+    fn rotate(&mut self, kind: u8, value: u8) -> u8 {
+        let carry = (self.f & C != 0) as u8;
+        let (result, out) = match kind {
+            0 => (value.rotate_left(1), value >> 7),
+            1 => (value.rotate_right(1), value & 1),
+            2 => (value << 1 | carry, value >> 7),
+            3 => (value >> 1 | carry << 7, value & 1),
+            4 => (value << 1, value >> 7),
+            5 => (value >> 1 | (value & 0x80), value & 1),
+            6 => (value.rotate_left(4), 0),
+            _ => (value >> 1, value & 1),
+        };
+        self.flags(result == 0, false, false, out != 0);
+        result
+    }
+
+    /* Adjusts A to BCD after addition or subtraction, N tells which one it was */
+    fn daa(&mut self) {
+        let (n, h, mut c) = (self.f & N != 0, self.f & H != 0, self.f & C != 0);
+        let mut adjust = 0;
+        if n {
+            if c {
+                adjust |= 0x60;
+            }
+            if h {
+                adjust |= 0x06;
+            }
+            self.a = self.a.wrapping_sub(adjust);
+        } else {
+            if c || self.a > 0x99 {
+                adjust |= 0x60;
+                c = true;
+            }
+            if h || self.a & 0xF > 0x9 {
+                adjust |= 0x06;
+            }
+            self.a = self.a.wrapping_add(adjust);
+        }
+        self.flags(self.a == 0, n, false, c);
+    }
+}
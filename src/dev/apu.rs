@@ -1,6 +1,11 @@
 #![allow(non_snake_case, non_camel_case_types)]
 
+#[cfg(feature = "serde")]
+use std::any::Any;
+use std::collections::VecDeque;
+
 use super::*;
+use super::super::mem::IORegs;
 
 const CPU_FREQUENCY: u32 = 1 << 20;
 const SEQUENCER_FREQUENCY: u32 = 512;
@@ -10,10 +15,194 @@ const DUTY_CYCLE_COUNT: u16 = 4;
 const DUTY_CYCLE_STEPS: u16 = 8;
 pub const BUFF_SIZE: usize = 1024;
 pub const PLAYBACK_FREQUENCY: u32 = 44100;
-const SAMPLE_APPEND_RATE: u16 = (CPU_FREQUENCY / PLAYBACK_FREQUENCY) as u16 + 1;
+/* Samples of each channel kept for oscilloscope views, ~23ms at default rate */
+pub const SCOPE_SAMPLES: usize = 1024;
 const WAVE_RAM_SAMPLE_COUNT: usize = 32;
 const WAVE_RAM_BASE: u16 = 0xFF30;
-const NOISE_LSFR_SIZE: usize = 15;
+/* 15 bit shift register starts with all bits set */
+const NOISE_LFSR_INIT: u16 = 0x7FFF;
+/* Length counters count down from these minus NRx1 length */
+const SQUARE_MAX_LENGTH: u16 = 64;
+const WAVE_MAX_LENGTH: u16 = 256;
+const NOISE_MAX_LENGTH: u16 = 64;
+/* Registers of channels 1-4 which are handled the same way for all of them */
+const NRX1: [u16; 4] = [ioregs::NR_11, ioregs::NR_21, ioregs::NR_31, ioregs::NR_41];
+const NRX4: [u16; 4] = [ioregs::NR_14, ioregs::NR_24, ioregs::NR_34, ioregs::NR_44];
+/* NR32 is wave channel's output level, it has no envelope */
+const NRX2: [u16; 4] = [ioregs::NR_12, ioregs::NR_22, ioregs::NR_32, ioregs::NR_42];
+/* NR50 bits sending VIN to left and right terminal, their master volume is in the 3 bits below */
+const VIN_LEFT: u8 = 0x08;
+const VIN_RIGHT: u8 = 0x80;
+const MASTER_VOLUME_STEPS: i64 = 8;
+
+/*
+ * What channel is doing right now, decoded for debug views, see APU::channel_debug.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChannelDebugInfo {
+    /* NR52 status bit, channel is playing */
+    pub enabled: bool,
+    pub dac_on: bool,
+    /* Waveform repeats per second, after sweep. Noise channel gives LFSR shifts per second. */
+    pub frequency: f64,
+    /* Envelope volume 0-15, wave channel has none */
+    pub volume: u16,
+    /* Length counter, sequencer steps left before it stops */
+    pub length: u16,
+    /* Wave duty 0-3(12.5%, 25%, 50%, 75%) of square channels */
+    pub duty: Option<u8>,
+}
+
+/* Audio cart puts on VIN pin(e.g. extra sound chip), asked for one sample each time APU mixes one */
+pub type VinSource = Box<dyn FnMut() -> i16 + Send>;
+
+/*
+ * Samples are produced at exactly `rate`(PLAYBACK_FREQUENCY by default). CPU_FREQUENCY isn't a multiple of it, so instead of
+ * integer rate (which made the pitch drift) the counter accumulates fractions of a sample on each tick.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct SampleClock {
+    counter: u32,
+    rate: u32,
+}
+
+impl SampleClock {
+    fn new() -> Self {
+        Self {
+            counter: 0,
+            rate: PLAYBACK_FREQUENCY,
+        }
+    }
+
+    fn due(&mut self) -> bool {
+        self.counter += self.rate;
+        if self.counter >= CPU_FREQUENCY {
+            self.counter -= CPU_FREQUENCY;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/*
+ * Counts down at 256Hz from max - NRx1 length, loaded when NRx1 is written. With length enable(NRx4 bit 6) set, channel
+ * stops once it reaches 0 and counter stays there until channel is triggered, which reloads it with max.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct LengthCounter {
+    counter: u16,
+    max: u16,
+}
+
+impl LengthCounter {
+    fn new(max: u16, length: u16) -> Self {
+        let mut res = Self { counter: 0, max };
+        res.load(length);
+        res
+    }
+
+    fn load(&mut self, length: u16) {
+        self.counter = self.max - length.min(self.max);
+    }
+
+    /* Returns true when counter just ran out and channel has to stop */
+    fn clock(&mut self, enabled: bool) -> bool {
+        if !enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+
+    /* When next sequencer step doesn't clock length, reloaded counter already lost one clock */
+    fn trigger(&mut self, enabled: bool, skipped: bool) {
+        if self.counter == 0 {
+            self.counter = self.max;
+            if enabled && skipped {
+                self.counter -= 1;
+            }
+        }
+    }
+}
+
+/*
+ * Volume envelope of channels 1, 2 and 4. Every period(NRx2 bits 0-2) clocks of 64Hz sequencer step volume moves one
+ * step up(NRx2 bit 3 set) or down, staying within 0-15. Period 0 leaves volume alone, timer then reloads with 8.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Envelope {
+    volume: u16,
+    timer: u8,
+}
+
+impl Envelope {
+    fn new(volume: u16, period: u8) -> Self {
+        let mut res = Self { volume: 0, timer: 0 };
+        res.trigger(volume, period);
+        res
+    }
+
+    fn trigger(&mut self, volume: u16, period: u8) {
+        self.volume = volume;
+        self.timer = Self::reload(period);
+    }
+
+    fn clock(&mut self, period: u8, increase: bool) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = Self::reload(period);
+        if period == 0 {
+            return;
+        }
+        if increase && self.volume < 0xF {
+            self.volume += 1;
+        } else if !increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    fn reload(period: u8) -> u8 {
+        if period == 0 {
+            8
+        } else {
+            period
+        }
+    }
+
+    /*
+     * "Zombie mode": NRx2 written while channel plays changes volume right away, some games use it to set volume
+     * mid-note without retriggering. Volume goes up by 1 if envelope had period 0 and could still move, or by 2 if
+     * it was going down. Flipping direction turns volume into 16 minus it. Everything wraps around in 4 bits.
+     */
+    fn zombie_write(&mut self, old: u8, new: u8) {
+        let increase = old & 0x08 != 0;
+        let running = if increase { self.volume < 0xF } else { self.volume > 0 };
+        if old & 0x07 == 0 && running {
+            self.volume += 1;
+        } else if !increase {
+            self.volume += 2;
+        }
+        if (old ^ new) & 0x08 != 0 {
+            self.volume = 16u16.wrapping_sub(self.volume);
+        }
+        self.volume &= 0xF;
+    }
+}
+
+/*
+ * APU behaviour which differs between models and revisions, off unless asked for in [quirks].
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct APUQuirks {
+    /* NRx2 writes to playing channel change its volume, see Envelope::zombie_write */
+    pub zombie_mode: bool,
+}
 
 const DUTY_CYCLES: [[bool; DUTY_CYCLE_STEPS as usize]; DUTY_CYCLE_COUNT as usize] = [
     [false, true, true, true, true, true, true, true], // 12.5%
@@ -22,194 +211,190 @@ const DUTY_CYCLES: [[bool; DUTY_CYCLE_STEPS as usize]; DUTY_CYCLE_COUNT as usize
     [false, false, false, false, false, false, true, true], // 75%
 ];
 
+/*
+ * One of four sound channels. Registers are reached through IORegs alone, so channel doesn't care about mapper and can
+ * be driven on its own. APU handles what's common to all of them: NRx4 trigger bit, NR52 status bits, when length,
+ * sweep and envelope are clocked, and mixing outputs.
+ */
+trait Channel: Send {
+    /* Channel was started by NRx4 bit 7, skipped as in LengthCounter::trigger */
+    fn trigger(&mut self, io: &IORegs, skipped: bool);
+    /* One CPU cycle of running channel */
+    fn tick(&mut self, io: &IORegs);
+    /* Returns true when length ran out and channel has to stop */
+    fn length_clock(&mut self, enabled: bool) -> bool;
+    /* Write of NRx1 */
+    fn load_length(&mut self, value: u8);
+    fn length(&self) -> u16;
+    fn envelope_clock(&mut self, _: &IORegs) {}
+    /* NRx2 is being written while channel plays, with its old and new value */
+    fn envelope_write(&mut self, _old: u8, _new: u8) {}
+    /* Returns true when frequency overflowed and channel has to stop */
+    fn sweep_clock(&mut self, _: &IORegs) -> bool {
+        false
+    }
+    /* Channel can't play with DAC off, it stops right away */
+    fn dac_enabled(&self, io: &IORegs) -> bool;
+    /* Current amplitude */
+    fn output(&self, io: &IORegs) -> i16;
+    /* Envelope volume, 0-15 */
+    fn volume(&self) -> u16 {
+        0
+    }
+    /* Where in the waveform channel is: duty step, wave RAM sample or noise LFSR */
+    fn position(&self) -> u16;
+    /* In Hz, as in ChannelDebugInfo */
+    fn frequency(&self, io: &IORegs) -> f64;
+    fn duty(&self, _: &IORegs) -> Option<u8> {
+        None
+    }
+    /* For saving channels as their concrete types */
+    #[cfg(feature = "serde")]
+    fn as_any(&self) -> &dyn Any;
+}
+
 trait SquareWaveRegisters {
-    fn SWEEP_TIME(&self, mmu: &mut MMU<impl BankController>) -> u16;
-    fn SWEEP_SHIFTS(&self, mmu: &mut MMU<impl BankController>) -> u8;
-    fn SWEEP_DIRECTION(&self, mmu: &mut MMU<impl BankController>) -> bool;
-    fn SOUND_LENGTH(&self, mmu: &mut MMU<impl BankController>) -> u16;
-    fn WAVE_DUTY(&self, mmu: &mut MMU<impl BankController>) -> u8;
-    fn ENVELOPE_SHIFTS(&self, mmu: &mut MMU<impl BankController>) -> u8;
-    fn ENVELOPE_DIRECTION(&self, mmu: &mut MMU<impl BankController>) -> bool;
-    fn INITIAL_VOLUME(&self, mmu: &mut MMU<impl BankController>) -> u16;
-    fn FREQ(&self, mmu: &mut MMU<impl BankController>) -> u16;
-    fn COUNTER_CONSECUTIVE_SELECT(&self, mmu: &mut MMU<impl BankController>) -> bool;
-    fn INITIAL(&self, mmu: &mut MMU<impl BankController>) -> bool;
-    fn _INITIAL(&self, mmu: &mut MMU<impl BankController>, value: bool);
-    fn ENABLED(&self, mmu: &mut MMU<impl BankController>) -> bool;
-    fn _ENABLED(&self, mmu: &mut MMU<impl BankController>, value: bool);
+    fn SWEEP_TIME(&self, io: &IORegs) -> u16;
+    fn SWEEP_SHIFTS(&self, io: &IORegs) -> u8;
+    fn SWEEP_DIRECTION(&self, io: &IORegs) -> bool;
+    fn SOUND_LENGTH(&self, io: &IORegs) -> u8;
+    fn WAVE_DUTY(&self, io: &IORegs) -> u8;
+    fn ENVELOPE_SHIFTS(&self, io: &IORegs) -> u8;
+    fn ENVELOPE_DIRECTION(&self, io: &IORegs) -> bool;
+    fn INITIAL_VOLUME(&self, io: &IORegs) -> u16;
+    fn FREQ(&self, io: &IORegs) -> u16;
+    fn COUNTER_CONSECUTIVE_SELECT(&self, io: &IORegs) -> bool;
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Channel1Regs;
 impl SquareWaveRegisters for Channel1Regs {
     // NR 10 - Sweep register
-    fn SWEEP_TIME(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_10) >> 4) as u16
+    fn SWEEP_TIME(&self, io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_10) >> 4) as u16
     }
-    fn SWEEP_SHIFTS(&self, mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_10) & 7
+    fn SWEEP_SHIFTS(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_10) & 7
     }
-    fn SWEEP_DIRECTION(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_10, 3)
+    fn SWEEP_DIRECTION(&self, io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_10, 3)
     }
 
     // NR 11 - Length and wave duty registers
-    fn SOUND_LENGTH(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_11) & 0x3F) as u16
+    fn SOUND_LENGTH(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_11)
     }
-    fn WAVE_DUTY(&self, mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_11) >> 6
+    fn WAVE_DUTY(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_11) >> 6
     }
 
     // NR 12 - Volume Envelope register
-    fn ENVELOPE_SHIFTS(&self, mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_12) & 7
+    fn ENVELOPE_SHIFTS(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_12) & 7
     }
-    fn ENVELOPE_DIRECTION(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_22, 3)
+    fn ENVELOPE_DIRECTION(&self, io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_12, 3)
     }
-    fn INITIAL_VOLUME(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_12) >> 4) as u16
+    fn INITIAL_VOLUME(&self, io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_12) >> 4) as u16
     }
 
     // NR13 and NR14 - frequency
-    fn FREQ(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (((mmu.read(ioregs::NR_14) & 7) as u16) << 8) + mmu.read(ioregs::NR_13) as u16
+    fn FREQ(&self, io: &IORegs) -> u16 {
+        (((io.get(ioregs::NR_14) & 7) as u16) << 8) + io.get(ioregs::NR_13) as u16
     }
-    // NR 14 - Counter/Consecutive selection and initial flags
-    fn COUNTER_CONSECUTIVE_SELECT(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_14, 6)
-    }
-    fn INITIAL(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_14, 7)
-    }
-    fn _INITIAL(&self, mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_14, 7, value)
-    }
-
-    // NR52 - Sound ON/OFF
-    fn ENABLED(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_52, 0)
-    }
-    fn _ENABLED(&self, mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_52, 0, value)
+    // NR 14 - Counter/Consecutive selection
+    fn COUNTER_CONSECUTIVE_SELECT(&self, io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_14, 6)
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Channel2Regs;
 impl SquareWaveRegisters for Channel2Regs {
     // No sweep in channel2
-    fn SWEEP_TIME(&self, _: &mut MMU<impl BankController>) -> u16 {
+    fn SWEEP_TIME(&self, _: &IORegs) -> u16 {
         0
     }
-    fn SWEEP_SHIFTS(&self, _: &mut MMU<impl BankController>) -> u8 {
+    fn SWEEP_SHIFTS(&self, _: &IORegs) -> u8 {
         0
     }
-    fn SWEEP_DIRECTION(&self, _: &mut MMU<impl BankController>) -> bool {
+    fn SWEEP_DIRECTION(&self, _: &IORegs) -> bool {
         false
     }
 
     // NR 21 - Length and wave duty registers
-    fn SOUND_LENGTH(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_21) & 0x3F) as u16
+    fn SOUND_LENGTH(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_21)
     }
-    fn WAVE_DUTY(&self, mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_21) >> 6
+    fn WAVE_DUTY(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_21) >> 6
     }
 
     // NR 22 - Volume Envelope register
-    fn ENVELOPE_SHIFTS(&self, mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_22) & 7
+    fn ENVELOPE_SHIFTS(&self, io: &IORegs) -> u8 {
+        io.get(ioregs::NR_22) & 7
     }
-    fn ENVELOPE_DIRECTION(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_22, 3)
+    fn ENVELOPE_DIRECTION(&self, io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_22, 3)
     }
-    fn INITIAL_VOLUME(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_22) >> 4) as u16
+    fn INITIAL_VOLUME(&self, io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_22) >> 4) as u16
     }
 
     // NR23 and NR24 - frequency
-    fn FREQ(&self, mmu: &mut MMU<impl BankController>) -> u16 {
-        (((mmu.read(ioregs::NR_24) & 7) as u16) << 8) + mmu.read(ioregs::NR_23) as u16
+    fn FREQ(&self, io: &IORegs) -> u16 {
+        (((io.get(ioregs::NR_24) & 7) as u16) << 8) + io.get(ioregs::NR_23) as u16
     }
-    // NR 24 - Counter/Consecutive selection and initial flags
-    fn COUNTER_CONSECUTIVE_SELECT(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_24, 6)
-    }
-    fn INITIAL(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_24, 7)
-    }
-    fn _INITIAL(&self, mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_24, 7, value)
-    }
-
-    // NR52 - Sound ON/OFF
-    fn ENABLED(&self, mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_52, 1)
-    }
-    fn _ENABLED(&self, mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_52, 1, value)
+    // NR 24 - Counter/Consecutive selection
+    fn COUNTER_CONSECUTIVE_SELECT(&self, io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_24, 6)
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct SquareWaveChannel<T: SquareWaveRegisters> {
     /* frequency with sweep function transforms */
     frequency: u16,
     /* volume with envelope function transforms */
-    volume: u16,
+    envelope: Envelope,
     /* Decremented by frame sequencer. 256Hz */
-    length: u16,
+    length: LengthCounter,
     /* Initialized with (2048-frequency). Decremented in each CPU cycle. If 0 reached, increment duty cycle. */
     timer: u16,
     /* 8 duty cycles. Wraps when over 7. */
     duty_cycle: u16,
     /* sweep timer */
     sweep_timer: u16,
-    envelope_count: u8,
-    /* Output buffer */
-    buff: Vec<i16>,
-    /* Used to fillup buffer for player with PLAYBACK_FREQUENCY sampling rate, not CPU_FREQUENCY */
-    sample_counter: u16,
     /* Provides access to memory mapped registers */
     regs: T,
 }
 
 impl<T: SquareWaveRegisters> SquareWaveChannel<T> {
-    fn new(mmu: &mut MMU<impl BankController>, regs: T) -> Self {
+    fn new(io: &IORegs, regs: T) -> Self {
         Self {
-            frequency: regs.FREQ(mmu),
-            volume: regs.INITIAL_VOLUME(mmu),
-            length: regs.SOUND_LENGTH(mmu),
-            timer: 2048 - regs.FREQ(mmu),
+            frequency: regs.FREQ(io),
+            envelope: Envelope::new(regs.INITIAL_VOLUME(io), regs.ENVELOPE_SHIFTS(io)),
+            length: LengthCounter::new(SQUARE_MAX_LENGTH, (regs.SOUND_LENGTH(io) & 0x3F) as u16),
+            timer: 2048 - regs.FREQ(io),
             duty_cycle: 0,
-            sweep_timer: regs.SWEEP_TIME(mmu),
-            envelope_count: regs.ENVELOPE_SHIFTS(mmu),
-            buff: Vec::with_capacity(BUFF_SIZE),
-            sample_counter: 0,
+            sweep_timer: regs.SWEEP_TIME(io),
             regs: regs,
         }
     }
+}
 
-    fn reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.buff.clear();
-        self.frequency = self.regs.FREQ(mmu);
-        self.volume = self.regs.INITIAL_VOLUME(mmu);
-        self.length = self.regs.SOUND_LENGTH(mmu);
+impl<T: SquareWaveRegisters + Send + 'static> Channel for SquareWaveChannel<T> {
+    fn trigger(&mut self, io: &IORegs, skipped: bool) {
+        self.frequency = self.regs.FREQ(io);
+        self.envelope.trigger(self.regs.INITIAL_VOLUME(io), self.regs.ENVELOPE_SHIFTS(io));
+        self.length.trigger(self.regs.COUNTER_CONSECUTIVE_SELECT(io), skipped);
         self.timer = 2048 - self.frequency;
         self.duty_cycle = 0;
-        self.sweep_timer = self.regs.SWEEP_TIME(mmu);
-        self.envelope_count = self.regs.ENVELOPE_SHIFTS(mmu);
+        self.sweep_timer = self.regs.SWEEP_TIME(io);
     }
 
-    fn tick(&mut self, mmu: &mut MMU<impl BankController>) {
-        // If triggered start.
-        if self.regs.INITIAL(mmu) {
-            self.reset(mmu);
-            self.regs._INITIAL(mmu, false);
-            self.regs._ENABLED(mmu, true);
-        }
-        if !self.regs.ENABLED(mmu) {
-            return;
-        }
+    fn tick(&mut self, _: &IORegs) {
         // Update timer and duty cycle
         if self.timer > 0 {
             self.timer -= 1
@@ -218,111 +403,134 @@ impl<T: SquareWaveRegisters> SquareWaveChannel<T> {
             self.duty_cycle = (self.duty_cycle + 1) % DUTY_CYCLE_STEPS;
             self.timer = 2048 - self.frequency;
         }
-        // Generate sample
-        self.sample_counter += 1;
-        if self.sample_counter == SAMPLE_APPEND_RATE {
-            let is_on = DUTY_CYCLES[self.regs.WAVE_DUTY(mmu) as usize][self.duty_cycle as usize];
-            let sample = if is_on {
-                (i16::max_value() / 0xF) * (self.volume as i16)
-            } else {
-                0
-            };
-            self.buff.push(sample);
-            self.sample_counter = 0;
-        }
     }
 
-    fn buffer(&mut self) -> &mut Vec<i16> {
-        &mut self.buff
+    fn length_clock(&mut self, enabled: bool) -> bool {
+        self.length.clock(enabled)
     }
 
-    fn length(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !self.regs.ENABLED(mmu) || self.length == 0 {
-            return;
-        }
-        self.length -= 1;
-        if self.length == 0 && self.regs.COUNTER_CONSECUTIVE_SELECT(mmu) {
-            self.regs._ENABLED(mmu, false);
-        }
+    fn load_length(&mut self, value: u8) {
+        self.length.load((value & 0x3F) as u16);
     }
 
-    fn sweep(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !self.regs.ENABLED(mmu) || self.sweep_timer == 0{
-            return;
+    fn length(&self) -> u16 {
+        self.length.counter
+    }
+
+    fn sweep_clock(&mut self, io: &IORegs) -> bool {
+        if self.sweep_timer == 0 {
+            return false;
         }
         self.sweep_timer -= 1;
         if self.sweep_timer == 0 {
-            let delta = self.frequency / (2 as u16).pow(self.regs.SWEEP_SHIFTS(mmu) as u32);
-            if self.regs.SWEEP_DIRECTION(mmu) {
+            let delta = self.frequency / 2u16.pow(self.regs.SWEEP_SHIFTS(io) as u32);
+            if self.regs.SWEEP_DIRECTION(io) {
                 if self.frequency >= delta {
                     self.frequency -= delta;
                 }
             } else if self.frequency + delta > 0x7FF {
-                self.regs._ENABLED(mmu, false);
+                return true;
             } else {
                 self.frequency += delta;
             }
-            self.sweep_timer = self.regs.SWEEP_TIME(mmu);
+            self.sweep_timer = self.regs.SWEEP_TIME(io);
         }
+        false
     }
 
-    fn envelope(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !self.regs.ENABLED(mmu) || self.volume == 0 {
-            return;
-        }
-        if self.regs.ENVELOPE_DIRECTION(mmu) {
-            if self.volume < 0xF {
-                self.volume += 1;
-            }
+    fn envelope_clock(&mut self, io: &IORegs) {
+        self.envelope.clock(self.regs.ENVELOPE_SHIFTS(io), self.regs.ENVELOPE_DIRECTION(io));
+    }
+
+    fn envelope_write(&mut self, old: u8, new: u8) {
+        self.envelope.zombie_write(old, new);
+    }
+
+    // DAC is on when NRx2 bits 3-7 aren't all 0
+    fn dac_enabled(&self, io: &IORegs) -> bool {
+        self.regs.INITIAL_VOLUME(io) != 0 || self.regs.ENVELOPE_DIRECTION(io)
+    }
+
+    fn output(&self, io: &IORegs) -> i16 {
+        if DUTY_CYCLES[self.regs.WAVE_DUTY(io) as usize][self.duty_cycle as usize] {
+            (i16::MAX / 0xF) * (self.envelope.volume as i16)
         } else {
-            if self.volume > 0 {
-                self.volume -= 1
-            }
-        }
-        if self.envelope_count > 0 {
-            self.envelope_count -= 1;
+            0
         }
     }
+
+    fn volume(&self) -> u16 {
+        self.envelope.volume
+    }
+
+    fn position(&self) -> u16 {
+        self.duty_cycle
+    }
+
+    fn frequency(&self, _: &IORegs) -> f64 {
+        CPU_FREQUENCY as f64 / ((2048 - self.frequency) * DUTY_CYCLE_STEPS) as f64
+    }
+
+    fn duty(&self, io: &IORegs) -> Option<u8> {
+        Some(self.regs.WAVE_DUTY(io))
+    }
+
+    #[cfg(feature = "serde")]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct WaveRamChannel {
-    length: u16,
+    length: LengthCounter,
     frequency: u16,
     timer: u16,
     position_counter: usize,
-    sample_counter: u16,
-    buff: Vec<i16>,
 }
 
 impl WaveRamChannel {
-    fn new(mmu: &mut MMU<impl BankController>) -> Self {
+    fn new(io: &IORegs) -> Self {
         Self {
-            length: Self::SOUND_LENGTH(mmu),
-            frequency: Self::FREQ(mmu),
-            timer: 2048 - Self::FREQ(mmu),
-            sample_counter: 0,
+            length: LengthCounter::new(WAVE_MAX_LENGTH, Self::SOUND_LENGTH(io)),
+            frequency: Self::FREQ(io),
+            timer: 2048 - Self::FREQ(io),
             position_counter: 0,
-            buff: Vec::with_capacity(BUFF_SIZE),
         }
     }
 
-    fn reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        //self.buff.clear();
-        self.length = Self::SOUND_LENGTH(mmu);
-        self.frequency = Self::FREQ(mmu);
+    // NR30 - Sound ON/OFF
+    fn OUTPUTTING(io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_30, 7)
+    }
+
+    // NR31 - Sound Length
+    fn SOUND_LENGTH(io: &IORegs) -> u16 {
+        io.get(ioregs::NR_31) as u16
+    }
+
+    // NR32 - Output level
+    fn OUTPUT_LEVEL(io: &IORegs) -> u8 {
+        (io.get(ioregs::NR_32) >> 5) & 3
+    }
+
+    // NR 33 and NR 34 - frequency
+    fn FREQ(io: &IORegs) -> u16 {
+        (((io.get(ioregs::NR_34) & 7) as u16) << 8) + io.get(ioregs::NR_33) as u16
+    }
+    fn COUNTER_CONSECUTIVE_SELECT(io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_34, 6)
+    }
+}
+
+impl Channel for WaveRamChannel {
+    fn trigger(&mut self, io: &IORegs, skipped: bool) {
+        self.length.trigger(Self::COUNTER_CONSECUTIVE_SELECT(io), skipped);
+        self.frequency = Self::FREQ(io);
         self.timer = (2048 - self.frequency) / 2;
     }
 
-    fn tick(&mut self, mmu: &mut MMU<impl BankController>) {
-        // If triggered start.
-        if Self::INITIAL(mmu) {
-            self.reset(mmu);
-            Self::_INITIAL(mmu, false);
-            Self::_ENABLED(mmu, true);
-        }
-        if !Self::ENABLED(mmu) || !Self::OUTPUTTING(mmu) {
-            return;
-        }
+    fn tick(&mut self, _: &IORegs) {
         // Update timer and position in wave ram
         if self.timer > 0 {
             self.timer -= 1
@@ -331,256 +539,247 @@ impl WaveRamChannel {
             self.position_counter = (self.position_counter + 1) % WAVE_RAM_SAMPLE_COUNT;
             self.timer = (2048 - self.frequency) / 2;
         }
-        // Generate sample
-        self.sample_counter += 1;
-        if self.sample_counter == SAMPLE_APPEND_RATE {
-            let offset = (self.position_counter as u16) / 2;
-            let sample_byte = mmu.read(WAVE_RAM_BASE + offset);
-            let mut volume = if self.position_counter % 2 == 0 {
-                sample_byte >> 4
-            } else {
-                sample_byte & 0xF
-            };
-            volume = match Self::OUTPUT_LEVEL(mmu) {
-                0 => 0,
-                1 => volume,
-                2 => volume >> 1,
-                3 => volume >> 2,
-                x => panic!("Invalid output level {}", x),
-            };
-            let sample = (i16::max_value() / 0xF) * (volume as i16);
-            self.buff.push(sample);
-            self.sample_counter = 0;
-        }
-    }
-
-    fn length(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !Self::ENABLED(mmu) {
-            return;
-        }
-        if self.length > 0 {
-            self.length -= 1;
-        }
-        if self.length == 0 {
-            if Self::COUNTER_CONSECUTIVE_SELECT(mmu) {
-                Self::_ENABLED(mmu, false);
-            }
-        }
     }
 
-    fn buffer(&mut self) -> &mut Vec<i16> {
-        &mut self.buff
+    fn length_clock(&mut self, enabled: bool) -> bool {
+        self.length.clock(enabled)
     }
 
-    // NR30 - Sound ON/OFF
-    fn OUTPUTTING(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_30, 7)
-    }
-    fn _OUTPUTTING(mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_30, 7, value)
+    fn load_length(&mut self, value: u8) {
+        self.length.load(value as u16);
     }
 
-    // NR31 - Sound Length
-    fn SOUND_LENGTH(mmu: &mut MMU<impl BankController>) -> u16 {
-        mmu.read(ioregs::NR_31) as u16
+    fn length(&self) -> u16 {
+        self.length.counter
     }
 
-    // NR32 - Output level
-    fn OUTPUT_LEVEL(mmu: &mut MMU<impl BankController>) -> u8 {
-        (mmu.read(ioregs::NR_32) >> 5) & 3
+    fn dac_enabled(&self, io: &IORegs) -> bool {
+        Self::OUTPUTTING(io)
     }
 
-    // NR 33 and NR 34 - frequency
-    fn FREQ(mmu: &mut MMU<impl BankController>) -> u16 {
-        (((mmu.read(ioregs::NR_34) & 7) as u16) << 8) + mmu.read(ioregs::NR_33) as u16
-    }
-    fn COUNTER_CONSECUTIVE_SELECT(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_34, 6)
-    }
-    fn INITIAL(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_34, 7)
+    fn output(&self, io: &IORegs) -> i16 {
+        let offset = (self.position_counter as u16) / 2;
+        let sample_byte = io.get(WAVE_RAM_BASE + offset);
+        let volume = if self.position_counter & 1 == 0 {
+            sample_byte >> 4
+        } else {
+            sample_byte & 0xF
+        };
+        let volume = match Self::OUTPUT_LEVEL(io) {
+            0 => 0,
+            1 => volume,
+            2 => volume >> 1,
+            _ => volume >> 2,
+        };
+        (i16::MAX / 0xF) * (volume as i16)
     }
-    fn _INITIAL(mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_34, 7, value)
+
+    fn position(&self) -> u16 {
+        self.position_counter as u16
     }
 
-    // NR52 - Sound ON/OFF
-    fn ENABLED(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_52, 2)
+    // Timer runs at twice the rate of square channels
+    fn frequency(&self, _: &IORegs) -> f64 {
+        2.0 * CPU_FREQUENCY as f64 / ((2048 - self.frequency) as f64 * WAVE_RAM_SAMPLE_COUNT as f64)
     }
-    fn _ENABLED(mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_52, 2, value)
+
+    #[cfg(feature = "serde")]
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct NoiseChannel {
-    volume: u16,
-    length: u16,
-    envelope_count: u8,
+    envelope: Envelope,
+    length: LengthCounter,
     timer: u16,
-    sample_counter: u16,
-    lsfr: [bool; NOISE_LSFR_SIZE],
-    buff: Vec<i16>,
+    /* Output is high while bit 0 is clear */
+    lfsr: u16,
 }
 
 impl NoiseChannel {
-    fn new(mmu: &mut MMU<impl BankController>) -> Self {
+    fn new(io: &IORegs) -> Self {
         Self {
-            volume: Self::INITIAL_VOLUME(mmu),
-            length: Self::SOUND_LENGTH(mmu),
-            envelope_count: Self::ENVELOPE_SHIFTS(mmu),
-            timer: Self::FREQ_RATIO(mmu) << Self::FREQ_SHIFT_CLOCK(mmu),
-            sample_counter: 0,
-            lsfr: [true; NOISE_LSFR_SIZE],
-            buff: Vec::with_capacity(BUFF_SIZE),
+            envelope: Envelope::new(Self::INITIAL_VOLUME(io), Self::ENVELOPE_SHIFTS(io)),
+            length: LengthCounter::new(NOISE_MAX_LENGTH, Self::SOUND_LENGTH(io)),
+            timer: Self::FREQ_RATIO(io) << Self::FREQ_SHIFT_CLOCK(io),
+            lfsr: NOISE_LFSR_INIT,
         }
     }
 
-    fn reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.buff.clear();
-        self.volume = Self::INITIAL_VOLUME(mmu);
-        self.length = Self::SOUND_LENGTH(mmu);
-        self.timer = Self::FREQ_RATIO(mmu) << Self::FREQ_SHIFT_CLOCK(mmu);
-        self.envelope_count = Self::ENVELOPE_SHIFTS(mmu);
-        self.lsfr = [true; NOISE_LSFR_SIZE];
+    // NR 41 - Length register
+    fn SOUND_LENGTH(io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_41) & 0x3F) as u16
     }
 
-    fn tick(&mut self, mmu: &mut MMU<impl BankController>) {
-        // If triggered start.
-        if Self::INITIAL(mmu) {
-            self.reset(mmu);
-            Self::_INITIAL(mmu, false);
-            Self::_ENABLED(mmu, true);
-        }
-        if !Self::ENABLED(mmu) {
-            return;
+    // NR 42 - Volume Envelope register
+    fn ENVELOPE_SHIFTS(io: &IORegs) -> u8 {
+        io.get(ioregs::NR_42) & 7
+    }
+    fn ENVELOPE_DIRECTION(io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_42, 3)
+    }
+    fn INITIAL_VOLUME(io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_42) >> 4) as u16
+    }
+
+    // NR 43 - Frequency config
+    fn FREQ_RATIO(io: &IORegs) -> u16 {
+        let x = (io.get(ioregs::NR_43) & 7) as u16;
+        if x == 0 {
+            8
+        } else {
+            16 * x
         }
-        // Update timer and position in wave ram
+    }
+    fn LFSR_7BIT(io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_43, 3)
+    }
+    fn FREQ_SHIFT_CLOCK(io: &IORegs) -> u16 {
+        (io.get(ioregs::NR_43) >> 4) as u16
+    }
+
+    // NR 44 - Counter/Consecutive selection
+    fn COUNTER_CONSECUTIVE_SELECT(io: &IORegs) -> bool {
+        io.read_bit(ioregs::NR_44, 6)
+    }
+}
+
+impl Channel for NoiseChannel {
+    fn trigger(&mut self, io: &IORegs, skipped: bool) {
+        self.envelope.trigger(Self::INITIAL_VOLUME(io), Self::ENVELOPE_SHIFTS(io));
+        self.length.trigger(Self::COUNTER_CONSECUTIVE_SELECT(io), skipped);
+        self.timer = Self::FREQ_RATIO(io) << Self::FREQ_SHIFT_CLOCK(io);
+        self.lfsr = NOISE_LFSR_INIT;
+    }
+
+    fn tick(&mut self, io: &IORegs) {
+        // Update timer and shift LFSR: XOR of bits 0 and 1 goes into bit 14, and into bit 6 too in 7 bit mode
         if self.timer > 0 {
             self.timer -= 1
         };
         if self.timer == 0 {
-            let new = self.lsfr[0] ^ self.lsfr[1];
-            for i in 1..NOISE_LSFR_SIZE {
-                self.lsfr[i - 1] = self.lsfr[i];
-            }
-            self.lsfr[NOISE_LSFR_SIZE - 1] = new;
-            if Self::LSFR_7BIT(mmu) {
-                self.lsfr[NOISE_LSFR_SIZE / 2 - 1] = new;
+            let new = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (new << 14);
+            if Self::LFSR_7BIT(io) {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (new << 6);
             }
-            self.timer = Self::FREQ_RATIO(mmu) << Self::FREQ_SHIFT_CLOCK(mmu);
-        }
-        // Generate sample
-        self.sample_counter += 1;
-        if self.sample_counter == SAMPLE_APPEND_RATE {
-            let sample = if !self.lsfr[0] {
-                (i16::max_value() / 0xF) * (self.volume as i16)
-            } else {
-                0
-            };
-            self.buff.push(sample);
-            self.sample_counter = 0;
+            self.timer = Self::FREQ_RATIO(io) << Self::FREQ_SHIFT_CLOCK(io);
         }
     }
 
-    fn length(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !Self::ENABLED(mmu) || self.volume == 0 {
-            return;
-        }
-        self.length -= 1;
-        if self.length == 0 && Self::COUNTER_CONSECUTIVE_SELECT(mmu) {
-            Self::_ENABLED(mmu, false);
-        }
+    fn length_clock(&mut self, enabled: bool) -> bool {
+        self.length.clock(enabled)
     }
 
-    fn envelope(&mut self, mmu: &mut MMU<impl BankController>) {
-        if !Self::ENABLED(mmu) || self.volume == 0 {
-            return;
-        }
-        if Self::ENVELOPE_DIRECTION(mmu) {
-            if self.volume < 0xF {
-                self.volume += 1;
-            }
-        } else {
-            if self.volume > 0 {
-                self.volume -= 1
-            }
-        }
-        if self.envelope_count > 0 {
-            self.envelope_count -= 1;
-        }
+    fn load_length(&mut self, value: u8) {
+        self.length.load((value & 0x3F) as u16);
     }
 
-    fn buffer(&mut self) -> &mut Vec<i16> {
-        &mut self.buff
+    fn length(&self) -> u16 {
+        self.length.counter
     }
 
-    // NR 41 - Length register
-    fn SOUND_LENGTH(mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_41) & 0x3F) as u16
+    fn envelope_clock(&mut self, io: &IORegs) {
+        self.envelope.clock(Self::ENVELOPE_SHIFTS(io), Self::ENVELOPE_DIRECTION(io));
     }
 
-    // NR 42 - Volume Envelope register
-    fn ENVELOPE_SHIFTS(mmu: &mut MMU<impl BankController>) -> u8 {
-        mmu.read(ioregs::NR_42) & 7
+    fn envelope_write(&mut self, old: u8, new: u8) {
+        self.envelope.zombie_write(old, new);
     }
-    fn ENVELOPE_DIRECTION(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_42, 3)
-    }
-    fn INITIAL_VOLUME(mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_42) >> 4) as u16
+
+    fn dac_enabled(&self, io: &IORegs) -> bool {
+        io.get(ioregs::NR_42) & 0xF8 != 0
     }
 
-    // NR 43 - Frequency config
-    fn FREQ_RATIO(mmu: &mut MMU<impl BankController>) -> u16 {
-        let x = (mmu.read(ioregs::NR_43) & 7) as u16;
-        if x == 0 {
-            8
+    fn output(&self, _: &IORegs) -> i16 {
+        if self.lfsr & 1 == 0 {
+            (i16::MAX / 0xF) * (self.envelope.volume as i16)
         } else {
-            16 * x
+            0
         }
     }
-    fn LSFR_7BIT(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_43, 3)
+
+    fn volume(&self) -> u16 {
+        self.envelope.volume
     }
-    fn FREQ_SHIFT_CLOCK(mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::NR_43) >> 4) as u16
+
+    fn position(&self) -> u16 {
+        self.lfsr
     }
 
-    // NR 44 - Counter/Consecutive selection and initial flags
-    fn COUNTER_CONSECUTIVE_SELECT(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_44, 6)
+    fn frequency(&self, io: &IORegs) -> f64 {
+        CPU_FREQUENCY as f64 / (Self::FREQ_RATIO(io) as f64 * (1u32 << Self::FREQ_SHIFT_CLOCK(io)) as f64)
     }
-    fn INITIAL(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_44, 7)
+
+    #[cfg(feature = "serde")]
+    fn as_any(&self) -> &dyn Any {
+        self
     }
-    fn _INITIAL(mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_44, 7, value)
+}
+
+type Channels = [Box<dyn Channel>; 4];
+
+/* Channels are saved in their fixed order, as concrete types */
+#[cfg(feature = "serde")]
+mod saved_channels {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    type Saved = (SquareWaveChannel<Channel1Regs>, SquareWaveChannel<Channel2Regs>, WaveRamChannel, NoiseChannel);
+
+    fn concrete<T: 'static>(chan: &dyn Channel) -> &T {
+        chan.as_any().downcast_ref().expect("channel in wrong slot")
     }
 
-    // NR52 - Sound ON/OFF
-    fn ENABLED(mmu: &mut MMU<impl BankController>) -> bool {
-        mmu.read_bit(ioregs::NR_52, 3)
+    pub fn serialize<S: Serializer>(channels: &Channels, serializer: S) -> Result<S::Ok, S::Error> {
+        (
+            concrete::<SquareWaveChannel<Channel1Regs>>(channels[0].as_ref()),
+            concrete::<SquareWaveChannel<Channel2Regs>>(channels[1].as_ref()),
+            concrete::<WaveRamChannel>(channels[2].as_ref()),
+            concrete::<NoiseChannel>(channels[3].as_ref()),
+        )
+            .serialize(serializer)
     }
-    fn _ENABLED(mmu: &mut MMU<impl BankController>, value: bool) {
-        mmu.set_bit(ioregs::NR_52, 3, value)
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Channels, D::Error> {
+        let (chan1, chan2, chan3, chan4) = Saved::deserialize(deserializer)?;
+        Ok([Box::new(chan1), Box::new(chan2), Box::new(chan3), Box::new(chan4)])
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct APU {
+    pub quirks: APUQuirks,
     /* If sequencer_cycle % (1MHz/512Hz) == 0 then sequencer_step increments */
     sequencer_cycle: u16,
     /* Number between 0-7. It wraps around. */
     sequencer_step: u16,
-    sample_counter: u16,
-    /* Quadrangular wave patterns with sweep and envelope functions. */
-    chan1: SquareWaveChannel<Channel1Regs>,
-    chan2: SquareWaveChannel<Channel2Regs>,
-    chan3: WaveRamChannel,
-    chan4: NoiseChannel,
+    sample_clock: SampleClock,
+    /* Two quadrangular wave channels(first one with sweep), wave RAM and noise */
+    #[cfg_attr(feature = "serde", serde(with = "saved_channels"))]
+    channels: Channels,
     /* Buffers for left/right mixed samples */
     left: Vec<i16>,
     right: Vec<i16>,
+    /* Loudest sample of each channel since levels were last taken */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    levels: [u16; 4],
+    /* Last SCOPE_SAMPLES samples of each channel, oldest first */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    scope: [VecDeque<i16>; 4],
+    /* Channels left out of the mix, and the only one heard when soloed. Settings, not part of emulated state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    muted: [bool; 4],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    solo: Option<u8>,
+    /* Cart's audio, silent when there is none. Hardware outside of GB, so not saved either. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    vin: Option<VinSource>,
+    /* Times each channel was triggered, for event log */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    triggers: [u64; 4],
 }
 
 impl<T: BankController> Clocked<T> for APU {
@@ -590,121 +789,287 @@ impl<T: BankController> Clocked<T> for APU {
     }
 
     fn step(&mut self, mmu: &mut MMU<T>) {
-        self.chan1.tick(mmu);
-        self.chan2.tick(mmu);
-        self.chan3.tick(mmu);
-        self.chan4.tick(mmu);
+        let io = &mut mmu.ioregs;
+        let skipped = self.length_skipped();
+        for (i, chan) in self.channels.iter_mut().enumerate() {
+            // If triggered start.
+            if io.read_bit(NRX4[i], 7) {
+                io.set_bit(NRX4[i], 7, false);
+                Self::start(chan.as_mut(), io, i, skipped);
+                self.triggers[i] += 1;
+            }
+            if !chan.dac_enabled(io) {
+                io.set_bit(ioregs::NR_52, i as u8, false);
+            }
+            if io.read_bit(ioregs::NR_52, i as u8) {
+                chan.tick(io);
+            }
+        }
 
         self.sequencer_cycle += 1;
         if self.sequencer_cycle == SEQUENCER_UPDATE_RATE {
-            match self.sequencer_step {
-                0 | 2 | 4 | 6 => {
-                    self.chan1.length(mmu);
-                    self.chan2.length(mmu);
-                    self.chan3.length(mmu);
-                    self.chan4.length(mmu);
+            let step = self.sequencer_step;
+            for (i, chan) in self.channels.iter_mut().enumerate() {
+                let enabled = io.read_bit(ioregs::NR_52, i as u8);
+                // Length on even steps, sweep on 2 and 6, envelope on 7
+                let length = step & 1 == 0 && chan.length_clock(io.read_bit(NRX4[i], 6));
+                let sweep = enabled && (step == 2 || step == 6) && chan.sweep_clock(io);
+                if enabled && step == 7 {
+                    chan.envelope_clock(io);
                 }
-                _ => {}
-            };
-            match self.sequencer_step {
-                2 | 6 => {
-                    self.chan1.sweep(mmu);
-                    // No sweep for chan2, chan3, chan4
+                if length || sweep {
+                    io.set_bit(ioregs::NR_52, i as u8, false);
                 }
-                _ => {}
-            };
-            match self.sequencer_step {
-                7 => {
-                    self.chan1.envelope(mmu);
-                    self.chan2.envelope(mmu);
-                    // Noe envelope for chan3
-                    self.chan4.envelope(mmu);
-                }
-                _ => {}
-            };
+            }
 
             self.sequencer_cycle = 0;
             self.sequencer_step = (self.sequencer_step + 1) % SEQUENCER_STEP_COUNT;
         }
-        self.sample_counter += 1;
-        if self.sample_counter == SAMPLE_APPEND_RATE {
+        if self.sample_clock.due() {
+            let (nr_50, nr_51) = (io.get(ioregs::NR_50), io.get(ioregs::NR_51));
             let mut lSample = 0i64;
             let mut rSample = 0i64;
             let mut lActive = 0;
             let mut rActive = 0;
-            if self.chan1_samples().len() > 0 {
-                let val = *self.chan1_samples().first().unwrap() as i64;
-                if APU::SO1(mmu, 1) {
-                    lActive += 1;
-                    lSample += val;
-                }
-                if APU::SO2(mmu, 1) {
-                    rActive += 1;
-                    rSample += val;
-                }
-                self.chan1_samples().clear();
-            }
-            if self.chan2_samples().len() > 0 {
-                let val = *self.chan2_samples().first().unwrap() as i64;
-                if APU::SO1(mmu, 2) {
-                    lActive += 1;
-                    lSample += val;
+            for (i, chan) in self.channels.iter().enumerate() {
+                let playing = io.read_bit(ioregs::NR_52, i as u8);
+                let val = if playing { chan.output(io) } else { 0 };
+                let scope = &mut self.scope[i];
+                if scope.len() == SCOPE_SAMPLES {
+                    scope.pop_front();
                 }
-                if APU::SO2(mmu, 2) {
-                    rActive += 1;
-                    rSample += val;
+                scope.push_back(val);
+                if !playing {
+                    continue;
                 }
-                self.chan2_samples().clear();
-            }
-            if self.chan3_samples().len() > 0 {
-                let val = *self.chan3_samples().first().unwrap() as i64;
-                if APU::SO1(mmu, 3) {
+                let val = val as i64;
+                self.levels[i] = self.levels[i].max(val.unsigned_abs() as u16);
+                let val = if self.audible(i as u8 + 1) { val } else { 0 };
+                if nr_51 & (1 << i) != 0 {
                     lActive += 1;
                     lSample += val;
                 }
-                if APU::SO2(mmu, 3) {
+                if nr_51 & (0x10 << i) != 0 {
                     rActive += 1;
                     rSample += val;
                 }
-                self.chan3_samples().clear();
             }
-            if self.chan4_samples().len() > 0 {
-                let val = *self.chan4_samples().first().unwrap() as i64;
-                if APU::SO1(mmu, 4) {
+
+            // VIN is mixed in like fifth channel, into terminals NR50 sends it to
+            if let Some(vin) = self.vin.as_mut() {
+                let val = vin() as i64;
+                if nr_50 & VIN_LEFT != 0 {
                     lActive += 1;
                     lSample += val;
                 }
-                if APU::SO2(mmu, 4) {
+                if nr_50 & VIN_RIGHT != 0 {
                     rActive += 1;
                     rSample += val;
                 }
-                self.chan4_samples().clear();
             }
-
+            // Master volume 0-7 of each terminal, 7 leaves the mix as it is and 0 still isn't silence
+            let lVolume = (nr_50 & 0x07) as i64 + 1;
+            let rVolume = ((nr_50 >> 4) & 0x07) as i64 + 1;
             self.left
-                .push(lSample.checked_div(lActive).unwrap_or(0) as i16);
+                .push((lSample.checked_div(lActive).unwrap_or(0) * lVolume / MASTER_VOLUME_STEPS) as i16);
             self.right
-                .push(rSample.checked_div(rActive).unwrap_or(0) as i16);
-            self.sample_counter = 0;
+                .push((rSample.checked_div(rActive).unwrap_or(0) * rVolume / MASTER_VOLUME_STEPS) as i16);
         }
     }
 }
 
 impl APU {
     pub fn new(mmu: &mut MMU<impl BankController>) -> Self {
+        let io = &mmu.ioregs;
         Self {
+            quirks: APUQuirks::default(),
             sequencer_cycle: 0,
             sequencer_step: 0,
-            sample_counter: 0,
-            chan1: SquareWaveChannel::new(mmu, Channel1Regs),
-            chan2: SquareWaveChannel::new(mmu, Channel2Regs),
-            chan3: WaveRamChannel::new(mmu),
-            chan4: NoiseChannel::new(mmu),
+            sample_clock: SampleClock::new(),
+            channels: [
+                Box::new(SquareWaveChannel::new(io, Channel1Regs)),
+                Box::new(SquareWaveChannel::new(io, Channel2Regs)),
+                Box::new(WaveRamChannel::new(io)),
+                Box::new(NoiseChannel::new(io)),
+            ],
             left: Vec::with_capacity(BUFF_SIZE),
             right: Vec::with_capacity(BUFF_SIZE),
+            levels: [0; 4],
+            scope: Default::default(),
+            muted: [false; 4],
+            solo: None,
+            vin: None,
+            triggers: [0; 4],
         }
     }
 
+    /* Starts channel i(0-3), it only plays when its DAC is on */
+    fn start(chan: &mut dyn Channel, io: &mut IORegs, i: usize, skipped: bool) {
+        chan.trigger(io, skipped);
+        io.set_bit(ioregs::NR_52, i as u8, chan.dac_enabled(io));
+    }
+
+    /* NR52 bit 7 */
+    pub fn powered(&self, mmu: &mut MMU<impl BankController>) -> bool {
+        mmu.read_bit(ioregs::NR_52, 7)
+    }
+
+    /*
+     * Write to NR52, only power bit is writable. Turning power off zeroes NR10-NR51 and stops all channels, wave RAM
+     * is kept. Once turned on again frame sequencer starts from its first step.
+     */
+    pub fn write_power(&mut self, mmu: &mut MMU<impl BankController>, value: u8) {
+        let was = self.powered(mmu);
+        let on = value & 0x80 != 0;
+        let mut status = mmu.read(ioregs::NR_52) & 0x0F;
+        if was && !on {
+            for addr in ioregs::NR_10..=ioregs::NR_51 {
+                mmu.write(addr, 0);
+            }
+            status = 0;
+        }
+        if !was && on {
+            self.sequencer_cycle = 0;
+            self.sequencer_step = 0;
+        }
+        mmu.write(ioregs::NR_52, (value & 0x80) | status);
+    }
+
+    /* Triggers channel 1-4, same as setting NRx4 bit 7 */
+    pub fn trigger(&mut self, mmu: &mut MMU<impl BankController>, chan: u8) {
+        let i = (chan as usize).wrapping_sub(1);
+        let skipped = self.length_skipped();
+        if let Some(channel) = self.channels.get_mut(i) {
+            mmu.ioregs.set_bit(NRX4[i], 7, false);
+            Self::start(channel.as_mut(), &mut mmu.ioregs, i, skipped);
+            self.triggers[i] += 1;
+        }
+    }
+
+    /*
+     * Leaves channel 1-4 out of the mix, without touching NR52 or anything game sees. Muted channel still counts
+     * as playing, so others don't get louder.
+     */
+    pub fn set_muted(&mut self, chan: u8, muted: bool) {
+        if let Some(flag) = self.muted.get_mut((chan as usize).wrapping_sub(1)) {
+            *flag = muted;
+        }
+    }
+
+    pub fn muted(&self, chan: u8) -> bool {
+        self.muted.get((chan as usize).wrapping_sub(1)).cloned().unwrap_or(false)
+    }
+
+    /* Only given channel is heard, None lets all which aren't muted be heard again */
+    pub fn set_solo(&mut self, chan: Option<u8>) {
+        self.solo = chan.filter(|chan| (1..=4).contains(chan));
+    }
+
+    pub fn solo(&self) -> Option<u8> {
+        self.solo
+    }
+
+    /* Takes mute and solo settings and VIN source of other APU, e.g. one being replaced on reset */
+    pub fn keep_mixer(&mut self, other: &mut APU) {
+        self.muted = other.muted;
+        self.solo = other.solo;
+        self.vin = other.vin.take();
+    }
+
+    /* Channel goes into the mix */
+    pub fn audible(&self, chan: u8) -> bool {
+        !self.muted(chan) && self.solo.unwrap_or(chan) == chan
+    }
+
+    /* Next sequencer step doesn't clock length counters */
+    fn length_skipped(&self) -> bool {
+        self.sequencer_step & 1 == 1
+    }
+
+    /*
+     * Length counter side of writes to NRx1 and NRx4, has to run before value lands in register. NRx1 reloads counter.
+     * Enabling length in NRx4 when next sequencer step doesn't clock it clocks it once right away, which stops
+     * the channel if counter runs out and it isn't triggered by the same write.
+     */
+    pub fn write_length(&mut self, mmu: &mut MMU<impl BankController>, addr: u16, value: u8) {
+        let skipped = self.length_skipped();
+        if let Some(i) = NRX1.iter().position(|reg| *reg == addr) {
+            self.channels[i].load_length(value);
+        } else if let Some(i) = NRX4.iter().position(|reg| *reg == addr) {
+            let enabling = !mmu.read_bit(addr, 6) && value & 0x40 != 0;
+            if enabling && skipped && self.channels[i].length_clock(true) && value & 0x80 == 0 {
+                mmu.set_bit(ioregs::NR_52, i as u8, false);
+            }
+        }
+    }
+
+    /* NRx2 write, needs register value from before it. Only matters with zombie mode quirk. */
+    pub fn write_envelope(&mut self, mmu: &mut MMU<impl BankController>, addr: u16, value: u8) {
+        if !self.quirks.zombie_mode {
+            return;
+        }
+        if let Some(i) = NRX2.iter().position(|reg| *reg == addr) {
+            if mmu.read_bit(ioregs::NR_52, i as u8) {
+                self.channels[i].envelope_write(mmu.read(addr), value);
+            }
+        }
+    }
+
+    /* Envelope volume(0-15) of channel 1, 2 or 4, wave channel has none */
+    pub fn volume(&self, chan: u8) -> u16 {
+        self.channel(chan).map(|chan| chan.volume()).unwrap_or(0)
+    }
+
+    /* Times channel 1-4 was triggered since APU was made */
+    pub fn triggers(&self, chan: u8) -> u64 {
+        self.triggers.get((chan as usize).wrapping_sub(1)).cloned().unwrap_or(0)
+    }
+
+    /* Noise channel shift register, bits 0-14 */
+    pub fn noise_lfsr(&self) -> u16 {
+        self.channels[3].position()
+    }
+
+    /* Length counter of channel 1-4, sequencer steps left before it stops */
+    pub fn length(&self, chan: u8) -> u16 {
+        self.channel(chan).map(|chan| chan.length()).unwrap_or(0)
+    }
+
+    /* Status of channel 1-4 for debug views, without decoding NR registers. Nonexistent channel gives default. */
+    pub fn channel_debug(&self, mmu: &MMU<impl BankController>, chan: u8) -> ChannelDebugInfo {
+        let io = &mmu.ioregs;
+        match self.channel(chan) {
+            Some(channel) => ChannelDebugInfo {
+                enabled: io.read_bit(ioregs::NR_52, chan - 1),
+                dac_on: channel.dac_enabled(io),
+                frequency: channel.frequency(io),
+                volume: channel.volume(),
+                length: channel.length(),
+                duty: channel.duty(io),
+            },
+            None => ChannelDebugInfo::default(),
+        }
+    }
+
+    fn channel(&self, chan: u8) -> Option<&dyn Channel> {
+        self.channels.get((chan as usize).wrapping_sub(1)).map(|chan| chan.as_ref())
+    }
+
+    /* Connects cart audio to VIN, None leaves it silent. NR50 decides which terminals hear it. */
+    pub fn set_vin(&mut self, source: Option<VinSource>) {
+        self.vin = source;
+    }
+
+    pub fn has_vin(&self) -> bool {
+        self.vin.is_some()
+    }
+
+    /* Master volume(0-7) of left and right terminal, NR50 */
+    pub fn master_volume(mmu: &mut MMU<impl BankController>) -> (u8, u8) {
+        let nr_50 = mmu.read(ioregs::NR_50);
+        (nr_50 & 0x07, (nr_50 >> 4) & 0x07)
+    }
+
     /* Is channel conected to left channel? */
     pub fn SO1(mmu: &mut MMU<impl BankController>, chan: u8) -> bool {
         if chan > 4 || chan == 0 {
@@ -725,49 +1090,55 @@ impl APU {
         (nr_51 & (1 << chan)) != 0
     }
 
-    pub fn left_samples(&mut self) -> &mut Vec<i16> {
-        &mut self.left
-    }
-    pub fn right_samples(&mut self) -> &mut Vec<i16> {
-        &mut self.right
+    /* Changes rate at which samples are produced. It can't be higher than CPU frequency. */
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_clock.rate = rate.clamp(1, CPU_FREQUENCY);
     }
 
-    pub fn chan1_disable(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan1.regs._ENABLED(mmu, false);
-    }
-    pub fn chan2_disable(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan2.regs._ENABLED(mmu, false);
-    }
-    pub fn chan3_disable(&mut self, mmu: &mut MMU<impl BankController>) {
-        WaveRamChannel::_ENABLED(mmu, false);
-    }
-    pub fn chan4_disable(&mut self, mmu: &mut MMU<impl BankController>) {
-        NoiseChannel::_ENABLED(mmu, false);
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_clock.rate
     }
 
-    pub fn chan1_samples(&mut self) -> &mut Vec<i16> {
-        self.chan1.buffer()
-    }
-    pub fn chan2_samples(&mut self) -> &mut Vec<i16> {
-        self.chan2.buffer()
-    }
-    pub fn chan3_samples(&mut self) -> &mut Vec<i16> {
-        self.chan3.buffer()
+    /* Peak amplitude of channels 1-4 since last call, for activity meters */
+    pub fn take_levels(&mut self) -> [u16; 4] {
+        std::mem::take(&mut self.levels)
     }
-    pub fn chan4_samples(&mut self) -> &mut Vec<i16> {
-        self.chan4.buffer()
+
+    /*
+     * Recent waveform of channel 1-4 for oscilloscope views, oldest first, at most SCOPE_SAMPLES long. Taken at sample
+     * rate before mute and solo, stopped channel shows up as 0.
+     */
+    pub fn scope(&self, chan: u8) -> Vec<i16> {
+        match self.scope.get((chan as usize).wrapping_sub(1)) {
+            Some(scope) => scope.iter().cloned().collect(),
+            None => Vec::new(),
+        }
     }
 
-    pub fn chan1_reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan1.reset(mmu);
+    /* Number of values pull_samples() has ready, counting left and right separately */
+    pub fn available_samples(&self) -> usize {
+        2 * self.left.len().min(self.right.len())
     }
-    pub fn chan2_reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan2.reset(mmu);
+
+    /*
+     * Moves mixed samples into out, interleaved as L, R, L, R..., oldest first. Returns how many values were written,
+     * always even. Made for audio callbacks, which hand out a buffer to fill.
+     */
+    pub fn pull_samples(&mut self, out: &mut [i16]) -> usize {
+        let len = (out.len() / 2).min(self.available_samples() / 2);
+        for (i, pair) in out.chunks_exact_mut(2).take(len).enumerate() {
+            pair[0] = self.left[i];
+            pair[1] = self.right[i];
+        }
+        self.left.drain(..len);
+        self.right.drain(..len);
+        2 * len
     }
-    pub fn chan3_reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan3.reset(mmu);
+
+    pub fn left_samples(&mut self) -> &mut Vec<i16> {
+        &mut self.left
     }
-    pub fn chan4_reset(&mut self, mmu: &mut MMU<impl BankController>) {
-        self.chan4.reset(mmu);
+    pub fn right_samples(&mut self) -> &mut Vec<i16> {
+        &mut self.right
     }
 }
@@ -1,6 +1,8 @@
 #![allow(non_snake_case, non_camel_case_types, dead_code)]
 
 use super::*;
+use super::super::mem::mbc::RomOnly;
+use super::super::mem::{ROM_SWITCHABLE_ADDR, VRAM_ADDR};
 use std::fmt;
 use std::num::Wrapping;
 
@@ -46,9 +48,9 @@ fn add_w_carry(op1: u16, op2: u16) -> bool {
 fn sub_b_carry(op1: u8, op2: u8) -> bool {
     op1 < op2
 }
-// ex. SP+r8. It checks overflow on 7th bit
+// ex. SP+r8. Offset is added to low byte as unsigned, whatever its sign
 fn add_signed_carry(op1: u16, op2: u8) -> bool {
-    (safe_signed_add(op1, op2) & 0xFF) < (op1 & 0xFF)
+    add_b_carry(op1 as u8, op2)
 }
 
 // Predicates for half carry flag check
@@ -62,7 +64,7 @@ fn sub_b_hcarry(op1: u8, op2: u8) -> bool {
     (op1 & 0xF) < (op2 & 0xF)
 }
 fn add_signed_hcarry(op1: u16, op2: u8) -> bool {
-    (safe_signed_add(op1, op2) & 0xF) < (op1 & 0xF)
+    add_b_hcarry(op1 as u8, op2)
 }
 
 // Safe add/sub to prevent runtime overflow errorsaaaa
@@ -79,12 +81,8 @@ fn safe_w_sub(op1: u16, op2: u16) -> u16 {
     (Wrapping(op1) - Wrapping(op2)).0
 }
 fn safe_signed_add(op1: u16, op2: u8) -> u16 {
-    let s = op2 as i8;
-    if s >= 0 {
-        (Wrapping(op1) + Wrapping(op2 as u16)).0
-    } else {
-        (Wrapping(op1) - Wrapping((-s) as u16)).0
-    }
+    // Sign extended, so -128 doesn't overflow on negation
+    op1.wrapping_add(op2 as i8 as u16)
 }
 pub const ZP_ADDR: u16 = 0xFF00;
 const B_IDX: u8 = 0;
@@ -244,8 +242,14 @@ fn handle_cb(cpu: &mut CPU, s: &mut State<impl BankController>, op: u8) -> u8 {
     }
 }
 
-/* Decoder for Gameboy CPU (LR35902) instructions */
-fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
+/*
+ * Decoder for Gameboy CPU (LR35902) instructions. 0xCB prefixed ones are told apart by the second byte, next is
+ * called to fetch it, only for them.
+ */
+fn decode<T: BankController>(op: u8, next: impl FnOnce() -> u8) -> Option<Instruction<'static, T>> {
+    if op == 0xCB {
+        return Some(decode_cb(next()));
+    }
     let (mnemo, size, f): (&str, u8, Box<InstructionHandler<T>>) = match op {
         /* Misc/Control instructions */
         0x00 => ("NOP",    1, Box::new(|_, _, _, _, _| 1)),
@@ -294,9 +298,6 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
             1
         })),
 
-        /* 0xCB instruction set */
-        0xCB => ("PREFIX CB", 2, Box::new(|cpu, s, _, op, _| { handle_cb(cpu, s, op) })),
-
         /* 8bit load/store/move instructions */
         // To B register
         0x40 | 0x41 | 0x42 | 0x43 | 0x44 | 0x45 | 0x46 | 0x47 => ("LD B, reg", 1, Box::new(|cpu, s, op, _, _| {
@@ -480,32 +481,12 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
         0x88 | 0x89 | 0x8A | 0x8B | 0x8C | 0x8D | 0x8E | 0x8F => ("ADC A, reg", 1, Box::new(|cpu, s, op, _, _| {
             let idx = op & 0x7;
             let val = cpu.reg(s, idx);
-            let carry = if cpu.C { 1 } else { 0 };
-            // If carry happens when adding (+ val)
-            cpu.H = add_b_hcarry(cpu.A, val);
-            cpu.C = add_b_carry(cpu.A, val);
-            cpu.A = safe_b_add(cpu.A, val);
-            // If carry happens when (+ carry)
-            cpu.H |= add_b_hcarry(cpu.A, carry);
-            cpu.C |= add_b_carry(cpu.A, carry);
-            cpu.A  = safe_b_add(cpu.A, carry);
-            cpu.N = false;
-            cpu.Z = cpu.A == 0;
+            cpu.adc(val);
             if idx == ADDR_HL_IDX { 2 } else { 1 }
         })),
         // Add immediate with carry
         0xCE => ("ADC A, d8", 2, Box::new(|cpu, _, _, val, _| {
-            let carry = if cpu.C { 1 } else { 0 };
-            // If carry happens when (+ val)
-            cpu.H = add_b_hcarry(cpu.A, val);
-            cpu.C = add_b_carry(cpu.A, val);
-            cpu.A = safe_b_add(cpu.A, val);
-            // If carry happens when (+ carry)
-            cpu.H |= add_b_hcarry(cpu.A, carry);
-            cpu.C |= add_b_carry(cpu.A, carry);
-            cpu.A  = safe_b_add(cpu.A, carry);
-            cpu.N = false;
-            cpu.Z = cpu.A == 0;
+            cpu.adc(val);
             2
         })),
         // Sub register without carry
@@ -532,32 +513,12 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
         0x98 | 0x99 | 0x9A | 0x9B | 0x9C | 0x9D | 0x9E | 0x9F => ("SBC A, reg", 1, Box::new(|cpu, s, op, _, _| {
             let idx = op & 0x7;
             let val = cpu.reg(s, idx);
-            let carry = if cpu.C { 1 } else { 0 };
-            // If carry happens when (- reg)
-            cpu.H = sub_b_hcarry(cpu.A, val);
-            cpu.C = sub_b_carry(cpu.A, val);
-            cpu.A = safe_b_sub(cpu.A, val);
-            // If carry happens when (- carry)
-            cpu.H |= sub_b_hcarry(cpu.A, carry);
-            cpu.C |= sub_b_carry(cpu.A, carry);
-            cpu.A  = safe_b_sub(cpu.A, carry);
-            cpu.N = true;
-            cpu.Z = cpu.A == 0;
+            cpu.sbc(val);
             if idx == ADDR_HL_IDX { 2 } else { 1 }
         })),
         // Sub immediate with carry
         0xDE => ("SBC A, d8", 2, Box::new(|cpu, _, _, val, _| {
-            let carry = if cpu.C { 1 } else { 0 };
-            // If carry happens when (- reg)
-            cpu.H = sub_b_hcarry(cpu.A, val);
-            cpu.C = sub_b_carry(cpu.A, val);
-            cpu.A = safe_b_sub(cpu.A, val);
-            // If carry happens when (- carry)
-            cpu.H |= sub_b_hcarry(cpu.A, carry);
-            cpu.C |= sub_b_carry(cpu.A, carry);
-            cpu.A  = safe_b_sub(cpu.A, carry);
-            cpu.N = true;
-            cpu.Z = cpu.A == 0;
+            cpu.sbc(val);
             2
         })),
         // AND with register
@@ -666,15 +627,15 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
 
         /* 16 bit ALU */
         // 16bit increments
-        0x03 => ("INC BC", 1, Box::new(|cpu, _, _, _, _| { cpu.BC.set(safe_w_add(cpu.BC.val(), 1)); 2 })),
-        0x13 => ("INC DE", 1, Box::new(|cpu, _, _, _, _| { cpu.DE.set(safe_w_add(cpu.DE.val(), 1)); 2 })),
-        0x23 => ("INC HL", 1, Box::new(|cpu, _, _, _, _| { cpu.HL.set(safe_w_add(cpu.HL.val(), 1)); 2 })),
-        0x33 => ("INC SP", 1, Box::new(|cpu, _, _, _, _| { cpu.SP = safe_w_add(cpu.SP, 1);  2 })),
+        0x03 => ("INC BC", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.BC.val()); cpu.BC.set(safe_w_add(cpu.BC.val(), 1)); 2 })),
+        0x13 => ("INC DE", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.DE.val()); cpu.DE.set(safe_w_add(cpu.DE.val(), 1)); 2 })),
+        0x23 => ("INC HL", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.HL.val()); cpu.HL.set(safe_w_add(cpu.HL.val(), 1)); 2 })),
+        0x33 => ("INC SP", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.SP); cpu.SP = safe_w_add(cpu.SP, 1);  2 })),
         // 16 bit decrements
-        0x0B => ("DEC BC", 1, Box::new(|cpu, _, _, _, _| { cpu.BC.set(safe_w_sub(cpu.BC.val(), 1)); 2 })),
-        0x1B => ("DEC DE", 1, Box::new(|cpu, _, _, _, _| { cpu.DE.set(safe_w_sub(cpu.DE.val(), 1)); 2 })),
-        0x2B => ("DEC HL", 1, Box::new(|cpu, _, _, _, _| { cpu.HL.set(safe_w_sub(cpu.HL.val(), 1)); 2 })),
-        0x3B => ("DEC SP", 1, Box::new(|cpu, _, _, _, _| { cpu.SP = safe_w_sub(cpu.SP, 1); 2 })),
+        0x0B => ("DEC BC", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.BC.val()); cpu.BC.set(safe_w_sub(cpu.BC.val(), 1)); 2 })),
+        0x1B => ("DEC DE", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.DE.val()); cpu.DE.set(safe_w_sub(cpu.DE.val(), 1)); 2 })),
+        0x2B => ("DEC HL", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.HL.val()); cpu.HL.set(safe_w_sub(cpu.HL.val(), 1)); 2 })),
+        0x3B => ("DEC SP", 1, Box::new(|cpu, s, _, _, _| { s.oam_bug(cpu.SP); cpu.SP = safe_w_sub(cpu.SP, 1); 2 })),
         // 16 bit adds
         0x09 => ("ADD HL, BC", 1, Box::new(|cpu, _, _, _, _| {
             let (r1, r2) = (&mut cpu.HL, &mut cpu.BC);
@@ -787,14 +748,14 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
         })),
 
         /* RESTARTS */
-        0xC7 => ("RST 00", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0000); 4 })),
-        0xCF => ("RST 08", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0008); 4 })),
-        0xD7 => ("RST 10", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0010); 4 })),
-        0xDF => ("RST 18", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0018); 4 })),
-        0xE7 => ("RST 20", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0020); 4 })),
-        0xEF => ("RST 28", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0028); 4 })),
-        0xF7 => ("RST 30", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0030); 4 })),
-        0xFF => ("RST 38", 1, Box::new(|cpu, s, _, _, _| { cpu.call(s, 0x0038); 4 })),
+        0xC7 => ("RST 00", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0000); 4 })),
+        0xCF => ("RST 08", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0008); 4 })),
+        0xD7 => ("RST 10", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0010); 4 })),
+        0xDF => ("RST 18", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0018); 4 })),
+        0xE7 => ("RST 20", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0020); 4 })),
+        0xEF => ("RST 28", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0028); 4 })),
+        0xF7 => ("RST 30", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0030); 4 })),
+        0xFF => ("RST 38", 1, Box::new(|cpu, s, _, _, _| { cpu.rst(s, 0x0038); 4 })),
 
         /* CALLS */
         0xCD => ("CALL a16", 3, Box::new(|cpu, s, _, op1, op2| { cpu.call(s, word(op2, op1)); 6 })),
@@ -836,6 +797,56 @@ fn decode<T: BankController>(op: u8) -> Option<Instruction<'static, T>> {
     Some(Instruction::new(mnemo, size, f))
 }
 
+/* 0xCB instruction set, op is the byte after prefix. Operands are encoded in it, so the handler ignores its own. */
+fn decode_cb<T: BankController>(op: u8) -> Instruction<'static, T> {
+    let mnemo = match op >> 6 {
+        0 => CB_SHIFT_MNEMOS[((op >> 3) & 0x7) as usize],
+        1 => "BIT bit, reg",
+        2 => "RES bit, reg",
+        _ => "SET bit, reg",
+    };
+    Instruction::new(mnemo, 2, Box::new(move |cpu, s, _, _, _| handle_cb(cpu, s, op)))
+}
+
+const REG_NAMES: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const CB_SHIFT_MNEMOS: [&str; 8] =
+    ["RLC reg", "RRC reg", "RL reg", "RR reg", "SLA reg", "SRA reg", "SWAP reg", "SRL reg"];
+
+/*
+ * disassemble()
+ * Decodes instruction at addr. `bytes` start with the opcode, operand bytes which are missing are read as 0.
+ * Returns textual form with operands filled in and instruction size, or None for invalid opcodes.
+ */
+pub fn disassemble(addr: u16, bytes: &[u8]) -> Option<(String, u8)> {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let op = byte(0);
+    let Instruction { mnemo, size, .. } = decode::<RomOnly>(op, || byte(1))?;
+
+    if op == 0xCB {
+        let cb = byte(1);
+        let bit = ((cb >> 3) & 0x7).to_string();
+        let text = mnemo.replace("bit", &bit).replace("reg", REG_NAMES[(cb & 0x7) as usize]);
+        return Some((text, size));
+    }
+
+    // INC/DEC encode register in bits 3-5, everything else in bits 0-2
+    let reg_idx = if mnemo == "INC reg" || mnemo == "DEC reg" { (op >> 3) & 0x7 } else { op & 0x7 };
+    let next = safe_w_add(addr, size as u16);
+    let text = mnemo
+        .replace("reg", REG_NAMES[reg_idx as usize])
+        .replace("d16", &format!("${:04X}", word(byte(2), byte(1))))
+        .replace("a16", &format!("${:04X}", word(byte(2), byte(1))))
+        .replace("d8", &format!("${:02X}", byte(1)))
+        .replace("a8", &format!("$FF{:02X}", byte(1)));
+    // Relative jumps show target address, SP arithmetic shows signed offset
+    let text = if text.starts_with("JR") {
+        text.replace("r8", &format!("${:04X}", safe_signed_add(next, byte(1))))
+    } else {
+        text.replace("r8", &format!("{}", byte(1) as i8))
+    };
+    Some((text, size))
+}
+
 #[repr(C)]
 pub union Reg {
     /* For lower and upper register bytes */
@@ -879,6 +890,19 @@ impl Default for Reg {
         Self { word: 0x0000 }
     }
 }
+// Union can't derive it, register is stored as plain 16-bit value
+#[cfg(feature = "serde")]
+impl serde::Serialize for Reg {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.val())
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Reg {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <u16 as serde::Deserialize>::deserialize(deserializer).map(Reg::new)
+    }
+}
 impl fmt::Debug for Reg {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -893,6 +917,7 @@ impl fmt::Debug for Reg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPU {
     /* Main registers */
     pub A: u8,
@@ -911,6 +936,9 @@ pub struct CPU {
     pub STOP: bool,
     pub HALT: bool,
     HALT_BUG: bool,
+    /* Shadow call stack, only kept when call trace is on. Not part of saved state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    call_stack: Option<Vec<CallFrame>>,
 }
 impl Default for CPU {
     // Default F = 0xB0 = 0b10110000 = ZHC
@@ -930,32 +958,79 @@ impl Default for CPU {
             STOP: false,
             HALT: false,
             HALT_BUG: false,
+            call_stack: None,
         }
     }
 }
 
-/*
- * Bit 0: V-Blank  Interrupt Request (INT 40h)
- * Bit 1: LCD STAT Interrupt Request (INT 48h)
- * Bit 2: Timer    Interrupt Request (INT 50h)
- * Bit 3: Serial   Interrupt Request (INT 58h)
- * Bit 4: Joypad   Interrupt Request (INT 60h)
- */
-const VBLANK_INT: usize = 0;
-const STAT_INT: usize = 1;
-const TIMER_INT: usize = 2;
-const SERIAL_INT: usize = 3;
-const JOYPAD_INT: usize = 4;
+/* How routine was entered */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+/* Single entry of call trace */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    /* Address of the routine */
+    pub target: u16,
+    /* Address pushed on the stack */
+    pub ret: u16,
+    /* Where the return address is kept */
+    pub sp: u16,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            CallKind::Call => "CALL",
+            CallKind::Rst => "RST",
+            CallKind::Interrupt => "INT",
+        };
+        write!(f, "{} 0x{:04X}, returns to 0x{:04X}, SP=0x{:04X}", kind, self.target, self.ret, self.sp)
+    }
+}
+
+/* Deepest call trace kept, oldest entries are dropped past it. Code that never returns shouldn't eat memory. */
+pub const CALL_TRACE_DEPTH: usize = 1024;
 
-const IVT_SIZE: usize = 5;
-const IVT: [u8; IVT_SIZE] = [0x40, 0x48, 0x50, 0x58, 0x60];
+/* Bits of F which exist, Z N H C */
+const F_MASK: u8 = 0xF0;
 
 impl CPU {
     pub fn new() -> Self {
         Default::default()
     }
 
+    /* Starts or stops tracking CALL/RST/interrupt entries. Trace starts empty, calls made before are unknown. */
+    pub fn set_call_trace(&mut self, on: bool) {
+        self.call_stack = if on { Some(Vec::new()) } else { None };
+    }
+
+    pub fn call_trace(&self) -> bool {
+        self.call_stack.is_some()
+    }
+
+    /*
+     * Routines CPU is currently in, innermost first. Empty when call trace is off. Frames the stack pointer
+     * already moved past(e.g. return address popped without RET) are left out.
+     */
+    pub fn backtrace(&self) -> Vec<CallFrame> {
+        self.call_stack
+            .iter()
+            .flatten()
+            .rev()
+            .filter(|frame| frame.sp >= self.SP)
+            .cloned()
+            .collect()
+    }
+
     // step() executes single instruction and returns number of machine cycles taken
+    // Fetch wraps from 0xFFFF to 0x0000. Opcode and operands are plain reads, wherever PC points: code can run from
+    // IO registers, OAM or IE, getting their current values, and debugger and observers see each fetched byte.
     pub fn step(&mut self, state: &mut State<impl BankController>) -> u64 {
         // If HALT or STOP flags set, CPU executes NOPs without incrementing PC.
         if self.HALT || self.STOP { return 1; }
@@ -963,25 +1038,42 @@ impl CPU {
         let pc = self.PC.val();
         let op = state.safe_read(pc);
 
+        // Second byte of 0xCB prefixed instruction is fetched by decode, it's not read again as operand
+        let mut cb = None;
         let Instruction {
             size,
             handler: mut f,
             mnemo
-        } = decode(op)
+        } = decode(op, || *cb.insert(state.safe_read(pc.wrapping_add(1))))
             .unwrap_or_else(|| panic!("Unrecognized OPCODE 0x{:x} at 0x{:x}. {:?}", op, pc, self));
         let argc = size - 1;
-        let op1 = if argc >= 1 {
-            state.safe_read(pc + 1)
-        } else {
-            0
+        let op1 = match cb {
+            Some(cb) => cb,
+            None if argc >= 1 => state.safe_read(pc.wrapping_add(1)),
+            None => 0,
         };
         let op2 = if argc >= 2 {
-            state.safe_read(pc + 2)
+            state.safe_read(pc.wrapping_add(2))
         } else {
             0
         };
 
-        // println!("PC 0x:{:4x}, {}", self.PC.val(), mnemo);
+        if log::log_enabled!(target: "cpu", log::Level::Trace) {
+            let text = disassemble(pc, &[op, op1, op2]).map_or_else(|| mnemo.to_string(), |(text, _)| text);
+            match state.symbols.as_ref() {
+                Some(symbols) => {
+                    let bank = state.mmu.mapper.current_rom_bank();
+                    let label = symbols.symbolize(bank, pc).unwrap_or_default();
+                    log::trace!(target: "cpu", "PC 0x{:04x}({}): {}", pc, label, symbols.annotate(&text, bank));
+                }
+                // Code in switchable area is ambiguous without bank
+                None if (ROM_SWITCHABLE_ADDR..VRAM_ADDR).contains(&pc) => {
+                    let bank = state.mmu.mapper.current_rom_bank();
+                    log::trace!(target: "cpu", "PC 0x{:04x}(bank 0x{:02X}): {}", pc, bank, text);
+                }
+                None => log::trace!(target: "cpu", "PC 0x{:04x}: {}", pc, text),
+            }
+        }
 
         if !self.HALT_BUG {
             self.PC.set(safe_w_add(self.PC.val(), size as u16));
@@ -1000,10 +1092,11 @@ impl CPU {
          * 0 - Disable jumps to IVT
          * 1 - Enable jumps to IVT
          */
-        let in_e = state.safe_read(ioregs::IE);
-        let in_f = state.safe_read(ioregs::IF);
-        let is_requested = |bit: usize| {
-            (in_f & (1 << bit) & in_e) != 0
+        // Polled by CPU itself, not a read program made, so observers and bus stats don't see it
+        let in_e = state.mmu.peek(ioregs::IE);
+        let in_f = state.mmu.peek(ioregs::IF);
+        let is_requested = |interrupt: Interrupt| {
+            (in_f & interrupt.mask() & in_e) != 0
         };
 
         if !self.IME && self.HALT {
@@ -1011,15 +1104,14 @@ impl CPU {
             return 1;
         }
 
-        for bit in 0..IVT_SIZE {
+        for interrupt in Interrupt::ALL {
             // If it's stopped only JOYPAD interrupt can resume.
-            // if self.STOP && bit != JOYPAD_INT { continue; }
-            if is_requested(bit) {
-                println!("INT {}, IME: {}, H: {}", bit, self.IME, self.HALT);
+            // if self.STOP && interrupt != Interrupt::Joypad { continue; }
+            if is_requested(interrupt) {
+                log::trace!(target: "cpu", "INT {:?}, IME: {}, H: {}", interrupt, self.IME, self.HALT);
                 let mut cycles = 0;
                 if self.IME {
-                    self.call(state, IVT[bit] as u16);
-                    state.mmu.set_bit(ioregs::IF, bit as u8, false);
+                    self.dispatch(state);
                     self.IME = false;
                     cycles += 5;
                 }
@@ -1032,6 +1124,28 @@ impl CPU {
         0
     }
 
+    // A + val + C, flags come from the whole sum at once
+    fn adc(&mut self, val: u8) {
+        let carry = self.C as u16;
+        let sum = self.A as u16 + val as u16 + carry;
+        self.H = (self.A & 0xF) as u16 + (val & 0xF) as u16 + carry > 0xF;
+        self.C = sum > 0xFF;
+        self.A = sum as u8;
+        self.N = false;
+        self.Z = self.A == 0;
+    }
+
+    // A - val - C, flags come from the whole difference at once
+    fn sbc(&mut self, val: u8) {
+        let carry = self.C as i16;
+        let diff = self.A as i16 - val as i16 - carry;
+        self.H = (self.A & 0xF) as i16 - (val & 0xF) as i16 - carry < 0;
+        self.C = diff < 0;
+        self.A = diff as u8;
+        self.N = true;
+        self.Z = self.A == 0;
+    }
+
     // Some utility methods
     fn read_HL(&self, state: &mut State<impl BankController>) -> u8 {
         state.safe_read(self.HL.val())
@@ -1070,7 +1184,7 @@ impl CPU {
         };
     }
 
-    // Returns flag register as byte
+    // Returns flag register as byte, lower nibble is always 0
     pub fn F(&self) -> u8 {
         let mut f = 0u8;
         f |= if self.Z { 1 << 7 } else { 0 };
@@ -1080,8 +1194,9 @@ impl CPU {
         f
     }
 
-    // Updates flags using received byte
+    // Updates flags using received byte. Lower nibble isn't there in hardware, so POP AF can't set it.
     pub fn set_F(&mut self, val: u8) {
+        let val = val & F_MASK;
         self.Z = val & (1 << 7) != 0;
         self.N = val & (1 << 6) != 0;
         self.H = val & (1 << 5) != 0;
@@ -1089,11 +1204,60 @@ impl CPU {
     }
 
     fn call(&mut self, state: &mut State<impl BankController>, addr: u16) {
-        self.push_u16(state, self.PC.val());
+        self.enter(state, addr, CallKind::Call);
+    }
+
+    fn rst(&mut self, state: &mut State<impl BankController>, addr: u16) {
+        self.enter(state, addr, CallKind::Rst);
+    }
+
+    fn enter(&mut self, state: &mut State<impl BankController>, addr: u16, kind: CallKind) {
+        let ret = self.PC.val();
+        self.push_u16(state, ret);
         self.PC.set(addr);
+        self.trace_call(kind, addr, ret);
+    }
+
+    fn trace_call(&mut self, kind: CallKind, target: u16, ret: u16) {
+        if let Some(stack) = self.call_stack.as_mut() {
+            if stack.len() == CALL_TRACE_DEPTH {
+                stack.remove(0);
+            }
+            stack.push(CallFrame { kind, target, ret, sp: self.SP });
+        }
+    }
+
+    /*
+     * Interrupt dispatch, 5 cycles. Interrupt to take is picked after upper byte of PC is pushed, so when that
+     * push lands on IE(SP was 0x0000) or IF and clears the request, next pending one is taken instead, or none
+     * and CPU jumps to 0x0000. Only IF bit of the interrupt taken is cleared.
+     */
+    fn dispatch(&mut self, state: &mut State<impl BankController>) {
+        let ret = self.PC.val();
+        self.SP = safe_w_sub(self.SP, 1);
+        state.safe_write(self.SP, (ret >> 8) as u8);
+        let pending = state.mmu.read(ioregs::IE) & state.mmu.read(ioregs::IF);
+        let target = match Interrupt::ALL.iter().find(|interrupt| pending & interrupt.mask() != 0) {
+            Some(interrupt) => {
+                state.mmu.set_bit(ioregs::IF, interrupt.bit(), false);
+                interrupt.vector()
+            }
+            None => 0x0000,
+        };
+        self.SP = safe_w_sub(self.SP, 1);
+        state.safe_write(self.SP, ret as u8);
+        self.PC.set(target);
+        self.trace_call(CallKind::Interrupt, target, ret);
     }
 
     fn ret(&mut self, state: &mut State<impl BankController>) {
+        let sp = self.SP;
+        if let Some(stack) = self.call_stack.as_mut() {
+            // Drops the matching frame, and ones left behind when game unwound the stack by itself
+            while stack.last().is_some_and(|frame| frame.sp <= sp) {
+                stack.pop();
+            }
+        }
         let addr = self.pop_u16(state);
         self.PC.set(addr);
     }
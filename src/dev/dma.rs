@@ -2,10 +2,16 @@
 
 use super::*;
 
-const TRANSFER_SIZE: usize = 140;
+/* Whole sprite attribute table, 40 sprites 4 bytes each */
+const TRANSFER_SIZE: usize = 0xA0;
+/* DMA reads pages up to WRAM end. Higher ones land in WRAM too, like echo, it never sees OAM or IO. */
+const LAST_SOURCE_PAGE: u8 = 0xDF;
+const ECHO_OFFSET: u8 = 0x20;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DMA {
     active: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_array"))]
     buff: [u8; TRANSFER_SIZE],
 }
 
@@ -23,8 +29,11 @@ impl<T: BankController> Clocked<T> for DMA {
             return;
         }
         let addr = DMA::FROM(mmu);
-        for i in 0..TRANSFER_SIZE {
-            self.buff[i] = mmu.read(addr + i as u16);
+        log::trace!(target: "dma", "OAM transfer from 0x{:04X}", addr);
+        for i in (0..TRANSFER_SIZE).step_by(2) {
+            let [low, high] = mmu.read_word(addr.wrapping_add(i as u16)).to_le_bytes();
+            self.buff[i] = low;
+            self.buff[i + 1] = high;
         }
         let dest = &mut mmu.oam[..];
         for i in 0..TRANSFER_SIZE {
@@ -47,7 +56,18 @@ impl DMA {
     pub fn active(&self) -> bool {
         self.active
     }
+    /*
+     * Source of transfer, from page in DMA register. ROM and cart RAM are read with banks currently selected,
+     * pages past 0xDF are clamped to WRAM.
+     */
     fn FROM(mmu: &mut MMU<impl BankController>) -> u16 {
-        (mmu.read(ioregs::DMA) as u16) << 8
+        let page = mmu.read(ioregs::DMA);
+        let page = if page > LAST_SOURCE_PAGE {
+            log::debug!(target: "dma", "OAM transfer from page 0x{:02X} reads WRAM", page);
+            page - ECHO_OFFSET
+        } else {
+            page
+        };
+        (page as u16) << 8
     }
 }
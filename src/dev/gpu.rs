@@ -16,7 +16,7 @@ pub const VBLANK_HEIGHT: usize = 10;
 const OAM_SEARCH_CYCLES: u64 = 20;
 const LCD_TRANSFER_CYCLES: u64 = 43;
 const HBLANK_CYCLES: u64 = 51;
-const SCANLINE_CYCLES: u64 = OAM_SEARCH_CYCLES + LCD_TRANSFER_CYCLES + HBLANK_CYCLES;
+pub const SCANLINE_CYCLES: u64 = OAM_SEARCH_CYCLES + LCD_TRANSFER_CYCLES + HBLANK_CYCLES;
 pub const FRAME_CYCLES: u64 = SCANLINE_CYCLES * (VBLANK_HEIGHT + SCREEN_HEIGHT) as u64;
 
 pub const SCANLINE_STEPS: u64 = 3; // OAM -> LCD -> HBLANK -> (OAM -> LCD -> HBLANK ->)
@@ -35,7 +35,6 @@ pub const WHITE: Color = (255, 255, 255);
 pub const LIGHT_GRAY: Color = (184, 184, 184);
 pub const DARK_GRAY: Color = (115, 115, 155);
 pub const BLACK: Color = (0, 0, 0);
-pub const TRANSPARENT: Color = (0, 255, 0);
 
 fn get_color(num: u8) -> Color {
     match num {
@@ -47,7 +46,68 @@ fn get_color(num: u8) -> Color {
     }
 }
 
+/* What framebuffer pixel was last drawn by, for priority visualization */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelSource {
+    /* Nothing drawn: background disabled in LCDC or hidden */
+    Blank,
+    Background,
+    Window,
+    Sprite,
+    /* Sprite with BG priority flag, drawn over background color 0 */
+    SpriteBehind,
+    /* Background which covered a sprite with BG priority flag */
+    SpriteHidden,
+}
+
+impl PixelSource {
+    /* Color pixels of this source are mixed with */
+    pub fn tint(self) -> Color {
+        match self {
+            PixelSource::Blank => (128, 128, 128),
+            PixelSource::Background => (0, 96, 255),
+            PixelSource::Window => (0, 200, 0),
+            PixelSource::Sprite => (255, 0, 0),
+            PixelSource::SpriteBehind => (255, 0, 255),
+            PixelSource::SpriteHidden => (255, 200, 0),
+        }
+    }
+}
+
+/*
+ * Palette register pixel's color went through: BGP for background and window, OBP0 or OBP1 for sprites. GBC
+ * colorizes DMG games with separate palette for each, frontends can do the same.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteLayer {
+    Background,
+    Object0,
+    Object1,
+}
+
+impl PaletteLayer {
+    pub const ALL: [PaletteLayer; 3] = [PaletteLayer::Background, PaletteLayer::Object0, PaletteLayer::Object1];
+}
+
+/* Half of color, half of tint of the source */
+pub fn tint_pixel(color: Color, source: PixelSource) -> Color {
+    let (r, g, b) = source.tint();
+    let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+    (mix(color.0, r), mix(color.1, g), mix(color.2, b))
+}
+
+/* Line GPU has drawn, with scroll and window registers as they were when drawing started */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Scanline {
+    pub ly: u8,
+    pub scx: u8,
+    pub scy: u8,
+    pub wx: u8,
+    pub wy: u8,
+}
+
 #[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sprite {
     y: u8,
     x: u8,
@@ -76,7 +136,29 @@ fn read_oam(mmu: &mut MMU<impl BankController>, sprites: &mut [Sprite; SPRITE_CO
     sprites.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
 }
 
-#[derive(Debug, PartialEq)]
+/* Layers restored from save state are all shown */
+#[cfg(feature = "serde")]
+fn shown() -> bool {
+    true
+}
+
+fn blank_sources() -> Vec<PixelSource> {
+    vec![PixelSource::Blank; SCREEN_WIDTH * SCREEN_HEIGHT]
+}
+
+fn rgba8(pixels: &[Color], out: &mut Vec<u8>) {
+    out.clear();
+    out.reserve(4 * pixels.len());
+    for (r, g, b) in pixels {
+        out.extend_from_slice(&[*r, *g, *b, 0xFF]);
+    }
+}
+
+fn background_layers() -> Vec<PaletteLayer> {
+    vec![PaletteLayer::Background; SCREEN_WIDTH * SCREEN_HEIGHT]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GPUMode {
     HBLANK,
     VBLANK,
@@ -90,16 +172,71 @@ impl Default for GPUMode {
     }
 }
 
+/*
+ * Hardware differences and bugs, which not every game expects.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GPUQuirks {
+    /* Writing LY resets it to 0, instead of being ignored */
+    pub ly_write_resets: bool,
+    /* DMG bug: writing STAT in HBLANK, VBLANK or when LY=LYC requests STAT interrupt */
+    pub stat_write_bug: bool,
+    /* DMG bug: 16-bit INC/DEC of value in FE00-FEFF during OAM search corrupts OAM */
+    pub oam_bug: bool,
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GPU {
+    pub quirks: GPUQuirks,
+    /*
+     * Layers drawn to framebuffer, on top of what LCDC enables, for debugging graphics. Hidden background is drawn
+     * with color 0, so sprites behind it still show up. Not a hardware setting, so not saved in save states.
+     */
+    #[cfg_attr(feature = "serde", serde(skip, default = "shown"))]
+    pub show_background: bool,
+    #[cfg_attr(feature = "serde", serde(skip, default = "shown"))]
+    pub show_window: bool,
+    #[cfg_attr(feature = "serde", serde(skip, default = "shown"))]
+    pub show_sprites: bool,
     ly: u8,
     lx: u8,
     /* Keeps track of number of window lines rendered */
     wy: u8,
     /* Indicates wheater the window was drawn on current scanline */
     win_rendered: bool,
+    /* STAT interrupt line, all enabled STAT sources ORed together */
+    stat_line: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_array"))]
     pub sprites: [Sprite; SPRITE_COUNT],
     sprites_line: [usize; SCANLINE_SPRITE_COUNT],
+    /* Picture being drawn(back buffer), lines below current one are still from the previous frame */
     pub framebuff: Vec<Color>,
+    /* Last finished picture(front buffer), copied from framebuff when VBLANK is entered */
+    completed: Vec<Color>,
+    /* Color numbers(before palette) of background and window on current line, sprite priority looks at them */
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_array"))]
+    bg_line: [u8; SCREEN_WIDTH],
+    /* Source of each framebuffer pixel, and of each completed frame one */
+    #[cfg_attr(feature = "serde", serde(skip, default = "blank_sources"))]
+    sources: Vec<PixelSource>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "blank_sources"))]
+    completed_sources: Vec<PixelSource>,
+    /* Palette of each framebuffer pixel, and of each completed frame one */
+    #[cfg_attr(feature = "serde", serde(skip, default = "background_layers"))]
+    layers: Vec<PaletteLayer>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "background_layers"))]
+    completed_layers: Vec<PaletteLayer>,
+    /* Times VBLANK was entered and left, Runtime watches them to run frame callbacks */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    vblanks: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frames: u64,
+    /* Line being drawn or drawn last, and number of lines drawn with LCD on */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    scanline: Scanline,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    drawn_lines: u64,
 }
 
 impl<T: BankController> Clocked<T> for GPU {
@@ -118,13 +255,24 @@ impl<T: BankController> Clocked<T> for GPU {
             GPUMode::OAM_SEARCH => {
                 read_oam(mmu, &mut self.sprites);
                 self.oam_scanline(mmu);
+                self.scanline = Scanline {
+                    ly: self.ly,
+                    scx: GPU::SCX(mmu),
+                    scy: GPU::SCY(mmu),
+                    wx: GPU::WX(mmu),
+                    wy: GPU::WY(mmu),
+                };
                 GPU::_MODE(mmu, GPUMode::LCD_TRANSFER);
+                self.update_stat(mmu);
             }
             GPUMode::LCD_TRANSFER => {
                 for _ in 0..4 {
                     if self.lx == SCREEN_WIDTH as u8 {
+                        if GPU::LCD_DISPLAY_ENABLE(mmu) {
+                            self.drawn_lines += 1;
+                        }
                         GPU::_MODE(mmu, GPUMode::HBLANK);
-                        GPU::hblank_stat_int(mmu);
+                        self.update_stat(mmu);
                         break;
                     }
                     if GPU::LCD_DISPLAY_ENABLE(mmu) {
@@ -141,15 +289,17 @@ impl<T: BankController> Clocked<T> for GPU {
                     self.wy += 1;
                 }
                 self.update_ly(mmu);
-                GPU::lyc_stat_int(mmu);
                 if self.ly == SCREEN_HEIGHT as u8 {
                     GPU::_MODE(mmu, GPUMode::VBLANK);
                     GPU::vblank_int(mmu);
-                    GPU::vblank_stat_int(mmu);
+                    self.completed.copy_from_slice(&self.framebuff);
+                    self.completed_layers.copy_from_slice(&self.layers);
+                    self.completed_sources.copy_from_slice(&self.sources);
+                    self.vblanks += 1;
                 } else {
                     GPU::_MODE(mmu, GPUMode::OAM_SEARCH);
-                    GPU::oam_stat_int(mmu);
                 }
+                self.update_stat(mmu);
             }
             GPUMode::VBLANK => {
                 self.lx = 0;
@@ -158,12 +308,12 @@ impl<T: BankController> Clocked<T> for GPU {
                     self.wy = 0;
                     self.update_ly(mmu);
                     GPU::_MODE(mmu, GPUMode::OAM_SEARCH);
-                    GPU::oam_stat_int(mmu);
+                    self.frames += 1;
                 } else {
                     self.ly += 1;
                 }
                 self.update_ly(mmu);
-                GPU::lyc_stat_int(mmu);
+                self.update_stat(mmu);
             }
         };
     }
@@ -172,13 +322,28 @@ impl<T: BankController> Clocked<T> for GPU {
 impl GPU {
     pub fn new(mmu: &mut MMU<impl BankController>) -> Self {
         let mut res = Self {
+            quirks: GPUQuirks::default(),
+            show_background: true,
+            show_window: true,
+            show_sprites: true,
             lx: 0,
             ly: 0,
             wy: 0,
             win_rendered: false,
+            stat_line: false,
             sprites: [Default::default(); SPRITE_COUNT],
             sprites_line: [0xFF; SCANLINE_SPRITE_COUNT],
             framebuff: vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT],
+            completed: vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT],
+            bg_line: [0; SCREEN_WIDTH],
+            sources: blank_sources(),
+            completed_sources: blank_sources(),
+            layers: background_layers(),
+            completed_layers: background_layers(),
+            vblanks: 0,
+            frames: 0,
+            scanline: Scanline::default(),
+            drawn_lines: 0,
         };
         GPU::_LCD_DISPLAY_ENABLE(mmu, true);
         GPU::_MODE(mmu, GPUMode::OAM_SEARCH);
@@ -263,6 +428,8 @@ impl GPU {
 
         if pixel_idx < self.framebuff.len() {
             self.framebuff[pixel_idx] = GPU::bg_color(mmu, color);
+            self.bg_line[lx - 7] = color;
+            self.sources[pixel_idx] = PixelSource::Window;
         }
     }
 
@@ -316,6 +483,8 @@ impl GPU {
 
         if pixel_idx < self.framebuff.len() {
             self.framebuff[pixel_idx] = GPU::bg_color(mmu, color);
+            self.bg_line[lx] = color;
+            self.sources[pixel_idx] = PixelSource::Background;
         }
     }
 
@@ -378,29 +547,45 @@ impl GPU {
 
                 let pixel_idx = ly as usize * SCREEN_WIDTH + lx as usize;
 
-                // Handle sprite priority
-                let bg_color_0_id = GPU::BG_COLOR_0_SHADE(mmu);
-                let bg_color_0 = GPU::bg_color(mmu, bg_color_0_id);
-                if sprite.priority && self.framebuff[pixel_idx] != bg_color_0 {
+                // Handle sprite priority: sprite behind background shows only over its color 0, whatever palette says
+                if sprite.priority && self.bg_line[lx as usize] != 0 {
+                    if color.is_some() {
+                        self.sources[pixel_idx] = PixelSource::SpriteHidden;
+                    }
                     return;
                 }
 
-                // Put it in the framebuff
-                if pixel_idx < self.framebuff.len() && color != TRANSPARENT {
+                // Put it in the framebuff, color 0 of sprite is transparent
+                if let (true, Some(color)) = (pixel_idx < self.framebuff.len(), color) {
                     self.framebuff[pixel_idx] = color;
+                    self.layers[pixel_idx] =
+                        if sprite.palette { PaletteLayer::Object1 } else { PaletteLayer::Object0 };
+                    self.sources[pixel_idx] = if sprite.priority {
+                        PixelSource::SpriteBehind
+                    } else {
+                        PixelSource::Sprite
+                    };
                 }
             }
         }
     }
 
     fn draw_dot(&mut self, mmu: &mut MMU<impl BankController>){
+        let pixel_idx = self.ly as usize * SCREEN_WIDTH + self.lx as usize;
+        self.sources[pixel_idx] = PixelSource::Blank;
+        self.layers[pixel_idx] = PaletteLayer::Background;
+        self.bg_line[self.lx as usize] = 0;
         if GPU::DISPLAY_PRIORITY(mmu) {
-            self.draw_background(mmu);
-            if GPU::WINDOW_ENABLED(mmu) {
+            if self.show_background {
+                self.draw_background(mmu);
+            } else {
+                self.framebuff[pixel_idx] = GPU::bg_color(mmu, 0);
+            }
+            if GPU::WINDOW_ENABLED(mmu) && self.show_window {
                 self.draw_window(mmu);
             }
         }
-        if GPU::SPRITE_ENABLED(mmu) {
+        if GPU::SPRITE_ENABLED(mmu) && self.show_sprites {
             self.draw_sprite(mmu);
         }
     }
@@ -412,40 +597,182 @@ impl GPU {
         GPU::_COINCIDENCE_FLAG(mmu, self.ly == lyc);
     }
 
-    fn vblank_stat_int(mmu: &mut MMU<impl BankController>) {
-        if GPU::MODE_1_VBLANK_INTERRUPT_ENABLE(mmu) {
+    /*
+     * All STAT interrupt sources are ORed into single line, and interrupt is requested only on its rising edge.
+     * While one source keeps the line high, others can't trigger another interrupt("STAT blocking").
+     * Has to be called whenever mode, LY=LYC flag or STAT enable bits change.
+     */
+    pub fn update_stat(&mut self, mmu: &mut MMU<impl BankController>) {
+        let line = GPU::LCD_DISPLAY_ENABLE(mmu)
+            && match GPU::MODE(mmu) {
+                GPUMode::HBLANK => GPU::MODE_0_HBLANK_INTERRUPT_ENABLE(mmu),
+                GPUMode::VBLANK => GPU::MODE_1_VBLANK_INTERRUPT_ENABLE(mmu),
+                GPUMode::OAM_SEARCH => GPU::MODE_2_OAM_INTERRUPT_ENABLE(mmu),
+                GPUMode::LCD_TRANSFER => false,
+            }
+            || GPU::COINCIDENCE_INTERRUPT_ENABLE(mmu) && GPU::COINCIDENCE_FLAG(mmu);
+        if line && !self.stat_line {
             GPU::stat_int(mmu);
         }
+        self.stat_line = line;
     }
 
-    fn hblank_stat_int(mmu: &mut MMU<impl BankController>) {
-        if GPU::MODE_0_HBLANK_INTERRUPT_ENABLE(mmu) {
-            GPU::stat_int(mmu);
+    /*
+     * Color of pixel in picture being drawn(back buffer), before any palette frontend applies. Lines below current
+     * one are still from previous frame, completed_pixel gives one of a whole frame. Panics outside the screen.
+     */
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        assert!(x < SCREEN_WIDTH && y < SCREEN_HEIGHT, "pixel {},{} outside the screen", x, y);
+        self.framebuff[y * SCREEN_WIDTH + x]
+    }
+
+    /* Same as pixel, from last completed frame */
+    pub fn completed_pixel(&self, x: usize, y: usize) -> Color {
+        assert!(x < SCREEN_WIDTH && y < SCREEN_HEIGHT, "pixel {},{} outside the screen", x, y);
+        self.completed[y * SCREEN_WIDTH + x]
+    }
+
+    /*
+     * Pixels of one line of picture being drawn, from left to right. Line just drawn is there right away, for
+     * scanline callbacks. Panics outside the screen.
+     */
+    pub fn row(&self, y: usize) -> &[Color] {
+        assert!(y < SCREEN_HEIGHT, "line {} outside the screen", y);
+        &self.framebuff[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]
+    }
+
+    /* Same as row, from last completed frame */
+    pub fn completed_row(&self, y: usize) -> &[Color] {
+        assert!(y < SCREEN_HEIGHT, "line {} outside the screen", y);
+        &self.completed[y * SCREEN_WIDTH..(y + 1) * SCREEN_WIDTH]
+    }
+
+    /*
+     * Picture being drawn as RGBA bytes, 4 per pixel, line by line, alpha always 255. Buffer's content is replaced,
+     * so one buffer can be reused for every frame. May mix two frames, see completed_rgba8.
+     */
+    pub fn as_rgba8(&self, out: &mut Vec<u8>) {
+        rgba8(&self.framebuff, out);
+    }
+
+    /* Same as as_rgba8, for last completed frame */
+    pub fn completed_rgba8(&self, out: &mut Vec<u8>) {
+        rgba8(&self.completed, out);
+    }
+
+    /*
+     * Last picture GPU finished drawing, changes only when VBLANK is entered. Unlike framebuff it never mixes two
+     * frames, so it's what frontends should show when they look at GPU at any other time.
+     */
+    pub fn completed_frame(&self) -> &[Color] {
+        &self.completed
+    }
+
+    /* What each framebuffer pixel was drawn by, see PixelSource */
+    pub fn sources(&self) -> &[PixelSource] {
+        &self.sources
+    }
+
+    /* What each pixel of completed frame was drawn by */
+    pub fn completed_sources(&self) -> &[PixelSource] {
+        &self.completed_sources
+    }
+
+    /* Palette each pixel of completed frame went through, see PaletteLayer */
+    pub fn completed_layers(&self) -> &[PaletteLayer] {
+        &self.completed_layers
+    }
+
+    /* Framebuffer with pixels tinted by their source, shows which layer and priority drew what */
+    pub fn tinted_framebuff(&self) -> Vec<Color> {
+        self.framebuff.iter().zip(&self.sources).map(|(color, source)| tint_pixel(*color, *source)).collect()
+    }
+
+    /* Line drawn last(or being drawn, in LCD_TRANSFER) */
+    pub fn scanline(&self) -> Scanline {
+        self.scanline
+    }
+
+    /* Lines drawn to framebuffer so far, Runtime watches it to run scanline callbacks */
+    pub fn drawn_lines(&self) -> u64 {
+        self.drawn_lines
+    }
+
+    /* Times GPU entered VBLANK, so finished drawing a picture */
+    pub fn vblanks(&self) -> u64 {
+        self.vblanks
+    }
+
+    /* Times GPU left VBLANK, so finished whole frame */
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn stat_line(&self) -> bool {
+        self.stat_line
+    }
+
+    /*
+     * OAM search reads one 8 byte row per machine cycle. When corruption is triggered, first word of the row being
+     * read gets mixed with the previous row, and the rest of the row is replaced with the previous one.
+     * Elapsed is number of machine cycles since OAM search started.
+     */
+    pub fn corrupt_oam(&mut self, mmu: &mut MMU<impl BankController>, elapsed: u64) {
+        if !self.quirks.oam_bug || !GPU::LCD_DISPLAY_ENABLE(mmu) || GPU::MODE(mmu) != GPUMode::OAM_SEARCH {
+            return;
+        }
+        let row = elapsed as usize;
+        if row == 0 || row >= SPRITE_COUNT / 2 {
+            return;
+        }
+        log::trace!(target: "ppu", "OAM bug corrupted row {}", row);
+        let oam = &mut mmu.oam;
+        let word = |oam: &[u8], off: usize| (oam[off] as u16) | ((oam[off + 1] as u16) << 8);
+        let (cur, prev) = (row * 8, (row - 1) * 8);
+        let (a, b, c) = (word(oam, cur), word(oam, prev), word(oam, prev + 4));
+        let mixed = ((a ^ c) & (b ^ c)) ^ c;
+        oam[cur] = mixed as u8;
+        oam[cur + 1] = (mixed >> 8) as u8;
+        for i in 2..8 {
+            oam[cur + i] = oam[prev + i];
         }
     }
 
-    fn oam_stat_int(mmu: &mut MMU<impl BankController>) {
-        if GPU::MODE_2_OAM_INTERRUPT_ENABLE(mmu) {
-            GPU::stat_int(mmu);
+    /* LY is read only */
+    pub fn write_ly(&mut self, mmu: &mut MMU<impl BankController>) {
+        if self.quirks.ly_write_resets {
+            self.ly = 0;
+            self.update_ly(mmu);
+            self.update_stat(mmu);
         }
     }
 
-    fn lyc_stat_int(mmu: &mut MMU<impl BankController>) {
-        if GPU::COINCIDENCE_INTERRUPT_ENABLE(mmu) && GPU::COINCIDENCE_FLAG(mmu){
-            GPU::stat_int(mmu);
+    /* Mode and LY=LYC flag(bits 0-2) are read only */
+    pub fn write_stat(&mut self, mmu: &mut MMU<impl BankController>, value: u8) {
+        let stat = mmu.read(ioregs::STAT);
+        if self.quirks.stat_write_bug {
+            // For one cycle STAT behaves as if all sources were enabled
+            let bugged = GPU::LCD_DISPLAY_ENABLE(mmu)
+                && (GPU::COINCIDENCE_FLAG(mmu) || matches!(GPU::MODE(mmu), GPUMode::HBLANK | GPUMode::VBLANK));
+            if bugged && !self.stat_line {
+                GPU::stat_int(mmu);
+            }
         }
+        mmu.write(ioregs::STAT, (value & 0xF8) | (stat & 0x07));
+        // Enabling source which condition is already met raises STAT line
+        self.update_stat(mmu);
     }
 
     // Triggers VBLANK interrupt
     fn vblank_int(mmu: &mut MMU<impl BankController>) {
         if Self::LCD_DISPLAY_ENABLE(mmu) {
-            mmu.set_bit(ioregs::IF, 0, true);
+            request_interrupt(mmu, Interrupt::VBlank);
         }
     }
     // Triggers STAT interrupt
     fn stat_int(mmu: &mut MMU<impl BankController>) {
         if Self::LCD_DISPLAY_ENABLE(mmu) {
-            mmu.set_bit(ioregs::IF, 1, true);
+            request_interrupt(mmu, Interrupt::Stat);
         }
     }
 
@@ -679,28 +1006,29 @@ impl GPU {
         })
     }
 
-    pub fn obp0_color<T: BankController>(mmu: &mut MMU<T>, color: u8) -> Color {
+    /* None for color 0, which is transparent */
+    pub fn obp0_color<T: BankController>(mmu: &mut MMU<T>, color: u8) -> Option<Color> {
         if color == 0 {
-            return TRANSPARENT;
+            return None;
         }
-        get_color(match color {
+        Some(get_color(match color {
             1 => GPU::OBP0_COLOR_1_SHADE(mmu),
             2 => GPU::OBP0_COLOR_2_SHADE(mmu),
             3 => GPU::OBP0_COLOR_3_SHADE(mmu),
             _ => 0x80,
-        })
+        }))
     }
 
-    pub fn obp1_color<T: BankController>(mmu: &mut MMU<T>, color: u8) -> Color {
+    pub fn obp1_color<T: BankController>(mmu: &mut MMU<T>, color: u8) -> Option<Color> {
         if color == 0 {
-            return TRANSPARENT;
+            return None;
         }
-        get_color(match color {
+        Some(get_color(match color {
             1 => GPU::OBP1_COLOR_1_SHADE(mmu),
             2 => GPU::OBP1_COLOR_2_SHADE(mmu),
             3 => GPU::OBP1_COLOR_3_SHADE(mmu),
             _ => 0x40,
-        })
+        }))
     }
 
     fn bytes_to_color_num(b1: u8, b2: u8, off: u16) -> u8 {
@@ -1,8 +1,55 @@
 #![allow(non_snake_case, non_camel_case_types)]
 
+use std::str::FromStr;
+
 use super::*;
 
+/* Frames turbo buttons stay pressed, and then released, by default. That's 15 presses per second. */
+pub const DEFAULT_TURBO_FRAMES: u8 = 2;
+
+/* GB buttons, in the order of their bits in Joypad::held and action bitmasks(see BUTTON_*) */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] =
+        [Button::Right, Button::Left, Button::Up, Button::Down, Button::A, Button::B, Button::Select, Button::Start];
+
+    /* Bit of the button in action bitmask */
+    pub const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "right" => Ok(Button::Right),
+            "left" => Ok(Button::Left),
+            "up" => Ok(Button::Up),
+            "down" => Ok(Button::Down),
+            "a" => Ok(Button::A),
+            "b" => Ok(Button::B),
+            "select" => Ok(Button::Select),
+            "start" => Ok(Button::Start),
+            _ => Err(format!("unknown button {}, expected one of: right, left, up, down, a, b, select, start", text)),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Joypad {
     up: bool,
     down: bool,
@@ -12,96 +59,203 @@ pub struct Joypad {
     b: bool,
     select: bool,
     start: bool,
-    interrupt: bool,
+    /* Turbo A and B held, they press and release their button every turbo_frames emulated frames */
+    turbo_a: bool,
+    turbo_b: bool,
+    turbo_frames: u8,
+    /* Frames since turbo button was pressed, while any is held */
+    turbo_counter: u32,
+    /* Input lines P10-P13 as of last step, interrupt is requested when any of them goes low */
+    lines: u8,
+    /* Controller selected by SGB multiplayer, reported when no column is selected */
+    player: u8,
 }
 
 impl Joypad {
     pub fn new() -> Self {
-        Default::default()
+        Self { lines: 0x0F, turbo_frames: DEFAULT_TURBO_FRAMES, ..Default::default() }
     }
 
+    /* Called once per emulated frame, drives turbo buttons */
+    pub fn frame(&mut self) {
+        if self.turbo_a || self.turbo_b {
+            self.turbo_counter = self.turbo_counter.wrapping_add(1);
+        }
+    }
+
+    /* Turbo buttons are pressed in the first half of each period, starting right when they're held */
+    fn turbo_pressed(&self) -> bool {
+        (self.turbo_counter / self.turbo_frames.max(1) as u32) & 1 == 0
+    }
+
+    /* Checks input lines for high to low transitions, caused either by pressed button or by selecting a column */
     pub fn step(&mut self, mmu: &mut MMU<impl BankController>) {
+        let lines = self.lines(mmu.read(ioregs::P1));
+        if self.lines & !lines != 0 {
+            Joypad::joypad_int(mmu);
+        }
+        self.lines = lines;
+    }
+
+    /*
+     * P1 as CPU reads it. Memory keeps only select bits written by CPU, input lines are computed from buttons
+     * held. Unused bits 6-7 read as 1.
+     */
+    pub fn read_p1(&self, mmu: &mut MMU<impl BankController>) -> u8 {
+        let select = mmu.read(ioregs::P1) & 0x30;
+        0xC0 | select | self.lines(select)
+    }
+
+    /*
+     * Lines are pulled low by pressed buttons of selected columns, with both columns selected they're ANDed.
+     * With none selected SGB reports current controller, which is 0x0F for the first one.
+     */
+    fn lines(&self, p1: u8) -> u8 {
+        let held = self.held();
+        let mut low = 0;
         // Button keys selected
-        if !mmu.read_bit(ioregs::P1, 5) {
-            mmu.set_bit(ioregs::P1, 0, !self.a);
-            mmu.set_bit(ioregs::P1, 1, !self.b);
-            mmu.set_bit(ioregs::P1, 2, !self.select);
-            mmu.set_bit(ioregs::P1, 3, !self.start);
+        if p1 & 0x20 == 0 {
+            low |= held >> 4;
         }
         // Direction keys selected
-        else if !mmu.read_bit(ioregs::P1, 4) {
-            mmu.set_bit(ioregs::P1, 0, !self.right);
-            mmu.set_bit(ioregs::P1, 1, !self.left);
-            mmu.set_bit(ioregs::P1, 2, !self.up);
-            mmu.set_bit(ioregs::P1, 3, !self.down);
+        if p1 & 0x10 == 0 {
+            low |= held & 0x0F;
         }
         // No column selected
-        else {
-            mmu.write(ioregs::P1, 0xFF);
+        if p1 & 0x30 == 0x30 {
+            return 0x0F - self.player;
         }
-        if self.interrupt {
-            Joypad::joypad_int(mmu);
-            self.interrupt = false;
+        0x0F & !low
+    }
+
+    /*
+     * Buttons held now, turbo ones only while they're pressed: directions in low nibble(right, left, up, down),
+     * buttons in high one(A, B, select, start), both in the order P1 reports them.
+     */
+    pub fn held(&self) -> u8 {
+        let mut held = Button::ALL.iter().filter(|button| self.pressed(**button)).fold(0, |acc, button| acc | button.mask());
+        if self.turbo_pressed() {
+            if self.turbo_a {
+                held |= Button::A.mask();
+            }
+            if self.turbo_b {
+                held |= Button::B.mask();
+            }
         }
+        held
     }
 
-    pub fn down(&mut self, val: bool) {
-        if val && !self.down {
-            self.interrupt = true;
+    /* Holds or releases the button, it's seen by CPU on next P1 read */
+    pub fn set(&mut self, button: Button, pressed: bool) {
+        *self.button_mut(button) = pressed;
+    }
+
+    pub fn press(&mut self, button: Button) {
+        self.set(button, true);
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.set(button, false);
+    }
+
+    /* Whether the button is held, turbo buttons aside */
+    pub fn pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Right => self.right,
+            Button::Left => self.left,
+            Button::Up => self.up,
+            Button::Down => self.down,
+            Button::A => self.a,
+            Button::B => self.b,
+            Button::Select => self.select,
+            Button::Start => self.start,
         }
-        self.down = val;
     }
 
-    pub fn left(&mut self, val: bool) {
-        if val && !self.left {
-            self.interrupt = true;
+    fn button_mut(&mut self, button: Button) -> &mut bool {
+        match button {
+            Button::Right => &mut self.right,
+            Button::Left => &mut self.left,
+            Button::Up => &mut self.up,
+            Button::Down => &mut self.down,
+            Button::A => &mut self.a,
+            Button::B => &mut self.b,
+            Button::Select => &mut self.select,
+            Button::Start => &mut self.start,
         }
-        self.left = val;
     }
 
+    #[deprecated(note = "use set(Button::Down, val)")]
+    pub fn down(&mut self, val: bool) {
+        self.set(Button::Down, val);
+    }
+
+    #[deprecated(note = "use set(Button::Left, val)")]
+    pub fn left(&mut self, val: bool) {
+        self.set(Button::Left, val);
+    }
+
+    #[deprecated(note = "use set(Button::Right, val)")]
     pub fn right(&mut self, val: bool) {
-        if val && !self.right {
-            self.interrupt = true;
-        }
-        self.right = val;
+        self.set(Button::Right, val);
     }
 
+    #[deprecated(note = "use set(Button::A, val)")]
     pub fn a(&mut self, val: bool) {
-        if val && !self.a {
-            self.interrupt = true;
-        }
-        self.a = val;
+        self.set(Button::A, val);
     }
 
+    #[deprecated(note = "use set(Button::B, val)")]
     pub fn b(&mut self, val: bool) {
-        if val && !self.b {
-            self.interrupt = true;
-        }
-        self.b = val;
+        self.set(Button::B, val);
     }
 
+    #[deprecated(note = "use set(Button::Select, val)")]
     pub fn select(&mut self, val: bool) {
-        if val && !self.select {
-            self.interrupt = true;
-        }
-        self.select = val;
+        self.set(Button::Select, val);
     }
 
+    #[deprecated(note = "use set(Button::Start, val)")]
     pub fn start(&mut self, val: bool) {
-        if val && !self.start {
-            self.interrupt = true;
-        }
-        self.start = val;
+        self.set(Button::Start, val);
     }
 
+    #[deprecated(note = "use set(Button::Up, val)")]
     pub fn up(&mut self, val: bool) {
-        if val && !self.up {
-            self.interrupt = true;
+        self.set(Button::Up, val);
+    }
+
+    pub fn turbo_a(&mut self, val: bool) {
+        self.start_turbo(val && !self.turbo_a);
+        self.turbo_a = val;
+    }
+
+    pub fn turbo_b(&mut self, val: bool) {
+        self.start_turbo(val && !self.turbo_b);
+        self.turbo_b = val;
+    }
+
+    /* Newly held turbo button starts pressed, unless the other one is already repeating */
+    fn start_turbo(&mut self, pressed: bool) {
+        if pressed && !self.turbo_a && !self.turbo_b {
+            self.turbo_counter = 0;
         }
-        self.up = val;
+    }
+
+    /* Frames turbo buttons stay pressed, then as many released. 0 is treated as 1. */
+    pub fn set_turbo_frames(&mut self, frames: u8) {
+        self.turbo_frames = frames;
+    }
+
+    pub fn turbo_frames(&self) -> u8 {
+        self.turbo_frames
+    }
+
+    pub fn set_player(&mut self, player: u8) {
+        self.player = player & 0x3;
     }
 
     fn joypad_int(mmu: &mut MMU<impl BankController>) {
-        mmu.set_bit(ioregs::IF, 4, true);
+        request_interrupt(mmu, Interrupt::Joypad);
     }
 }
@@ -16,9 +16,26 @@ pub use dma::*;
 pub mod joypad;
 pub use joypad::*;
 
+pub mod serial;
+pub use serial::*;
+
+pub mod printer;
+pub use printer::*;
+
+#[cfg(feature = "sgb")]
+pub mod sgb;
+#[cfg(feature = "sgb")]
+pub use sgb::*;
+
 use super::mem::ioregs;
+use super::utils::Interrupt;
 use super::{BankController, State, MMU};
 
+/* Sets interrupt's IF bit, CPU takes it once IE and IME let it */
+pub(crate) fn request_interrupt(mmu: &mut MMU<impl BankController>, interrupt: Interrupt) {
+    mmu.set_bit(ioregs::IF, interrupt.bit(), true);
+}
+
 pub trait Clocked<T: BankController> {
     /*
      * next_time()
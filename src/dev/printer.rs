@@ -0,0 +1,276 @@
+#![allow(non_snake_case, non_camel_case_types)]
+
+use std::path::PathBuf;
+
+use super::super::utils::write_gray_png;
+use super::*;
+
+/* Printed image is always 20 tiles wide */
+pub const PRINTER_WIDTH: usize = 160;
+/* Printer RAM fits 9 bands, each band is 20x2 tiles */
+const BAND_SIZE: usize = 0x280;
+const BUFFER_SIZE: usize = 9 * BAND_SIZE;
+const TILES_PER_ROW: usize = PRINTER_WIDTH / 8;
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+/* Printer answers with it after checksum */
+const ALIVE: u8 = 0x81;
+
+/* Commands */
+const CMD_INIT: u8 = 0x01;
+const CMD_PRINT: u8 = 0x02;
+const CMD_DATA: u8 = 0x04;
+const CMD_STATUS: u8 = 0x0F;
+
+/* Status bits */
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+const STATUS_PRINTING: u8 = 0x02;
+const STATUS_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED: u8 = 0x08;
+
+/* Games wait for printing to end by polling status. Number of polls printer reports being busy. */
+const PRINT_POLLS: u8 = 4;
+
+/* Printer shades for 2bit colors after applying palette, white to black */
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+/*
+ * Where we are in the packet:
+ * MAGIC(2) | COMMAND | COMPRESSION | LENGTH(2) | DATA(LENGTH) | CHECKSUM(2) | ALIVE | STATUS
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Magic(usize),
+    Command,
+    Compression,
+    Length(usize),
+    Data,
+    Checksum(usize),
+    Alive,
+    Status,
+}
+
+/*
+ * Game Boy Printer. Receives packets through link cable and saves each printed image as PNG.
+ */
+pub struct Printer {
+    out_dir: PathBuf,
+    stage: Stage,
+    command: u8,
+    compressed: bool,
+    length: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    /* Decoded tile data waiting to be printed */
+    buffer: Vec<u8>,
+    status: u8,
+    busy: u8,
+    /* Images printed so far */
+    printed: Vec<PathBuf>,
+}
+
+impl Printer {
+    pub fn new(out_dir: PathBuf) -> Self {
+        Self {
+            out_dir,
+            stage: Stage::Magic(0),
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            status: 0,
+            busy: 0,
+            printed: Vec::new(),
+        }
+    }
+
+    pub fn printed(&self) -> &[PathBuf] {
+        &self.printed
+    }
+
+    /* Takes next byte of packet. Returns what printer sends back at the same time. */
+    fn receive(&mut self, byte: u8) -> u8 {
+        match self.stage {
+            Stage::Magic(i) => {
+                self.stage = match (i, byte == MAGIC[i]) {
+                    (0, true) => Stage::Magic(1),
+                    (1, true) => Stage::Command,
+                    // Lost sync, wait for start of next packet
+                    _ => Stage::Magic(if byte == MAGIC[0] { 1 } else { 0 }),
+                };
+            }
+            Stage::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                self.stage = Stage::Compression;
+            }
+            Stage::Compression => {
+                self.compressed = byte & 0x1 != 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.stage = Stage::Length(0);
+            }
+            Stage::Length(0) => {
+                self.length = byte as usize;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.stage = Stage::Length(1);
+            }
+            Stage::Length(_) => {
+                self.length |= (byte as usize) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.data.clear();
+                self.stage = if self.length == 0 { Stage::Checksum(0) } else { Stage::Data };
+            }
+            Stage::Data => {
+                self.data.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.data.len() == self.length {
+                    self.stage = Stage::Checksum(0);
+                }
+            }
+            Stage::Checksum(0) => {
+                self.received_checksum = byte as u16;
+                self.stage = Stage::Checksum(1);
+            }
+            Stage::Checksum(_) => {
+                self.received_checksum |= (byte as u16) << 8;
+                self.stage = Stage::Alive;
+            }
+            Stage::Alive => {
+                self.stage = Stage::Status;
+                return ALIVE;
+            }
+            Stage::Status => {
+                // Status sent back is the one from before executing this packet
+                let status = self.status();
+                self.execute();
+                self.stage = Stage::Magic(0);
+                return status;
+            }
+        }
+        0x00
+    }
+
+    fn status(&mut self) -> u8 {
+        if self.busy > 0 {
+            self.busy -= 1;
+            if self.busy == 0 {
+                self.status &= !STATUS_PRINTING;
+            }
+        }
+        self.status
+    }
+
+    fn execute(&mut self) {
+        if self.received_checksum != self.checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+        match self.command {
+            CMD_INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy = 0;
+            }
+            CMD_DATA => {
+                let data = std::mem::take(&mut self.data);
+                let decoded = if self.compressed { Printer::decompress(&data) } else { data };
+                let free = BUFFER_SIZE - self.buffer.len();
+                self.buffer.extend_from_slice(&decoded[..decoded.len().min(free)]);
+                if !self.buffer.is_empty() {
+                    self.status |= STATUS_UNPROCESSED;
+                }
+                if self.buffer.len() == BUFFER_SIZE {
+                    self.status |= STATUS_FULL;
+                }
+            }
+            CMD_PRINT => {
+                // Data is: sheets, margins, palette, exposure
+                let palette = self.data.get(2).cloned().unwrap_or(0);
+                if let Err(e) = self.print(palette) {
+                    log::warn!(target: "printer", "unable to save image: {}", e);
+                }
+                self.buffer.clear();
+                self.status = (self.status | STATUS_PRINTING) & !(STATUS_UNPROCESSED | STATUS_FULL);
+                self.busy = PRINT_POLLS;
+            }
+            CMD_STATUS => {}
+            other => log::warn!(target: "printer", "unknown command 0x{:02X}", other),
+        }
+    }
+
+    /* RLE: bit 7 set means next byte repeated (N & 0x7F) + 2 times, otherwise N + 1 raw bytes follow */
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let n = data[i];
+            i += 1;
+            if n & 0x80 != 0 {
+                if let Some(byte) = data.get(i) {
+                    out.resize(out.len() + (n & 0x7F) as usize + 2, *byte);
+                }
+                i += 1;
+            } else {
+                let end = (i + n as usize + 1).min(data.len());
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+        }
+        out
+    }
+
+    /* Converts buffered tiles to grayscale image. Palette 0 is treated as default 0xE4. */
+    pub fn render(&self, palette: u8) -> Vec<u8> {
+        let palette = if palette == 0 { 0xE4 } else { palette };
+        let tile_rows = self.buffer.len() / (TILES_PER_ROW * 16);
+        let mut pixels = vec![SHADES[0]; PRINTER_WIDTH * tile_rows * 8];
+        for (tile_idx, tile) in self.buffer.chunks_exact(16).enumerate() {
+            let (tx, ty) = (tile_idx % TILES_PER_ROW, tile_idx / TILES_PER_ROW);
+            if ty >= tile_rows {
+                break;
+            }
+            for row in 0..8 {
+                let (b1, b2) = (tile[2 * row], tile[2 * row + 1]);
+                for col in 0..8 {
+                    let bit = 7 - col;
+                    let color = ((b1 >> bit) & 1) | (((b2 >> bit) & 1) << 1);
+                    let shade = (palette >> (2 * color)) & 0x3;
+                    pixels[(ty * 8 + row) * PRINTER_WIDTH + tx * 8 + col] = SHADES[shade as usize];
+                }
+            }
+        }
+        pixels
+    }
+
+    fn print(&mut self, palette: u8) -> std::io::Result<()> {
+        let pixels = self.render(palette);
+        if pixels.is_empty() {
+            return Ok(());
+        }
+        let name = format!(
+            "print-{}-{}.png",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            self.printed.len()
+        );
+        let path = self.out_dir.join(name);
+        write_gray_png(&path, PRINTER_WIDTH, pixels.len() / PRINTER_WIDTH, &pixels)?;
+        self.printed.push(path);
+        Ok(())
+    }
+}
+
+impl SerialLink for Printer {
+    fn exchange(&mut self, out: u8) -> u8 {
+        self.receive(out)
+    }
+
+    /* Printer never drives the clock */
+    fn respond(&mut self, _: u8) -> Option<u8> {
+        None
+    }
+}
@@ -0,0 +1,164 @@
+#![allow(non_snake_case, non_camel_case_types)]
+
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+/* Internal clock runs at 8192Hz, which is 128 machine cycles per bit */
+pub const SERIAL_BIT_CYCLES: u64 = 128;
+/* What GB receives when nothing is connected */
+pub const DISCONNECTED: u8 = 0xFF;
+
+/*
+ * Whatever is on the other end of link cable. Transfer always swaps one byte each way.
+ */
+pub trait SerialLink: Send {
+    /*
+     * GB drives the clock(SC bit 0 set). Sends byte and returns one received from the other side.
+     */
+    fn exchange(&mut self, out: u8) -> u8;
+
+    /*
+     * Other side drives the clock. Polled while GB waits for transfer with byte ready in SB.
+     * Returns received byte once the other side has clocked the transfer, None when it didn't yet.
+     */
+    fn respond(&mut self, out: u8) -> Option<u8>;
+
+    /*
+     * Called after each emulated frame. Lets links that have to stay in sync with the other side wait for it.
+     */
+    fn frame(&mut self) {}
+}
+
+/* One side of in-memory cable */
+#[derive(Default)]
+struct Port {
+    /* Byte waiting in SB for the other side to clock the transfer */
+    ready: Option<u8>,
+    /* Byte received from transfer clocked by the other side */
+    received: Option<u8>,
+}
+
+/*
+ * Link cable between two emulated GBs in the same process. Both ends have to be stepped close to each other in
+ * time for transfers to behave like on hardware.
+ */
+pub struct LocalLink {
+    ports: Arc<Mutex<[Port; 2]>>,
+    side: usize,
+}
+
+impl LocalLink {
+    /* Both ends of the cable */
+    pub fn pair() -> (LocalLink, LocalLink) {
+        let ports = Arc::new(Mutex::new([Port::default(), Port::default()]));
+        (LocalLink { ports: ports.clone(), side: 0 }, LocalLink { ports, side: 1 })
+    }
+}
+
+impl SerialLink for LocalLink {
+    fn exchange(&mut self, out: u8) -> u8 {
+        let mut ports = self.ports.lock().unwrap();
+        let other = &mut ports[1 - self.side];
+        match other.ready.take() {
+            Some(byte) => {
+                other.received = Some(out);
+                byte
+            }
+            // Other side isn't waiting for transfer
+            None => DISCONNECTED,
+        }
+    }
+
+    fn respond(&mut self, out: u8) -> Option<u8> {
+        let mut ports = self.ports.lock().unwrap();
+        let port = &mut ports[self.side];
+        match port.received.take() {
+            Some(byte) => Some(byte),
+            None => {
+                port.ready = Some(out);
+                None
+            }
+        }
+    }
+}
+
+/*
+ * Serial port. Shifts SB out through link, then raises serial interrupt.
+ */
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Serial {
+    /* Not part of saved state, links are reconnected by whoever restores it */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    link: Option<Box<dyn SerialLink>>,
+    /* Bits shifted so far in transfer with internal clock */
+    bits: u8,
+}
+
+impl<T: BankController> Clocked<T> for Serial {
+    fn next_time(&self, _: &mut MMU<T>) -> u64 {
+        SERIAL_BIT_CYCLES
+    }
+
+    fn step(&mut self, mmu: &mut MMU<T>) {
+        let sc = mmu.read(ioregs::SC);
+        if sc & 0x80 == 0 {
+            return;
+        }
+        let out = mmu.read(ioregs::SB);
+        let received = if sc & 0x01 != 0 {
+            self.bits += 1;
+            if self.bits < 8 {
+                return;
+            }
+            self.bits = 0;
+            match &mut self.link {
+                Some(link) => link.exchange(out),
+                None => DISCONNECTED,
+            }
+        } else {
+            // With external clock and nothing connected transfer never finishes
+            match self.link.as_mut().and_then(|link| link.respond(out)) {
+                Some(byte) => byte,
+                None => return,
+            }
+        };
+        mmu.write(ioregs::SB, received);
+        mmu.write(ioregs::SC, sc & 0x7F);
+        Serial::serial_int(mmu);
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn connect(&mut self, link: Box<dyn SerialLink>) {
+        self.link = Some(link);
+    }
+
+    pub fn disconnect(&mut self) -> Option<Box<dyn SerialLink>> {
+        self.link.take()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.link.is_some()
+    }
+
+    pub fn frame(&mut self) {
+        if let Some(link) = &mut self.link {
+            link.frame();
+        }
+    }
+
+    /* Write to SC restarts bit counter */
+    pub fn start(&mut self) {
+        self.bits = 0;
+    }
+
+    fn serial_int(mmu: &mut MMU<impl BankController>) {
+        request_interrupt(mmu, Interrupt::Serial);
+    }
+}
@@ -0,0 +1,469 @@
+#![allow(non_snake_case, non_camel_case_types)]
+
+use super::super::VRAM_ADDR;
+use super::*;
+
+/* SGB output is 256x224, with Game Boy screen in the middle of it */
+pub const SGB_WIDTH: usize = 256;
+pub const SGB_HEIGHT: usize = 224;
+const SCREEN_X: usize = (SGB_WIDTH - SCREEN_WIDTH) / 2;
+const SCREEN_Y: usize = (SGB_HEIGHT - SCREEN_HEIGHT) / 2;
+
+const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+/* Size of data copied from VRAM by *_TRN commands */
+const TRANSFER_SIZE: usize = 0x1000;
+/* Screen is split into 20x18 cells of 8x8 pixels, each can have own palette */
+const CELLS_X: usize = SCREEN_WIDTH / 8;
+const CELLS_Y: usize = SCREEN_HEIGHT / 8;
+const SYSTEM_PALETTE_COUNT: usize = 512;
+const ATTR_FILE_COUNT: usize = 45;
+const ATTR_FILE_SIZE: usize = CELLS_X * CELLS_Y / 4;
+/* Border is made of 32x28 tiles, 4 bits per pixel */
+const BORDER_TILES_X: usize = SGB_WIDTH / 8;
+const BORDER_TILES_Y: usize = SGB_HEIGHT / 8;
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_TILE_COUNT: usize = 256;
+const BORDER_PALETTES_OFFSET: usize = 0x800;
+/* Border palettes are numbered 4-7 */
+const BORDER_PALETTE_BASE: usize = 4;
+
+/* Commands */
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const ATTR_TRN: u8 = 0x15;
+const MASK_EN: u8 = 0x17;
+
+/* Converts SNES BGR555 color to RGB */
+fn snes_color(lower: u8, upper: u8) -> Color {
+    let raw = ((upper as u16) << 8) | lower as u16;
+    let scale = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    (scale(raw & 0x1F), scale((raw >> 5) & 0x1F), scale((raw >> 10) & 0x1F))
+}
+
+/* GPU framebuffer holds DMG shades, SGB palettes are indexed with them */
+fn shade(color: Color) -> usize {
+    match color {
+        WHITE => 0,
+        LIGHT_GRAY => 1,
+        DARK_GRAY => 2,
+        _ => 3,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SGBMask {
+    Cancel,
+    /* Keep showing last frame */
+    Freeze,
+    Black,
+    /* Fill screen with color 0 */
+    Color0,
+}
+
+/*
+ * Super Game Boy. Cart talks to it with packets sent bit by bit through P1 register.
+ * Handles palette, attribute, multiplayer and border commands. Sound and SNES program commands are ignored.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SGB {
+    enabled: bool,
+    /* Packet currently being received */
+    receiving: bool,
+    bit: usize,
+    packet: [u8; PACKET_SIZE],
+    /* All packets of current command */
+    command: Vec<u8>,
+    last_select: u8,
+    /* Multiplayer */
+    players: u8,
+    player: u8,
+    /* Palettes used for Game Boy screen */
+    palettes: [[Color; 4]; 4],
+    system_palettes: Vec<[Color; 4]>,
+    /* Palette number of each 8x8 screen cell */
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_array"))]
+    attributes: [u8; CELLS_X * CELLS_Y],
+    attr_files: Vec<u8>,
+    mask: SGBMask,
+    border_tiles: Vec<u8>,
+    /* Border tile map followed by border palettes, as sent by PCT_TRN */
+    border_map: Vec<u8>,
+    pub framebuff: Vec<Color>,
+}
+
+impl Default for SGB {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SGB {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            receiving: false,
+            bit: 0,
+            packet: [0; PACKET_SIZE],
+            command: Vec::new(),
+            last_select: 0x30,
+            players: 1,
+            player: 0,
+            palettes: [[WHITE, LIGHT_GRAY, DARK_GRAY, BLACK]; 4],
+            system_palettes: vec![[WHITE, LIGHT_GRAY, DARK_GRAY, BLACK]; SYSTEM_PALETTE_COUNT],
+            attributes: [0; CELLS_X * CELLS_Y],
+            attr_files: vec![0; ATTR_FILE_COUNT * ATTR_FILE_SIZE],
+            mask: SGBMask::Cancel,
+            border_tiles: vec![0; BORDER_TILE_COUNT * BORDER_TILE_SIZE],
+            border_map: vec![0; TRANSFER_SIZE],
+            framebuff: vec![WHITE; SGB_WIDTH * SGB_HEIGHT],
+        }
+    }
+
+    /* SGB only listens to carts which declare SGB support in header */
+    pub fn enable(&mut self, flg: bool) {
+        self.enabled = flg;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn palette(&self, idx: usize) -> [Color; 4] {
+        self.palettes[idx]
+    }
+
+    pub fn attribute(&self, x: usize, y: usize) -> u8 {
+        self.attributes[y * CELLS_X + x]
+    }
+
+    pub fn mask(&self) -> SGBMask {
+        self.mask
+    }
+
+    /*
+     * Called on every write to P1. Both P14 and P15 low resets transfer, then each bit is sent by pulling one of
+     * them low(P14 - 0, P15 - 1) and releasing both. 128 bits are followed by a stop bit.
+     */
+    pub fn write_p1(&mut self, mmu: &mut MMU<impl BankController>, joypad: &mut Joypad, value: u8) {
+        if !self.enabled {
+            return;
+        }
+        let select = value & 0x30;
+        match (self.last_select, select) {
+            (_, 0x00) => {
+                self.receiving = true;
+                self.bit = 0;
+                self.packet = [0; PACKET_SIZE];
+            }
+            (0x30, 0x10) | (0x30, 0x20) if self.receiving => {
+                if self.bit == PACKET_BITS {
+                    self.receiving = false;
+                    self.packet_done(mmu, joypad);
+                } else {
+                    if select == 0x10 {
+                        self.packet[self.bit / 8] |= 1 << (self.bit % 8);
+                    }
+                    self.bit += 1;
+                }
+            }
+            // Releasing P15 after reading buttons switches to next controller
+            (0x10, 0x30) if !self.receiving && self.players > 1 => {
+                self.player = (self.player + 1) % self.players;
+                joypad.set_player(self.player);
+            }
+            _ => {}
+        }
+        self.last_select = select;
+    }
+
+    fn packet_done(&mut self, mmu: &mut MMU<impl BankController>, joypad: &mut Joypad) {
+        self.command.extend_from_slice(&self.packet);
+        let packets = (self.command[0] & 0x7).max(1) as usize;
+        if self.command.len() >= packets * PACKET_SIZE {
+            let data = std::mem::take(&mut self.command);
+            self.execute(mmu, joypad, &data);
+        }
+    }
+
+    fn execute(&mut self, mmu: &mut MMU<impl BankController>, joypad: &mut Joypad, data: &[u8]) {
+        match data[0] >> 3 {
+            PAL01 => self.set_palettes(0, 1, data),
+            PAL23 => self.set_palettes(2, 3, data),
+            PAL03 => self.set_palettes(0, 3, data),
+            PAL12 => self.set_palettes(1, 2, data),
+            ATTR_BLK => self.attr_blk(data),
+            ATTR_LIN => self.attr_lin(data),
+            ATTR_DIV => self.attr_div(data),
+            ATTR_CHR => self.attr_chr(data),
+            PAL_SET => self.pal_set(data),
+            PAL_TRN => {
+                let vram = SGB::vram_transfer(mmu);
+                for (i, palette) in self.system_palettes.iter_mut().enumerate() {
+                    for (j, color) in palette.iter_mut().enumerate() {
+                        let off = i * 8 + j * 2;
+                        *color = snes_color(vram[off], vram[off + 1]);
+                    }
+                }
+            }
+            MLT_REQ => {
+                self.players = match data[1] & 0x3 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+                joypad.set_player(0);
+            }
+            CHR_TRN => {
+                let vram = SGB::vram_transfer(mmu);
+                let half = (data[1] & 1) as usize * self.border_tiles.len() / 2;
+                let len = self.border_tiles.len() / 2;
+                self.border_tiles[half..half + len].copy_from_slice(&vram[..len]);
+            }
+            PCT_TRN => self.border_map = SGB::vram_transfer(mmu),
+            ATTR_TRN => {
+                let vram = SGB::vram_transfer(mmu);
+                let len = self.attr_files.len();
+                self.attr_files.copy_from_slice(&vram[..len]);
+            }
+            MASK_EN => self.mask = SGB::decode_mask(data[1]),
+            _ => {}
+        }
+    }
+
+    fn decode_mask(value: u8) -> SGBMask {
+        match value & 0x3 {
+            1 => SGBMask::Freeze,
+            2 => SGBMask::Black,
+            3 => SGBMask::Color0,
+            _ => SGBMask::Cancel,
+        }
+    }
+
+    /* Color 0 is shared between all palettes, so setting it for one sets it for all */
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color0 = snes_color(data[1], data[2]);
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+        for i in 0..3 {
+            self.palettes[first][i + 1] = snes_color(data[3 + 2 * i], data[4 + 2 * i]);
+            self.palettes[second][i + 1] = snes_color(data[9 + 2 * i], data[10 + 2 * i]);
+        }
+    }
+
+    fn pal_set(&mut self, data: &[u8]) {
+        for i in 0..4 {
+            let idx = (((data[2 + 2 * i] as usize) << 8) | data[1 + 2 * i] as usize) % SYSTEM_PALETTE_COUNT;
+            self.palettes[i] = self.system_palettes[idx];
+        }
+        // Color 0 of first palette is used by all of them
+        let color0 = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+        let flags = data[9];
+        if flags & 0x80 != 0 {
+            self.apply_attr_file((flags & 0x3F) as usize);
+        }
+        if flags & 0x40 != 0 {
+            self.mask = SGBMask::Cancel;
+        }
+    }
+
+    fn apply_attr_file(&mut self, file: usize) {
+        if file >= ATTR_FILE_COUNT {
+            return;
+        }
+        let base = file * ATTR_FILE_SIZE;
+        for cell in 0..self.attributes.len() {
+            let byte = self.attr_files[base + cell / 4];
+            self.attributes[cell] = (byte >> (6 - 2 * (cell % 4))) & 0x3;
+        }
+    }
+
+    /*
+     * Each data set describes rectangle and palettes for cells inside it, on its border and outside of it.
+     * When only inside or outside is changed, border gets the same palette.
+     */
+    fn attr_blk(&mut self, data: &[u8]) {
+        let count = (data[1] as usize).min((data.len() - 2) / 6);
+        for set in data[2..2 + count * 6].chunks(6) {
+            let (ctrl, pals) = (set[0] & 0x7, set[1]);
+            let (x1, y1, x2, y2) = (set[2] as usize, set[3] as usize, set[4] as usize, set[5] as usize);
+            let (inside, outside) = (pals & 0x3, (pals >> 4) & 0x3);
+            let border = match ctrl {
+                0x1 => Some(inside),
+                0x4 => Some(outside),
+                c if c & 0x2 != 0 => Some((pals >> 2) & 0x3),
+                _ => None,
+            };
+            for y in 0..CELLS_Y {
+                for x in 0..CELLS_X {
+                    let on_border = (x == x1 || x == x2) && y >= y1 && y <= y2
+                        || (y == y1 || y == y2) && x >= x1 && x <= x2;
+                    let is_inside = x > x1 && x < x2 && y > y1 && y < y2;
+                    let pal = if on_border {
+                        border
+                    } else if is_inside && ctrl & 0x1 != 0 {
+                        Some(inside)
+                    } else if !is_inside && ctrl & 0x4 != 0 {
+                        Some(outside)
+                    } else {
+                        None
+                    };
+                    if let Some(pal) = pal {
+                        self.attributes[y * CELLS_X + x] = pal;
+                    }
+                }
+            }
+        }
+    }
+
+    fn attr_lin(&mut self, data: &[u8]) {
+        let count = (data[1] as usize).min(data.len() - 2);
+        for line in &data[2..2 + count] {
+            let idx = (line & 0x1F) as usize;
+            let pal = (line >> 5) & 0x3;
+            if line & 0x80 != 0 {
+                // Horizontal line, changes whole row
+                if idx < CELLS_Y {
+                    self.attributes[idx * CELLS_X..(idx + 1) * CELLS_X].iter_mut().for_each(|a| *a = pal);
+                }
+            } else if idx < CELLS_X {
+                for y in 0..CELLS_Y {
+                    self.attributes[y * CELLS_X + idx] = pal;
+                }
+            }
+        }
+    }
+
+    fn attr_div(&mut self, data: &[u8]) {
+        let (pals, coord) = (data[1], data[2] as usize);
+        let (after, before, line) = (pals & 0x3, (pals >> 2) & 0x3, (pals >> 4) & 0x3);
+        let horizontal = pals & 0x40 != 0;
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let pos = if horizontal { y } else { x };
+                self.attributes[y * CELLS_X + x] = match pos {
+                    p if p < coord => before,
+                    p if p == coord => line,
+                    _ => after,
+                };
+            }
+        }
+    }
+
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize % CELLS_X, data[2] as usize % CELLS_Y);
+        let count = ((data[4] as usize) << 8) | data[3] as usize;
+        let vertical = data[5] != 0;
+        let available = (data.len() - 6) * 4;
+        for i in 0..count.min(available).min(CELLS_X * CELLS_Y) {
+            let pal = (data[6 + i / 4] >> (6 - 2 * (i % 4))) & 0x3;
+            self.attributes[y * CELLS_X + x] = pal;
+            if vertical {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x = (x + 1) % CELLS_X;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y = (y + 1) % CELLS_Y;
+                }
+            }
+        }
+    }
+
+    /*
+     * *_TRN commands take 4KB of data that's currently on screen. Carts prepare BG map to display tiles in order,
+     * so data is read by following the BG map the same way GPU would.
+     */
+    fn vram_transfer(mmu: &mut MMU<impl BankController>) -> Vec<u8> {
+        let tile_map = (if GPU::BG_TILE_MAP(mmu) { TILE_MAP_2 } else { TILE_MAP_1 } - VRAM_ADDR) as usize;
+        let unsigned = GPU::TILE_ADDRESSING(mmu);
+        let mut data = Vec::with_capacity(TRANSFER_SIZE);
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let tile = mmu.vram[tile_map + 32 * y + x];
+                let addr = if unsigned {
+                    TILE_BLOCK_1 + TILE_SIZE * tile as u16
+                } else {
+                    (TILE_BLOCK_2 as i32 + TILE_SIZE as i32 * (tile as i8) as i32) as u16
+                };
+                let offset = (addr - VRAM_ADDR) as usize;
+                data.extend_from_slice(&mmu.vram[offset..offset + TILE_SIZE as usize]);
+            }
+        }
+        data.truncate(TRANSFER_SIZE);
+        data
+    }
+
+    /* Composes SGB framebuffer out of border and colorized Game Boy screen */
+    pub fn render(&mut self, screen: &[Color]) {
+        let backdrop = self.palettes[0][0];
+        if self.mask != SGBMask::Freeze {
+            for y in 0..SCREEN_HEIGHT {
+                for x in 0..SCREEN_WIDTH {
+                    let color = match self.mask {
+                        SGBMask::Black => BLACK,
+                        SGBMask::Color0 => backdrop,
+                        _ => {
+                            let pal = self.attributes[(y / 8) * CELLS_X + x / 8] as usize;
+                            self.palettes[pal][shade(screen[y * SCREEN_WIDTH + x])]
+                        }
+                    };
+                    self.framebuff[(y + SCREEN_Y) * SGB_WIDTH + x + SCREEN_X] = color;
+                }
+            }
+        }
+
+        for ty in 0..BORDER_TILES_Y {
+            for tx in 0..BORDER_TILES_X {
+                let off = 2 * (ty * BORDER_TILES_X + tx);
+                let entry = ((self.border_map[off + 1] as u16) << 8) | self.border_map[off] as u16;
+                self.render_border_tile(tx, ty, entry, backdrop);
+            }
+        }
+    }
+
+    /* Border tiles are in SNES 4bpp format. Color 0 is transparent and shows what's under it. */
+    fn render_border_tile(&mut self, tx: usize, ty: usize, entry: u16, backdrop: Color) {
+        let tile = &self.border_tiles[(entry & 0xFF) as usize * BORDER_TILE_SIZE..][..BORDER_TILE_SIZE];
+        let palette = (((entry >> 10) & 0x7) as usize).max(BORDER_PALETTE_BASE) - BORDER_PALETTE_BASE;
+        let (x_flip, y_flip) = (entry & 0x4000 != 0, entry & 0x8000 != 0);
+        for row in 0..8 {
+            let src_row = if y_flip { 7 - row } else { row };
+            let planes = [tile[2 * src_row], tile[2 * src_row + 1], tile[16 + 2 * src_row], tile[17 + 2 * src_row]];
+            for col in 0..8 {
+                let bit = if x_flip { col } else { 7 - col };
+                let idx = planes.iter().enumerate().fold(0, |acc, (n, p)| acc | (((p >> bit) & 1) as usize) << n);
+                let (x, y) = (tx * 8 + col, ty * 8 + row);
+                let in_screen = (SCREEN_X..SCREEN_X + SCREEN_WIDTH).contains(&x) && (SCREEN_Y..SCREEN_Y + SCREEN_HEIGHT).contains(&y);
+                let pixel = &mut self.framebuff[y * SGB_WIDTH + x];
+                if idx != 0 {
+                    let off = BORDER_PALETTES_OFFSET + (palette * 16 + idx) * 2;
+                    *pixel = snes_color(self.border_map[off], self.border_map[off + 1]);
+                } else if !in_screen {
+                    *pixel = backdrop;
+                }
+            }
+        }
+    }
+}
@@ -16,83 +16,147 @@ pub const STEPS_16384HZ: u64 = 64;
 pub const STEPS_65536HZ: u64 = 16;
 pub const STEPS_262144HZ: u64 = 4;
 
+/* Bit of system counter whose falling edge clocks TIMA in given mode */
+impl TimerMode {
+    pub fn counter_bit(&self) -> u16 {
+        match self {
+            TimerMode::FQ_4096HZ => 1 << 9,
+            TimerMode::FQ_16384HZ => 1 << 7,
+            TimerMode::FQ_65536HZ => 1 << 5,
+            TimerMode::FQ_262144HZ => 1 << 3,
+        }
+    }
+}
+
+/* T-cycles per machine cycle */
+const COUNTER_STEP: u16 = 4;
+
+/*
+ * After TIMA overflows it reads 0 for one machine cycle, then it's reloaded with TMA and interrupt is requested.
+ * Writing TIMA in the first cycle cancels the reload. In the reload cycle TIMA writes are ignored and TMA writes
+ * go straight to TIMA.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Overflow {
+    None,
+    Delay,
+    Reload,
+}
+
+/*
+ * Timer is built around 16-bit system counter, incremented every T-cycle. DIV is its upper byte.
+ * TIMA is incremented on falling edge of (selected counter bit AND timer enable), so anything that drops
+ * that signal increments TIMA: writing DIV, disabling timer or switching mode.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Timer {
-    div_cycle: u64,
-    tima_cycle: u64,
+    counter: u16,
+    /* Last value of the signal clocking TIMA */
+    signal: bool,
+    overflow: Overflow,
 }
 
 impl<T: BankController> Clocked<T> for Timer {
-    // The timer clock is much slower than main 1MHz clock.
-    // It means that timer does fraction of work per one machine cycle.
-    // ie. timer with 65536Hz clock will increment by 1/16 per one machine cycle
-    // for this reason next_time() returns 1, because Timer cannot overrun CPU
+    // Timer changes state every machine cycle, so it cannot overrun CPU.
     fn next_time(&self, _: &mut MMU<T>) -> u64 {
         1
     }
 
     fn step(&mut self, mmu: &mut MMU<T>) {
-        // DIV is clocked by 16384Hz clock
-        if self.div_cycle % STEPS_16384HZ == 0 {
-            let div = Timer::DIV(mmu);
-            let new = if div == 0xFF { 0 } else { div + 1 };
-            Timer::_DIV(mmu, new);
-            self.div_cycle = 0;
-        }
-        self.div_cycle += 1;
-
-        if !Timer::ENABLED(mmu) {
-            return;
-        };
-
-        let mode = Timer::MODE(mmu);
-        let mut check_ticks = |steps: u64| {
-            // If not enough cycles passed
-            if self.tima_cycle % steps != 0 {
-                return;
-            }
-
-            let count = Timer::TIMA(mmu);
-            if count == 0xFF {
-                // Trigger timer interrupt
-                Timer::timer_int(mmu);
-                // Reload TIMA with TMA
+        self.overflow = match self.overflow {
+            Overflow::Delay => {
                 let tma = Timer::TMA(mmu);
                 Timer::_TIMA(mmu, tma);
-            } else {
-                Timer::_TIMA(mmu, count + 1);
+                Timer::timer_int(mmu);
+                Overflow::Reload
             }
-
-            self.tima_cycle = 0;
+            _ => Overflow::None,
         };
 
-        match mode {
-            TimerMode::FQ_16384HZ => check_ticks(STEPS_16384HZ),
-            TimerMode::FQ_65536HZ => check_ticks(STEPS_65536HZ),
-            TimerMode::FQ_262144HZ => check_ticks(STEPS_262144HZ),
-            TimerMode::FQ_4096HZ => check_ticks(STEPS_4096HZ),
-        };
-        self.tima_cycle += 1;
+        self.counter = self.counter.wrapping_add(COUNTER_STEP);
+        Timer::_DIV(mmu, (self.counter >> 8) as u8);
+        self.update_signal(mmu);
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Timer {
     pub fn new() -> Self {
         Self {
-            div_cycle: 0,
-            tima_cycle: 0
+            counter: 0,
+            signal: false,
+            overflow: Overflow::None,
         }
     }
 
+    pub fn counter(&self) -> u16 {
+        self.counter
+    }
+
+    /* Used when boot ROM is skipped, to start with counter it would have left */
+    pub fn set_counter<T: BankController>(&mut self, mmu: &mut MMU<T>, counter: u16) {
+        self.counter = counter;
+        Timer::_DIV(mmu, (counter >> 8) as u8);
+        self.signal = Timer::ENABLED(mmu) && counter & Timer::MODE(mmu).counter_bit() != 0;
+    }
+
     fn timer_int<T: BankController>(mmu: &mut MMU<T>) {
-        mmu.set_bit(ioregs::IF, 2, true);
+        request_interrupt(mmu, Interrupt::Timer);
     }
 
-    pub fn div<T: BankController>(&mut self, mmu: &mut MMU<T>, _: u8) {
-        mmu.write(ioregs::DIV, 0);
+    fn update_signal<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        let signal = Timer::ENABLED(mmu) && self.counter & Timer::MODE(mmu).counter_bit() != 0;
+        if self.signal && !signal {
+            self.increment(mmu);
+        }
+        self.signal = signal;
     }
 
-    pub fn tima<T: BankController>(&mut self, mmu: &mut MMU<T>, val: u8) {
-        mmu.write(ioregs::TIMA, val);
+    fn increment<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        let (tima, overflow) = Timer::TIMA(mmu).overflowing_add(1);
+        Timer::_TIMA(mmu, tima);
+        if overflow {
+            self.overflow = Overflow::Delay;
+        }
+    }
+
+    /*
+     * Register writes. They're called after value was written to memory.
+     */
+
+    // Any write resets whole system counter
+    pub fn write_div<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        self.counter = 0;
+        Timer::_DIV(mmu, 0);
+        self.update_signal(mmu);
+    }
+
+    pub fn write_tima<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        match self.overflow {
+            Overflow::Delay => self.overflow = Overflow::None,
+            Overflow::Reload => {
+                let tma = Timer::TMA(mmu);
+                Timer::_TIMA(mmu, tma);
+            }
+            Overflow::None => {}
+        }
+    }
+
+    pub fn write_tma<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        if self.overflow == Overflow::Reload {
+            let tma = Timer::TMA(mmu);
+            Timer::_TIMA(mmu, tma);
+        }
+    }
+
+    pub fn write_tac<T: BankController>(&mut self, mmu: &mut MMU<T>) {
+        self.update_signal(mmu);
     }
 
     pub fn DIV<T: BankController>(mmu: &mut MMU<T>) -> u8 {
@@ -1,180 +1,1804 @@
-pub mod mem;
-pub use mem::*;
-pub mod utils;
-pub use utils::*;
-pub mod dev;
-pub use dev::*;
-pub mod state;
-pub use state::*;
-
-use std::io::prelude::*;
+extern crate gameboy;
+
+mod overlay;
+mod timing;
+#[cfg(feature = "tui")]
+mod tui;
+
+use gameboy::*;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
 use std::time::{Duration, Instant};
-use std::{env, fs, thread};
+use std::{fs, process, thread};
+
+use overlay::{Notices, Stats};
+use timing::FrameTiming;
 
-use sdl2::audio::{AudioQueue, AudioSpecDesired};
-use sdl2::event::Event;
-use sdl2::keyboard::{Keycode, Scancode};
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use sdl2::audio::AudioSpecDesired;
+use sdl2::controller::GameController;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::{FullscreenType, WindowContext};
+use structopt::StructOpt;
 
 const WINDOW_NAME: &str = "GAMEBOY EMU";
-const SCALE: u32 = 3;
-const FRAME_TIME: Duration = Duration::from_millis(1000 / 60);
+/* Emulated frames buffered between emulation and SDL threads */
+const FRAME_QUEUE_SIZE: usize = 2;
+/*
+ * How much audio should be queued(in fractions of second) before we stop asking for new frames.
+ * Audio device consumes samples at exact rate, so keeping the queue at this level is what paces the emulation.
+ */
+const AUDIO_BACKLOG_DIVISOR: u32 = 30;
+/* Each stereo sample is two i16 values */
+const AUDIO_SAMPLE_BYTES: u32 = 4;
+/* Above this speed resampled audio is just noise, so it's muted */
+const AUDIO_MUTE_SPEED: f32 = 2.0;
+/* With pacing other than audio, queued audio above this many AUDIO_BACKLOG_DIVISOR fractions is dropped */
+const MAX_AUDIO_BACKLOG: u32 = 4;
+/* How long SDL thread waits for events while paused, there's no audio to pace it */
+const PAUSED_WAIT_MS: u32 = 50;
+/*
+ * Controller motor runs this long after cart turned it on, unless it's turned on again. Games keep switching
+ * it while rumbling, so it only stops by itself when emulation does, e.g. when paused.
+ */
+const RUMBLE_MS: u32 = 1000;
+/* F1-F4 save state to slot, with shift held they load it */
+#[cfg(feature = "serde")]
+const STATE_SLOT_KEYS: [Keycode; 4] = [Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4];
+
+/*
+ * Frame is what emulation thread produces: picture and audio generated while emulating it.
+ */
+struct Frame {
+    /* Final colors, with palette already applied */
+    pixels: Vec<(u8, u8, u8)>,
+    /* Picture is wider than GB screen when SGB border is shown, or more GBs are side by side */
+    width: usize,
+    /* Picture pixels per side of GB pixel, more than 1 with dot matrix rendering */
+    dots: usize,
+    /* Interleaved stereo samples: L, R, L, R... */
+    audio: Vec<i16>,
+    /* Time emulation thread took to make it */
+    emulation: Duration,
+    /* Only while debug overlay is shown */
+    stats: Option<Stats>,
+    /* Results of actions done since previous frame, shown on screen */
+    notices: Vec<String>,
+}
+
+/*
+ * Messages sent from SDL thread to emulation thread.
+ */
+enum Message {
+    /* Button state of given player changed */
+    Buttons(usize, Input),
+    /* Emulation speed multiplier changed */
+    Speed(f32),
+    /* Reset all GBs, true for hard reset */
+    Reset(bool),
+    /* All GBs paused or unpaused, emulation thread waits for next message while paused */
+    Pause(bool),
+    /* Debug overlay was shown or hidden, emulation thread collects stats for it only when shown */
+    Overlay(bool),
+    /* Layer of all GBs shown or hidden */
+    ToggleLayer(Layer),
+    /* Pictures tinted by what drew each pixel turned on or off */
+    ToggleSources,
+    /* Sound channel 1-4 of all GBs muted or unmuted */
+    ToggleMute(u8),
+    /* Only sound channel 1-4 heard, or all of them again if it already was */
+    ToggleSolo(u8),
+    /* Cart dropped on the window, goes into the first GB */
+    LoadRom(Box<DroppedCart>),
+    /* Save state of the first GB to file of given slot */
+    #[cfg(feature = "serde")]
+    SaveState(PathBuf, usize),
+    #[cfg(feature = "serde")]
+    LoadState(PathBuf, usize),
+}
+
+/*
+ * Cart dropped on the window, with everything that goes along with it.
+ */
+struct DroppedCart {
+    loaded: LoadedRom,
+    symbols: Option<Symbols>,
+    /* Palette remembered for the game, or the one CGB picks for it */
+    palette: Palette,
+    files: GameFiles,
+}
+
+/*
+ * Files game's progress is kept in. Written when the game is closed: on exit, or when other cart is dropped.
+ */
+struct GameFiles {
+    /* Battery backed cart RAM, None when cart has no battery */
+    battery: Option<PathBuf>,
+    /* Big cart RAM is mapped to battery save instead of being loaded, see map_battery */
+    mmap_battery: bool,
+    /* State restored when game is opened, None when autosave is off */
+    #[cfg(feature = "serde")]
+    autosave: Option<PathBuf>,
+}
+
+impl GameFiles {
+    fn new(save_dir: &Path, rom: &Path, header: &CartHeader, autosave: bool, mmap_battery: bool) -> Self {
+        #[cfg(not(feature = "serde"))]
+        let _ = autosave;
+        Self {
+            battery: header.has_battery().then(|| battery_path(save_dir, rom)),
+            mmap_battery,
+            #[cfg(feature = "serde")]
+            autosave: autosave.then(|| autosave_path(save_dir, rom)),
+        }
+    }
+}
+
+/* GPU layers which can be hidden, to see what draws a glitch */
+#[derive(Copy, Clone)]
+enum Layer {
+    Background,
+    Window,
+    Sprites,
+}
+
+impl Layer {
+    fn flag(self, gpu: &mut GPU) -> &mut bool {
+        match self {
+            Layer::Background => &mut gpu.show_background,
+            Layer::Window => &mut gpu.show_window,
+            Layer::Sprites => &mut gpu.show_sprites,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Layer::Background => "Background",
+            Layer::Window => "Window",
+            Layer::Sprites => "Sprites",
+        }
+    }
+}
+
+/* Button state, as polled from keyboard */
+#[derive(Copy, Clone, Default, PartialEq)]
+struct Input {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+    a: bool,
+    b: bool,
+    select: bool,
+    start: bool,
+    turbo_a: bool,
+    turbo_b: bool,
+}
+
+#[derive(StructOpt)]
+#[structopt(name = "gameboy", about = "Game Boy emulator")]
+enum Command {
+    /// Run ROM in a window
+    Run(Box<RunArgs>),
+    /// Print cart header
+    Info {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+    },
+    /// Disassemble part of ROM
+    Disasm {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// ROM offsets to disassemble, as START-END in hex. END is exclusive.
+        #[structopt(long, default_value = "100-150", parse(try_from_str = parse_range))]
+        range: (usize, usize),
+        /// Symbol file(RGBDS or WLA-DX .sym) with labels to show [default: ROM path with .sym extension, if it exists]
+        #[structopt(long, parse(from_os_str))]
+        symbols: Option<PathBuf>,
+    },
+    /// Check Nintendo logo and header/global checksums. Exits with 1 if any of them is invalid.
+    VerifyHeader {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+    },
+    /// Compare two logs written with run --audit and print first frame they differ at. Exits with 1 if there is one.
+    AuditDiff {
+        #[structopt(parse(from_os_str))]
+        first: PathBuf,
+        #[structopt(parse(from_os_str))]
+        second: PathBuf,
+    },
+}
+
+/*
+ * Options of run command. Each one given overrides value from config file.
+ */
+#[derive(StructOpt)]
+struct RunArgs {
+    /// ROM file to run(.gb, .gbc or .zip containing one of them) [default: ROM played last time]
+    #[structopt(parse(from_os_str))]
+    rom: Option<PathBuf>,
+    /// Config file [default: gameboy.toml, if it exists]
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Window size multiplier. Window can be resized, picture keeps its aspect ratio. [default: window size from
+    /// last time]
+    #[structopt(short, long)]
+    scale: Option<u32>,
+    /// Start in fullscreen. F11 toggles it.
+    #[structopt(long)]
+    fullscreen: bool,
+    /// Scale picture only by whole multiples, so all pixels have the same size
+    #[structopt(long)]
+    integer_scale: bool,
+    /// How much of previous frame is mixed into each one(0-0.9), hides sprites flickering at 30 Hz like DMG LCD
+    #[structopt(long)]
+    frame_blend: Option<f32>,
+    /// Draw each GB pixel as LCD dot, with thin gaps between them, for picture looking like real screen
+    #[structopt(long)]
+    dot_matrix: bool,
+    /// What times the frames: audio(sound card plays at its own pace), exact(59.7275 Hz by host clock),
+    /// vsync(display refresh) or uncapped(as fast as possible, without sound) [default: audio]
+    #[structopt(long)]
+    pacing: Option<Pacing>,
+    /// Audio sample rate in Hz
+    #[structopt(long)]
+    sample_rate: Option<u32>,
+    /// Boot ROM to run before the cart
+    #[structopt(long, parse(from_os_str))]
+    bootrom: Option<PathBuf>,
+    /// Start right in the cart, skipping boot ROM and its logo animation even when one is given
+    #[structopt(long)]
+    fast_boot: bool,
+    /// Emulated hardware: DMG0, DMG, MGB, CGB or SGB [default: picked based on cart header]
+    #[structopt(long)]
+    model: Option<HardwareModel>,
+    /// Directory for saves
+    #[structopt(long, parse(from_os_str))]
+    save_dir: Option<PathBuf>,
+    /// Save state on exit and restore it next time the game is opened. Not in local link mode.
+    #[structopt(long)]
+    autosave: bool,
+    /// Map cart RAM of 32KB and more to battery save file, so it's saved as the game writes it
+    #[structopt(long)]
+    mmap_saves: bool,
+    /// 4 comma separated colors, from lightest to darkest, e.g. "#E0F8D0,#88C070,#346856,#081820". Remembered for
+    /// the game, it's used again next time the game is opened without this option.
+    #[structopt(long, parse(try_from_str = parse_palette_arg))]
+    palette: Option<[(u8, u8, u8); 4]>,
+    /// Refuse to run carts with invalid header
+    #[structopt(long)]
+    strict_header: bool,
+    /// What illegal memory accesses(writes to ROM, accesses to missing cart RAM) do: ignore, log(with mmu=debug),
+    /// trap(stop in --debug) or panic [default: log]
+    #[structopt(long)]
+    bus: Option<BusPolicy>,
+    /// Connect Game Boy Printer to link port. Printed images are saved as PNG to save directory.
+    #[structopt(long, conflicts_with_all = &["listen", "connect"])]
+    printer: bool,
+    /// Wait for other emulator to connect link cable on this address, e.g. 0.0.0.0:5000
+    #[structopt(long, conflicts_with = "connect")]
+    listen: Option<String>,
+    /// Connect link cable to other emulator listening on this address
+    #[structopt(long)]
+    connect: Option<String>,
+    /// Keep both linked emulators in lockstep, frame by frame. Deterministic, but slowed down by latency.
+    #[structopt(long)]
+    lockstep: bool,
+    /// Run two GBs connected with link cable, side by side in one window. Second one uses player2_keys.
+    #[structopt(long, conflicts_with_all = &["printer", "listen", "connect"])]
+    local_link: bool,
+    /// ROM for second GB in local link mode [default: same as the first one]
+    #[structopt(long, parse(from_os_str), requires = "local-link")]
+    player2_rom: Option<PathBuf>,
+    /// Count memory accesses and save them on exit, as grayscale map(.png) or per address counters(.csv)
+    #[structopt(long, parse(from_os_str))]
+    heatmap: Option<PathBuf>,
+    /// Write hash of emulated state(CPU registers, WRAM, VRAM, IO), chained over all frames so far, after every
+    /// frame. Logs of two runs or of both netplay peers are compared with audit-diff. First GB only in local link mode.
+    #[structopt(long, parse(from_os_str))]
+    audit: Option<PathBuf>,
+    /// Log PPU modes, bank switches, interrupts, DMA and sound channel triggers, and save the latest of them on exit
+    /// as Chrome trace(.json), to see in chrome://tracing. First GB only in local link mode.
+    #[structopt(long, parse(from_os_str))]
+    trace_events: Option<PathBuf>,
+    /// Comma separated categories to log: ppu, bank, interrupt, dma, apu [default: all of them]
+    #[structopt(long, use_delimiter = true, requires = "trace-events")]
+    trace_categories: Vec<EventCategory>,
+    /// Save PPU modes and interrupts of the last frame on exit, 154 lines by 114 cycles, as picture(.png) or text
+    /// diagram(any other extension). First GB only in local link mode.
+    #[structopt(long, parse(from_os_str))]
+    timeline: Option<PathBuf>,
+    /// Report likely emulation faults(execution of unmapped memory, writes to ROM, stack underflow...) as warnings,
+    /// each followed by backtrace of calls which led to it
+    #[structopt(long)]
+    doctor: bool,
+    /// Symbol file(RGBDS or WLA-DX .sym), labels are shown in CPU traces and backtraces
+    /// [default: ROM path with .sym extension, if it exists]
+    #[structopt(long, parse(from_os_str))]
+    symbols: Option<PathBuf>,
+    /// Start with debug overlay(FPS, frame times, registers, PPU state, channel meters) shown. F12 toggles it.
+    #[structopt(long, conflicts_with = "debug")]
+    overlay: bool,
+    /// Start in terminal debugger instead of window, without sound. Works over SSH.
+    #[structopt(long, conflicts_with = "local-link")]
+    debug: bool,
+    /// Log filter, per module, e.g. "warn,mmu=debug,cpu=trace". Modules: cpu, mmu, mbc, dma, ppu, printer, link, doctor,
+    /// watchdog
+    #[structopt(long)]
+    log: Option<String>,
+}
+
+/*
+ * Key bindings resolved to SDL scancodes.
+ */
+struct Keys {
+    up: Vec<Scancode>,
+    down: Vec<Scancode>,
+    left: Vec<Scancode>,
+    right: Vec<Scancode>,
+    a: Vec<Scancode>,
+    b: Vec<Scancode>,
+    select: Vec<Scancode>,
+    start: Vec<Scancode>,
+    turbo_a: Vec<Scancode>,
+    turbo_b: Vec<Scancode>,
+    fast_forward: Vec<Scancode>,
+    slower: Vec<Scancode>,
+    faster: Vec<Scancode>,
+    reset: Vec<Scancode>,
+    pause: Vec<Scancode>,
+    fullscreen: Vec<Scancode>,
+    overlay: Vec<Scancode>,
+    toggle_background: Vec<Scancode>,
+    toggle_window: Vec<Scancode>,
+    toggle_sprites: Vec<Scancode>,
+    toggle_sources: Vec<Scancode>,
+    /* Channels 1-4 */
+    toggle_channels: [Vec<Scancode>; 4],
+}
+
+impl Keys {
+    /* Change of what's drawn(layers, pixel source tint) made by key, if any */
+    fn view_toggle(&self, code: Scancode) -> Option<Message> {
+        if self.toggle_background.contains(&code) {
+            Some(Message::ToggleLayer(Layer::Background))
+        } else if self.toggle_window.contains(&code) {
+            Some(Message::ToggleLayer(Layer::Window))
+        } else if self.toggle_sprites.contains(&code) {
+            Some(Message::ToggleLayer(Layer::Sprites))
+        } else if self.toggle_sources.contains(&code) {
+            Some(Message::ToggleSources)
+        } else {
+            None
+        }
+    }
+
+    /* Sound channel muted or soloed by key, if any */
+    fn mixer_toggle(&self, code: Scancode, solo: bool) -> Option<Message> {
+        let chan = self.toggle_channels.iter().position(|keys| keys.contains(&code))? as u8 + 1;
+        Some(if solo { Message::ToggleSolo(chan) } else { Message::ToggleMute(chan) })
+    }
+}
+
+/*
+ * Core logs with module names as targets. Only warnings are shown by default, RUST_LOG or --log
+ * can make any module more verbose.
+ */
+fn init_logger(filter: Option<&str>) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"));
+    if let Some(filter) = filter {
+        builder.parse_filters(filter);
+    }
+    builder.init();
+}
 
 fn main() {
-    if env::args().len() != 2 {
-        panic!("Usage: {} [rom]", env::args().nth(0).unwrap());
-    }
-    let path = env::args().nth(1).unwrap();
-    let mut file = fs::File::open(path).unwrap();
-    let mut rom = Vec::new();
-    file.read_to_end(&mut rom).unwrap();
-/*
-    let header = CartHeader::new(rom.iter().take(0x150).skip(0x100).map(|x| *x).collect());
-    println!("{}", header);
-*/
-    // Mapper type shouldn't be hardcoded here
-    let mut runtime = Runtime::new(mbc::MBC1::new(rom));
-    runtime.state.mmu.disable_bootrom();
-    runtime.cpu.PC.set(0x100);
+    let command = Command::from_args();
+    init_logger(match &command {
+        Command::Run(args) => args.log.as_deref(),
+        _ => None,
+    });
+    match command {
+        Command::Run(args) => run(*args),
+        Command::Info { rom } => info(&read_rom(&rom, false)),
+        Command::Disasm { rom, range, symbols } => {
+            let symbols = read_symbols(symbols.as_deref(), &rom).unwrap_or_default();
+            disasm(&read_rom(&rom, false).rom, range, &symbols)
+        }
+        Command::VerifyHeader { rom } => {
+            if !verify_header(&read_rom(&rom, false)) {
+                process::exit(1);
+            }
+        }
+        Command::AuditDiff { first, second } => {
+            if !audit_diff(&first, &second) {
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn read_rom(path: &Path, strict: bool) -> LoadedRom {
+    load_rom(path, strict).unwrap_or_else(|e| {
+        eprintln!("Unable to load ROM: {}", e);
+        process::exit(1);
+    })
+}
+
+/*
+ * Symbols given on command line have to load. Otherwise ones next to the ROM are picked up, if there are any,
+ * and not being able to read them is just a warning.
+ */
+fn read_symbols(path: Option<&Path>, rom: &Path) -> Option<Symbols> {
+    match path {
+        Some(path) => Some(Symbols::load(path).unwrap_or_else(|e| {
+            eprintln!("Invalid symbol file: {}", e);
+            process::exit(1);
+        })),
+        None => sibling_symbols(rom),
+    }
+}
+
+fn sibling_symbols(rom: &Path) -> Option<Symbols> {
+    let path = rom.with_extension("sym");
+    if !path.is_file() {
+        return None;
+    }
+    Symbols::load(&path)
+        .map_err(|e| eprintln!("Warning: invalid symbol file: {}", e))
+        .ok()
+}
+
+fn info(loaded: &LoadedRom) {
+    println!("{}", loaded.header);
+    println!("Hardware: {:?}", loaded.mode);
+}
+
+/*
+ * Prints instructions in given range. Addresses are shown as BANK:ADDR, the way CPU would see them
+ * when the bank is mapped. Labels from symbols are printed before instructions they point at and replace
+ * addresses in operands.
+ */
+fn disasm(rom: &[u8], (start, end): (usize, usize), symbols: &Symbols) {
+    let end = end.min(rom.len());
+    let mut offset = start;
+    while offset < end {
+        let bank = offset / ROM_BANK_SIZE;
+        let addr = if bank == 0 { offset } else { ROM_BANK_SIZE + offset % ROM_BANK_SIZE } as u16;
+        let (text, size) = disassemble(addr, &rom[offset..end])
+            .unwrap_or_else(|| (format!("DB ${:02X}", rom[offset]), 1));
+        // Code in bank 0 is assumed to call into bank 1
+        let mapped = bank.max(1);
+        if let Some(label) = symbols.label(mapped, addr) {
+            println!("{}:", label);
+        }
+        let text = symbols.annotate(&text, mapped);
+        let bytes = rom[offset..(offset + size as usize).min(end)]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<String>>()
+            .join(" ");
+        println!("{:02X}:{:04X}  {:<8}  {}", bank, addr, bytes, text);
+        offset += size as usize;
+    }
+}
+
+fn verify_header(loaded: &LoadedRom) -> bool {
+    let (header, rom) = (&loaded.header, &loaded.rom[..]);
+    let status = |ok: bool| if ok { "OK" } else { "INVALID" };
+    println!("Nintendo logo: {}", status(header.verify_logo()));
+    println!(
+        "Header checksum: 0x{:02X}, expected 0x{:02X} - {}",
+        header.checksum(),
+        header.calc_header_checksum(),
+        status(header.verify_header_checksum())
+    );
+    println!(
+        "Global checksum: 0x{:04X}, expected 0x{:04X} - {}",
+        header.global_checksum(),
+        CartHeader::calc_global_checksum(rom),
+        status(header.verify_global_checksum(rom))
+    );
+    header.problems(rom).is_empty()
+}
+
+fn audit_diff(first: &Path, second: &Path) -> bool {
+    let (a, b) = match (load_audit(first), load_audit(second)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Unable to read audit log: {}", e);
+            process::exit(1);
+        }
+    };
+    match first_divergence(&a, &b) {
+        Some(frame) => {
+            println!("Runs diverge at frame {}", frame);
+            false
+        }
+        None => {
+            println!("Runs match for {} frames ({} and {} recorded)", a.len().min(b.len()), a.len(), b.len());
+            true
+        }
+    }
+}
+
+fn run(args: RunArgs) {
+    let base = load_config(&args).unwrap_or_else(|e| {
+        eprintln!("Invalid config: {}", e);
+        process::exit(1);
+    });
+    let session_path = base.save_dir.join(SESSION_FILE);
+    let mut session = Session::load(&session_path).unwrap_or_else(|e| {
+        eprintln!("Invalid session: {}", e);
+        process::exit(1);
+    });
+    let rom = args.rom.clone().or_else(|| session.last_rom.clone()).unwrap_or_else(|| {
+        eprintln!("No ROM given, and none was played before");
+        process::exit(1);
+    });
+    let loaded = read_rom(&rom, base.strict.header);
+    let mut game = Session::key(&loaded.header);
+    if let Some(palette) = args.palette {
+        session.game_mut(&game).palette = Some(palette);
+    }
+    let mut config = game_config(&session, &game, &base);
+    boot_colors(&mut config, &loaded.header, &session.game(&game));
+    // Save states aren't supported in local link mode
+    let autosave = config.autosave && !args.local_link;
+    let files = GameFiles::new(&config.save_dir, &rom, &loaded.header, autosave, config.mmap_saves);
+    let emulated_clock = emulated_clock(&args);
+    let mut runtime = build_runtime(loaded, &config, emulated_clock);
+    let mut opened = Vec::new();
+    open_game(&mut runtime, &files, &mut opened);
+    if args.printer {
+        runtime.state.serial.connect(Box::new(Printer::new(config.save_dir.clone())));
+    }
+    if let Some(link) = connect_link(&args) {
+        runtime.state.serial.connect(Box::new(link.unwrap_or_else(|e| {
+            eprintln!("Unable to connect link cable: {}", e);
+            process::exit(1);
+        })));
+    }
+    let heatmap = args.heatmap.as_ref().map(|_| Heatmap::new());
+    if let Some(heatmap) = &heatmap {
+        runtime.state.mmu.subscribe(0x0000..=0xFFFF, None, heatmap.recorder());
+    }
+    if let Some(path) = &args.audit {
+        runtime.set_audit(Some(Audit::logging(path).unwrap_or_else(|e| {
+            eprintln!("Unable to create audit log: {}: {}", path.display(), e);
+            process::exit(1);
+        })));
+    }
+    if args.trace_events.is_some() || args.timeline.is_some() {
+        let mut events = EventLog::default();
+        if !args.trace_categories.is_empty() {
+            events = events.with_categories(&args.trace_categories);
+        } else if args.trace_events.is_none() {
+            events = events.with_categories(&[EventCategory::Ppu, EventCategory::Interrupt]);
+        }
+        runtime.set_event_log(Some(events));
+    }
+    runtime.state.symbols = read_symbols(args.symbols.as_deref(), &rom);
+    let mut runtimes = vec![runtime];
+    if args.local_link {
+        let path = args.player2_rom.as_ref().unwrap_or(&rom);
+        let mut second = build_runtime(read_rom(path, config.strict.header), &config, emulated_clock);
+        second.state.symbols = match &args.player2_rom {
+            Some(path) => sibling_symbols(path),
+            None => runtimes[0].state.symbols.clone(),
+        };
+        let (cable1, cable2) = LocalLink::pair();
+        runtimes[0].state.serial.connect(Box::new(cable1));
+        second.state.serial.connect(Box::new(cable2));
+        runtimes.push(second);
+    }
+    if args.doctor {
+        runtimes.iter_mut().for_each(|runtime| {
+            runtime.state.doctor = Some(Doctor::new());
+            runtime.cpu.set_call_trace(true);
+        });
+    }
+    if args.debug {
+        debug(&mut runtimes[0]);
+        close_game(&mut runtimes[0], &files, &config, &mut Vec::new());
+        save_heatmap(heatmap, &args);
+        save_events(runtimes[0].take_event_log(), &args);
+        save_session(session, &session_path, &rom);
+        return;
+    }
+    let mut players = [&config.keys, &config.player2_keys]
+        .iter()
+        .map(|bindings| resolve_keys(bindings))
+        .collect::<Result<Vec<Keys>, String>>()
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid key bindings: {}", e);
+            process::exit(1);
+        });
+    let sample_rate = runtimes[0].state.apu.sample_rate();
+    let audio_backlog = sample_rate / AUDIO_BACKLOG_DIVISOR;
+    // GBs are shown side by side
+    let (width, height) = runtimes
+        .iter()
+        .map(|runtime| screen_size(&runtime.state))
+        .fold((0, 0), |(width, height), (w, h)| (width + w, height.max(h)));
+
+    // Motor of each GB's cart drives controller of its player, which only SDL thread can touch
+    let (rumble_tx, rumble_rx) = mpsc::channel();
+    for (player, runtime) in runtimes.iter_mut().enumerate() {
+        let rumble_tx = rumble_tx.clone();
+        runtime.on_rumble(Box::new(move |on| {
+            let _ = rumble_tx.send((player, on));
+        }));
+    }
+
+    let (frame_tx, frame_rx) = mpsc::sync_channel(FRAME_QUEUE_SIZE);
+    let (input_tx, input_rx) = mpsc::channel();
+    let palette = config.clone();
+    let emulation = thread::spawn(move || emulate(runtimes, palette, files, emulated_clock, frame_tx, input_rx));
 
     let sdl_context = sdl2::init().unwrap();
 
     let audio_subsystem = sdl_context.audio().unwrap();
     let audio_spec = AudioSpecDesired {
-        freq: Some(apu::PLAYBACK_FREQUENCY as i32),
+        freq: Some(sample_rate as i32),
         channels: Some(2),
         samples: Some(apu::BUFF_SIZE as u16),
     };
     let q = audio_subsystem
         .open_queue::<i16, _>(None, &audio_spec)
         .unwrap();
+    q.resume();
+
+    let mut controllers = open_controllers(&sdl_context);
 
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window(
-            WINDOW_NAME,
-            SCALE * SCREEN_WIDTH as u32,
-            SCALE * SCREEN_HEIGHT as u32,
-        )
+    // Window size from last time doesn't fit GBs side by side
+    let (window_width, window_height) = match (args.scale, session.window) {
+        (None, Some(size)) if !args.local_link => size,
+        _ => (config.scale * width as u32, config.scale * height as u32),
+    };
+    let mut window = video_subsystem
+        .window(WINDOW_NAME, window_width, window_height)
         .position_centered()
+        .resizable()
         .build()
         .map_err(|e| e.to_string())
         .unwrap();
+    if config.fullscreen {
+        window.set_fullscreen(FullscreenType::Desktop).unwrap();
+    }
     let mut events = sdl_context.event_pump().unwrap();
-    let mut canvas = window
-        .into_canvas()
-        .software()
+    // Software renderer presents right away, vsync needs a hardware one
+    let canvas = match config.pacing {
+        Pacing::Vsync => window.into_canvas().present_vsync(),
+        _ => window.into_canvas().software(),
+    };
+    let mut canvas = canvas
         .build()
         .map_err(|e| e.to_string())
         .unwrap();
+    // Everything is drawn in GB pixels, SDL scales it to the window
+    canvas.set_logical_size(width as u32, height as u32).unwrap();
+    set_integer_scale(&mut canvas, config.integer_scale);
+    let texture_creator = canvas.texture_creator();
+    let mut screen = None;
 
+    let mut inputs = vec![Input::default(); if args.local_link { 2 } else { 1 }];
+    let mut timing = FrameTiming::new();
+    let mut show_overlay = args.overlay;
+    if show_overlay && input_tx.send(Message::Overlay(true)).is_err() {
+        return;
+    }
+    let mut speed: f32 = 1.0;
+    let mut unlocked = config.pacing == Pacing::Uncapped;
+    let mut pacer = FramePacer::new(REFRESH_RATE, Instant::now());
+    // Paused with pause key, or because window lost focus
+    let (mut paused, mut focused) = (false, true);
+    let mut was_paused = false;
+    // Changes when other cart is dropped on the window, save state slots and game settings follow it
+    let mut rom_path = rom;
+    let mut notices = Notices::new();
+    opened.into_iter().for_each(|notice| notices.push(notice));
+    if let Some(notice) = slot_notice(&session.game(&game)) {
+        notices.push(notice);
+    }
+    // Drawn again when notices change while no new frames come, e.g. when paused
+    let mut shown: Option<Frame> = None;
     'emulating: loop {
-        let frame_start = Instant::now();
-        let now = Instant::now();
+        let mut latest = None;
+        if was_paused {
+            // No audio to wait for, only frames made after messages(e.g. layer toggles) get drawn
+            latest = frame_rx.try_iter().map(|frame| received(frame, &mut notices, &mut timing)).last();
+        } else if unlocked {
+            // No pacing at all: take whatever emulation thread has produced and drop the audio.
+            match frame_rx.recv() {
+                Ok(frame) => latest = Some(received(frame, &mut notices, &mut timing)),
+                Err(_) => break 'emulating,
+            }
+            latest = frame_rx.try_iter().map(|frame| received(frame, &mut notices, &mut timing)).last().or(latest);
+            q.clear();
+        } else if config.pacing == Pacing::Audio {
+            // Pull frames until there is enough audio queued. If rendering is slow the queue drains
+            // faster, so more frames get pulled here and only the last one is drawn.
+            while q.size() < audio_backlog * AUDIO_SAMPLE_BYTES {
+                match frame_rx.recv() {
+                    Ok(frame) => {
+                        q.queue(&frame.audio);
+                        latest = Some(received(frame, &mut notices, &mut timing));
+                    }
+                    Err(_) => break 'emulating,
+                }
+            }
+        } else {
+            // One frame each time host clock or display says so. Sound card clock runs a bit off,
+            // so audio it hasn't kept up with is dropped rather than piling up as lag.
+            match frame_rx.recv() {
+                Ok(frame) => {
+                    if q.size() < MAX_AUDIO_BACKLOG * audio_backlog * AUDIO_SAMPLE_BYTES {
+                        q.queue(&frame.audio);
+                    }
+                    latest = Some(received(frame, &mut notices, &mut timing));
+                }
+                Err(_) => break 'emulating,
+            }
+        }
 
-        // CPU, GPU and other devices emulated here.
-        while runtime.cpu_cycles() < CPU_CYCLES_PER_FRAME {
-            runtime.step();
-            let apu = &mut runtime.state.apu;
-             play_stereo_samples(&q, apu);
-
-        }
-        runtime.reset_cycles();
-        // Print how long internal updates took
-        // println!("Internal: {}ms", now.elapsed().as_millis());
-        // println!("NR 50: 0b{:8b}", runtime.state.safe_read(NR_50));
-        // println!("NR 51: 0b{:8b}", runtime.state.safe_read(NR_51));
-        // println!("NR 52: 0b{:8b}", runtime.state.safe_read(NR_52));
-
-        // Measure how long SDL part takes
-        let now = Instant::now();
-        // Handle events stream
-        for event in events.poll_iter() {
-            if let Event::Quit { .. }
-            | Event::KeyDown {
-                keycode: Some(Keycode::Escape),
-                ..
-            } = event
-            {
+        // Render last picture GPU finished
+        let fresh = latest.is_some();
+        if fresh {
+            shown = latest;
+        }
+        if let (true, Some(frame)) = (notices.update() || fresh, &shown) {
+            let started = Instant::now();
+            let texture = fit_picture(&mut canvas, &texture_creator, &mut screen, &config, frame);
+            draw_frame(&mut canvas, texture, frame);
+            if let (true, Some(stats)) = (show_overlay, &frame.stats) {
+                overlay::draw(&mut canvas, stats, &timing);
+            }
+            notices.draw(&mut canvas, frame.screen_size().1);
+            canvas.present();
+            if fresh {
+                timing.drawn(started.elapsed());
+            }
+        }
+
+        // Instead of sleeping, wait for events until queued audio is close to running out, or next frame is due.
+        // Vsync already waited in present().
+        let backlog = q.size() / AUDIO_SAMPLE_BYTES;
+        let excess = backlog.saturating_sub(audio_backlog);
+        let paced = !was_paused && !unlocked && config.pacing == Pacing::Exact;
+        if paced {
+            pacer.frame_done(Instant::now());
+        }
+        let timeout = match (was_paused, unlocked, config.pacing) {
+            (true, _, _) => PAUSED_WAIT_MS,
+            (false, true, _) | (false, false, Pacing::Vsync) => 0,
+            (false, false, Pacing::Exact) => pacer.remaining(Instant::now()).as_millis() as u32,
+            (false, false, _) => 1000 * excess / sample_rate,
+        };
+        let started = Instant::now();
+        let first = events.wait_event_timeout(timeout);
+        timing.slept(started.elapsed());
+        let mut new_speed = speed;
+        let keys = &players[0];
+        // Keys of dropped cart, they replace player 1 keys once all events are handled
+        let mut rebound = None;
+        for event in first.into_iter().chain(events.poll_iter()) {
+            match event {
+                // Window closed, or SIGINT/SIGTERM which SDL turns into quit too
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'emulating,
+                Event::KeyDown {
+                    scancode: Some(code),
+                    ..
+                } if keys.slower.contains(&code) => new_speed = (new_speed / 2.0).max(MIN_SPEED),
+                Event::KeyDown {
+                    scancode: Some(code),
+                    ..
+                } if keys.faster.contains(&code) => new_speed = (new_speed * 2.0).min(MAX_SPEED),
+                Event::KeyDown {
+                    scancode: Some(code),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keys.reset.contains(&code) => {
+                    let hard = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    notices.push(String::from(if hard { "Hard reset" } else { "Reset" }));
+                    if input_tx.send(Message::Reset(hard)).is_err() {
+                        break 'emulating;
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat: false,
+                    ..
+                } if keys.pause.contains(&code) => paused = !paused,
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat: false,
+                    ..
+                } if keys.fullscreen.contains(&code) => {
+                    let window = canvas.window_mut();
+                    let mode = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    if let Err(e) = window.set_fullscreen(mode) {
+                        eprintln!("Unable to switch fullscreen: {}", e);
+                    }
+                }
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => focused = false,
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => focused = true,
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat: false,
+                    ..
+                } if keys.overlay.contains(&code) => {
+                    show_overlay = !show_overlay;
+                    if input_tx.send(Message::Overlay(show_overlay)).is_err() {
+                        break 'emulating;
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat: false,
+                    ..
+                } if keys.view_toggle(code).is_some() => {
+                    let toggle = keys.view_toggle(code).unwrap();
+                    if input_tx.send(toggle).is_err() {
+                        break 'emulating;
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(code),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keys.mixer_toggle(code, false).is_some() => {
+                    let solo = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let toggle = keys.mixer_toggle(code, solo).unwrap();
+                    if input_tx.send(toggle).is_err() {
+                        break 'emulating;
+                    }
+                }
+                #[cfg(feature = "serde")]
+                Event::KeyDown {
+                    keycode: Some(code),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if STATE_SLOT_KEYS.contains(&code) => {
+                    if args.local_link {
+                        eprintln!("Save states aren't supported in local link mode");
+                        notices.push(String::from("No save states in local link mode"));
+                        continue;
+                    }
+                    let slot = STATE_SLOT_KEYS.iter().position(|key| *key == code).unwrap() + 1;
+                    session.game_mut(&game).slot = Some(slot);
+                    let path = state_slot_path(&config.save_dir, &rom_path, slot);
+                    let msg = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        Message::LoadState(path, slot)
+                    } else {
+                        Message::SaveState(path, slot)
+                    };
+                    if input_tx.send(msg).is_err() {
+                        break 'emulating;
+                    }
+                }
+                Event::DropFile { filename, .. } => {
+                    let path = PathBuf::from(filename);
+                    let loaded = match load_rom(&path, config.strict.header) {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            eprintln!("Unable to load ROM: {}", e);
+                            notices.push(String::from("Unable to load ROM"));
+                            continue;
+                        }
+                    };
+                    let symbols = sibling_symbols(&path);
+                    let files = GameFiles::new(&config.save_dir, &path, &loaded.header, autosave, config.mmap_saves);
+                    rom_path = path;
+                    game = Session::key(&loaded.header);
+                    let mut dropped = game_config(&session, &game, &base);
+                    boot_colors(&mut dropped, &loaded.header, &session.game(&game));
+                    match resolve_keys(&dropped.keys) {
+                        Ok(keys) => rebound = Some(keys),
+                        Err(e) => eprintln!("Invalid key bindings of {}: {}", game, e),
+                    }
+                    if let Some(notice) = slot_notice(&session.game(&game)) {
+                        notices.push(notice);
+                    }
+                    let cart = Box::new(DroppedCart { loaded, symbols, palette: dropped.colors(), files });
+                    if input_tx.send(Message::LoadRom(cart)).is_err() {
+                        break 'emulating;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(keys) = rebound {
+            players[0] = keys;
+        }
+        if new_speed != speed {
+            speed = new_speed;
+            notices.push(format!("Speed {}x", speed));
+            canvas.window_mut().set_title(&window_title(speed, was_paused)).unwrap();
+            if input_tx.send(Message::Speed(speed)).is_err() {
                 break 'emulating;
             }
         }
+        let now_paused = paused || (config.pause_unfocused && !focused);
+        if now_paused != was_paused {
+            was_paused = now_paused;
+            notices.push(String::from(if was_paused { "Paused" } else { "Resumed" }));
+            // Whatever is still queued would play after the picture froze
+            q.clear();
+            canvas.window_mut().set_title(&window_title(speed, was_paused)).unwrap();
+            if input_tx.send(Message::Pause(was_paused)).is_err() {
+                break 'emulating;
+            }
+        }
+
+        for (player, on) in rumble_rx.try_iter() {
+            if let Some(controller) = controllers.get_mut(player) {
+                let strength = if on { u16::MAX } else { 0 };
+                // Controllers without motors refuse, they just don't rumble
+                let _ = controller.set_rumble(strength, strength, RUMBLE_MS);
+            }
+        }
+
         // Poll keyboard for button updates
-        let joypad = &mut runtime.state.joypad;
         let keyboard = events.keyboard_state();
-        joypad
-            .up(keyboard.is_scancode_pressed(Scancode::W)
-                | keyboard.is_scancode_pressed(Scancode::Up));
-        joypad.down(
-            keyboard.is_scancode_pressed(Scancode::S)
-                | keyboard.is_scancode_pressed(Scancode::Down),
-        );
-        joypad.left(
-            keyboard.is_scancode_pressed(Scancode::A)
-                | keyboard.is_scancode_pressed(Scancode::Left),
-        );
-        joypad.right(
-            keyboard.is_scancode_pressed(Scancode::D)
-                | keyboard.is_scancode_pressed(Scancode::Right),
-        );
-        joypad.a(keyboard.is_scancode_pressed(Scancode::Z));
-        joypad.b(keyboard.is_scancode_pressed(Scancode::X));
-        joypad.select(keyboard.is_scancode_pressed(Scancode::Space));
-        joypad.start(
-            keyboard.is_scancode_pressed(Scancode::Return)
-                | keyboard.is_scancode_pressed(Scancode::Return2),
-        );
-
-        // Render current state of GPU framebuffer
-        let gpu = &mut runtime.state.gpu;
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
-        for (i, (r, g, b)) in gpu.framebuff.iter().enumerate() {
-            let y = i / SCREEN_WIDTH;
-            let x = i % SCREEN_WIDTH;
-            let rect = Rect::new(
-                SCALE as i32 * x as i32,
-                SCALE as i32 * y as i32,
-                SCALE,
-                SCALE,
-            );
-
-            canvas.set_draw_color(Color::RGB(*r, *g, *b));
-            canvas.fill_rect(rect).unwrap();
-        }
-        canvas.present();
-        println!("Render : {}ms", now.elapsed().as_millis());
-
-        // If some time left, sleep to get refresh rate of 60Hz
-        if let Some(sleep_time) = FRAME_TIME.checked_sub(frame_start.elapsed()) {
-            println!("Sleeping extra: {}ms", sleep_time.as_millis());
-            thread::sleep(sleep_time);
-        }
-        println!("---------------");
-    }
-}
-
-fn play_stereo_samples(queue: &AudioQueue<i16>, apu: &mut APU) {
-    let left = apu.left_samples().clone();
-    if left.len() < apu::BUFF_SIZE {
-        return;
+        let pressed = |codes: &[Scancode]| codes.iter().any(|c| keyboard.is_scancode_pressed(*c));
+        // Emulation runs as fast as possible while fast forward key is held
+        unlocked = pressed(&players[0].fast_forward) || config.pacing == Pacing::Uncapped;
+        for (player, input) in inputs.iter_mut().enumerate() {
+            let keys = &players[player];
+            let current = Input {
+                up: pressed(&keys.up),
+                down: pressed(&keys.down),
+                left: pressed(&keys.left),
+                right: pressed(&keys.right),
+                a: pressed(&keys.a),
+                b: pressed(&keys.b),
+                select: pressed(&keys.select),
+                start: pressed(&keys.start),
+                turbo_a: pressed(&keys.turbo_a),
+                turbo_b: pressed(&keys.turbo_b),
+            };
+            if current != *input {
+                *input = current;
+                if input_tx.send(Message::Buttons(player, current)).is_err() {
+                    break 'emulating;
+                }
+            }
+        }
+        // Events woke the thread up before next frame was due. Pacer restarts by itself after pause and fast forward.
+        if paced {
+            let started = Instant::now();
+            thread::sleep(pacer.remaining(started));
+            timing.slept(timing.slept + started.elapsed());
+        }
     }
-    let l_buff = &left[..apu::BUFF_SIZE];
 
-    let right = apu.right_samples().clone();
-    if right.len() < apu::BUFF_SIZE {
-        return;
+    // Queued audio would go on playing while saves are written, then get cut off with a click
+    q.pause();
+    q.clear();
+    drop(q);
+    // Dropping receiver makes emulation thread quit on next frame, dropping sender wakes it up when paused.
+    // It writes game's progress before it's done.
+    drop(frame_rx);
+    drop(input_tx);
+    let events = emulation.join().unwrap();
+    save_heatmap(heatmap, &args);
+    save_events(events, &args);
+    // Fullscreen size says nothing about the window, and local link one is wider than a single GB needs
+    let window = canvas.window();
+    if window.fullscreen_state() == FullscreenType::Off && !args.local_link {
+        session.window = Some(window.size());
+    }
+    save_session(session, &session_path, &rom_path);
+}
+
+/* Game controllers connected at start, first one is player 1's. Only used for rumble, buttons come from keyboard. */
+fn open_controllers(sdl_context: &sdl2::Sdl) -> Vec<GameController> {
+    let subsystem = match sdl_context.game_controller() {
+        Ok(subsystem) => subsystem,
+        Err(e) => {
+            eprintln!("Warning: no game controllers, carts won't rumble: {}", e);
+            return Vec::new();
+        }
+    };
+    let count = subsystem.num_joysticks().unwrap_or(0);
+    (0..count)
+        .filter(|&index| subsystem.is_game_controller(index))
+        .filter_map(|index| subsystem.open(index).ok())
+        .collect()
+}
+
+/* Speed is shown when it isn't 1x, and pause whenever emulation is paused */
+fn window_title(speed: f32, paused: bool) -> String {
+    let mut title = String::from(WINDOW_NAME);
+    if speed != 1.0 {
+        title.push_str(&format!(" ({}x)", speed));
+    }
+    if paused {
+        title.push_str(" - paused");
     }
-    let r_buff = &right[..apu::BUFF_SIZE];
+    title
+}
+
+/* Config for a game, with settings remembered for it. Invalid ones are only a warning. */
+fn game_config(session: &Session, game: &str, config: &EmuConfig) -> EmuConfig {
+    session.game(game).apply(config).unwrap_or_else(|e| {
+        eprintln!("Warning: invalid settings of {}: {}", game, e);
+        config.clone()
+    })
+}
+
+/*
+ * CGB boot ROM colorizes DMG games, see compat_palette. Without it CGB model picks the same colors, unless game has
+ * palette of its own.
+ */
+fn boot_colors(config: &mut EmuConfig, header: &CartHeader, settings: &GameSettings) {
+    let boot_rom = config.bootrom.is_some() && !config.fast_boot;
+    let dmg_game = header.cgb_support() == CGBRequirement::Unsupported;
+    if config.model == Some(HardwareModel::CGB) && !boot_rom && dmg_game && settings.palette.is_none() {
+        config.set_colors(&compat_palette(header));
+    }
+}
+
+/* Reminder of the slot game's state was last saved to or loaded from */
+#[cfg(feature = "serde")]
+fn slot_notice(settings: &GameSettings) -> Option<String> {
+    settings.slot.map(|slot| format!("Slot {} used last time, Shift+F{} loads it", slot, slot))
+}
+
+#[cfg(not(feature = "serde"))]
+fn slot_notice(_settings: &GameSettings) -> Option<String> {
+    None
+}
+
+/* ROM played last is stored as absolute path, so it's found from any working directory */
+fn save_session(mut session: Session, path: &Path, rom: &Path) {
+    session.last_rom = Some(fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf()));
+    if let Err(e) = session.save(path) {
+        eprintln!("Unable to save session: {}", e);
+    }
+}
+
+/*
+ * Puts game's progress back: battery save, then autosave state on top of it. What was restored goes to
+ * notices.
+ */
+fn open_game<T: Mapper>(runtime: &mut Runtime<T>, files: &GameFiles, notices: &mut Vec<String>) {
+    if let Some(path) = &files.battery {
+        restore_battery(runtime, path, files.mmap_battery);
+    }
+    #[cfg(feature = "serde")]
+    if let Some(path) = files.autosave.as_ref().filter(|path| path.exists()) {
+        match SaveState::load(path).and_then(|state| state.restore(runtime)) {
+            Ok(()) => {
+                println!("State restored from {}", path.display());
+                notices.push(String::from("Resumed from autosave"));
+            }
+            Err(e) => eprintln!("Warning: unable to restore autosave: {}", e),
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = notices;
+}
+
+/* Writes game's progress: battery save, and state when autosave is on. Failures go to notices. */
+fn close_game<T: Mapper>(runtime: &mut Runtime<T>, files: &GameFiles, config: &EmuConfig, notices: &mut Vec<String>) {
+    flush_battery(runtime, files.battery.as_deref(), notices);
+    #[cfg(feature = "serde")]
+    if let Some(path) = &files.autosave {
+        let screen = frame_pixels(&mut runtime.state, config).0;
+        let saved = SaveState::capture(runtime, &screen).and_then(|state| state.save(path).map_err(|e| e.to_string()));
+        match saved {
+            Ok(()) => println!("State autosaved to {}", path.display()),
+            Err(e) => {
+                eprintln!("Unable to autosave state: {}", e);
+                notices.push(String::from("Unable to autosave"));
+            }
+        }
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = config;
+}
+
+/*
+ * Battery save goes into cart RAM before the game starts, or big RAM is mapped to it when mmap is on. Broken one
+ * is only a warning, game starts without it.
+ */
+fn restore_battery<T: BankController>(runtime: &mut Runtime<T>, path: &Path, mmap: bool) {
+    let mapper = &mut runtime.state.mmu.mapper;
+    let loaded = if mmap { map_battery(mapper, path) } else { load_battery(mapper, path) };
+    match loaded {
+        Ok(true) => println!("Battery save loaded from {}", path.display()),
+        Ok(false) => {}
+        Err(e) => eprintln!("Warning: unable to load battery save: {}", e),
+    }
+}
+
+/* Writes cart RAM to battery save, if cart has one, telling on screen how it went */
+fn flush_battery<T: BankController>(runtime: &mut Runtime<T>, path: Option<&Path>, notices: &mut Vec<String>) {
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+    match save_battery(&mut runtime.state.mmu.mapper, path) {
+        Ok(true) => {
+            println!("Battery save written to {}", path.display());
+            notices.push(String::from("Game saved"));
+        }
+        Ok(false) => {}
+        Err(e) => {
+            eprintln!("Unable to write battery save: {}", e);
+            notices.push(String::from("Unable to save game"));
+        }
+    }
+}
+
+fn save_events(events: Option<EventLog>, args: &RunArgs) {
+    let events = match events {
+        Some(events) => events,
+        None => return,
+    };
+    if let Some(path) = &args.trace_events {
+        match events.save_chrome_trace(path) {
+            Ok(()) => println!("{} events written to {}", events.len(), path.display()),
+            Err(e) => eprintln!("Unable to save event trace: {}", e),
+        }
+    }
+    if let Some(path) = &args.timeline {
+        let saved = FrameTimeline::last(&events)
+            .ok_or_else(|| String::from("no complete frame was logged"))
+            .and_then(|timeline| timeline.save(path));
+        match saved {
+            Ok(()) => println!("Frame timeline written to {}", path.display()),
+            Err(e) => eprintln!("Unable to save frame timeline: {}", e),
+        }
+    }
+}
+
+fn save_heatmap(heatmap: Option<Heatmap>, args: &RunArgs) {
+    if let (Some(heatmap), Some(path)) = (heatmap, &args.heatmap) {
+        if let Err(e) = heatmap.save(path) {
+            eprintln!("Unable to save heatmap: {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn debug<T: BankController>(runtime: &mut Runtime<T>) {
+    if let Err(e) = tui::run(runtime) {
+        eprintln!("Terminal debugger failed: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn debug<T: BankController>(_runtime: &mut Runtime<T>) {
+    eprintln!("Built without terminal debugger, enable tui feature");
+    process::exit(1);
+}
+
+/*
+ * Audits and netplay need runs with the same inputs to be the same, so cart clock counts emulated cycles there,
+ * instead of following the host's.
+ */
+fn emulated_clock(args: &RunArgs) -> bool {
+    args.audit.is_some() || args.listen.is_some() || args.connect.is_some()
+}
+
+/* Mapper of the cart, see emulated_clock */
+fn cart_mapper(rom: Vec<u8>, emulated_clock: bool) -> Result<mbc::Cart, String> {
+    if emulated_clock {
+        mbc::Cart::with_emulated_clock(rom)
+    } else {
+        mbc::Cart::try_from(rom)
+    }
+}
+
+/* Sets up GB for given cart, the way config says */
+fn build_runtime(loaded: LoadedRom, config: &EmuConfig, emulated_clock: bool) -> Runtime<mbc::Cart> {
+    let model = cart_model(&loaded, config);
+    let mut runtime = Runtime::new(cart_mapper(loaded.rom, emulated_clock).unwrap_or_else(|e| {
+        eprintln!("Unable to load ROM: {}", e);
+        process::exit(1);
+    }));
+    configure(&mut runtime, model, &loaded.header, config);
+    match &config.bootrom {
+        Some(path) if !config.fast_boot => {
+            runtime.state.mmu.bootstrap = load_bootrom(path, model).unwrap_or_else(|e| {
+                eprintln!("Invalid boot ROM: {}", e);
+                process::exit(1);
+            })
+        }
+        _ => runtime.skip_bootrom(model),
+    }
+    runtime
+}
+
+/*
+ * Puts other cart into running GB. Link cable, observers and other settings made at start stay.
+ * Nothing changes when cart can't be loaded.
+ */
+fn swap_cart(
+    runtime: &mut Runtime<mbc::Cart>,
+    loaded: LoadedRom,
+    config: &EmuConfig,
+    emulated_clock: bool,
+) -> Result<(), String> {
+    let model = cart_model(&loaded, config);
+    let bootstrap = match &config.bootrom {
+        Some(path) if !config.fast_boot => Some(load_bootrom(path, model)?),
+        _ => None,
+    };
+    runtime.load_mapper(cart_mapper(loaded.rom, emulated_clock)?);
+    configure(runtime, model, &loaded.header, config);
+    if let Some(bootstrap) = bootstrap {
+        runtime.state.mmu.bootstrap = bootstrap;
+    }
+    // Boot again, now as the model picked for new cart
+    runtime.reset(true);
+    Ok(())
+}
+
+/* Model the cart runs on. Warns about anything that won't be emulated. */
+fn cart_model(loaded: &LoadedRom, config: &EmuConfig) -> HardwareModel {
+    for warning in &loaded.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    let model = config.model.unwrap_or_else(|| HardwareModel::detect(&loaded.header));
+    if loaded.mode == HardwareMode::CGB && !model.cgb() {
+        eprintln!("Warning: CGB mode isn't supported yet, running as {}", model);
+    }
+    if model.cgb() {
+        eprintln!("Warning: CGB isn't supported yet, only its initial registers and quirks are emulated");
+    }
+    if model.sgb() && !cfg!(feature = "sgb") {
+        eprintln!("Warning: built without SGB support, only its initial registers and quirks are emulated");
+    }
+    model
+}
+
+/* Settings taken from config, some of them depend on model and cart */
+fn configure<T: BankController>(runtime: &mut Runtime<T>, model: HardwareModel, header: &CartHeader, config: &EmuConfig) {
+    #[cfg(feature = "sgb")]
+    runtime.state.sgb.enable(model.sgb() && header.sgb_support());
+    #[cfg(not(feature = "sgb"))]
+    let _ = header;
+    runtime.state.apu.set_sample_rate(config.sample_rate);
+    runtime.state.gpu.quirks = config.quirks.gpu(model);
+    runtime.state.apu.quirks = config.quirks.apu();
+    runtime.state.joypad.set_turbo_frames(config.turbo_frames);
+    runtime.state.mmu.policy = config.strict.bus;
+    runtime.set_model(model);
+}
+
+/*
+ * Mapper of the game open_game and close_game keep progress of. It has to be buildable from ROM, and with serde
+ * serializable too, for save states.
+ */
+#[cfg(feature = "serde")]
+trait Mapper: BankController + TryFrom<Vec<u8>, Error = String> + serde::Serialize + serde::de::DeserializeOwned {}
+#[cfg(feature = "serde")]
+impl<T> Mapper for T where
+    T: BankController + TryFrom<Vec<u8>, Error = String> + serde::Serialize + serde::de::DeserializeOwned
+{
+}
+#[cfg(not(feature = "serde"))]
+trait Mapper: BankController + TryFrom<Vec<u8>, Error = String> {}
+#[cfg(not(feature = "serde"))]
+impl<T: BankController + TryFrom<Vec<u8>, Error = String>> Mapper for T {}
+
+/*
+ * Emulation thread. Runs until SDL thread stops receiving frames, then writes progress of the game.
+ * With more than one GB(local link), all are emulated together and shown side by side.
+ * Gives back event log of the first GB, if it kept one.
+ */
+fn emulate(
+    mut runtimes: Vec<Runtime<mbc::Cart>>,
+    mut config: EmuConfig,
+    mut files: GameFiles,
+    emulated_clock: bool,
+    frames: SyncSender<Frame>,
+    input: Receiver<Message>,
+) -> Option<EventLog> {
+    let mut overlay = false;
+    let mut tinted = false;
+    // Go out with the next frame
+    let mut notices = Vec::new();
+    let mut blend = FrameBlend::new(config.frame_blend);
+    let dots = DotMatrix::default();
+    'emulating: loop {
+        // Apply latest button state and speed. While paused nothing happens until next message comes.
+        let mut next = if runtimes[0].paused() {
+            input.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            input.try_recv()
+        };
+        loop {
+            match next {
+                Ok(Message::Buttons(player, buttons)) => {
+                    if let Some(runtime) = runtimes.get_mut(player) {
+                        apply_input(&mut runtime.state.joypad, buttons);
+                    }
+                }
+                Ok(Message::Speed(speed)) => runtimes.iter_mut().for_each(|runtime| runtime.set_speed(speed)),
+                Ok(Message::Reset(hard)) => runtimes.iter_mut().for_each(|runtime| runtime.reset(hard)),
+                Ok(Message::Pause(paused)) => runtimes.iter_mut().for_each(|runtime| runtime.set_paused(paused)),
+                Ok(Message::Overlay(shown)) => overlay = shown,
+                Ok(Message::ToggleSources) => {
+                    tinted = !tinted;
+                    notices.push(String::from(if tinted { "Pixel sources shown" } else { "Pixel sources hidden" }));
+                }
+                Ok(Message::ToggleMute(chan)) => {
+                    let muted = !runtimes[0].state.apu.muted(chan);
+                    runtimes.iter_mut().for_each(|runtime| runtime.state.apu.set_muted(chan, muted));
+                    notices.push(format!("Channel {} {}", chan, if muted { "muted" } else { "unmuted" }));
+                }
+                Ok(Message::ToggleSolo(chan)) => {
+                    let solo = match runtimes[0].state.apu.solo() {
+                        Some(soloed) if soloed == chan => None,
+                        _ => Some(chan),
+                    };
+                    runtimes.iter_mut().for_each(|runtime| runtime.state.apu.set_solo(solo));
+                    notices.push(match solo {
+                        Some(chan) => format!("Channel {} solo", chan),
+                        None => String::from("All channels heard"),
+                    });
+                }
+                Ok(Message::ToggleLayer(layer)) => {
+                    for runtime in runtimes.iter_mut() {
+                        let shown = layer.flag(&mut runtime.state.gpu);
+                        *shown = !*shown;
+                    }
+                    let shown = *layer.flag(&mut runtimes[0].state.gpu);
+                    notices.push(format!("{} {}", layer.name(), if shown { "shown" } else { "hidden" }));
+                }
+                Ok(Message::LoadRom(cart)) => {
+                    let title = cart.loaded.header.title();
+                    // Progress of the game being replaced goes to disk first, its cart RAM is gone after the swap
+                    close_game(&mut runtimes[0], &files, &config, &mut notices);
+                    match swap_cart(&mut runtimes[0], cart.loaded, &config, emulated_clock) {
+                        Ok(()) => {
+                            runtimes[0].state.symbols = cart.symbols;
+                            config.set_colors(&cart.palette);
+                            files = cart.files;
+                            open_game(&mut runtimes[0], &files, &mut notices);
+                            blend.clear();
+                            println!("Loaded {}", title);
+                            notices.push(format!("Loaded {}", title));
+                        }
+                        Err(e) => {
+                            eprintln!("Unable to load ROM: {}", e);
+                            notices.push(String::from("Unable to load ROM"));
+                        }
+                    }
+                }
+                #[cfg(feature = "serde")]
+                Ok(Message::SaveState(path, slot)) => {
+                    let screen = frame_pixels(&mut runtimes[0].state, &config).0;
+                    let saved = SaveState::capture(&mut runtimes[0], &screen)
+                        .and_then(|state| state.save(&path).map_err(|e| e.to_string()));
+                    match saved {
+                        Ok(()) => {
+                            println!("State saved to {}", path.display());
+                            notices.push(format!("State saved to slot {}", slot));
+                        }
+                        Err(e) => {
+                            eprintln!("Unable to save state: {}", e);
+                            notices.push(format!("Unable to save state to slot {}", slot));
+                        }
+                    }
+                }
+                #[cfg(feature = "serde")]
+                Ok(Message::LoadState(path, slot)) => {
+                    match SaveState::load(&path).and_then(|state| state.restore(&mut runtimes[0])) {
+                        Ok(()) => {
+                            blend.clear();
+                            println!("State loaded from {}", path.display());
+                            notices.push(format!("State loaded from slot {}", slot));
+                        }
+                        Err(e) => {
+                            eprintln!("Unable to load state: {}", e);
+                            notices.push(format!("Unable to load state from slot {}", slot));
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'emulating,
+            }
+            next = input.try_recv();
+        }
+
+        // CPU, GPU and other devices emulated here.
+        // With speed other than 1x, more or less emulated time fits in a single host frame.
+        // Other GBs are kept within one instruction of the first one, so link cable transfers line up.
+        let started = Instant::now();
+        let (first, others) = runtimes.split_first_mut().unwrap();
+        while first.cpu_cycles() < first.cycles_per_frame() {
+            first.step();
+            for other in others.iter_mut() {
+                while other.cpu_cycles() < first.cpu_cycles() {
+                    other.step();
+                }
+            }
+        }
+        for runtime in runtimes.iter_mut() {
+            runtime.reset_cycles();
+            runtime.state.serial.frame();
+        }
+
+        let emulation = started.elapsed();
+
+        let mut frame = compose_frame(&mut runtimes, &config, tinted);
+        blend.apply(&mut frame.pixels);
+        if config.dot_matrix {
+            let background = config.colors().layer(PaletteLayer::Background)[0];
+            frame.pixels = dots.render(&frame.pixels, frame.width, background);
+            frame.width *= dots.dot();
+            frame.dots = dots.dot();
+        }
+        frame.notices = std::mem::take(&mut notices);
+        frame.emulation = emulation;
+        if overlay {
+            frame.stats = Some(Stats::capture(&mut runtimes[0]));
+        }
+        // Blocks when SDL thread has enough frames queued.
+        if frames.send(frame).is_err() {
+            break;
+        }
+    }
+    close_game(&mut runtimes[0], &files, &config, &mut notices);
+    runtimes[0].take_event_log()
+}
+
+fn apply_input(joypad: &mut Joypad, input: Input) {
+    joypad.set(Button::Up, input.up);
+    joypad.set(Button::Down, input.down);
+    joypad.set(Button::Left, input.left);
+    joypad.set(Button::Right, input.right);
+    joypad.set(Button::A, input.a);
+    joypad.set(Button::B, input.b);
+    joypad.set(Button::Select, input.select);
+    joypad.set(Button::Start, input.start);
+    joypad.turbo_a(input.turbo_a);
+    joypad.turbo_b(input.turbo_b);
+}
+
+/* Size of picture shown in the window: GB screen, or whole SGB screen with border */
+#[cfg(feature = "sgb")]
+fn screen_size<T: BankController>(state: &State<T>) -> (usize, usize) {
+    if state.sgb.enabled() {
+        (SGB_WIDTH, SGB_HEIGHT)
+    } else {
+        (SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
+
+#[cfg(not(feature = "sgb"))]
+fn screen_size<T: BankController>(_state: &State<T>) -> (usize, usize) {
+    (SCREEN_WIDTH, SCREEN_HEIGHT)
+}
+
+/* Copies finished picture, returns it with its width. SGB picture already has its own colors. */
+#[cfg(feature = "sgb")]
+fn frame_pixels<T: BankController>(state: &mut State<T>, config: &EmuConfig) -> (Vec<(u8, u8, u8)>, usize) {
+    if state.sgb.enabled() {
+        state.sgb.render(state.gpu.completed_frame());
+        (state.sgb.framebuff.clone(), SGB_WIDTH)
+    } else {
+        (config.colors().apply(state.gpu.completed_frame(), state.gpu.completed_layers()), SCREEN_WIDTH)
+    }
+}
+
+#[cfg(not(feature = "sgb"))]
+fn frame_pixels<T: BankController>(state: &mut State<T>, config: &EmuConfig) -> (Vec<(u8, u8, u8)>, usize) {
+    (config.colors().apply(state.gpu.completed_frame(), state.gpu.completed_layers()), SCREEN_WIDTH)
+}
+
+/* Picture tinted by what drew each pixel, see PixelSource. SGB picture with border is left as it is. */
+fn tinted_pixels<T: BankController>(state: &mut State<T>, config: &EmuConfig) -> (Vec<(u8, u8, u8)>, usize) {
+    let (pixels, width) = frame_pixels(state, config);
+    if width != SCREEN_WIDTH {
+        return (pixels, width);
+    }
+    let tinted = pixels.iter().zip(state.gpu.completed_sources()).map(|(color, source)| tint_pixel(*color, *source));
+    (tinted.collect(), width)
+}
+
+/*
+ * Puts pictures of all GBs side by side, tinted by pixel source if asked to. Only the first one is heard,
+ * audio of others is dropped.
+ */
+fn compose_frame<T: BankController>(runtimes: &mut [Runtime<T>], config: &EmuConfig, tinted: bool) -> Frame {
+    let pictures = runtimes
+        .iter_mut()
+        .map(|runtime| match tinted {
+            true => tinted_pixels(&mut runtime.state, config),
+            false => frame_pixels(&mut runtime.state, config),
+        })
+        .collect::<Vec<_>>();
+    let width = pictures.iter().map(|(_, w)| w).sum::<usize>();
+    let height = pictures.iter().map(|(pixels, w)| pixels.len() / w).max().unwrap_or(0);
+    let mut pixels = vec![(255, 255, 255); width * height];
+    let mut left = 0;
+    for (picture, w) in &pictures {
+        for (i, pixel) in picture.iter().enumerate() {
+            pixels[(i / w) * width + left + i % w] = *pixel;
+        }
+        left += w;
+    }
+
+    let audio = resample(stereo_samples(&mut runtimes[0].state.apu), runtimes[0].speed());
+    for runtime in runtimes[1..].iter_mut() {
+        stereo_samples(&mut runtime.state.apu);
+    }
+    Frame { pixels, width, dots: 1, audio, emulation: Duration::ZERO, stats: None, notices: Vec::new() }
+}
+
+impl Frame {
+    /* In GB pixels, which overlay and notices are drawn in */
+    fn screen_size(&self) -> (usize, usize) {
+        (self.width / self.dots, self.pixels.len() / self.width / self.dots)
+    }
+}
+
+/* Notices of each frame are shown, even if it's not the one drawn */
+fn received(mut frame: Frame, notices: &mut Notices, timing: &mut FrameTiming) -> Frame {
+    timing.received(frame.emulation);
+    for text in frame.notices.drain(..) {
+        notices.push(text);
+    }
+    frame
+}
+
+/*
+ * Picture size changes when cart with SGB border replaces one without it. Window follows it, unless it's
+ * fullscreen, and so does the texture picture is drawn to. Canvas is sized in GB pixels, dot matrix picture
+ * gets larger texture scaled down to them.
+ */
+fn fit_picture<'s, 't>(
+    canvas: &mut WindowCanvas,
+    creator: &'t TextureCreator<WindowContext>,
+    screen: &'s mut Option<Texture<'t>>,
+    config: &EmuConfig,
+    frame: &Frame,
+) -> &'s mut Texture<'t> {
+    let (width, height) = frame.screen_size();
+    let (width, height) = (width as u32, height as u32);
+    if canvas.logical_size() != (width, height) {
+        canvas.set_logical_size(width, height).unwrap();
+        if canvas.window().fullscreen_state() == FullscreenType::Off {
+            canvas.window_mut().set_size(config.scale * width, config.scale * height).unwrap();
+        }
+        *screen = None;
+    }
+    let (texture_width, texture_height) = (frame.width as u32, (frame.pixels.len() / frame.width) as u32);
+    if screen.as_ref().is_some_and(|texture| {
+        let query = texture.query();
+        (query.width, query.height) != (texture_width, texture_height)
+    }) {
+        *screen = None;
+    }
+    screen.get_or_insert_with(|| {
+        creator.create_texture_streaming(PixelFormatEnum::RGB24, texture_width, texture_height).unwrap()
+    })
+}
+
+/* Picture is scaled to the window keeping its aspect ratio, with black bars around it when shapes differ */
+fn draw_frame(canvas: &mut WindowCanvas, texture: &mut Texture, frame: &Frame) {
+    let bytes = frame.pixels.iter().flat_map(|&(r, g, b)| [r, g, b]).collect::<Vec<u8>>();
+    texture.update(None, &bytes, 3 * frame.width).unwrap();
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.copy(texture, None, None).unwrap();
+}
+
+/* Picture is only scaled by whole multiples, leaving wider bars. sdl2 crate has no wrapper for it. */
+fn set_integer_scale(canvas: &mut WindowCanvas, enabled: bool) {
+    let enabled = if enabled { sdl2::sys::SDL_bool::SDL_TRUE } else { sdl2::sys::SDL_bool::SDL_FALSE };
+    unsafe {
+        sdl2::sys::SDL_RenderSetIntegerScale(canvas.raw(), enabled);
+    }
+}
+
+/* Drains APU buffers into single interleaved stereo buffer */
+fn stereo_samples(apu: &mut APU) -> Vec<i16> {
+    let mut mixed = vec![0; apu.available_samples()];
+    apu.pull_samples(&mut mixed);
+    mixed
+}
+
+/*
+ * Stretches interleaved stereo buffer, so audio for one host frame is always about one frame long.
+ * Nearest neighbour is good enough here. Above AUDIO_MUTE_SPEED produces silence of the right length.
+ */
+fn resample(samples: Vec<i16>, speed: f32) -> Vec<i16> {
+    if speed == 1.0 {
+        return samples;
+    }
+    let len = samples.len() / 2;
+    let target = (len as f32 / speed) as usize;
+    if speed > AUDIO_MUTE_SPEED {
+        return vec![0; 2 * target];
+    }
+    let mut out = Vec::with_capacity(2 * target);
+    for i in 0..target {
+        let src = ((i as f32 * speed) as usize).min(len - 1);
+        out.push(samples[2 * src]);
+        out.push(samples[2 * src + 1]);
+    }
+    out
+}
+
+/*
+ * Config file is read first, then command line options are applied on top of it.
+ */
+fn load_config(args: &RunArgs) -> Result<EmuConfig, String> {
+    let mut config = match &args.config {
+        Some(path) => EmuConfig::load(path)?,
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => EmuConfig::load(Path::new(DEFAULT_CONFIG_FILE))?,
+        None => EmuConfig::default(),
+    };
+    if let Some(scale) = args.scale {
+        config.scale = scale;
+    }
+    if let Some(rate) = args.sample_rate {
+        config.sample_rate = rate;
+    }
+    if let Some(path) = &args.bootrom {
+        config.bootrom = Some(path.clone());
+    }
+    if let Some(path) = &args.save_dir {
+        config.save_dir = path.clone();
+    }
+    if let Some(model) = args.model {
+        config.model = Some(model);
+    }
+    if let Some(palette) = args.palette {
+        config.palette = palette;
+    }
+    if let Some(weight) = args.frame_blend {
+        config.frame_blend = weight;
+    }
+    if let Some(pacing) = args.pacing {
+        config.pacing = pacing;
+    }
+    config.strict.header |= args.strict_header;
+    config.fast_boot |= args.fast_boot;
+    config.autosave |= args.autosave;
+    config.mmap_saves |= args.mmap_saves;
+    if let Some(policy) = args.bus {
+        config.strict.bus = policy;
+    }
+    config.fullscreen |= args.fullscreen;
+    config.integer_scale |= args.integer_scale;
+    config.dot_matrix |= args.dot_matrix;
+    if config.scale == 0 {
+        return Err(String::from("scale must be at least 1"));
+    }
+    if config.sample_rate == 0 {
+        return Err(String::from("sample rate must be at least 1"));
+    }
+    if config.turbo_frames == 0 {
+        return Err(String::from("turbo frames must be at least 1"));
+    }
+    if !(0.0..=MAX_BLEND).contains(&config.frame_blend) {
+        return Err(format!("frame blend must be between 0 and {}", MAX_BLEND));
+    }
+    Ok(config)
+}
+
+/* Save state slots are kept next to battery saves, named after the ROM */
+#[cfg(feature = "serde")]
+fn state_slot_path(save_dir: &Path, rom: &Path, slot: usize) -> PathBuf {
+    let name = rom.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    save_dir.join(format!("{}.state{}", name, slot))
+}
+
+#[cfg(feature = "serde")]
+fn autosave_path(save_dir: &Path, rom: &Path) -> PathBuf {
+    let name = rom.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    save_dir.join(format!("{}.autosave", name))
+}
+
+/* Network link cable, if one was requested */
+fn connect_link(args: &RunArgs) -> Option<std::io::Result<NetLink>> {
+    if let Some(addr) = &args.listen {
+        println!("Waiting for link cable connection on {}...", addr);
+        Some(NetLink::listen(addr.as_str(), args.lockstep))
+    } else {
+        args.connect
+            .as_ref()
+            .map(|addr| NetLink::connect(addr.as_str(), args.lockstep))
+    }
+}
+
+fn parse_range(text: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Invalid range: {}, expected START-END", text);
+    let mut parts = text.splitn(2, '-');
+    let mut bound = || {
+        let part = parts.next().ok_or_else(invalid)?.trim().trim_start_matches("0x");
+        usize::from_str_radix(part, 16).map_err(|_| invalid())
+    };
+    let (start, end) = (bound()?, bound()?);
+    if start > end {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+fn parse_palette_arg(text: &str) -> Result<[(u8, u8, u8); 4], String> {
+    parse_palette(text).ok_or_else(|| format!("Invalid palette: {}", text))
+}
+
+fn resolve_keys(bindings: &KeyBindings) -> Result<Keys, String> {
+    let resolve = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| Scancode::from_name(name).ok_or_else(|| format!("Unknown key \"{}\"", name)))
+            .collect::<Result<Vec<Scancode>, String>>()
+    };
+    Ok(Keys {
+        up: resolve(&bindings.up)?,
+        down: resolve(&bindings.down)?,
+        left: resolve(&bindings.left)?,
+        right: resolve(&bindings.right)?,
+        a: resolve(&bindings.a)?,
+        b: resolve(&bindings.b)?,
+        select: resolve(&bindings.select)?,
+        start: resolve(&bindings.start)?,
+        turbo_a: resolve(&bindings.turbo_a)?,
+        turbo_b: resolve(&bindings.turbo_b)?,
+        fast_forward: resolve(&bindings.fast_forward)?,
+        slower: resolve(&bindings.slower)?,
+        faster: resolve(&bindings.faster)?,
+        reset: resolve(&bindings.reset)?,
+        pause: resolve(&bindings.pause)?,
+        fullscreen: resolve(&bindings.fullscreen)?,
+        overlay: resolve(&bindings.overlay)?,
+        toggle_background: resolve(&bindings.toggle_background)?,
+        toggle_window: resolve(&bindings.toggle_window)?,
+        toggle_sprites: resolve(&bindings.toggle_sprites)?,
+        toggle_sources: resolve(&bindings.toggle_sources)?,
+        toggle_channels: [
+            resolve(&bindings.toggle_channel1)?,
+            resolve(&bindings.toggle_channel2)?,
+            resolve(&bindings.toggle_channel3)?,
+            resolve(&bindings.toggle_channel4)?,
+        ],
+    })
+}
 
-    let mut mixed = [0; apu::BUFF_SIZE * 2];
-    for i in 0..apu::BUFF_SIZE {
-        mixed[2 * i] = l_buff[i];
-        mixed[2 * i + 1] = r_buff[i];
+fn load_bootrom(path: &Path, model: HardwareModel) -> Result<Vec<u8>, String> {
+    let bootrom = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if bootrom.len() != model.bootrom_size() {
+        return Err(format!(
+            "{}: expected {} bytes for {} boot ROM, got {}",
+            path.display(),
+            model.bootrom_size(),
+            model,
+            bootrom.len()
+        ));
     }
-    queue.queue(&mixed);
-    queue.resume();
-    apu.left_samples().clear();
-    apu.right_samples().clear();
+    Ok(bootrom)
 }
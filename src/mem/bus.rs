@@ -0,0 +1,169 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::*;
+
+/* Access memory map doesn't allow. Real hardware ignores it, but it may as well be an emulation bug. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IllegalAccess {
+    /* Write to ROM which mapper doesn't take as bank switching */
+    RomWrite { addr: Addr, value: Byte },
+    /* Write to boot ROM while it's still mapped */
+    BootromWrite { addr: Addr, value: Byte },
+    /* Cart RAM isn't there, is disabled or too small. Reads give 0xFF. */
+    Missing { kind: AccessKind, addr: Addr },
+}
+
+impl fmt::Display for IllegalAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IllegalAccess::RomWrite { addr, value } => write!(f, "write of 0x{:02X} to ROM at 0x{:04X}", value, addr),
+            IllegalAccess::BootromWrite { addr, value } => {
+                write!(f, "write of 0x{:02X} to boot ROM at 0x{:04X}", value, addr)
+            }
+            IllegalAccess::Missing { kind: AccessKind::Read, addr } => {
+                write!(f, "read of missing cart RAM at 0x{:04X}", addr)
+            }
+            IllegalAccess::Missing { kind: AccessKind::Write, addr } => {
+                write!(f, "write to missing cart RAM at 0x{:04X}", addr)
+            }
+        }
+    }
+}
+
+/*
+ * What MMU does on illegal access. Access itself is always dropped like on hardware, reads of missing memory
+ * give 0xFF. Real games poke ROM quite often, so by default it's only logged.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BusPolicy {
+    Ignore,
+    /* Logged with "mmu" target, at debug level */
+    #[default]
+    Log,
+    /* Logged and execution stops in debugger, like on breakpoint. Without debugger it's the same as Log. */
+    Trap,
+    Panic,
+}
+
+impl FromStr for BusPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Ok(BusPolicy::Ignore),
+            "log" => Ok(BusPolicy::Log),
+            "trap" => Ok(BusPolicy::Trap),
+            "panic" => Ok(BusPolicy::Panic),
+            _ => Err(format!("unknown bus policy {}, expected one of: ignore, log, trap, panic", text)),
+        }
+    }
+}
+
+/* Areas of memory map, as BusStats counts accesses */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Rom,
+    Vram,
+    CartRam,
+    Wram,
+    Echo,
+    Oam,
+    Io,
+    Hram,
+}
+
+impl Region {
+    pub const ALL: [Region; 8] =
+        [Region::Rom, Region::Vram, Region::CartRam, Region::Wram, Region::Echo, Region::Oam, Region::Io, Region::Hram];
+
+    /* Unusable area 0xFEA0-0xFEFF counts as OAM, IE as IO, like MMU maps them */
+    pub fn of(addr: Addr) -> Self {
+        match addr {
+            0x0000..=0x7FFF => Region::Rom,
+            0x8000..=0x9FFF => Region::Vram,
+            0xA000..=0xBFFF => Region::CartRam,
+            0xC000..=0xDFFF => Region::Wram,
+            0xE000..=0xFDFF => Region::Echo,
+            0xFE00..=0xFEFF => Region::Oam,
+            0xFF00..=0xFF7F | 0xFFFF => Region::Io,
+            0xFF80..=0xFFFE => Region::Hram,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Rom => "ROM",
+            Region::Vram => "VRAM",
+            Region::CartRam => "SRAM",
+            Region::Wram => "WRAM",
+            Region::Echo => "ECHO",
+            Region::Oam => "OAM",
+            Region::Io => "IO",
+            Region::Hram => "HRAM",
+        }
+    }
+}
+
+/*
+ * Accesses CPU made, through State::safe_read and safe_write, per region of memory map. Devices reaching
+ * their registers through MMU aren't counted, that's not traffic the program makes. Runtime keeps the ones made
+ * during last frame too, see Runtime::frame_bus_stats.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BusStats {
+    reads: [u64; Region::ALL.len()],
+    writes: [u64; Region::ALL.len()],
+    /* Accesses BusPolicy was applied to, whatever it is */
+    pub illegal: u64,
+}
+
+impl BusStats {
+    pub fn count(&mut self, kind: AccessKind, addr: Addr) {
+        let region = Region::of(addr) as usize;
+        match kind {
+            AccessKind::Read => self.reads[region] += 1,
+            AccessKind::Write => self.writes[region] += 1,
+        }
+    }
+
+    pub fn reads(&self, region: Region) -> u64 {
+        self.reads[region as usize]
+    }
+
+    pub fn writes(&self, region: Region) -> u64 {
+        self.writes[region as usize]
+    }
+
+    pub fn total_reads(&self) -> u64 {
+        self.reads.iter().sum()
+    }
+
+    pub fn total_writes(&self) -> u64 {
+        self.writes.iter().sum()
+    }
+
+    /* Accesses made since earlier snapshot of the same counters */
+    pub fn since(&self, earlier: &BusStats) -> BusStats {
+        let mut delta = *self;
+        for i in 0..Region::ALL.len() {
+            delta.reads[i] = delta.reads[i].saturating_sub(earlier.reads[i]);
+            delta.writes[i] = delta.writes[i].saturating_sub(earlier.writes[i]);
+        }
+        delta.illegal = delta.illegal.saturating_sub(earlier.illegal);
+        delta
+    }
+}
+
+impl fmt::Display for BusStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} reads, {} writes", self.total_reads(), self.total_writes())?;
+        for region in Region::ALL {
+            let (reads, writes) = (self.reads(region), self.writes(region));
+            if reads + writes > 0 {
+                write!(f, ", {} {}/{}", region.name(), reads, writes)?;
+            }
+        }
+        write!(f, ", {} illegal", self.illegal)
+    }
+}
@@ -43,8 +43,50 @@ pub const OBP_1: u16 = 0xFF49;
 pub const WY: u16 = 0xFF4A;
 pub const WX: u16 = 0xFF4B;
 pub const BOOT: u16 = 0xFF50;
+/* CGB only: speed switch, VRAM bank, infrared port, WRAM bank */
+pub const KEY1: u16 = 0xFF4D;
+pub const VBK: u16 = 0xFF4F;
+pub const RP: u16 = 0xFF56;
+pub const SVBK: u16 = 0xFF70;
 pub const IE: u16 = 0xFFFF;
 
+/*
+ * Registers of CGB hardware, as (writable bits, bits always read as given). Other models don't have them,
+ * they read 0xFF and ignore writes. FF72-FF75 are undocumented, but exist on CGB.
+ * Only values are kept, double speed and banking aren't emulated, so KEY1 always reports normal speed and
+ * RP never receives light.
+ */
+pub fn cgb_register(addr: u16) -> Option<(Byte, Byte)> {
+    match addr {
+        KEY1 => Some((0x01, 0x7E)),
+        VBK => Some((0x01, 0xFE)),
+        RP => Some((0xC1, 0x3E)),
+        SVBK => Some((0x07, 0xF8)),
+        0xFF72..=0xFF74 => Some((0xFF, 0x00)),
+        0xFF75 => Some((0x70, 0x8F)),
+        _ => None,
+    }
+}
+
+/* Register names, as in Pan Docs */
+pub const NAMES: [(&str, u16); 47] = [
+    ("P1", P1), ("SB", SB), ("SC", SC), ("DIV", DIV), ("TIMA", TIMA), ("TMA", TMA), ("TAC", TAC), ("IF", IF),
+    ("NR10", NR_10), ("NR11", NR_11), ("NR12", NR_12), ("NR13", NR_13), ("NR14", NR_14),
+    ("NR21", NR_21), ("NR22", NR_22), ("NR23", NR_23), ("NR24", NR_24),
+    ("NR30", NR_30), ("NR31", NR_31), ("NR32", NR_32), ("NR33", NR_33), ("NR34", NR_34),
+    ("NR41", NR_41), ("NR42", NR_42), ("NR43", NR_43), ("NR44", NR_44),
+    ("NR50", NR_50), ("NR51", NR_51), ("NR52", NR_52),
+    ("LCDC", LCDC), ("STAT", STAT), ("SCY", SCY), ("SCX", SCX), ("LY", LY), ("LYC", LYC), ("DMA", DMA),
+    ("BGP", BGP), ("OBP0", OBP_0), ("OBP1", OBP_1), ("WY", WY), ("WX", WX), ("BOOT", BOOT), ("IE", IE),
+    ("KEY1", KEY1), ("VBK", VBK), ("RP", RP), ("SVBK", SVBK),
+];
+
+/* Address of register with given name, case doesn't matter */
+pub fn by_name(name: &str) -> Option<u16> {
+    NAMES.iter().find(|(other, _)| other.eq_ignore_ascii_case(name)).map(|(_, addr)| *addr)
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IORegs {
     regs: Vec<Byte>,
 }
@@ -94,4 +136,13 @@ impl IORegs {
     pub fn get(&self, addr: u16) -> Byte {
         self.regs[(addr - IO_REGS_ADDR) as usize]
     }
+
+    pub fn read_bit(&self, addr: u16, n: u8) -> bool {
+        self.get(addr) & (1 << n) != 0
+    }
+
+    pub fn set_bit(&mut self, addr: u16, n: u8, flg: bool) {
+        let byte = self.get(addr);
+        self.set(addr, if flg { byte | (1 << n) } else { byte & !(1 << n) });
+    }
 }
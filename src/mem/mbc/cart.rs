@@ -0,0 +1,114 @@
+use super::*;
+use crate::utils::header::{CartHeader, CartType};
+
+/*
+ * Mapper picked from cart type in ROM header, for frontends running whatever cart they're given. Cart type
+ * which isn't known, or ROM too short to have a header, gets MBC1, most games of unknown carts get by with it.
+ * Each access is passed to the mapper inside. MBC3 clock is host's, unless cart is made with_emulated_clock.
+ */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Cart {
+    RomOnly(RomOnly),
+    MBC1(MBC1),
+    MBC2(MBC2),
+    MBC3(MBC3),
+    MBC3Emulated(MBC3<EmulatedClock>),
+    MBC5(MBC5),
+    HuC3(HuC3),
+    TAMA5(TAMA5),
+}
+
+impl Cart {
+    /*
+     * Same as try_from, but MBC3 clock counts emulated cycles from 1970 instead of following the host's. Runs with
+     * the same inputs get the same clock readings then, for audits and netplay, where both sides have to agree.
+     */
+    pub fn with_emulated_clock(rom: Vec<Byte>) -> Result<Self, String> {
+        Cart::pick(rom, true)
+    }
+
+    fn pick(rom: Vec<Byte>, emulated_clock: bool) -> Result<Self, String> {
+        let cart_type = CartHeader::from_rom(&rom).map(|header| header.cart_type());
+        Ok(match cart_type {
+            Some(CartType::RomOnly()) => Cart::RomOnly(RomOnly::try_from(rom)?),
+            Some(CartType::Mbc1()) => Cart::MBC1(MBC1::try_from(rom)?),
+            Some(CartType::Mbc2()) => Cart::MBC2(MBC2::try_from(rom)?),
+            Some(CartType::Mbc3()) if emulated_clock => Cart::MBC3Emulated(MBC3::try_from(rom)?),
+            Some(CartType::Mbc3()) => Cart::MBC3(MBC3::try_from(rom)?),
+            Some(CartType::Mbc5()) => Cart::MBC5(MBC5::try_from(rom)?),
+            Some(CartType::HuC3()) => Cart::HuC3(HuC3::try_from(rom)?),
+            Some(CartType::Tama5()) => Cart::TAMA5(TAMA5::try_from(rom)?),
+            Some(CartType::Unknown(code)) => {
+                log::warn!(target: "mbc", "Unknown cart type 0x{:02X}, running it as MBC1", code);
+                Cart::MBC1(MBC1::try_from(rom)?)
+            }
+            None => Cart::MBC1(MBC1::try_from(rom)?),
+        })
+    }
+
+    fn mapper(&self) -> &dyn BankController {
+        match self {
+            Cart::RomOnly(mbc) => mbc,
+            Cart::MBC1(mbc) => mbc,
+            Cart::MBC2(mbc) => mbc,
+            Cart::MBC3(mbc) => mbc,
+            Cart::MBC3Emulated(mbc) => mbc,
+            Cart::MBC5(mbc) => mbc,
+            Cart::HuC3(mbc) => mbc,
+            Cart::TAMA5(mbc) => mbc,
+        }
+    }
+
+    fn mapper_mut(&mut self) -> &mut dyn BankController {
+        match self {
+            Cart::RomOnly(mbc) => mbc,
+            Cart::MBC1(mbc) => mbc,
+            Cart::MBC2(mbc) => mbc,
+            Cart::MBC3(mbc) => mbc,
+            Cart::MBC3Emulated(mbc) => mbc,
+            Cart::MBC5(mbc) => mbc,
+            Cart::HuC3(mbc) => mbc,
+            Cart::TAMA5(mbc) => mbc,
+        }
+    }
+}
+
+impl TryFrom<Vec<Byte>> for Cart {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        Cart::pick(rom, false)
+    }
+}
+
+impl BankController for Cart {
+    fn read_rom(&mut self, addr: Addr) -> Byte { self.mapper_mut().read_rom(addr) }
+
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool { self.mapper_mut().write_rom(addr, value) }
+
+    fn read_ram(&mut self, addr: Addr) -> Option<Byte> { self.mapper_mut().read_ram(addr) }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool { self.mapper_mut().write_ram(addr, value) }
+
+    fn peek_rom(&self, addr: Addr) -> Byte { self.mapper().peek_rom(addr) }
+
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> { self.mapper().peek_ram(addr) }
+
+    fn reset(&mut self) { self.mapper_mut().reset() }
+
+    fn current_rom_bank(&self) -> usize { self.mapper().current_rom_bank() }
+
+    fn current_ram_bank(&self) -> Option<usize> { self.mapper().current_ram_bank() }
+
+    fn tick(&mut self, cycles: u64) { self.mapper_mut().tick(cycles) }
+
+    fn rumble(&self) -> bool { self.mapper().rumble() }
+
+    fn debug_state(&self) -> MapperDebugInfo { self.mapper().debug_state() }
+
+    fn rom(&self) -> &[Byte] { self.mapper().rom() }
+
+    fn ram(&mut self) -> MutMem<'_> { self.mapper_mut().ram() }
+
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { self.mapper_mut().cart_ram() }
+}
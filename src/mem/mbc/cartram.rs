@@ -0,0 +1,128 @@
+use std::fs::OpenOptions;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+use memmap2::MmapMut;
+
+use super::Byte;
+
+enum Storage {
+    Memory(Vec<Byte>),
+    /* Shared mapping of battery save, writes go straight to the file */
+    Mapped { map: MmapMut, path: PathBuf },
+}
+
+/*
+ * Cart RAM of MBCs with banked RAM. Kept in memory, or mapped to battery save file(see map_file), so saves of
+ * big RAM carts persist as game writes them, without copying whole RAM around. Either way it's a slice of bytes.
+ * Save states keep its contents only, restored one is in memory until it takes the mapping over(take_mapping).
+ */
+pub struct CartRam {
+    storage: Storage,
+}
+
+impl CartRam {
+    /* Zeroed RAM of given size, in memory */
+    pub fn new(size: usize) -> Self {
+        Self { storage: Storage::Memory(vec![0; size]) }
+    }
+
+    /*
+     * Maps RAM to file: its contents become cart RAM when it's as big as RAM, missing or empty file gets current
+     * RAM written in. True when save was loaded from the file. File of other size is refused, like by
+     * load_battery, and RAM is left as it was. Cart without RAM has nothing to map, it stays in memory.
+     * File shouldn't be changed by other programs while mapped.
+     */
+    pub fn map_file(&mut self, path: &Path) -> Result<bool, String> {
+        let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        if self.is_empty() {
+            return Ok(false);
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(error)?;
+        let size = file.metadata().map_err(error)?.len() as usize;
+        let loaded = size != 0;
+        if loaded && size != self.len() {
+            return Err(format!("{}: save has {} bytes, cart RAM {}", path.display(), size, self.len()));
+        }
+        if !loaded {
+            file.set_len(self.len() as u64).map_err(error)?;
+        }
+        // Safety: mapping is owned by this RAM only, file is expected to be left alone while mapped
+        let mut map = unsafe { MmapMut::map_mut(&file) }.map_err(error)?;
+        if !loaded {
+            map.copy_from_slice(self);
+        }
+        self.storage = Storage::Mapped { map, path: path.to_path_buf() };
+        Ok(loaded)
+    }
+
+    /* File RAM is mapped to, None when it's in memory */
+    pub fn path(&self) -> Option<&Path> {
+        match &self.storage {
+            Storage::Memory(_) => None,
+            Storage::Mapped { path, .. } => Some(path),
+        }
+    }
+
+    /* Makes sure writes so far are on disk. Nothing to do for RAM in memory. */
+    pub fn flush(&self) -> Result<(), String> {
+        match &self.storage {
+            Storage::Memory(_) => Ok(()),
+            Storage::Mapped { map, path } => map.flush().map_err(|e| format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /*
+     * This RAM goes into the file other one is mapped to, and keeps the mapping from now on, other one is left
+     * with its contents in memory. Nothing happens when other isn't mapped or is of other size. For restoring
+     * state without losing battery save mapping.
+     */
+    pub fn take_mapping(&mut self, mapped: &mut CartRam) {
+        if mapped.path().is_none() || mapped.len() != self.len() {
+            return;
+        }
+        mapped.copy_from_slice(self);
+        std::mem::swap(self, mapped);
+    }
+}
+
+impl Deref for CartRam {
+    type Target = [Byte];
+
+    fn deref(&self) -> &[Byte] {
+        match &self.storage {
+            Storage::Memory(data) => data,
+            Storage::Mapped { map, .. } => map,
+        }
+    }
+}
+
+impl DerefMut for CartRam {
+    fn deref_mut(&mut self) -> &mut [Byte] {
+        match &mut self.storage {
+            Storage::Memory(data) => data,
+            Storage::Mapped { map, .. } => map,
+        }
+    }
+}
+
+impl From<Vec<Byte>> for CartRam {
+    fn from(data: Vec<Byte>) -> Self {
+        Self { storage: Storage::Memory(data) }
+    }
+}
+
+/* Serialized as plain bytes, same as Vec<Byte> it replaced, so older save states still load */
+#[cfg(feature = "serde")]
+impl serde::Serialize for CartRam {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CartRam {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Byte>::deserialize(deserializer).map(CartRam::from)
+    }
+}
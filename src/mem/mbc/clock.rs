@@ -0,0 +1,58 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+/* CPU cycles in one second of emulated time */
+const CYCLES_PER_SECOND: u64 = 1 << 20;
+
+/*
+ * Where cart's real time clock(MBC3 RTC) takes time from. Mapper asks for it on latching,
+ * Runtime tells it about emulated time passing, see BankController::tick.
+ */
+pub trait TimeSource {
+    fn now(&self) -> DateTime<Utc>;
+    /* CPU cycles passed since last call */
+    fn advance(&mut self, _cycles: u64) {}
+}
+
+/* Host's wall clock, like clock of real cart ticking on while GB is off. Runs aren't repeatable with it. */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HostClock;
+
+impl TimeSource for HostClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/*
+ * Time counted from emulated CPU cycles since given start. Same inputs give the same clock readings, so movies,
+ * rewind and netplay stay deterministic. Kept in save states along with the mapper.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmulatedClock {
+    /* Unix timestamp clock starts at */
+    start: i64,
+    cycles: u64,
+}
+
+impl EmulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { start: start.timestamp(), cycles: 0 }
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+impl TimeSource for EmulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let seconds = self.start + (self.cycles / CYCLES_PER_SECOND) as i64;
+        Utc.timestamp_opt(seconds, 0).single().unwrap_or_default()
+    }
+
+    fn advance(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+}
@@ -0,0 +1,193 @@
+use super::*;
+
+/*
+ * Hudson HuC-3: MBC3 like banking, with what 0xA000-0xBFFF does picked by mode written to 0x0000-0x1FFF.
+ * Besides RAM there's RTC, talked to by commands, infrared port and a speaker. Only the RTC memory is emulated,
+ * clock doesn't tick and infrared sees no light, enough for games to get to their menus. Using these shows up
+ * in unsupported().
+ */
+const RAM_BANKS: usize = 4;
+const ROM_BANKS: usize = 128;
+/* Nibbles of RTC memory */
+const RTC_MEM_SIZE: usize = 256;
+
+/* Modes of 0xA000-0xBFFF */
+const MODE_RAM_READ: u8 = 0x0;
+const MODE_RAM: u8 = 0xA;
+const MODE_RTC_COMMAND: u8 = 0xB;
+const MODE_RTC_RESPONSE: u8 = 0xC;
+const MODE_SEMAPHORE: u8 = 0xD;
+const MODE_IR: u8 = 0xE;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HuC3 {
+    pub ram: CartRam,
+    pub rom: Vec<Byte>,
+    mode: u8,
+    rom_idx: u8,
+    ram_idx: u8,
+    /* Memory RTC commands read and write, a nibble per byte */
+    rtc_mem: Vec<Byte>,
+    rtc_addr: u8,
+    /* Command and its result, read back in MODE_RTC_RESPONSE */
+    rtc_response: u8,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unsupported: Unsupported,
+}
+
+impl HuC3 {
+    /* ROM and RAM are as big as cart header says, see rom_banks_of and ram_banks_of */
+    pub fn new(rom: Vec<Byte>) -> Self {
+        let mut mbc = Self {
+            ram: CartRam::new(RAM_BANK_SIZE*ram_banks_of(&rom, RAM_BANKS)),
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
+            mode: MODE_RAM_READ, rom_idx: 1, ram_idx: 0,
+            rtc_mem: vec![0; RTC_MEM_SIZE], rtc_addr: 0, rtc_response: 0,
+            unsupported: Unsupported::default(),
+        };
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for HuC-3"); }
+        for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
+        mbc
+    }
+
+    /* Features game used, which aren't emulated */
+    pub fn unsupported(&self) -> &[&'static str] {
+        self.unsupported.features()
+    }
+
+    // Command in high nibble, its argument in low one
+    fn rtc_command(&mut self, value: Byte) {
+        let arg = value & 0xF;
+        let mut result = 0;
+        match value >> 4 & 0x7 {
+            // Read nibble and move to the next one
+            0x1 => {
+                result = self.rtc_mem[self.rtc_addr as usize];
+                self.rtc_addr = self.rtc_addr.wrapping_add(1);
+            }
+            // Write nibble and move to the next one
+            0x3 => {
+                self.rtc_mem[self.rtc_addr as usize] = arg;
+                self.rtc_addr = self.rtc_addr.wrapping_add(1);
+            }
+            0x4 => self.rtc_addr = self.rtc_addr & 0xF0 | arg,
+            0x5 => self.rtc_addr = self.rtc_addr & 0x0F | arg << 4,
+            // Copying time between clock and memory, speaker tones
+            0x6 => self.unsupported.touch("HuC-3", "RTC clock and speaker"),
+            _ => self.unsupported.touch("HuC-3", "unknown RTC command"),
+        }
+        self.rtc_response = value & 0xF0 | result;
+    }
+}
+
+impl TryFrom<Vec<Byte>> for HuC3 {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "HuC-3")?;
+        Ok(HuC3::new(rom))
+    }
+}
+
+impl BankController for HuC3 {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
+        }
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
+    }
+
+    // Registers are at 0x0000-0x5FFF
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
+        if addr < 0x2000 {
+            self.mode = value & 0xF;
+        } else if addr < 0x4000 {
+            self.rom_idx = value & 0x7F;
+            if self.rom_idx == 0 { self.rom_idx = 1; }
+        } else if addr < 0x6000 {
+            self.ram_idx = value;
+        }
+        addr < 0x6000
+    }
+
+    fn read_ram(&mut self, addr: Addr) -> Option<Byte> {
+        match self.mode {
+            MODE_RAM_READ | MODE_RAM | MODE_RTC_RESPONSE | MODE_SEMAPHORE => {}
+            MODE_IR => self.unsupported.touch("HuC-3", "infrared port"),
+            _ => self.unsupported.touch("HuC-3", "unknown mode"),
+        }
+        self.peek_ram(addr)
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        match self.mode {
+            MODE_RAM_READ | MODE_RAM => {
+                let idx = self.current_ram_bank()?;
+                self.ram.get(idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize).copied()
+            }
+            MODE_RTC_RESPONSE => Some(self.rtc_response),
+            // Commands are done right away, RTC is always ready for the next one
+            MODE_SEMAPHORE => Some(0x01),
+            // No light seen
+            MODE_IR => Some(0xC0),
+            _ => Some(0x01),
+        }
+    }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        match self.mode {
+            MODE_RAM => {
+                let offset = match self.current_ram_bank() {
+                    Some(idx) => idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize,
+                    None => return false,
+                };
+                match self.ram.get_mut(offset) {
+                    Some(cell) => { *cell = value; true }
+                    None => false,
+                }
+            }
+            MODE_RTC_COMMAND => { self.rtc_command(value); true }
+            // Game starts command this way, it's already done
+            MODE_SEMAPHORE => true,
+            MODE_IR => { self.unsupported.touch("HuC-3", "infrared port"); true }
+            MODE_RAM_READ => false,
+            _ => { self.unsupported.touch("HuC-3", "unknown mode"); false }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.mode = MODE_RAM_READ;
+        self.rom_idx = 1;
+        self.ram_idx = 0;
+        self.rtc_addr = 0;
+        self.rtc_response = 0;
+    }
+
+    fn current_rom_bank(&self) -> usize {
+        self.rom_idx as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
+    }
+
+    // RAM is mapped in RAM modes only
+    fn current_ram_bank(&self) -> Option<usize> {
+        if self.ram.is_empty() || !matches!(self.mode, MODE_RAM_READ | MODE_RAM) { return None }
+        Some(self.ram_idx as usize & (self.ram.len() / RAM_BANK_SIZE - 1))
+    }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "HuC-3",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: self.ram.len() / RAM_BANK_SIZE,
+            ram_enabled: self.mode == MODE_RAM,
+            registers: vec![("mode", self.mode), ("RTC addr", self.rtc_addr)],
+        }
+    }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { Some(&mut self.ram) }
+}
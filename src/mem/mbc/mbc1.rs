@@ -5,8 +5,9 @@ const ROM_BANKS: usize = 128;
 pub const RAM_MODE: u8 = 1;
 pub const ROM_MODE: u8 = 0;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MBC1 {
-    pub ram: Vec<Byte>,
+    pub ram: CartRam,
     pub rom: Vec<Byte>,
     pub ram_enabled: bool,
     pub banking_mode: u8,
@@ -14,35 +15,49 @@ pub struct MBC1 {
 }
 
 impl MBC1 {
+    /* ROM and RAM are as big as cart header says, see rom_banks_of and ram_banks_of */
     pub fn new(rom: Vec<Byte>) -> Self {
         let mut mbc = Self {
-            ram: vec![0; RAM_BANK_SIZE*RAM_BANKS],
-            rom: vec![0; ROM_BANK_SIZE*ROM_BANKS],
+            ram: CartRam::new(RAM_BANK_SIZE*ram_banks_of(&rom, RAM_BANKS)),
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
             ram_enabled: false,
             banking_mode: ROM_MODE,
             idx: 0,
         };
-        if rom.len() > mbc.rom.len() { panic!("ROM too big for MBC1"); }
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for MBC1"); }
         for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
         mbc
     }
 }
 
+impl TryFrom<Vec<Byte>> for MBC1 {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "MBC1")?;
+        Ok(MBC1::new(rom))
+    }
+}
+
+impl MBC1 {
+    // Offset in ram of 0xA000-0xBFFF address, None when cart has no RAM
+    fn ram_offset(&self, addr: Addr) -> Option<usize> {
+        //if !self.ram_enabled { return None }
+        let ram_idx = self.current_ram_bank()?;
+        Some(ram_idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize)
+    }
+}
+
 impl BankController for MBC1 {
-    fn get_addr_type(&self, addr: Addr) -> AddrType {
-        let intervals = [
-            (0x0000, 0x1FFF),  // RAM enable
-            (0x6000, 0x7FFF),  // ROM/RAM banking mode
-            (0x2000, 0x3FFF), // ROM bank swap
-            (0x4000, 0x5FFF), // RAM/ROM bank swap
-        ];
-        for (start, end) in intervals.iter() {
-            if addr >= *start && addr <= *end { return AddrType::Status }
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
         }
-        AddrType::Write
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
     }
 
-    fn on_status(&mut self, addr: Addr, value: Byte) {
+    // Whole 0x0000-0x7FFF is MBC registers
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
         // 0x0000 - 0x2000 -> RAM ON/OFF
         // To enable: XXXX1010
         if addr < 0x2000 {
@@ -62,7 +77,7 @@ impl BankController for MBC1 {
         // 0x4000-0x6000 - ROM/RAM bank switch
         // XXXXXXBB
         if addr >= 0x4000 && addr < 0x6000 {
-            println!("2bit switch: 0x{:x}", value);
+            log::trace!(target: "mbc", "2bit switch: 0x{:x}", value);
             let masked = (value & 0x3) << 5;
             self.idx = masked | (self.idx & 0b00011111);
         }
@@ -71,34 +86,61 @@ impl BankController for MBC1 {
         if addr >= 0x6000 && addr < 0x8000 {
             self.banking_mode = value & 1;
         }
+        true
     }
 
-    fn get_base_rom(&mut self) -> Option<MutMem> { Some(&mut self.rom[..ROM_BANK_SIZE]) }
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        self.ram_offset(addr).map(|offset| self.ram[offset])
+    }
 
-    fn get_switchable_rom(&mut self) -> Option<MutMem> {
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        match self.ram_offset(addr) {
+            Some(offset) => { self.ram[offset] = value; true }
+            None => false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ram_enabled = false;
+        self.banking_mode = ROM_MODE;
+        self.idx = 0;
+    }
+
+    fn current_rom_bank(&self) -> usize {
         let mask = if self.banking_mode == ROM_MODE {
             0b01111111
         } else {
             0b00011111
         };
-        let rom_idx = self.idx & mask;
-        let start = (rom_idx as usize) * ROM_BANK_SIZE;
-        let end = start + ROM_BANK_SIZE;
-        Some(&mut self.rom[start..end])
+        (self.idx & mask) as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
     }
 
-    fn get_switchable_ram(&mut self) -> Option<MutMem> {
-        //if !self.ram_enabled { return None }
+    fn current_ram_bank(&self) -> Option<usize> {
+        if self.ram.is_empty() { return None }
 
         let mask = if self.banking_mode == RAM_MODE {
             0b01100000
         } else {
             0
         };
+        Some(((self.idx & mask) >> 5) as usize & (self.ram.len() / RAM_BANK_SIZE - 1))
+    }
 
-        let ram_idx = (self.idx & mask) >> 5;
-        let start = (ram_idx as usize) * RAM_BANK_SIZE;
-        let end = start + RAM_BANK_SIZE;
-        Some(&mut self.ram[start..end])
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "MBC1",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: self.ram.len() / RAM_BANK_SIZE,
+            ram_enabled: self.ram_enabled,
+            registers: vec![("mode", self.banking_mode)],
+        }
     }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { Some(&mut self.ram) }
 }
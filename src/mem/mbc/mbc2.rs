@@ -8,6 +8,7 @@ use super::*;
 const RAM_SIZE: usize = 512;
 const ROM_BANKS: usize = 16;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MBC2 {
     pub ram: Vec<Byte>,
     pub rom: Vec<Byte>,
@@ -16,63 +17,92 @@ pub struct MBC2 {
 }
 
 impl MBC2 {
+    /* ROM is as big as cart header says, see rom_banks_of */
     pub fn new(rom: Vec<Byte>) -> Self {
         let mut mbc = Self {
             ram: vec![0; RAM_SIZE],
-            rom: vec![0; ROM_BANK_SIZE*ROM_BANKS],
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
             ram_enabled: true, idx: 0,
         };
-        if rom.len() > mbc.rom.len() { panic!("ROM too big for MBC2"); }
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for MBC2"); }
         for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
         mbc
     }
 }
 
+impl TryFrom<Vec<Byte>> for MBC2 {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "MBC2")?;
+        Ok(MBC2::new(rom))
+    }
+}
+
 impl BankController for MBC2 {
-    fn get_addr_type(&self, addr: Addr) -> AddrType {
-        let intervals = [
-            (0x0000, 0x1FFF),  // RAM enable
-            (0x2000, 0x3FFF),  // ROM bank select
-        ];
-        for (start, end) in intervals.iter() {
-            if addr >= *start && addr <= *end { return AddrType::Status }
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
         }
-        AddrType::Write
-    }   
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
+    }
 
-    fn on_status(&mut self, addr: Addr, value: Byte) {
+    // Registers are at 0x0000-0x3FFF only
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
         // 0x0000 - 0x2000 -> RAM ON/OFF
         if addr & 0x1000 == 0 && addr < 0x2000 { 
-            //println!("RAM ENABLED: {} -> {}", self.ram_enabled, value & 0xF == 0xA);
+            log::trace!(target: "mbc", "RAM enabled: {} -> {}", self.ram_enabled, value & 0xF == 0xA);
             self.ram_enabled = value & 0xF == 0xA;
         }
 
         // 0x2000 - 0x4000 -> ROM Select
         if addr & 0x0100 != 0 && addr >= 0x2000 && addr < 0x4000 {
             let idx = value & 0xF;
-            //println!("ROM SELECT: {} -> {}", self.idx, idx);
+            log::trace!(target: "mbc", "ROM select: {} -> {}", self.idx, idx);
             self.idx = idx;
         }
+        addr < ROM_SWITCHABLE_ADDR
+    }
+
+    // Only lower 4 bits of each RAM cell exist
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        if !self.ram_enabled { return None }
+        self.ram.get((addr - RAM_SWITCHABLE_ADDR) as usize).map(|value| value & 0xF)
+    }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        if !self.ram_enabled { return false }
+        match self.ram.get_mut((addr - RAM_SWITCHABLE_ADDR) as usize) {
+            Some(cell) => { *cell = value & 0xF; true }
+            None => false,
+        }
     }
 
-    fn get_base_rom(&mut self) -> Option<MutMem> { 
-        Some(&mut self.rom[..ROM_BANK_SIZE]) 
+    fn reset(&mut self) {
+        self.ram_enabled = true;
+        self.idx = 0;
     }
 
-    fn get_switchable_rom(&mut self) -> Option<MutMem> {
-        let rom_idx = self.idx;
-        let start = (rom_idx as usize) * ROM_BANK_SIZE;
-        let end = start + ROM_BANK_SIZE;
-        Some(&mut self.rom[start..end])
+    fn current_rom_bank(&self) -> usize {
+        self.idx as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
     }
 
-    fn get_switchable_ram(&mut self) -> Option<MutMem> {
-        if !self.ram_enabled { return None }
+    // Only one bank, smaller than usual
+    fn current_ram_bank(&self) -> Option<usize> { Some(0) }
 
-        // Make sure there are only 4bit numbers in RAM.
-        for item in self.ram.iter_mut() { 
-            *item &= 0xF;
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "MBC2",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: 1,
+            ram_enabled: self.ram_enabled,
+            registers: vec![],
         }
+    }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
 
-        Some(&mut self.ram[..])     }
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
 }
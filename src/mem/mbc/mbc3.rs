@@ -5,25 +5,36 @@ const RAM_BANKS: usize = 8;
 const ROM_BANKS: usize = 128;
 const RTC_REG_SIZE: usize = 5;
 
-pub struct MBC3 {
-    pub ram: Vec<Byte>,
+/* RTC reads time from C, host's clock unless other time source is given with with_clock */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MBC3<C: TimeSource = HostClock> {
+    pub ram: CartRam,
     pub rom: Vec<Byte>,
     ram_rtc_enabled: bool,
     rom_idx: u8,
     ram_idx: u8,
     rtc_latch: bool,
     pub rtc_reg: Vec<Byte>,
+    pub clock: C,
 }
 
 impl MBC3 {
+    /* ROM and RAM are as big as cart header says, see rom_banks_of and ram_banks_of */
     pub fn new(rom: Vec<Byte>) -> Self { 
+        Self::with_clock(rom, HostClock)
+    }
+}
+
+impl<C: TimeSource> MBC3<C> {
+    pub fn with_clock(rom: Vec<Byte>, clock: C) -> Self {
         let mut mbc = Self {
-            ram: vec![0; RAM_BANK_SIZE*RAM_BANKS],
-            rom: vec![0; ROM_BANK_SIZE*ROM_BANKS],
+            ram: CartRam::new(RAM_BANK_SIZE*ram_banks_of(&rom, RAM_BANKS)),
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
             ram_rtc_enabled: true, rom_idx: 1, ram_idx: 0,
             rtc_latch: false, rtc_reg: vec![0; RTC_REG_SIZE],
+            clock,
         }; 
-        if rom.len() > mbc.rom.len() { panic!("ROM too big for MBC3"); }
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for MBC3"); }
         for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
         mbc
     }
@@ -39,21 +50,44 @@ impl MBC3 {
     }
 }
 
-impl BankController for MBC3 {
-    fn get_addr_type(&self, addr: Addr) -> AddrType {
-        let intervals = [
-            (0x0000, 0x1FFF), // RAM RTC enable
-            (0x2000, 0x3FFF), // ROM bank swap
-            (0x4000, 0x5FFF), // RAM bank number / RTC register select
-            (0x6000, 0x7FFF), // Latch clock data
-        ];
-        for (start, end) in intervals.iter() {
-            if addr >= *start && addr <= *end { return AddrType::Status }
+// Time source starts from its default, e.g. EmulatedClock from 1970
+impl<C: TimeSource + Default> TryFrom<Vec<Byte>> for MBC3<C> {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "MBC3")?;
+        Ok(MBC3::with_clock(rom, C::default()))
+    }
+}
+
+impl<C: TimeSource> MBC3<C> {
+    // Cell at 0xA000-0xBFFF address: byte of selected RAM bank, or RTC register while clock is halted
+    fn ram_cell(&mut self, addr: Addr) -> Option<&mut Byte> {
+        // When ram_idx points on RAM bank.
+        if self.ram_idx <= 0x7 {
+            let idx = self.current_ram_bank()?;
+            self.ram.get_mut(idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize)
+        } 
+        // When ram_idx points to part of RTC register
+        else {
+            let halted = self.rtc_reg[4] & 0x80 != 0;
+            if halted {
+                self.rtc_reg.get_mut((self.ram_idx - 8) as usize)
+            } else { None }
+        }
+    }
+}
+
+impl<C: TimeSource> BankController for MBC3<C> {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
         }
-        AddrType::Write
-    }   
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
+    }
 
-    fn on_status(&mut self, addr: Addr, value: Byte) {
+    // Whole 0x0000-0x7FFF is MBC registers
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
         // RAM RTC enable, same as MBC1
         if addr < 0x2000 {
             self.ram_rtc_enabled = value & 0xF == 0xA;
@@ -69,7 +103,7 @@ impl BankController for MBC3 {
         // Value in range 0x00-0x07 selects RAM idx.
         // Values in range 0x08-0x0C map RTC register to 0xA000-0xBFFF.
         if addr >= 0x4000 && addr < 0x6000 {
-            // Selection is done in get_switchable_ram
+            // Selection is done in ram_cell
             self.ram_idx = value;
         }
 
@@ -81,35 +115,65 @@ impl BankController for MBC3 {
                 // Flip HALT flag
                 self.rtc_reg[4] ^= 0x80;
                 // And update current register state.
-                self.datetime_to_rtc(Utc::now());
+                self.datetime_to_rtc(self.clock.now());
             } else { self.rtc_latch = false; }
         }
+        true
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        if self.ram_idx <= 0x7 {
+            let idx = self.current_ram_bank()?;
+            return self.ram.get(idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize).copied();
+        }
+        // RTC register reads only while clock is halted, same as ram_cell
+        if self.rtc_reg[4] & 0x80 == 0 { return None }
+        self.rtc_reg.get((self.ram_idx - 8) as usize).copied()
     }
 
-    fn get_base_rom(&mut self) -> Option<MutMem> { 
-        Some(&mut self.rom[..ROM_BANK_SIZE]) 
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        match self.ram_cell(addr) {
+            Some(cell) => { *cell = value; true }
+            None => false,
+        }
     }
 
-    fn get_switchable_rom(&mut self) -> Option<MutMem> {
-        let start = (self.rom_idx as usize) * ROM_BANK_SIZE;
-        let end = start + ROM_BANK_SIZE;
-        Some(&mut self.rom[start..end])
+    fn reset(&mut self) {
+        self.ram_rtc_enabled = true;
+        self.rom_idx = 1;
+        self.ram_idx = 0;
+        self.rtc_latch = false;
     }
 
-    fn get_switchable_ram(&mut self) -> Option<MutMem> {
-        // When ram_idx points on RAM bank.
-        if self.ram_idx <= 0x7 {
-            let start = (self.ram_idx as usize) * RAM_BANK_SIZE;
-            let end = start + RAM_BANK_SIZE;
-            Some(&mut self.ram[start..end])
-        } 
-        // When ram_idx points to part of RTC register
-        else {
-            let halted = self.rtc_reg[4] & 0x80 != 0;
-            if halted {
-                let rtc_idx = (self.ram_idx - 8) as usize;
-                Some(&mut self.rtc_reg[rtc_idx..rtc_idx+1])
-            } else { None }
+    fn current_rom_bank(&self) -> usize {
+        self.rom_idx as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.clock.advance(cycles);
+    }
+
+    fn current_ram_bank(&self) -> Option<usize> {
+        if self.ram_idx > 0x7 || self.ram.is_empty() { return None }
+        Some(self.ram_idx as usize & (self.ram.len() / RAM_BANK_SIZE - 1))
+    }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "MBC3",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: self.ram.len() / RAM_BANK_SIZE,
+            ram_enabled: self.ram_rtc_enabled,
+            // RAM bank or RTC register mapped at 0xA000-0xBFFF
+            registers: vec![("select", self.ram_idx), ("RTC halt", self.rtc_reg[4] >> 7)],
         }
     }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { Some(&mut self.ram) }
 }
@@ -0,0 +1,136 @@
+use super::*;
+
+/*
+ * MBC5 has 9-bit ROM bank number, so bank 0 can be selected at 0x4000-0x7FFF too, and up to 16 RAM banks.
+ * On rumble carts bit 3 of RAM bank number drives the motor instead, leaving 8 RAM banks.
+ */
+const RAM_BANKS: usize = 16;
+const ROM_BANKS: usize = 512;
+/* Header field with cart type, tells rumble carts apart */
+const CART_TYPE_ADDR: usize = 0x147;
+const RUMBLE_BIT: Byte = 0x08;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MBC5 {
+    pub ram: CartRam,
+    pub rom: Vec<Byte>,
+    ram_enabled: bool,
+    rom_idx: u16,
+    ram_idx: u8,
+    /* Cart has rumble motor(MBC5+RUMBLE), see RUMBLE_BIT */
+    rumble_cart: bool,
+    rumble: bool,
+}
+
+impl MBC5 {
+    /* ROM and RAM are as big as cart header says, see rom_banks_of and ram_banks_of */
+    pub fn new(rom: Vec<Byte>) -> Self {
+        let rumble_cart = matches!(rom.get(CART_TYPE_ADDR), Some(0x1C..=0x1E));
+        let ram_banks = if rumble_cart { RAM_BANKS / 2 } else { RAM_BANKS };
+        let mut mbc = Self {
+            ram: CartRam::new(RAM_BANK_SIZE*ram_banks_of(&rom, ram_banks)),
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
+            ram_enabled: false, rom_idx: 1, ram_idx: 0,
+            rumble_cart, rumble: false,
+        };
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for MBC5"); }
+        for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
+        mbc
+    }
+}
+
+impl TryFrom<Vec<Byte>> for MBC5 {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "MBC5")?;
+        Ok(MBC5::new(rom))
+    }
+}
+
+impl MBC5 {
+    // Offset in ram of 0xA000-0xBFFF address, None when RAM is disabled or cart has none
+    fn ram_offset(&self, addr: Addr) -> Option<usize> {
+        if !self.ram_enabled { return None }
+        let idx = self.current_ram_bank()?;
+        Some(idx * RAM_BANK_SIZE + (addr - RAM_SWITCHABLE_ADDR) as usize)
+    }
+}
+
+impl BankController for MBC5 {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
+        }
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
+    }
+
+    // Registers are at 0x0000-0x5FFF, 0x6000-0x7FFF has nothing
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
+        match addr {
+            // RAM enable, same as MBC1
+            0x0000..=0x1FFF => self.ram_enabled = value & 0xF == 0xA,
+            // Lower 8 bits of ROM bank, 0 isn't turned into 1
+            0x2000..=0x2FFF => self.rom_idx = (self.rom_idx & 0x100) | value as u16,
+            // 9th bit of ROM bank
+            0x3000..=0x3FFF => self.rom_idx = (self.rom_idx & 0xFF) | ((value as u16 & 1) << 8),
+            // RAM bank, on rumble carts its bit 3 is the motor
+            0x4000..=0x5FFF => {
+                self.ram_idx = value & 0x0F;
+                if self.rumble_cart {
+                    self.rumble = value & RUMBLE_BIT != 0;
+                    self.ram_idx &= !RUMBLE_BIT;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        self.ram_offset(addr).map(|offset| self.ram[offset])
+    }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        match self.ram_offset(addr) {
+            Some(offset) => { self.ram[offset] = value; true }
+            None => false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ram_enabled = false;
+        self.rom_idx = 1;
+        self.ram_idx = 0;
+        self.rumble = false;
+    }
+
+    fn current_rom_bank(&self) -> usize {
+        self.rom_idx as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
+    }
+
+    fn current_ram_bank(&self) -> Option<usize> {
+        if self.ram.is_empty() { return None }
+        Some(self.ram_idx as usize & (self.ram.len() / RAM_BANK_SIZE - 1))
+    }
+
+    fn rumble(&self) -> bool { self.rumble }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: if self.rumble_cart { "MBC5+RUMBLE" } else { "MBC5" },
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: self.ram.len() / RAM_BANK_SIZE,
+            ram_enabled: self.ram_enabled,
+            registers: if self.rumble_cart { vec![("rumble", self.rumble as Byte)] } else { vec![] },
+        }
+    }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { Some(&mut self.ram) }
+}
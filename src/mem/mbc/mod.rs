@@ -1,40 +1,159 @@
 pub mod romonly;
+pub mod ramonly;
 pub mod mbc1;
 pub mod mbc2;
 pub mod mbc3;
+pub mod mbc5;
+pub mod huc3;
+pub mod tama5;
+pub mod clock;
+pub mod cartram;
+pub mod cart;
 
 pub use mbc1::{MBC1};
 pub use mbc2::{MBC2};
 pub use mbc3::{MBC3};
+pub use mbc5::{MBC5};
+pub use huc3::{HuC3};
+pub use tama5::{TAMA5};
+pub use clock::{TimeSource, HostClock, EmulatedClock};
+pub use cartram::{CartRam};
+pub use cart::{Cart};
 pub use romonly::{RomOnly};
+pub use ramonly::{RamOnly};
 
-use super::{ROM_BANK_SIZE, RAM_BANK_SIZE, Addr, Byte, MutMem};
+use std::convert::TryFrom;
+use std::fmt;
 
+use super::{ROM_BANK_SIZE, RAM_BANK_SIZE, ROM_SWITCHABLE_ADDR, RAM_SWITCHABLE_ADDR, Addr, Byte, MutMem};
+
+/* Header fields with sizes of cart ROM and RAM */
+const ROM_SIZE_ADDR: usize = 0x148;
+const RAM_SIZE_ADDR: usize = 0x149;
+
+/*
+ * ROM banks of the cart: as many as header says, or ROM length rounded up to power of two if that's more
+ * or header is broken. At least 2, at most max_banks mapper can select. Bank numbers are masked by it,
+ * so selecting bank past the end mirrors like on hardware.
+ */
+fn rom_banks_of(rom: &[Byte], max_banks: usize) -> usize {
+    let header = match rom.get(ROM_SIZE_ADDR) {
+        Some(code) if *code <= 8 => 2 << code,
+        _ => 0,
+    };
+    let given = rom.len().div_ceil(ROM_BANK_SIZE);
+    header.max(given).next_power_of_two().clamp(2, max_banks)
+}
+
+/* RAM banks header asks for, at most max_banks. 2KB chip gets whole bank, unknown size all banks mapper has. */
+fn ram_banks_of(rom: &[Byte], max_banks: usize) -> usize {
+    let banks = match rom.get(RAM_SIZE_ADDR) {
+        Some(0x00) => 0,
+        Some(0x01) | Some(0x02) => 1,
+        Some(0x03) => 4,
+        Some(0x04) => 16,
+        Some(0x05) => 8,
+        _ => max_banks,
+    };
+    banks.min(max_banks)
+}
+
+/* Mappers' new() panics on ROM which doesn't fit, TryFrom<Vec<Byte>> uses this to fail gracefully */
+fn check_size(rom: &[Byte], max: usize, mapper: &str) -> Result<(), String> {
+    if rom.len() > max {
+        return Err(format!("ROM too big for {}: {} bytes, at most {} fit", mapper, rom.len(), max));
+    }
+    Ok(())
+}
 
 /*
- * AddrType is used by BankController to determine address type: wheater it is
- * will change MBC registers or perform bank switching or is just regular writable.
+ * Features of a cart which aren't emulated but game used, like HuC-3 infrared port. Each is logged as warning the
+ * first time, so it's clear why game doesn't do what it should. Not kept in save states.
  */
-#[derive(Copy, Clone)]
-pub enum AddrType {
-    Write,
-    Status,
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Unsupported {
+    touched: Vec<&'static str>,
 }
+
+impl Unsupported {
+    fn touch(&mut self, mapper: &str, feature: &'static str) {
+        if !self.touched.contains(&feature) {
+            log::warn!(target: "mbc", "{}: {} isn't supported, game may not work right", mapper, feature);
+            self.touched.push(feature);
+        }
+    }
+
+    /* Features game used so far, in order it first used them */
+    pub fn features(&self) -> &[&'static str] {
+        &self.touched
+    }
+}
+
+/* Mapper registers as debugger UIs and trace logs show them, see BankController::debug_state */
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapperDebugInfo {
+    /* Kind of mapper, like "MBC1" */
+    pub name: &'static str,
+    pub rom_bank: usize,
+    pub rom_banks: usize,
+    pub ram_bank: Option<usize>,
+    pub ram_banks: usize,
+    pub ram_enabled: bool,
+    /* Mapper specific registers, by name */
+    pub registers: Vec<(&'static str, Byte)>,
+}
+
+impl fmt::Display for MapperDebugInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ROM {:02X}/{:02X}", self.name, self.rom_bank, self.rom_banks)?;
+        match (self.ram_banks, self.ram_bank) {
+            (0, _) => write!(f, " no RAM")?,
+            (banks, Some(bank)) => write!(f, " RAM {:02X}/{:02X}", bank, banks)?,
+            (banks, None) => write!(f, " RAM --/{:02X}", banks)?,
+        }
+        if self.ram_banks > 0 && !self.ram_enabled {
+            write!(f, " off")?;
+        }
+        for (name, value) in self.registers.iter() {
+            write!(f, " {} {:02X}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
 /*
- * BankController trait represents memory mapper interface.
+ * BankController trait represents memory mapper interface. MMU passes each access to cart space through it,
+ * with addresses as CPU sees them, so mapper decides what every single read and write does.
  */
 pub trait BankController {
-    /*
-     * Checks whether the addr is special memory region for
-     * MBC configuration(setting registers, enabling RAM etc.). 
-     */
-    fn get_addr_type(&self, addr: Addr) -> AddrType;
-    /* Called when get_addr_type() returned Status addr type. */
-    fn on_status(&mut self, addr: Addr, value: Byte);
-    /* Gets base non-switchable ROM. 0x0000-0x4000 range */
-    fn get_base_rom(&mut self) -> Option<MutMem>;
-    /* Gets switchable ROM. 0x4000-0x8000 range */
-    fn get_switchable_rom(&mut self) -> Option<MutMem>;
-    /* Gets switchable RAM. 0xA000-0xC000 range */
-    fn get_switchable_ram(&mut self) -> Option<MutMem>;
+    /* Byte at 0x0000-0x7FFF, with banks currently selected. Same as peek_rom unless reads change mapper state. */
+    fn read_rom(&mut self, addr: Addr) -> Byte { self.peek_rom(addr) }
+    /* Write to 0x0000-0x7FFF, which sets MBC registers(RAM enable, bank switching etc.). False when ignored. */
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool;
+    /* Byte at 0xA000-0xBFFF: cart RAM or MBC register mapped there. None when nothing answers. */
+    fn read_ram(&mut self, addr: Addr) -> Option<Byte> { self.peek_ram(addr) }
+    /* Write to 0xA000-0xBFFF. False when nothing takes it. */
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool;
+    /* What read_rom would give, without side effects. For debugger and other tools, see MMU::peek. */
+    fn peek_rom(&self, addr: Addr) -> Byte;
+    /* What read_ram would give, without side effects */
+    fn peek_ram(&self, addr: Addr) -> Option<Byte>;
+    /* Puts MBC registers back to power on values. RAM content isn't touched. */
+    fn reset(&mut self) {}
+    /* Index of ROM bank currently mapped at 0x4000-0x8000 */
+    fn current_rom_bank(&self) -> usize { 1 }
+    /* Index of RAM bank currently mapped at 0xA000-0xBFFF. None without RAM, or with MBC register mapped there. */
+    fn current_ram_bank(&self) -> Option<usize> { None }
+    /* CPU cycles passed, for carts which keep time. Called by Runtime after each step. */
+    fn tick(&mut self, _cycles: u64) {}
+    /* Whether rumble motor of the cart is on, see Runtime::on_rumble. Carts without one never rumble. */
+    fn rumble(&self) -> bool { false }
+    /* Banks and registers, for debugger and logs */
+    fn debug_state(&self) -> MapperDebugInfo;
+    /* Whole ROM, for header checks and save states. Not for emulated accesses, they go through read_rom. */
+    fn rom(&self) -> &[Byte];
+    /* Whole cart RAM, for battery saves. Not for emulated accesses, they go through read_ram and write_ram. */
+    fn ram(&mut self) -> MutMem<'_> { &mut [] }
+    /* Storage of cart RAM, for mappers which can keep it in battery save file, see map_battery */
+    fn cart_ram(&mut self) -> Option<&mut CartRam> { None }
 }
\ No newline at end of file
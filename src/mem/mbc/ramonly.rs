@@ -0,0 +1,69 @@
+use super::*;
+
+/*
+ * No ROM at all, whole cart space is writable: 32KB at 0x0000-0x7FFF and 8KB at 0xA000-0xBFFF. Not real hardware,
+ * for tests and fuzzing, where programs are written anywhere and may modify themselves. See Runtime::with_code.
+ */
+const RAM_ONLY_CODE_SIZE: usize = 1 << 15;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RamOnly {
+    pub code: Vec<Byte>,
+    pub ram: Vec<Byte>,
+}
+
+impl RamOnly {
+    pub fn new(code: Vec<Byte>) -> Self {
+        let mut mbc = Self { code: vec![0; RAM_ONLY_CODE_SIZE], ram: vec![0; RAM_BANK_SIZE] };
+        if code.len() > mbc.code.len() { panic!("Code too big for RamOnly"); }
+        mbc.code[..code.len()].copy_from_slice(&code);
+        mbc
+    }
+}
+
+impl TryFrom<Vec<Byte>> for RamOnly {
+    type Error = String;
+
+    fn try_from(code: Vec<Byte>) -> Result<Self, String> {
+        check_size(&code, RAM_ONLY_CODE_SIZE, "RamOnly")?;
+        Ok(RamOnly::new(code))
+    }
+}
+
+impl BankController for RamOnly {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        self.code[addr as usize]
+    }
+
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
+        self.code[addr as usize] = value;
+        true
+    }
+
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        Some(self.ram[(addr - RAM_SWITCHABLE_ADDR) as usize])
+    }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        self.ram[(addr - RAM_SWITCHABLE_ADDR) as usize] = value;
+        true
+    }
+
+    fn current_ram_bank(&self) -> Option<usize> { Some(0) }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "RAM only",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.code.len() / ROM_BANK_SIZE,
+            ram_bank: self.current_ram_bank(),
+            ram_banks: self.ram.len() / RAM_BANK_SIZE,
+            ram_enabled: true,
+            registers: vec![],
+        }
+    }
+
+    fn rom(&self) -> &[Byte] { &self.code }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+}
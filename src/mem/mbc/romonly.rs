@@ -5,6 +5,7 @@ use super::*;
  */
 const ROM_ONLY_SIZE: usize = 1 << 15;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RomOnly {
     pub rom: Vec<Byte>,
 }
@@ -18,20 +19,37 @@ impl RomOnly {
     }
 }
 
-impl BankController for RomOnly {
-    fn get_addr_type(&self, _: Addr) -> AddrType { 
-        AddrType::Write 
-    }    
+impl TryFrom<Vec<Byte>> for RomOnly {
+    type Error = String;
 
-    fn on_status(&mut self, _: Addr, _: Byte) {}
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_ONLY_SIZE, "RomOnly")?;
+        Ok(RomOnly::new(rom))
+    }
+}
 
-    fn get_base_rom(&mut self) -> Option<MutMem> { 
-        Some(&mut self.rom[..ROM_BANK_SIZE])
+impl BankController for RomOnly {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        self.rom[addr as usize]
     }
-    
-    fn get_switchable_rom(&mut self) -> Option<MutMem> { 
-        Some(&mut self.rom[ROM_BANK_SIZE..ROM_BANK_SIZE*2])
+
+    fn write_rom(&mut self, _: Addr, _: Byte) -> bool { false }
+
+    fn peek_ram(&self, _: Addr) -> Option<Byte> { None }
+
+    fn write_ram(&mut self, _: Addr, _: Byte) -> bool { false }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "ROM only",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            ram_bank: None,
+            ram_banks: 0,
+            ram_enabled: false,
+            registers: vec![],
+        }
     }
 
-    fn get_switchable_ram(&mut self) -> Option<MutMem> { None }
+    fn rom(&self) -> &[Byte] { &self.rom }
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+use super::*;
+
+/*
+ * Bandai TAMA5: everything goes through two addresses in 0xA000-0xBFFF. Odd one selects register, even one
+ * writes its low nibble and reads it back. ROM bank and 32 bytes of RAM are reached through registers, RTC and
+ * alarm of the TAMA6 chip aren't emulated. Using them shows up in unsupported().
+ */
+const ROM_BANKS: usize = 32;
+const RAM_SIZE: usize = 32;
+const REGISTERS: usize = 16;
+
+const REG_BANK_LO: u8 = 0x0;
+const REG_BANK_HI: u8 = 0x1;
+/* Byte RAM write command stores */
+const REG_WRITE_LO: u8 = 0x4;
+const REG_WRITE_HI: u8 = 0x5;
+/* Command in bits 1-3, RAM address bit 4 in bit 0. Writing address low nibble runs the command. */
+const REG_ADDR_HI: u8 = 0x6;
+const REG_ADDR_LO: u8 = 0x7;
+/* Reads 1 once chip is ready, games wait for it */
+const REG_READY: u8 = 0xA;
+/* Byte RAM read command got */
+const REG_READ_LO: u8 = 0xC;
+const REG_READ_HI: u8 = 0xD;
+
+const COMMAND_RAM_WRITE: u8 = 0x0;
+const COMMAND_RAM_READ: u8 = 0x1;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TAMA5 {
+    pub ram: Vec<Byte>,
+    pub rom: Vec<Byte>,
+    /* Register selected with odd address */
+    reg: u8,
+    registers: [Byte; REGISTERS],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unsupported: Unsupported,
+}
+
+impl TAMA5 {
+    /* ROM is as big as cart header says, see rom_banks_of */
+    pub fn new(rom: Vec<Byte>) -> Self {
+        let mut mbc = Self {
+            ram: vec![0; RAM_SIZE],
+            rom: vec![0; ROM_BANK_SIZE*rom_banks_of(&rom, ROM_BANKS)],
+            reg: 0, registers: [0; REGISTERS],
+            unsupported: Unsupported::default(),
+        };
+        mbc.reset();
+        if rom.len() > ROM_BANK_SIZE*ROM_BANKS { panic!("ROM too big for TAMA5"); }
+        for (i, byte) in rom.into_iter().enumerate() { mbc.rom[i] = byte; }
+        mbc
+    }
+
+    /* Features game used, which aren't emulated */
+    pub fn unsupported(&self) -> &[&'static str] {
+        self.unsupported.features()
+    }
+
+    fn command(&self) -> u8 {
+        self.registers[REG_ADDR_HI as usize] >> 1
+    }
+
+    fn ram_addr(&self) -> usize {
+        ((self.registers[REG_ADDR_HI as usize] & 0x1) << 4 | self.registers[REG_ADDR_LO as usize]) as usize
+    }
+
+    fn write_register(&mut self, value: Byte) {
+        if self.reg as usize >= REGISTERS {
+            self.unsupported.touch("TAMA5", "unknown register");
+            return;
+        }
+        self.registers[self.reg as usize] = value & 0xF;
+        match self.reg {
+            REG_BANK_LO | REG_BANK_HI | REG_WRITE_LO | REG_WRITE_HI | REG_ADDR_HI => {}
+            REG_ADDR_LO => match self.command() {
+                COMMAND_RAM_WRITE => {
+                    let value = self.registers[REG_WRITE_HI as usize] << 4 | self.registers[REG_WRITE_LO as usize];
+                    let addr = self.ram_addr();
+                    self.ram[addr] = value;
+                }
+                // Result is read from REG_READ_LO and REG_READ_HI
+                COMMAND_RAM_READ => {}
+                _ => self.unsupported.touch("TAMA5", "RTC"),
+            },
+            _ => self.unsupported.touch("TAMA5", "unknown register"),
+        }
+    }
+}
+
+impl TryFrom<Vec<Byte>> for TAMA5 {
+    type Error = String;
+
+    fn try_from(rom: Vec<Byte>) -> Result<Self, String> {
+        check_size(&rom, ROM_BANK_SIZE*ROM_BANKS, "TAMA5")?;
+        Ok(TAMA5::new(rom))
+    }
+}
+
+impl BankController for TAMA5 {
+    fn peek_rom(&self, addr: Addr) -> Byte {
+        if addr < ROM_SWITCHABLE_ADDR {
+            return self.rom[addr as usize];
+        }
+        self.rom[self.current_rom_bank() * ROM_BANK_SIZE + (addr - ROM_SWITCHABLE_ADDR) as usize]
+    }
+
+    // No registers in ROM area
+    fn write_rom(&mut self, addr: Addr, value: Byte) -> bool {
+        log::trace!(target: "mbc", "TAMA5 ignored write 0x{:04X}: 0x{:02X}", addr, value);
+        false
+    }
+
+    fn read_ram(&mut self, addr: Addr) -> Option<Byte> {
+        if addr & 1 == 0 {
+            match self.reg {
+                REG_READY => {}
+                REG_READ_LO | REG_READ_HI if self.command() == COMMAND_RAM_READ => {}
+                REG_READ_LO | REG_READ_HI => self.unsupported.touch("TAMA5", "RTC"),
+                _ => self.unsupported.touch("TAMA5", "unknown register"),
+            }
+        }
+        self.peek_ram(addr)
+    }
+
+    // Only low nibble of registers exists
+    fn peek_ram(&self, addr: Addr) -> Option<Byte> {
+        if addr & 1 != 0 { return Some(0xFF) }
+        let value = match self.reg {
+            REG_READ_LO | REG_READ_HI => {
+                let value = if self.command() == COMMAND_RAM_READ { self.ram[self.ram_addr()] } else { 0 };
+                if self.reg == REG_READ_HI { value >> 4 } else { value & 0xF }
+            }
+            _ => 0x1,
+        };
+        Some(0xF0 | value)
+    }
+
+    fn write_ram(&mut self, addr: Addr, value: Byte) -> bool {
+        if addr & 1 != 0 {
+            self.reg = value;
+        } else {
+            self.write_register(value);
+        }
+        true
+    }
+
+    // Bank 1 at power on, like other mappers
+    fn reset(&mut self) {
+        self.reg = 0;
+        self.registers = [0; REGISTERS];
+        self.registers[REG_BANK_LO as usize] = 1;
+    }
+
+    fn current_rom_bank(&self) -> usize {
+        let bank = self.registers[REG_BANK_HI as usize] << 4 | self.registers[REG_BANK_LO as usize];
+        bank as usize & (self.rom.len() / ROM_BANK_SIZE - 1)
+    }
+
+    fn debug_state(&self) -> MapperDebugInfo {
+        MapperDebugInfo {
+            name: "TAMA5",
+            rom_bank: self.current_rom_bank(),
+            rom_banks: self.rom.len() / ROM_BANK_SIZE,
+            // RAM is behind registers, not mapped
+            ram_bank: None,
+            ram_banks: 1,
+            ram_enabled: true,
+            registers: vec![("reg", self.reg), ("command", self.command())],
+        }
+    }
+
+    fn rom(&self) -> &[Byte] { &self.rom }
+
+    fn ram(&mut self) -> MutMem<'_> { &mut self.ram }
+}
@@ -1,11 +1,17 @@
+use std::ops::RangeInclusive;
+
 use super::*;
 
 /*
  * MMU struct is responsible for handling address space of CPU.
  
  */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MMU<T: BankController> {
-    /* bootrap contains 256 of boot code. it gets executed first */
+    /*
+     * Boot code, it gets executed first. 256 bytes of DMG boot ROM are mapped at 0x0000, CGB_BOOTROM_SIZE bytes
+     * of CGB one at 0x0000-0x08FF, except for 0x100-0x1FF where cart header stays visible.
+     */
     pub bootstrap: Vec<Byte>,
     /* mapper represents the cartdrige and implements its own bank-switching method */
     pub mapper: T,
@@ -15,6 +21,22 @@ pub struct MMU<T: BankController> {
     pub ram: Vec<Byte>,
     pub hram: Vec<Byte>,
     pub ioregs: IORegs,
+    /* Notified about CPU accesses, see State::safe_read and State::safe_write */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub observers: Observers,
+    /* What happens on illegal access, see BusPolicy */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub policy: BusPolicy,
+    /* Last illegal access, not yet taken by State */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_illegal: Option<IllegalAccess>,
+    /* Accesses CPU made so far, counted by State::safe_read and safe_write. Not part of emulated state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub stats: BusStats,
+    /* Cached BOOT register state, so reads don't have to look it up every time */
+    boot_mapped: bool,
+    /* CGB only registers exist, see ioregs::cgb_register */
+    cgb: bool,
 }
 
 impl<T: BankController> MMU<T> {
@@ -27,6 +49,12 @@ impl<T: BankController> MMU<T> {
             ram: vec![0; RAM_BANK_SIZE],
             hram: vec![0; HRAM_SIZE],
             ioregs: IORegs::new(),
+            observers: Observers::new(),
+            policy: BusPolicy::default(),
+            last_illegal: None,
+            stats: BusStats::default(),
+            boot_mapped: true,
+            cgb: false,
         }
     }
 
@@ -47,74 +75,39 @@ impl<T: BankController> MMU<T> {
         byte & (1 << n) != 0
     }
 
+    /*
+     * Writes bytes one by one from addr on, wrapping around at the end of address space. Goes through write(),
+     * so ROM of real carts isn't touched, with mbc::RamOnly code can be placed anywhere.
+     */
+    pub fn load(&mut self, addr: Addr, bytes: &[Byte]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write(addr.wrapping_add(i as Addr), *byte);
+        }
+    }
+
     /* WRITES */
     pub fn write(&mut self, addr: Addr, byte: Byte) {
-        if addr < BOOSTRAP_SIZE as u16 && self.read(ioregs::BOOT) == 0x00 {
-            panic!("Attempt to write to bootstrap ROM at 0x{:X}", addr)
+        if self.boot_byte(addr).is_some() {
+            return self.illegal(IllegalAccess::BootromWrite { addr, value: byte });
         }
 
-        // Thing below is quite retarded, but I was hoping for some magic performacne boost.
-        let chunked = (
-            (addr >> 12) & 0xF,
-            (addr >> 8) & 0xF,
-            (addr >> 4) & 0xF,
-            addr & 0xF,
-        );
-        match chunked {
-            (0, _, _, _) | (1, _, _, _) | (2, _, _, _) | (3, _, _, _) => {
-                self.write_base_rom(addr, addr as usize, byte)
-            }
-            (4, _, _, _) | (5, _, _, _) | (6, _, _, _) | (7, _, _, _) => {
-                self.write_switchable_rom(addr, (addr - ROM_SWITCHABLE_ADDR) as usize, byte)
-            }
-            (8, _, _, _) | (9, _, _, _) => self.write_vram(addr, (addr - VRAM_ADDR) as usize, byte),
-            (10, _, _, _) | (11, _, _, _) => {
-                self.write_switchable_ram(addr, (addr - RAM_SWITCHABLE_ADDR) as usize, byte)
-            }
-            (12, _, _, _) | (13, _, _, _) => {
-                self.write_base_ram(addr, (addr - RAM_BASE_ADDR) as usize, byte)
+        match addr {
+            0x0000..=0x7FFF => self.write_rom(addr, byte),
+            0x8000..=0x9FFF => self.write_vram(addr, (addr - VRAM_ADDR) as usize, byte),
+            0xA000..=0xBFFF => self.write_switchable_ram(addr, byte),
+            0xC000..=0xDFFF => self.write_base_ram(addr, (addr - RAM_BASE_ADDR) as usize, byte),
+            0xE000..=0xFDFF => self.write_base_ram(addr, (addr - RAM_ECHO_ADDR) as usize, byte),
+            0xFE00..=0xFEFF => self.write_oam(addr, (addr - OAM_ADDR) as usize, byte),
+            0xFF00..=0xFF7F | 0xFFFF => {
+                self.write_io_reg(addr, (addr - IO_REGS_ADDR) as usize, byte)
             }
-            (14, _, _, _)
-            | (15, 0, _, _)
-            | (15, 1, _, _)
-            | (15, 2, _, _)
-            | (15, 3, _, _)
-            | (15, 4, _, _)
-            | (15, 5, _, _)
-            | (15, 6, _, _)
-            | (15, 7, _, _)
-            | (15, 8, _, _)
-            | (15, 9, _, _)
-            | (15, 10, _, _)
-            | (15, 11, _, _)
-            | (15, 12, _, _)
-            | (15, 13, _, _) => self.write_base_ram(addr, (addr - RAM_ECHO_ADDR) as usize, byte),
-            (15, 14, _, _) => self.write_oam(addr, (addr - OAM_ADDR) as usize, byte),
-            (15, 15, 0, _)
-            | (15, 15, 1, _)
-            | (15, 15, 2, _)
-            | (15, 15, 3, _)
-            | (15, 15, 4, _)
-            | (15, 15, 5, _)
-            | (15, 15, 6, _)
-            | (15, 15, 7, _)
-            | (15, 15, 15, 15) => self.write_io_reg(addr, (addr - IO_REGS_ADDR) as usize, byte),
-            (15, 15, _, _) => self.write_hram(addr, (addr - HRAM_ADDR) as usize, byte),
-            _ => panic!("Unmapped address 0x{:x}", addr),
+            0xFF80..=0xFFFE => self.write_hram(addr, (addr - HRAM_ADDR) as usize, byte),
         };
     }
 
-    fn write_base_rom(&mut self, addr: Addr, _: usize, value: Byte) {
-        match self.mapper.get_addr_type(addr) {
-            AddrType::Status => self.mapper.on_status(addr, value),
-            AddrType::Write => println!("Attempt to write to ROM at 0x{:X}", addr),
-        }
-    }
-
-    fn write_switchable_rom(&mut self, addr: Addr, _: usize, value: Byte) {
-        match self.mapper.get_addr_type(addr) {
-            AddrType::Status => self.mapper.on_status(addr, value),
-            AddrType::Write => println!("Attempt to write to ROM at 0x{:X}", addr),
+    fn write_rom(&mut self, addr: Addr, value: Byte) {
+        if !self.mapper.write_rom(addr, value) {
+            self.illegal(IllegalAccess::RomWrite { addr, value });
         }
     }
 
@@ -122,13 +115,9 @@ impl<T: BankController> MMU<T> {
         self.vram[offset] = value;
     }
 
-    fn write_switchable_ram(&mut self, addr: Addr, offset: usize, value: Byte) {
-        match self.mapper.get_addr_type(addr) {
-            AddrType::Status => panic!("Unable to send status at RAM address 0x{:X}", addr),
-            AddrType::Write => match self.mapper.get_switchable_ram() {
-                None => println!("Attempted to write to 0x{:x}, storage not present.", addr),
-                Some(arr) => arr[offset] = value,
-            },
+    fn write_switchable_ram(&mut self, addr: Addr, value: Byte) {
+        if !self.mapper.write_ram(addr, value) {
+            self.illegal(IllegalAccess::Missing { kind: AccessKind::Write, addr });
         }
     }
 
@@ -140,7 +129,16 @@ impl<T: BankController> MMU<T> {
         self.oam[offset] = value;
     }
 
-    fn write_io_reg(&mut self, _: Addr, offset: usize, value: Byte) {
+    fn write_io_reg(&mut self, addr: Addr, offset: usize, value: Byte) {
+        // Keep cached bootrom mapping in sync with BOOT register
+        if addr == ioregs::BOOT {
+            self.boot_mapped = value == 0x00;
+        }
+        let value = match ioregs::cgb_register(addr) {
+            Some(_) if !self.cgb => return,
+            Some((writable, _)) => value & writable,
+            None => value,
+        };
         self.ioregs.slice()[offset] = value;
     }
 
@@ -148,114 +146,166 @@ impl<T: BankController> MMU<T> {
         self.hram[offset] = value;
     }
 
+    /*
+     * Little endian word: low byte goes to addr, high one to the next address, wrapping around at the end of
+     * address space. Two separate accesses, so halves may land in different regions.
+     */
+    pub fn write_word(&mut self, addr: Addr, word: Word) {
+        let [low, high] = word.to_le_bytes();
+        self.write(addr, low);
+        self.write(addr.wrapping_add(1), high);
+    }
+
     /* READS */
     pub fn read(&mut self, addr: Addr) -> Byte {
-        if addr < BOOSTRAP_SIZE as u16 && self.read(ioregs::BOOT) == 0x00 {
-            return self.bootstrap[addr as usize];
+        if let Some(value) = self.boot_byte(addr) {
+            return value;
         }
 
-        // The thing below is quite retarded, but I was hoping for some magic optimalizations.
-        let chunked = (
-            (addr >> 12) & 0xF,
-            (addr >> 8) & 0xF,
-            (addr >> 4) & 0xF,
-            addr & 0xF,
-        );
-        match chunked {
-            (0, _, _, _) | (1, _, _, _) | (2, _, _, _) | (3, _, _, _) => {
-                self.read_base_rom(addr, addr as usize)
-            }
-            (4, _, _, _) | (5, _, _, _) | (6, _, _, _) | (7, _, _, _) => {
-                self.read_switchable_rom(addr, (addr - ROM_SWITCHABLE_ADDR) as usize)
-            }
-            (8, _, _, _) | (9, _, _, _) => self.read_vram(addr, (addr - VRAM_ADDR) as usize),
-            (10, _, _, _) | (11, _, _, _) => {
-                self.read_switchable_ram(addr, (addr - RAM_SWITCHABLE_ADDR) as usize)
-            }
-            (12, _, _, _) | (13, _, _, _) => {
-                self.read_base_ram(addr, (addr - RAM_BASE_ADDR) as usize)
-            }
-            (14, _, _, _)
-            | (15, 0, _, _)
-            | (15, 1, _, _)
-            | (15, 2, _, _)
-            | (15, 3, _, _)
-            | (15, 4, _, _)
-            | (15, 5, _, _)
-            | (15, 6, _, _)
-            | (15, 7, _, _)
-            | (15, 8, _, _)
-            | (15, 9, _, _)
-            | (15, 10, _, _)
-            | (15, 11, _, _)
-            | (15, 12, _, _)
-            | (15, 13, _, _) => self.read_base_ram(addr, (addr - RAM_ECHO_ADDR) as usize),
-            (15, 14, _, _) => self.read_oam(addr, (addr - OAM_ADDR) as usize),
-            (15, 15, 0, _)
-            | (15, 15, 1, _)
-            | (15, 15, 2, _)
-            | (15, 15, 3, _)
-            | (15, 15, 4, _)
-            | (15, 15, 5, _)
-            | (15, 15, 6, _)
-            | (15, 15, 7, _)
-            | (15, 15, 15, 15) => self.read_io_reg(addr, (addr - IO_REGS_ADDR) as usize),
-            (15, 15, _, _) => self.read_hram(addr, (addr - HRAM_ADDR) as usize),
-            _ => panic!("Unmapped address 0x{:x}", addr),
+        match addr {
+            0x0000..=0x7FFF => self.mapper.read_rom(addr),
+            0xA000..=0xBFFF => self.read_switchable_ram(addr),
+            _ => self.peek(addr),
         }
     }
 
-    fn read_base_rom(&mut self, addr: Addr, offset: usize) -> Byte {
-        match self.mapper.get_base_rom() {
-            Some(arr) => return arr[offset],
-            None => {
-                println!("Attempted to read unexistent memory at 0x{:x}", addr);
-                0xFF
-            }
-        }
+    /* Little endian word, see write_word */
+    pub fn read_word(&mut self, addr: Addr) -> Word {
+        Word::from_le_bytes([self.read(addr), self.read(addr.wrapping_add(1))])
     }
 
-    fn read_switchable_rom(&mut self, addr: Addr, offset: usize) -> Byte {
-        match self.mapper.get_switchable_rom() {
-            Some(arr) => return arr[offset],
-            None => {
-                println!("Attempted to read unexistent memory at 0x{:x}", addr);
-                0xFF
-            }
+    /*
+     * Same byte read() gives, but nothing is counted, no illegal access is reported and mapper state isn't
+     * touched. For debugger, disassembly and other tools looking at memory. Missing cart RAM gives 0xFF.
+     */
+    pub fn peek(&self, addr: Addr) -> Byte {
+        if let Some(value) = self.boot_byte(addr) {
+            return value;
+        }
+
+        match addr {
+            0x0000..=0x7FFF => self.mapper.peek_rom(addr),
+            0x8000..=0x9FFF => self.read_vram(addr, (addr - VRAM_ADDR) as usize),
+            0xA000..=0xBFFF => self.mapper.peek_ram(addr).unwrap_or(0xFF),
+            0xC000..=0xDFFF => self.read_base_ram(addr, (addr - RAM_BASE_ADDR) as usize),
+            0xE000..=0xFDFF => self.read_base_ram(addr, (addr - RAM_ECHO_ADDR) as usize),
+            0xFE00..=0xFEFF => self.read_oam(addr, (addr - OAM_ADDR) as usize),
+            0xFF00..=0xFF7F | 0xFFFF => self.read_io_reg(addr, (addr - IO_REGS_ADDR) as usize),
+            0xFF80..=0xFFFE => self.read_hram(addr, (addr - HRAM_ADDR) as usize),
         }
     }
 
-    fn read_vram(&mut self, _: Addr, offset: usize) -> Byte {
+    /* len bytes from addr on with peek(), wrapping around at the end of address space like load() */
+    pub fn peek_range(&self, addr: Addr, len: usize) -> Vec<Byte> {
+        (0..len).map(|i| self.peek(addr.wrapping_add(i as Addr))).collect()
+    }
+
+    fn read_vram(&self, _: Addr, offset: usize) -> Byte {
         self.vram[offset]
     }
 
-    fn read_switchable_ram(&mut self, addr: Addr, offset: usize) -> Byte {
-        match self.mapper.get_switchable_ram() {
-            Some(arr) => return arr[offset],
+    fn read_switchable_ram(&mut self, addr: Addr) -> Byte {
+        match self.mapper.read_ram(addr) {
+            Some(value) => value,
             None => {
-                println!("RAM: Attempted to read unexistent memory at 0x{:x}", addr);
+                self.illegal(IllegalAccess::Missing { kind: AccessKind::Read, addr });
                 0xFF
             }
         }
     }
 
-    fn read_base_ram(&mut self, _: Addr, offset: usize) -> Byte {
+    fn read_base_ram(&self, _: Addr, offset: usize) -> Byte {
         self.ram[offset]
     }
 
-    fn read_oam(&mut self, _: Addr, offset: usize) -> Byte {
+    fn read_oam(&self, _: Addr, offset: usize) -> Byte {
         self.oam[offset]
     }
 
-    fn read_io_reg(&mut self, _: Addr, offset: usize) -> Byte {
-        self.ioregs.slice()[offset]
+    fn read_io_reg(&self, addr: Addr, _: usize) -> Byte {
+        let value = self.ioregs.get(addr);
+        // Only 5 interrupt bits are there, others read as set
+        if addr == ioregs::IF {
+            return value | 0xE0;
+        }
+        match ioregs::cgb_register(addr) {
+            Some(_) if !self.cgb => 0xFF,
+            Some((writable, fixed)) => value & writable | fixed,
+            None => value,
+        }
     }
 
-    fn read_hram(&mut self, _: Addr, offset: usize) -> Byte {
+    fn read_hram(&self, _: Addr, offset: usize) -> Byte {
         self.hram[offset]
     }
 
+    /* Registers callback for CPU reads and/or writes in given address range */
+    pub fn subscribe(
+        &mut self,
+        range: RangeInclusive<Addr>,
+        kind: Option<AccessKind>,
+        callback: AccessCallback,
+    ) -> ObserverId {
+        self.observers.subscribe(range, kind, callback)
+    }
+
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        self.observers.unsubscribe(id)
+    }
+
+    /*
+     * Power on state of registers, with boot ROM mapped again. Memory is cleared only when asked,
+     * like after cutting power. Mapper keeps its RAM.
+     */
+    pub fn reset(&mut self, clear_memory: bool) {
+        if clear_memory {
+            for mem in [&mut self.vram, &mut self.oam, &mut self.ram, &mut self.hram] {
+                mem.iter_mut().for_each(|byte| *byte = 0);
+            }
+        }
+        self.ioregs = IORegs::new();
+        self.boot_mapped = true;
+        self.mapper.reset();
+    }
+
+    /* Last illegal access made since previous call, whatever the policy. Doctor looks at it too. */
+    pub fn take_illegal(&mut self) -> Option<IllegalAccess> {
+        self.last_illegal.take()
+    }
+
+    fn illegal(&mut self, access: IllegalAccess) {
+        self.last_illegal = Some(access);
+        self.stats.illegal += 1;
+        match self.policy {
+            BusPolicy::Ignore => {}
+            BusPolicy::Log | BusPolicy::Trap => log::debug!(target: "mmu", "{}", access),
+            BusPolicy::Panic => panic!("Illegal memory access: {}", access),
+        }
+    }
+
+    /* Byte of boot ROM while it's mapped at addr. Gap for cart header is there with any boot ROM size. */
+    fn boot_byte(&self, addr: Addr) -> Option<Byte> {
+        let header_gap = BOOSTRAP_SIZE..BOOSTRAP_SIZE * 2;
+        if !self.boot_mapped || header_gap.contains(&(addr as usize)) {
+            return None;
+        }
+        self.bootstrap.get(addr as usize).copied()
+    }
+
     pub fn disable_bootrom(&mut self) {
         self.write(ioregs::BOOT, 1);
     }
+
+    pub fn bootrom_mapped(&self) -> bool {
+        self.boot_mapped
+    }
+
+    /* Set by Runtime from hardware model, decides if CGB registers exist */
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb;
+    }
+
+    pub fn cgb(&self) -> bool {
+        self.cgb
+    }
 }
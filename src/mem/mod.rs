@@ -1,10 +1,14 @@
+pub mod bus;
 pub mod ioregs;
 pub mod mbc;
 pub mod mmu;
+pub mod observer;
 
+pub use bus::*;
 pub use ioregs::*;
 pub use mbc::*;
 pub use mmu::*;
+pub use observer::*;
 
 pub type Addr = u16;
 pub type Byte = u8;
@@ -25,6 +29,8 @@ pub const HRAM_ADDR: Addr = 0xFF80;
 pub const IO_REGS_ADDR: Addr = 0xFF00;
 
 pub const BOOSTRAP_SIZE: usize = 0x100;
+/* CGB boot ROM is 256 bytes mapped at 0x0000 and 0x700 more mapped at 0x200, cart header shows in between */
+pub const CGB_BOOTROM_SIZE: usize = 0x900;
 pub const RAM_BANK_SIZE: usize = 0x2000;
 pub const ROM_BANK_SIZE: usize = 0x4000;
 pub const VRAM_SIZE: usize = 0x2000;
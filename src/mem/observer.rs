@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/* Single memory access made by CPU */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Access {
+    pub kind: AccessKind,
+    /* Address of instruction making the access */
+    pub pc: Addr,
+    pub addr: Addr,
+    /* Value read or written */
+    pub value: Byte,
+    /* CPU cycle at start of the instruction */
+    pub cycle: u64,
+}
+
+pub type ObserverId = usize;
+pub type AccessCallback = Box<dyn FnMut(&Access) + Send>;
+
+struct Observer {
+    id: ObserverId,
+    range: RangeInclusive<Addr>,
+    /* None means both reads and writes */
+    kind: Option<AccessKind>,
+    callback: AccessCallback,
+}
+
+/*
+ * Callbacks notified about CPU memory accesses. Accesses made by other devices(GPU, DMA) aren't reported.
+ * Runtime keeps PC and cycle up to date, so each access can be tracked down to instruction making it.
+ */
+#[derive(Default)]
+pub struct Observers {
+    list: Vec<Observer>,
+    next_id: ObserverId,
+    pc: Addr,
+    cycle: u64,
+}
+
+impl Observers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /* Callback gets accesses to addresses in range, of given kind or any kind when None */
+    pub fn subscribe(
+        &mut self,
+        range: RangeInclusive<Addr>,
+        kind: Option<AccessKind>,
+        callback: AccessCallback,
+    ) -> ObserverId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push(Observer { id, range, kind, callback });
+        id
+    }
+
+    /* Returns false when there was no such observer */
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        let before = self.list.len();
+        self.list.retain(|observer| observer.id != id);
+        self.list.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn set_context(&mut self, pc: Addr, cycle: u64) {
+        self.pc = pc;
+        self.cycle = cycle;
+    }
+
+    pub fn notify(&mut self, kind: AccessKind, addr: Addr, value: Byte) {
+        if self.list.is_empty() {
+            return;
+        }
+        let access = Access { kind, pc: self.pc, addr, value, cycle: self.cycle };
+        for observer in self.list.iter_mut() {
+            if observer.range.contains(&addr) && observer.kind.is_none_or(|k| k == kind) {
+                (observer.callback)(&access);
+            }
+        }
+    }
+}
+
+/*
+ * Ring buffer with last accesses. Alternative to callbacks, which can be read from other thread.
+ */
+#[derive(Clone)]
+pub struct AccessLog {
+    entries: Arc<Mutex<VecDeque<Access>>>,
+    capacity: usize,
+}
+
+impl AccessLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /* Callback to subscribe with. Oldest entries are dropped once log is full. */
+    pub fn recorder(&self) -> AccessCallback {
+        let log = self.clone();
+        Box::new(move |access| {
+            let mut entries = log.entries.lock().unwrap();
+            entries.push_back(*access);
+            if entries.len() > log.capacity {
+                entries.pop_front();
+            }
+        })
+    }
+
+    /* Recorded accesses, oldest first */
+    pub fn entries(&self) -> Vec<Access> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::timing::FrameTiming;
+use gameboy::{
+    ioregs, BankController, BusStats, ChannelDebugInfo, GPUMode, Interrupt, LatencyStats, Region, Runtime, GPU,
+};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, WindowCanvas};
+
+/* Glyphs are 3x5, with one pixel of spacing. Sizes are in GB pixels, canvas scales them like the picture. */
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+const ADVANCE: i32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: i32 = GLYPH_HEIGHT + 2;
+const MARGIN: i32 = 2;
+/* Width of channel meter at full volume */
+const METER_WIDTH: i32 = 32;
+/* Wave duty of square channels, there is no percent glyph */
+const DUTIES: [&str; 4] = ["12", "25", "50", "75"];
+const PANEL: Color = Color { r: 0, g: 0, b: 0, a: 176 };
+const TEXT: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+const DIM: Color = Color { r: 128, g: 128, b: 128, a: 255 };
+const METER: Color = Color { r: 96, g: 224, b: 96, a: 255 };
+/* Notifications stay on screen for that long, at most NOTICES_SHOWN of them, newest at the bottom */
+const NOTICE_TIME: Duration = Duration::from_secs(2);
+const NOTICES_SHOWN: usize = 4;
+/* LCDC bits, from 7 down to 0 */
+const LCDC_FLAGS: [&str; 8] = ["LCD", "W9C00", "WIN", "T8000", "B9C00", "OBJ16", "OBJ", "BG"];
+
+/*
+ * State of the first GB for the overlay, captured by emulation thread after each frame while overlay is shown.
+ */
+pub struct Stats {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub ly: u8,
+    pub mode: GPUMode,
+    pub lcdc: u8,
+    /* Peak amplitude of each channel during the frame, and what each one is doing at its end */
+    pub levels: [u16; 4],
+    pub channels: [ChannelDebugInfo; 4],
+    /* Background, window and sprites shown */
+    pub layers: [bool; 3],
+    /* Memory accesses during the frame */
+    pub bus: BusStats,
+    /* Since power on */
+    pub vblank_latency: LatencyStats,
+}
+
+impl Stats {
+    pub fn capture<T: BankController>(runtime: &mut Runtime<T>) -> Self {
+        let cpu = &runtime.cpu;
+        let f = (cpu.Z as u16) << 7 | (cpu.N as u16) << 6 | (cpu.H as u16) << 5 | (cpu.C as u16) << 4;
+        let state = &mut runtime.state;
+        Self {
+            af: (cpu.A as u16) << 8 | f,
+            bc: cpu.BC.val(),
+            de: cpu.DE.val(),
+            hl: cpu.HL.val(),
+            sp: cpu.SP,
+            pc: cpu.PC.val(),
+            ly: state.mmu.peek(ioregs::LY),
+            mode: GPU::MODE(&mut state.mmu),
+            lcdc: state.mmu.peek(ioregs::LCDC),
+            levels: state.apu.take_levels(),
+            channels: [1, 2, 3, 4].map(|chan| state.apu.channel_debug(&state.mmu, chan)),
+            layers: [state.gpu.show_background, state.gpu.show_window, state.gpu.show_sprites],
+            bus: runtime.frame_bus_stats(),
+            vblank_latency: runtime.interrupt_latency().stats(Interrupt::VBlank),
+        }
+    }
+}
+
+/*
+ * Debug overlay drawn over the picture of the first GB. Emulation thread provides Stats, timing of frames is
+ * measured by SDL thread.
+ */
+pub fn draw(canvas: &mut WindowCanvas, stats: &Stats, timing: &FrameTiming) {
+    let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+    let mode = match stats.mode {
+        GPUMode::HBLANK => "HBLANK",
+        GPUMode::VBLANK => "VBLANK",
+        GPUMode::OAM_SEARCH => "OAM",
+        GPUMode::LCD_TRANSFER => "DRAW",
+    };
+    let flags = LCDC_FLAGS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| stats.lcdc & (0x80 >> i) != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<&str>>();
+    let shown = |on: bool| if on { "ON" } else { "OFF" };
+    let accesses = |region: Region| stats.bus.reads(region) + stats.bus.writes(region);
+    let lines = [
+        format!("FPS {:.1} DROPPED {}", timing.fps(), timing.dropped),
+        format!(
+            "EMU {:.1} DRAW {:.1} WAIT {:.1} MS",
+            ms(timing.emulation),
+            ms(timing.render),
+            ms(timing.slept)
+        ),
+        format!("AF {:04X} BC {:04X} DE {:04X}", stats.af, stats.bc, stats.de),
+        format!("HL {:04X} SP {:04X} PC {:04X}", stats.hl, stats.sp, stats.pc),
+        format!("LY {:02X} MODE {} LCDC {:02X}", stats.ly, mode, stats.lcdc),
+        flags.join(" "),
+        format!(
+            "BG {} WIN {} OBJ {}",
+            shown(stats.layers[0]),
+            shown(stats.layers[1]),
+            shown(stats.layers[2])
+        ),
+        format!(
+            "BUS R {} W {} ILL {}",
+            stats.bus.total_reads(),
+            stats.bus.total_writes(),
+            stats.bus.illegal
+        ),
+        format!(
+            "ROM {} WRAM {} HRAM {} IO {}",
+            accesses(Region::Rom),
+            accesses(Region::Wram),
+            accesses(Region::Hram),
+            accesses(Region::Io)
+        ),
+        format!(
+            "VBLANK LAT {:.1} MAX {}",
+            stats.vblank_latency.mean().unwrap_or(0.0),
+            stats.vblank_latency.max
+        ),
+    ];
+    let text_width = lines.iter().map(|line| line.len() as i32).max().unwrap_or(0) * ADVANCE;
+    // Channel meters go below the text, one per line, followed by channel status
+    let status = stats
+        .channels
+        .iter()
+        .map(|info| {
+            let duty = info.duty.map(|duty| DUTIES[duty as usize]).unwrap_or("--");
+            format!("{:6.0}HZ V{:2} L{:3} D{}", info.frequency, info.volume, info.length, duty)
+        })
+        .collect::<Vec<String>>();
+    let status_left = MARGIN + 4 * ADVANCE + METER_WIDTH + ADVANCE;
+    let status_width = status.iter().map(|line| line.len() as i32).max().unwrap_or(0) * ADVANCE;
+    let meters_top = MARGIN + lines.len() as i32 * LINE_HEIGHT;
+    let width = (2 * MARGIN + text_width).max(status_left + status_width + MARGIN);
+    let height = meters_top + 4 * LINE_HEIGHT + MARGIN;
+
+    let mut painter = Painter { canvas };
+    painter.canvas.set_blend_mode(BlendMode::Blend);
+    painter.rect(PANEL, 0, 0, width, height);
+    for (i, line) in lines.iter().enumerate() {
+        painter.text(TEXT, MARGIN, MARGIN + i as i32 * LINE_HEIGHT, line);
+    }
+    for (i, level) in stats.levels.iter().enumerate() {
+        let y = meters_top + i as i32 * LINE_HEIGHT;
+        let color = if stats.channels[i].enabled { TEXT } else { DIM };
+        painter.text(color, MARGIN, y, &format!("CH{}", i + 1));
+        let bar = *level as i32 * METER_WIDTH / i16::MAX as i32;
+        painter.rect(DIM, MARGIN + 4 * ADVANCE, y + GLYPH_HEIGHT / 2, METER_WIDTH, 1);
+        painter.rect(METER, MARGIN + 4 * ADVANCE, y, bar, GLYPH_HEIGHT);
+        painter.text(color, status_left, y, &status[i]);
+    }
+    painter.canvas.set_blend_mode(BlendMode::None);
+}
+
+/*
+ * Short messages about frontend actions(save states, speed, pause) shown in the bottom left corner, so they're
+ * seen without looking at the terminal.
+ */
+pub struct Notices {
+    shown: VecDeque<(String, Instant)>,
+    /* Something was added or went away since last draw */
+    changed: bool,
+}
+
+impl Notices {
+    pub fn new() -> Self {
+        Self { shown: VecDeque::new(), changed: false }
+    }
+
+    pub fn push(&mut self, text: String) {
+        if self.shown.len() == NOTICES_SHOWN {
+            self.shown.pop_front();
+        }
+        self.shown.push_back((text, Instant::now()));
+        self.changed = true;
+    }
+
+    /* Drops expired notifications. True when picture has to be drawn again, even without new frame. */
+    pub fn update(&mut self) -> bool {
+        while self.shown.front().is_some_and(|(_, since)| since.elapsed() >= NOTICE_TIME) {
+            self.shown.pop_front();
+            self.changed = true;
+        }
+        std::mem::take(&mut self.changed)
+    }
+
+    /* Height is the one of picture, in GB pixels */
+    pub fn draw(&self, canvas: &mut WindowCanvas, height: usize) {
+        let mut painter = Painter { canvas };
+        painter.canvas.set_blend_mode(BlendMode::Blend);
+        let bottom = height as i32 - MARGIN;
+        for (i, (text, _)) in self.shown.iter().rev().enumerate() {
+            let y = bottom - (i as i32 + 1) * LINE_HEIGHT;
+            let width = text.len() as i32 * ADVANCE + 2 * MARGIN;
+            painter.rect(PANEL, 0, y, width, LINE_HEIGHT);
+            painter.text(TEXT, MARGIN, y + 1, text);
+        }
+        painter.canvas.set_blend_mode(BlendMode::None);
+    }
+}
+
+/* Draws in GB pixels */
+struct Painter<'a> {
+    canvas: &'a mut WindowCanvas,
+}
+
+impl Painter<'_> {
+    fn rect(&mut self, color: Color, x: i32, y: i32, width: i32, height: i32) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        let rect = Rect::new(x, y, width as u32, height as u32);
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(rect).unwrap();
+    }
+
+    fn text(&mut self, color: Color, x: i32, y: i32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as i32 * ADVANCE;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> col) != 0 {
+                        self.rect(color, left + col, y + row as i32, 1, 1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/* Rows of 3x5 glyph, top to bottom, highest bit is the leftmost pixel. Unknown characters are blank. */
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; 5],
+    }
+}
@@ -1,12 +1,68 @@
+use std::convert::TryFrom;
+
 use super::*;
 
-/* CPU cycles per frame */
-pub const CPU_CYCLES_PER_FRAME: u64 = (1 << 20) / 60;
+/* CPU cycles per frame. It's what GPU needs for full frame, which gives ~59.7 frames per second. */
+pub const CPU_CYCLES_PER_FRAME: u64 = FRAME_CYCLES;
+
+/* Allowed range of emulation speed multiplier */
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 8.0;
+
+pub type CallbackId = usize;
+pub type FrameCallback<T> = Box<dyn FnMut(&mut State<T>) + Send>;
+/* Gets line with registers it was drawn with and its pixels, before palette is applied */
+pub type ScanlineCallback = Box<dyn FnMut(&Scanline, &[Color]) + Send>;
+/* Gets whether cart's rumble motor is on now */
+pub type RumbleCallback = Box<dyn FnMut(bool) + Send>;
+
+/*
+ * Callbacks run by Runtime once per emulated frame or line. They aren't part of emulated state, so reset, loading
+ * other ROM or save state keeps them.
+ */
+pub struct FrameCallbacks<T: BankController> {
+    vblank: Vec<(CallbackId, FrameCallback<T>)>,
+    frame_complete: Vec<(CallbackId, FrameCallback<T>)>,
+    scanline: Vec<(CallbackId, ScanlineCallback)>,
+    rumble: Vec<(CallbackId, RumbleCallback)>,
+    next_id: CallbackId,
+}
+
+impl<T: BankController> Default for FrameCallbacks<T> {
+    fn default() -> Self {
+        Self { vblank: Vec::new(), frame_complete: Vec::new(), scanline: Vec::new(), rumble: Vec::new(), next_id: 0 }
+    }
+}
+
+impl<T: BankController> FrameCallbacks<T> {
+    fn next_id(&mut self) -> CallbackId {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn run(list: &mut [(CallbackId, FrameCallback<T>)], state: &mut State<T>) {
+        for (_, callback) in list.iter_mut() {
+            callback(state);
+        }
+    }
+}
+
+/* Bus stats of frames, kept by Runtime. Like MMU counters, they aren't part of emulated state. */
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FrameBusStats {
+    /* Counters when current frame started */
+    start: BusStats,
+    /* Accesses made during last complete frame */
+    last: BusStats,
+    /* Frames between logged reports, see set_bus_report */
+    report: Option<u64>,
+}
 
 /*
  * Runtime is used to connect CPU with everything stored in State(memory, IO devices).
  * I created it, cuz borrow checker yelld at me for doing something like this: self.cpu.step(self) // multiple mutable borrow
  */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Runtime<T: BankController> {
     pub cpu: CPU,
     pub state: State<T>,
@@ -15,6 +71,39 @@ pub struct Runtime<T: BankController> {
     apu_cycles: u64,
     timer_cycles: u64,
     dma_cycles: u64,
+    serial_cycles: u64,
+    /* Emulation speed multiplier, 1.0 is real hardware speed */
+    speed: f32,
+    /* Decides registers set when boot ROM is skipped */
+    model: HardwareModel,
+    /* Whether skip_bootrom() was used, so reset can skip it again */
+    bootrom_skipped: bool,
+    /* Nothing is executed while paused, see set_paused */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    paused: bool,
+    // Derived default would need T: Default
+    #[cfg_attr(feature = "serde", serde(skip, default = "FrameCallbacks::default"))]
+    pub(crate) callbacks: FrameCallbacks<T>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) bus: FrameBusStats,
+    /* Like bus stats, not part of emulated state */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) latency: InterruptLatency,
+    /* Hashes of frames run so far, see set_audit. Checks emulated state, so isn't part of it. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) audit: Option<Audit>,
+    /* What happened when, see set_event_log */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) events: Option<EventLog>,
+}
+
+/* State compared before and after each step, differences go into event log */
+struct Watched {
+    mode: GPUMode,
+    rom_bank: usize,
+    ram_bank: Option<usize>,
+    dma: bool,
+    triggers: [u64; 4],
 }
 
 impl<T: BankController> Runtime<T> {
@@ -29,13 +118,79 @@ impl<T: BankController> Runtime<T> {
             apu_cycles: 0,
             timer_cycles: 0,
             dma_cycles: 0,
+            serial_cycles: 0,
+            speed: 1.0,
+            model: HardwareModel::DMG,
+            bootrom_skipped: false,
+            paused: false,
+            callbacks: FrameCallbacks::default(),
+            bus: FrameBusStats::default(),
+            latency: InterruptLatency::new(),
+            audit: None,
+            events: None,
         }
     }
 
     // Execute next instruction, handle interrupts and let other devices catchup.
     pub fn step(&mut self) {
-        self.cpu_cycles += self.cpu.interrupts(&mut self.state);
-        self.cpu_cycles += self.cpu.step(&mut self.state);
+        if self.stopped() {
+            return;
+        }
+        let (pc, start) = (self.cpu.PC.val(), self.cpu_cycles);
+        let gpu = &self.state.gpu;
+        let (vblanks, frames, lines) = (gpu.vblanks(), gpu.frames(), gpu.drawn_lines());
+        let rumble = self.state.mmu.mapper.rumble();
+        let watched = self.events.is_some().then(|| self.watched());
+        if self.state.doctor.as_ref().is_some_and(|doctor| doctor.locked()) {
+            // Locked up CPU doesn't even take interrupts, only time goes on
+            self.cpu_cycles += 1;
+        } else {
+            self.state.mmu.observers.set_context(self.cpu.PC.val(), self.cpu_cycles);
+            let cycles = self.cpu.interrupts(&mut self.state);
+            self.cpu_cycles += cycles;
+            // Only jump to handler takes that long
+            let interrupt = if cycles >= 5 { Interrupt::from_vector(self.cpu.PC.val()) } else { None };
+            if let Some(interrupt) = interrupt {
+                self.latency.entered(interrupt, self.cpu_cycles);
+                if let Some(events) = self.events.as_mut() {
+                    events.record(self.cpu_cycles, EventKind::Interrupt(interrupt));
+                }
+            }
+            self.state.mmu.observers.set_context(self.cpu.PC.val(), self.cpu_cycles);
+            self.state.gpu_lag = self.cpu_cycles - self.gpu_cycles;
+            // Left by accesses made outside of CPU, e.g. frontend poking memory with read()
+            self.state.mmu.take_illegal();
+            // Breakpoint stops before anything else looks at the instruction
+            let stopped = match self.state.debugger.as_mut() {
+                Some(debugger) if !self.cpu.HALT => {
+                    let bank = self.state.mmu.mapper.current_rom_bank();
+                    !debugger.before_instruction(self.cpu.PC.val(), bank, interrupt, self.cpu_cycles)
+                }
+                _ => false,
+            };
+            let reported = self.state.doctor.as_ref().map_or(0, |doctor| doctor.reports().len());
+            let runnable = match self.state.doctor.as_mut() {
+                Some(doctor) if !stopped => doctor.before_instruction(&self.cpu, &self.state.mmu, self.cpu_cycles),
+                _ => true,
+            };
+            self.cpu_cycles += match (stopped, runnable) {
+                (true, _) => 0,
+                (false, true) => self.cpu.step(&mut self.state),
+                (false, false) => 1,
+            };
+            if let Some(debugger) = self.state.debugger.as_mut() {
+                debugger.after_instruction();
+            }
+            if let Some(doctor) = self.state.doctor.as_mut().filter(|_| !stopped) {
+                doctor.after_instruction(&self.cpu, &self.state.mmu);
+                // Shows how CPU got to the fault, if call trace is on
+                if doctor.reports().len() > reported && self.cpu.call_trace() {
+                    for (depth, frame) in self.cpu.backtrace().iter().enumerate() {
+                        log::warn!(target: "doctor", "  #{} {}", depth, self.state.describe_frame(frame));
+                    }
+                }
+            }
+        }
         self.state.joypad.step(&mut self.state.mmu);
         self.dma_cycles = Runtime::catchup(
             &mut self.state.mmu,
@@ -55,24 +210,378 @@ impl<T: BankController> Runtime<T> {
             self.cpu_cycles,
             self.timer_cycles,
         );
+        self.serial_cycles = Runtime::catchup(
+            &mut self.state.mmu,
+            &mut self.state.serial,
+            self.cpu_cycles,
+            self.serial_cycles,
+        );
         self.apu_cycles = Runtime::catchup(
             &mut self.state.mmu,
             &mut self.state.apu,
             self.cpu_cycles + 1,
             self.apu_cycles,
         );
+        // Devices caught up, so requests made during the instruction are in IF now
+        self.latency.observe(self.state.mmu.ioregs.get(ioregs::IF), self.cpu_cycles);
+        if let Some(before) = watched {
+            self.log_events(before);
+        }
+        if let Some(watchdog) = self.state.watchdog.as_mut() {
+            // GPU keeps timing with LCD off, but real one doesn't get to VBlank then
+            let vblank = self.state.gpu.vblanks() != vblanks && GPU::LCD_DISPLAY_ENABLE(&mut self.state.mmu);
+            watchdog.after_instruction(pc, self.cpu.IME, self.cpu.HALT, self.cpu_cycles - start, vblank);
+        }
+        // Instruction is much shorter than a line, so at most one was finished
+        if self.state.gpu.drawn_lines() != lines {
+            let line = self.state.gpu.scanline();
+            let pixels = self.state.gpu.row(line.ly as usize);
+            for (_, callback) in self.callbacks.scanline.iter_mut() {
+                callback(&line, pixels);
+            }
+        }
+        if self.state.gpu.vblanks() != vblanks {
+            FrameCallbacks::run(&mut self.callbacks.vblank, &mut self.state);
+        }
+        if self.state.gpu.frames() != frames {
+            self.end_bus_frame();
+            self.state.joypad.frame();
+            // Before callbacks, which may poke the state from outside
+            if let Some(audit) = self.audit.as_mut() {
+                audit.record(&self.cpu, &self.state);
+            }
+            FrameCallbacks::run(&mut self.callbacks.frame_complete, &mut self.state);
+        }
+        self.state.mmu.mapper.tick(self.cpu_cycles - start);
+        self.check_rumble(rumble);
+    }
+
+    /* Stats of frame which just completed, logged when report is due */
+    fn end_bus_frame(&mut self) {
+        let stats = self.state.mmu.stats;
+        self.bus.last = stats.since(&self.bus.start);
+        self.bus.start = stats;
+        let frames = self.state.gpu.frames();
+        if self.bus.report.is_some_and(|every| frames.is_multiple_of(every)) {
+            log::info!(target: "bus", "frame {}: {}", frames, self.bus.last);
+        }
+    }
+
+    fn watched(&mut self) -> Watched {
+        let state = &mut self.state;
+        Watched {
+            mode: GPU::MODE(&mut state.mmu),
+            rom_bank: state.mmu.mapper.current_rom_bank(),
+            ram_bank: state.mmu.mapper.current_ram_bank(),
+            dma: state.dma.active(),
+            triggers: [1, 2, 3, 4].map(|chan| state.apu.triggers(chan)),
+        }
+    }
+
+    /* Logs what changed since before the step. Instruction is short, so nothing changes twice during it. */
+    fn log_events(&mut self, before: Watched) {
+        let now = self.watched();
+        let (cycle, events) = match self.events.as_mut() {
+            Some(events) => (self.cpu_cycles, events),
+            None => return,
+        };
+        if now.mode != before.mode {
+            events.record(cycle, EventKind::Mode(now.mode));
+        }
+        if now.rom_bank != before.rom_bank {
+            events.record(cycle, EventKind::RomBank(now.rom_bank));
+        }
+        if now.ram_bank != before.ram_bank {
+            events.record(cycle, EventKind::RamBank(now.ram_bank));
+        }
+        if now.dma != before.dma {
+            events.record(cycle, if now.dma { EventKind::DmaStart } else { EventKind::DmaEnd });
+        }
+        for (i, (now, before)) in now.triggers.iter().zip(before.triggers).enumerate() {
+            if *now != before {
+                events.record(cycle, EventKind::ChannelTrigger(i as u8 + 1));
+            }
+        }
+    }
+
+    /* Runs rumble callbacks when motor state isn't what it was before */
+    fn check_rumble(&mut self, before: bool) {
+        let rumble = self.state.mmu.mapper.rumble();
+        if rumble != before {
+            for (_, callback) in self.callbacks.rumble.iter_mut() {
+                callback(rumble);
+            }
+        }
+    }
+
+    /*
+     * Callback runs once per frame, right after GPU enters VBLANK, so whole picture is in framebuffer.
+     * It sees State after the instruction during which it happened.
+     */
+    pub fn on_vblank(&mut self, callback: FrameCallback<T>) -> CallbackId {
+        let id = self.callbacks.next_id();
+        self.callbacks.vblank.push((id, callback));
+        id
+    }
+
+    /* Callback runs once per frame, when VBLANK ends and GPU starts drawing next picture */
+    pub fn on_frame_complete(&mut self, callback: FrameCallback<T>) -> CallbackId {
+        let id = self.callbacks.next_id();
+        self.callbacks.frame_complete.push((id, callback));
+        id
+    }
+
+    /*
+     * Callback runs after GPU finished drawing each visible line, e.g. to find raster effects(parallax, wobble)
+     * by looking at scroll registers changing between lines. Not run while LCD is off.
+     */
+    pub fn on_scanline(&mut self, callback: ScanlineCallback) -> CallbackId {
+        let id = self.callbacks.next_id();
+        self.callbacks.scanline.push((id, callback));
+        id
+    }
+
+    /*
+     * Callback runs when cart turns its rumble motor on or off(MBC5 rumble carts), after the instruction which
+     * did it. Reset stopping the motor counts too.
+     */
+    pub fn on_rumble(&mut self, callback: RumbleCallback) -> CallbackId {
+        let id = self.callbacks.next_id();
+        self.callbacks.rumble.push((id, callback));
+        id
+    }
+
+    /*
+     * Removes callback added with on_vblank, on_frame_complete, on_scanline or on_rumble. Returns false when there
+     * was no such callback.
+     */
+    pub fn remove_callback(&mut self, id: CallbackId) -> bool {
+        let count = |callbacks: &FrameCallbacks<T>| {
+            callbacks.vblank.len() + callbacks.frame_complete.len() + callbacks.scanline.len() + callbacks.rumble.len()
+        };
+        let before = count(&self.callbacks);
+        self.callbacks.vblank.retain(|(other, _)| *other != id);
+        self.callbacks.frame_complete.retain(|(other, _)| *other != id);
+        self.callbacks.scanline.retain(|(other, _)| *other != id);
+        self.callbacks.rumble.retain(|(other, _)| *other != id);
+        count(&self.callbacks) != before
+    }
+
+    /*
+     * Starts straight from the cart, with registers and VRAM left as given model's boot ROM would leave them,
+     * like after logo animation, without running boot ROM at all
+     */
+    pub fn skip_bootrom(&mut self, model: HardwareModel) {
+        self.set_model(model);
+        self.bootrom_skipped = true;
+        self.set_boot_state();
+    }
+
+    /* State boot ROM of current model leaves right before jumping to the cart */
+    fn set_boot_state(&mut self) {
+        self.state.mmu.disable_bootrom();
+        let regs = self.model.boot_registers(self.state.mmu.peek(HEADER_CHECKSUM as Addr));
+        self.cpu.A = regs.A;
+        self.cpu.set_F(regs.F);
+        self.cpu.BC.set(regs.BC);
+        self.cpu.DE.set(regs.DE);
+        self.cpu.HL.set(regs.HL);
+        self.cpu.SP = regs.SP;
+        self.cpu.PC.set(HEADER_START as Addr);
+        self.state.timer.set_counter(&mut self.state.mmu, regs.counter);
+        // Boot ROM turns sound on for its chime, at full master volume
+        self.state.mmu.write(ioregs::NR_52, 0x80);
+        self.state.mmu.write(ioregs::NR_50, 0x77);
+        self.state.mmu.write(ioregs::NR_51, 0xF3);
+        let logo = self.state.mmu.peek_range(LOGO_START as Addr, NINTENDO_LOGO.len());
+        self.model.draw_boot_logo(&logo, &mut self.state.mmu.vram);
+    }
+
+    /*
+     * Model used by reset when boot ROM is skipped, also decides which IO registers exist. skip_bootrom() sets
+     * it too.
+     */
+    pub fn set_model(&mut self, model: HardwareModel) {
+        self.model = model;
+        self.state.mmu.set_cgb(model.cgb());
+    }
+
+    pub fn model(&self) -> HardwareModel {
+        self.model
+    }
+
+    /*
+     * Hard reset is like cutting power: memory is cleared and boot ROM runs again, unless it was skipped before.
+     * Soft reset restarts the cart right away, with registers left by boot ROM of the model, and keeps memory.
+     * Either way cart RAM, quirks, GPU layer flags, sample rate, muted channels, VIN source, speed, pause, link
+     * cable, memory observers and call trace setting stay as they were.
+     */
+    pub fn reset(&mut self, hard: bool) {
+        let rumble = self.state.mmu.mapper.rumble();
+        self.state.reset(hard);
+        let call_trace = self.cpu.call_trace();
+        self.cpu = CPU::new();
+        self.cpu.set_call_trace(call_trace);
+        self.reset_cycles();
+        if !hard || self.bootrom_skipped {
+            self.set_boot_state();
+        }
+        self.check_rumble(rumble);
+    }
+
+    /*
+     * Swaps the cart, like turning GB off, changing cart and turning it on again. Settings are kept, just like
+     * with hard reset, symbols of the old cart are dropped. Model isn't detected again, use set_model() before
+     * loading if it should change.
+     * On error runtime is left untouched.
+     */
+    pub fn load_rom(&mut self, rom: Vec<Byte>) -> Result<(), String>
+    where
+        T: TryFrom<Vec<Byte>, Error = String>,
+    {
+        self.load_mapper(T::try_from(rom)?);
+        Ok(())
+    }
+
+    /* Same as load_rom, with mapper made by the caller, e.g. with other clock than try_from gives it */
+    pub fn load_mapper(&mut self, mapper: T) {
+        self.state.mmu.mapper = mapper;
+        self.state.symbols = None;
+        self.reset(true);
     }
 
     pub fn cpu_cycles(&self) -> u64 {
         self.cpu_cycles
     }
 
+    /*
+     * Steps until breakpoint is hit or given number of machine cycles passed, whichever comes first.
+     * Returns the hit, execution can go on with next call.
+     */
+    pub fn run_until_break(&mut self, cycles: u64) -> Option<Hit> {
+        let end = self.cpu_cycles + cycles;
+        while self.cpu_cycles < end && !self.stopped() {
+            self.step();
+            if let Some(hit) = self.state.debugger.as_mut().and_then(|debugger| debugger.take_hit()) {
+                return Some(hit);
+            }
+        }
+        None
+    }
+
+    /*
+     * Steps until at least given number of machine cycles passed, returns how many did. Instructions aren't split,
+     * so it can be a few more than asked for, next call can ask for that much less. Stopped runtime runs none.
+     */
+    pub fn run_cycles(&mut self, cycles: u64) -> u64 {
+        let start = self.cpu_cycles;
+        while self.cpu_cycles - start < cycles && !self.stopped() {
+            self.step();
+        }
+        self.cpu_cycles - start
+    }
+
+    // Sets speed multiplier. Values outside MIN_SPEED-MAX_SPEED are clamped.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = if speed.is_nan() { 1.0 } else { speed.clamp(MIN_SPEED, MAX_SPEED) };
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    // Number of CPU cycles that should be emulated per one host frame with current speed, none while stopped.
+    pub fn cycles_per_frame(&self) -> u64 {
+        if self.stopped() {
+            return 0;
+        }
+        (CPU_CYCLES_PER_FRAME as f32 * self.speed) as u64
+    }
+
+    /*
+     * Freezes emulation: step() does nothing and no cycles are due per frame, so no audio is produced either.
+     * State stays as it was and emulation goes on from the same point once unpaused. Reset, loading other ROM
+     * and loading save state don't unpause.
+     */
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /* Accesses CPU made since power on */
+    pub fn bus_stats(&self) -> &BusStats {
+        &self.state.mmu.stats
+    }
+
+    /* Accesses CPU made during last complete frame */
+    pub fn frame_bus_stats(&self) -> BusStats {
+        self.bus.last
+    }
+
+    /* Logs stats of last frame with "bus" target, at info level, every that many frames. None turns it off. */
+    pub fn set_bus_report(&mut self, frames: Option<u64>) {
+        self.bus.report = frames.filter(|frames| *frames > 0);
+    }
+
+    /* Cycles between each interrupt's request and its handler being entered, since power on or clear */
+    pub fn interrupt_latency(&self) -> &InterruptLatency {
+        &self.latency
+    }
+
+    pub fn clear_interrupt_latency(&mut self) {
+        self.latency.clear();
+    }
+
+    /* Starts(or stops, with None) hashing state after every frame, to find where two runs stop being the same */
+    pub fn set_audit(&mut self, audit: Option<Audit>) {
+        self.audit = audit;
+    }
+
+    pub fn audit(&self) -> Option<&Audit> {
+        self.audit.as_ref()
+    }
+
+    /* Starts(or stops, with None) logging PPU modes, bank switches, interrupts, DMA and channel triggers */
+    pub fn set_event_log(&mut self, events: Option<EventLog>) {
+        self.events = events;
+    }
+
+    pub fn event_log(&self) -> Option<&EventLog> {
+        self.events.as_ref()
+    }
+
+    /* Stops logging and hands the log out, e.g. to save it */
+    pub fn take_event_log(&mut self) -> Option<EventLog> {
+        self.events.take()
+    }
+
+    /*
+     * Emulates frame by frame as it's iterated, each item has picture, audio and buttons of one frame. E.g. screens
+     * of the first 600 frames: runtime.frames().take(600).map(|frame| frame.framebuffer)
+     */
+    pub fn frames(&mut self) -> Frames<'_, T> {
+        Frames::new(self)
+    }
+
+    /* Paused, or aborted by watchdog. Either way step() does nothing. */
+    pub fn stopped(&self) -> bool {
+        self.paused || self.state.watchdog.as_ref().is_some_and(|watchdog| watchdog.aborted())
+    }
+
     pub fn reset_cycles(&mut self) {
+        if let Some(events) = self.events.as_mut() {
+            events.restart_cycles(self.cpu_cycles);
+        }
         self.cpu_cycles = 0;
         self.gpu_cycles = 0;
         self.apu_cycles = 0;
         self.timer_cycles = 0;
         self.dma_cycles = 0;
+        self.serial_cycles = 0;
+        self.latency.restart();
     }
 
     fn catchup(mmu: &mut MMU<T>, dev: &mut impl Clocked<T>, cpu_clk: u64, dev_clk: u64) -> u64 {
@@ -87,18 +596,50 @@ impl<T: BankController> Runtime<T> {
     }
 }
 
+impl Runtime<mbc::RamOnly> {
+    /*
+     * For unit tests: whole cart space is RAM, boot ROM is off and code is at addr, where PC points. Registers
+     * are CPU's defaults, more code or data can be put anywhere with MMU::load.
+     */
+    pub fn with_code(addr: Addr, code: &[Byte]) -> Self {
+        let mut runtime = Runtime::new(mbc::RamOnly::new(Vec::new()));
+        runtime.state.mmu.disable_bootrom();
+        runtime.state.mmu.load(addr, code);
+        runtime.cpu.PC.set(addr);
+        runtime
+    }
+}
+
 /*
  * State is middleware between CPU<->Memory/IO. It offers CPU safe interface for writng/reading memory which helps achieving
  * certain constrains that couldn't be done inside single device.
  * For example: updatde coincidence flag when LYC changes or disallow VRAM/OAM access when GPU is rendering.
  */
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State<T: BankController> {
     pub gpu: GPU,
     pub apu: APU,
     pub timer: Timer,
     pub dma: DMA,
     pub joypad: Joypad,
+    pub serial: Serial,
+    #[cfg(feature = "sgb")]
+    pub sgb: SGB,
     pub mmu: MMU<T>,
+    /* Machine cycles since GPU's last step, set by Runtime before each instruction */
+    pub gpu_lag: u64,
+    /* Doctor mode, off when None. Not part of saved state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub doctor: Option<Doctor>,
+    /* Breakpoints, off when None. Not part of saved state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub debugger: Option<Debugger>,
+    /* Watchdog, off when None. Not part of saved state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub watchdog: Option<Watchdog>,
+    /* Labels of the running game, for traces and backtraces. Not part of saved state. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub symbols: Option<Symbols>,
 }
 
 impl<T: BankController> State<T> {
@@ -116,36 +657,176 @@ impl<T: BankController> State<T> {
             timer: timer,
             dma: dma,
             joypad: joypad,
+            serial: Serial::new(),
+            #[cfg(feature = "sgb")]
+            sgb: SGB::new(),
+            gpu_lag: 0,
+            doctor: None,
+            debugger: None,
+            watchdog: None,
+            symbols: None,
+        }
+    }
+
+    /* Closest label before addr, with ROM bank that's mapped now. None without symbols. */
+    pub fn symbolize(&self, addr: Addr) -> Option<String> {
+        self.symbols.as_ref()?.symbolize(self.mmu.mapper.current_rom_bank(), addr)
+    }
+
+    /* Call trace entry, with labels of routine and return address when there are symbols */
+    pub fn describe_frame(&self, frame: &CallFrame) -> String {
+        match (self.symbolize(frame.target), self.symbolize(frame.ret)) {
+            (Some(target), Some(ret)) => format!("{} ({}, returns to {})", frame, target, ret),
+            (Some(target), None) => format!("{} ({})", frame, target),
+            _ => frame.to_string(),
+        }
+    }
+
+    /* Devices back to power on state, keeping their settings. See Runtime::reset. */
+    pub fn reset(&mut self, clear_memory: bool) {
+        self.mmu.reset(clear_memory);
+        let old = &self.gpu;
+        let (quirks, layers) = (old.quirks, (old.show_background, old.show_window, old.show_sprites));
+        self.gpu = GPU::new(&mut self.mmu);
+        self.gpu.quirks = quirks;
+        (self.gpu.show_background, self.gpu.show_window, self.gpu.show_sprites) = layers;
+        let sample_rate = self.apu.sample_rate();
+        let mut old = std::mem::replace(&mut self.apu, APU::new(&mut self.mmu));
+        self.apu.quirks = old.quirks;
+        self.apu.set_sample_rate(sample_rate);
+        self.apu.keep_mixer(&mut old);
+        self.timer = Timer::new();
+        self.dma = DMA::new();
+        // Buttons are still held, only SGB player selection goes away
+        self.joypad.set_player(0);
+        let link = self.serial.disconnect();
+        self.serial = Serial::new();
+        if let Some(link) = link {
+            self.serial.connect(link);
+        }
+        #[cfg(feature = "sgb")]
+        {
+            let enabled = self.sgb.enabled();
+            self.sgb = SGB::new();
+            self.sgb.enable(enabled);
+        }
+        self.gpu_lag = 0;
+        if let Some(doctor) = self.doctor.as_mut() {
+            doctor.reset();
+        }
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.reset();
+        }
+    }
+
+    /* Called on 16-bit INC/DEC. Value pointing at OAM can trigger OAM corruption bug. */
+    pub fn oam_bug(&mut self, value: Word) {
+        if (OAM_ADDR..OAM_ADDR + 0x100).contains(&value) {
+            self.gpu.corrupt_oam(&mut self.mmu, self.gpu_lag);
         }
     }
 
     pub fn safe_write(&mut self, addr: Addr, value: Byte) {
+        self.mmu.stats.count(AccessKind::Write, addr);
+        self.mmu.observers.notify(AccessKind::Write, addr, value);
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.check_access(AccessKind::Write, addr, value);
+        }
+        if let Some(doctor) = self.doctor.as_mut() {
+            doctor.check_write(addr);
+        }
+        // Registers which can't be written as they are
+        match addr {
+            ioregs::LY => return self.gpu.write_ly(&mut self.mmu),
+            ioregs::STAT => return self.gpu.write_stat(&mut self.mmu, value),
+            // Only power bit can be written, turning sound off clears its registers and they ignore writes until it's on
+            ioregs::NR_52 => return self.apu.write_power(&mut self.mmu, value),
+            ioregs::NR_10..=ioregs::NR_51 if !self.apu.powered(&mut self.mmu) => return,
+            _ => {}
+        }
+        // Length counters and envelopes need NRx4 and NRx2 value from before the write
+        match addr {
+            ioregs::NR_11 | ioregs::NR_21 | ioregs::NR_31 | ioregs::NR_41 => self.apu.write_length(&mut self.mmu, addr, value),
+            ioregs::NR_14 | ioregs::NR_24 | ioregs::NR_34 | ioregs::NR_44 => self.apu.write_length(&mut self.mmu, addr, value),
+            ioregs::NR_12 | ioregs::NR_22 | ioregs::NR_42 => self.apu.write_envelope(&mut self.mmu, addr, value),
+            _ => {}
+        }
         self.mmu.write(addr, value);
+        self.illegal();
         match addr {
             // LYC=LY flag should be updated constantly
             LYC => {
                 self.gpu.update_ly(&mut self.mmu);
+                self.gpu.update_stat(&mut self.mmu);
             },
-            // Write to DIV resets it to 0
-            DIV => {
-                self.mmu.write(addr, 0);
-            },
+            // Write to DIV resets it to 0, other timer registers have their quirks
+            DIV => self.timer.write_div(&mut self.mmu),
+            ioregs::TIMA => self.timer.write_tima(&mut self.mmu),
+            ioregs::TMA => self.timer.write_tma(&mut self.mmu),
+            ioregs::TAC => self.timer.write_tac(&mut self.mmu),
             // Write to DMA register starts DMA transfer
             ioregs::DMA => self.dma.start(),
+            // Write to SC starts new transfer
+            ioregs::SC => self.serial.start(),
+            // SGB receives packets through joypad port
+            #[cfg(feature = "sgb")]
+            ioregs::P1 => self.sgb.write_p1(&mut self.mmu, &mut self.joypad, value),
             _ => {}
         }
     }
 
+    /* Raises interrupt the way device would, by setting its IF bit */
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        request_interrupt(&mut self.mmu, interrupt);
+    }
+
+    /* Interrupt's IE bit is set, so CPU takes it when requested and IME is on */
+    pub fn interrupt_enabled(&self, interrupt: Interrupt) -> bool {
+        self.mmu.peek(ioregs::IE) & interrupt.mask() != 0
+    }
+
+    /* Same byte order as MMU::write_word, with both bytes going through safe_write */
     pub fn write_word(&mut self, addr: Addr, word: Word) {
-        self.safe_write(addr, (word & 0xFF) as u8);
-        self.safe_write(addr + 1, (word >> 8) as u8);
+        let [low, high] = word.to_le_bytes();
+        self.safe_write(addr, low);
+        self.safe_write(addr.wrapping_add(1), high);
     }
 
     pub fn safe_read(&mut self, addr: Addr) -> Byte {
-        self.mmu.read(addr)
+        let value = match addr {
+            // Input lines depend on buttons held
+            ioregs::P1 => self.joypad.read_p1(&mut self.mmu),
+            _ => self.mmu.read(addr),
+        };
+        self.mmu.stats.count(AccessKind::Read, addr);
+        self.mmu.observers.notify(AccessKind::Read, addr, value);
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.check_access(AccessKind::Read, addr, value);
+        }
+        self.illegal();
+        value
+    }
+
+    /* Hands illegal access made by MMU over to doctor, and to debugger under Trap policy */
+    fn illegal(&mut self) {
+        let access = match self.mmu.take_illegal() {
+            Some(access) => access,
+            None => return,
+        };
+        if let Some(doctor) = self.doctor.as_mut() {
+            if let IllegalAccess::RomWrite { addr, value } | IllegalAccess::BootromWrite { addr, value } = access {
+                doctor.rom_write(&self.mmu, addr, value);
+            }
+        }
+        if self.mmu.policy == BusPolicy::Trap {
+            if let Some(debugger) = self.debugger.as_mut() {
+                debugger.trap(access);
+            }
+        }
     }
 
+    /* Same byte order as MMU::read_word, with both bytes going through safe_read */
     pub fn read_word(&mut self, addr: Addr) -> Word {
-        self.safe_read(addr) as u16 + ((self.safe_read(addr + 1) as u16) << 8)
+        Word::from_le_bytes([self.safe_read(addr), self.safe_read(addr.wrapping_add(1))])
     }
 }
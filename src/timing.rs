@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/* FPS is counted over frames drawn during that long, timing is logged that often */
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/*
+ * Timing of frames as SDL thread sees them: emulation of the last one, drawing it, waiting for audio queue to
+ * drain, frames which never got drawn and FPS. Shown in debug overlay, and logged with "timing" target at info
+ * level once per second(`--log timing=info`).
+ */
+pub struct FrameTiming {
+    pub emulation: Duration,
+    pub render: Duration,
+    pub slept: Duration,
+    /* Frames replaced by newer ones before being drawn, since start */
+    pub dropped: u64,
+    /* Frames received since last one was drawn */
+    waiting: u64,
+    /* When frames of last FPS_WINDOW were drawn */
+    drawn: VecDeque<Instant>,
+    logged: Instant,
+}
+
+impl FrameTiming {
+    pub fn new() -> Self {
+        Self {
+            emulation: Duration::ZERO,
+            render: Duration::ZERO,
+            slept: Duration::ZERO,
+            dropped: 0,
+            waiting: 0,
+            drawn: VecDeque::new(),
+            logged: Instant::now(),
+        }
+    }
+
+    /* Frame came from emulation thread, which took that long to emulate it */
+    pub fn received(&mut self, emulation: Duration) {
+        self.emulation = emulation;
+        self.waiting += 1;
+    }
+
+    /* Latest frame was drawn, ones received before it never will be */
+    pub fn drawn(&mut self, render: Duration) {
+        let now = Instant::now();
+        self.render = render;
+        self.dropped += self.waiting.saturating_sub(1);
+        self.waiting = 0;
+        self.drawn.push_back(now);
+        while self.drawn.front().is_some_and(|drawn| now - *drawn > FPS_WINDOW) {
+            self.drawn.pop_front();
+        }
+        if now - self.logged >= FPS_WINDOW {
+            log::info!(target: "timing", "{}", self);
+            self.logged = now;
+        }
+    }
+
+    pub fn slept(&mut self, slept: Duration) {
+        self.slept = slept;
+    }
+
+    /* Frames drawn during last second */
+    pub fn fps(&self) -> f32 {
+        self.drawn.len() as f32 / FPS_WINDOW.as_secs_f32()
+    }
+}
+
+impl fmt::Display for FrameTiming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        write!(
+            f,
+            "{:.1} FPS, emulation {:.1} ms, render {:.1} ms, slept {:.1} ms, {} dropped",
+            self.fps(),
+            ms(self.emulation),
+            ms(self.render),
+            ms(self.slept),
+            self.dropped
+        )
+    }
+}
@@ -0,0 +1,384 @@
+use std::io;
+use std::time::Duration;
+
+use gameboy::{
+    disassemble, end_frame, ioregs, AccessKind, Addr, BankController, Breakpoint, Debugger, Runtime,
+    CPU_CYCLES_PER_FRAME,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+const HELP: &str = "s [N] step, c continue(Esc pauses), f frame, b ADDR, bi INT, bw/br ADDR [==XX|!=XX|&MM==XX], \
+d ID, m ADDR, q quit. ADDR is hex, label or IO register, b also takes BANK:ADDR. Enter repeats last command.";
+/* Bytes per line of memory pane */
+const MEMORY_ROW: u16 = 16;
+
+/*
+ * Terminal debugger. Emulation runs in the same thread, without window or sound, so it works over SSH.
+ * Everything it shows or does goes through library APIs: Debugger, call trace, symbols, disassemble().
+ * Runtime is left as it was when user quit, so game's progress can be saved.
+ */
+pub fn run<T: BankController>(runtime: &mut Runtime<T>) -> io::Result<()> {
+    if runtime.state.debugger.is_none() {
+        runtime.state.debugger = Some(Debugger::new());
+    }
+    runtime.cpu.set_call_trace(true);
+    let mut terminal = ratatui::init();
+    let result = Session::new(runtime).event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Session<'a, T: BankController> {
+    runtime: &'a mut Runtime<T>,
+    input: String,
+    last_command: String,
+    /* Result of last command, or why execution stopped */
+    message: String,
+    /* First address shown in memory pane */
+    memory: Addr,
+    running: bool,
+    quit: bool,
+}
+
+impl<'a, T: BankController> Session<'a, T> {
+    fn new(runtime: &'a mut Runtime<T>) -> Self {
+        Self {
+            runtime,
+            input: String::new(),
+            last_command: String::new(),
+            message: String::from(HELP),
+            memory: 0xC000,
+            running: false,
+            quit: false,
+        }
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if self.running {
+                self.run_frame();
+                // Keys are only peeked at while running, emulation doesn't wait for them
+                while event::poll(Duration::ZERO)? {
+                    self.handle(event::read()?);
+                }
+            } else {
+                self.handle(event::read()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, event: Event) {
+        let key = match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => return,
+        };
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if self.running {
+            if key.code == KeyCode::Esc {
+                self.running = false;
+                self.message = format!("Paused at {}", self.location(self.runtime.cpu.PC.val()));
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc => self.input.clear(),
+            KeyCode::Enter => {
+                let line = match self.input.trim() {
+                    "" => self.last_command.clone(),
+                    line => line.to_string(),
+                };
+                self.input.clear();
+                self.message.clear();
+                if let Err(e) = self.execute(&line) {
+                    self.message = e;
+                }
+                self.last_command = line;
+            }
+            KeyCode::PageUp => self.memory = self.memory.wrapping_sub(MEMORY_ROW * 8),
+            KeyCode::PageDown => self.memory = self.memory.wrapping_add(MEMORY_ROW * 8),
+            KeyCode::F(5) => self.running = true,
+            KeyCode::F(11) => self.step(1),
+            _ => {}
+        }
+    }
+
+    fn execute(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+        let mut arg = || words.next().ok_or_else(|| format!("{}: argument missing", command));
+        match command {
+            "s" | "step" => {
+                let count = match words.next() {
+                    Some(count) => count.parse().map_err(|_| format!("invalid count {}", count))?,
+                    None => 1,
+                };
+                self.step(count);
+            }
+            "c" | "continue" => self.running = true,
+            "f" | "frame" => self.run_frame(),
+            "b" | "break" => {
+                let breakpoint = self.parse_code(arg()?)?;
+                self.add(breakpoint);
+            }
+            "bi" => {
+                let interrupt = arg()?.parse()?;
+                self.add(Breakpoint::Interrupt(interrupt));
+            }
+            "bw" | "br" => {
+                let addr = self.parse_addr(arg()?)?;
+                let condition = words.next().unwrap_or("").parse()?;
+                let kind = if command == "bw" { AccessKind::Write } else { AccessKind::Read };
+                self.add(Breakpoint::Io { addr, kind, condition });
+            }
+            "d" | "delete" => {
+                let id = arg()?;
+                let id = id.parse().map_err(|_| format!("invalid breakpoint {}", id))?;
+                if !self.debugger().remove(id) {
+                    return Err(format!("no breakpoint {}", id));
+                }
+            }
+            "m" | "mem" => self.memory = self.parse_addr(arg()?)?,
+            "q" | "quit" => self.quit = true,
+            "h" | "help" => self.message = String::from(HELP),
+            _ => return Err(format!("unknown command {}, h for help", command)),
+        }
+        Ok(())
+    }
+
+    fn debugger(&mut self) -> &mut Debugger {
+        self.runtime.state.debugger.get_or_insert_with(Debugger::new)
+    }
+
+    fn add(&mut self, breakpoint: Breakpoint) {
+        let id = self.debugger().add(breakpoint);
+        self.message = format!("Breakpoint {}: {}", id, breakpoint);
+    }
+
+    /* Label, IO register name or hex number, with optional 0x or $ */
+    fn parse_addr(&self, text: &str) -> Result<Addr, String> {
+        if let Some((_, addr)) = self.runtime.state.symbols.as_ref().and_then(|symbols| symbols.address(text)) {
+            return Ok(addr);
+        }
+        if let Some(addr) = ioregs::by_name(text) {
+            return Ok(addr);
+        }
+        let digits = text.trim_start_matches("0x").trim_start_matches('$');
+        Addr::from_str_radix(digits, 16).map_err(|_| format!("invalid address {}", text))
+    }
+
+    /*
+     * Code address for PC breakpoint: BANK:ADDR, label with its bank, or address in bank mapped now.
+     * Bank is dropped outside switchable ROM.
+     */
+    fn parse_code(&self, text: &str) -> Result<Breakpoint, String> {
+        if let Some((bank, addr)) = text.split_once(':') {
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| format!("invalid bank {}", bank))?;
+            return Ok(Breakpoint::code(bank, self.parse_addr(addr)?));
+        }
+        if let Some((bank, addr)) = self.runtime.state.symbols.as_ref().and_then(|symbols| symbols.address(text)) {
+            return Ok(Breakpoint::code(bank, addr));
+        }
+        let bank = self.runtime.state.mmu.mapper.current_rom_bank();
+        Ok(Breakpoint::code(bank, self.parse_addr(text)?))
+    }
+
+    fn step(&mut self, count: usize) {
+        for _ in 0..count {
+            self.runtime.step();
+            self.check_frame();
+            if self.check_hit() {
+                return;
+            }
+        }
+    }
+
+    /* Runs until frame ends or breakpoint is hit */
+    fn run_frame(&mut self) {
+        let left = CPU_CYCLES_PER_FRAME.saturating_sub(self.runtime.cpu_cycles());
+        let hit = self.runtime.run_until_break(left.max(1));
+        self.check_frame();
+        if let Some(hit) = hit {
+            self.stop(hit.to_string());
+        }
+    }
+
+    fn check_hit(&mut self) -> bool {
+        match self.debugger().take_hit() {
+            Some(hit) => {
+                self.stop(hit.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn stop(&mut self, reason: String) {
+        self.running = false;
+        self.message = format!("Stopped, {}, now at {}", reason, self.location(self.runtime.cpu.PC.val()));
+    }
+
+    fn check_frame(&mut self) {
+        if self.runtime.cpu_cycles() >= CPU_CYCLES_PER_FRAME {
+            end_frame(self.runtime);
+        }
+    }
+
+    /* Address with closest label, if there are symbols */
+    fn location(&self, addr: Addr) -> String {
+        match self.runtime.state.symbolize(addr) {
+            Some(label) => format!("{}({})", self.code_addr(addr), label),
+            None => self.code_addr(addr),
+        }
+    }
+
+    /* Address prefixed with ROM bank mapped now, for switchable ROM, where same address means different code */
+    fn code_addr(&self, addr: Addr) -> String {
+        match Breakpoint::code(self.runtime.state.mmu.mapper.current_rom_bank(), addr) {
+            Breakpoint::BankedPc { bank, .. } => format!("{:02X}:{:04X}", bank, addr),
+            _ => format!("0x{:04X}", addr),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, command, status] =
+            Layout::vertical([Constraint::Min(10), Constraint::Length(3), Constraint::Length(2)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Length(36), Constraint::Min(40)]).areas(main);
+        let [registers, breakpoints, backtrace] =
+            Layout::vertical([Constraint::Length(9), Constraint::Min(3), Constraint::Min(3)]).areas(left);
+        let [code, memory] = Layout::vertical([Constraint::Min(5), Constraint::Length(10)]).areas(right);
+
+        frame.render_widget(Paragraph::new(self.registers()).block(Block::bordered().title("Registers")), registers);
+        frame.render_widget(Paragraph::new(self.breakpoints()).block(Block::bordered().title("Breakpoints")), breakpoints);
+        frame.render_widget(Paragraph::new(self.backtrace()).block(Block::bordered().title("Backtrace")), backtrace);
+        let title = format!("Disassembly at {}", self.location(self.runtime.cpu.PC.val()));
+        frame.render_widget(Paragraph::new(self.disassembly(code)).block(Block::bordered().title(title)), code);
+        frame.render_widget(Paragraph::new(self.memory(memory)).block(Block::bordered().title("Memory")), memory);
+
+        let prompt = if self.running { String::from("running, Esc pauses") } else { format!("> {}", self.input) };
+        frame.render_widget(Paragraph::new(prompt).block(Block::bordered().title("Command")), command);
+        if !self.running {
+            frame.set_cursor_position((command.x + 3 + self.input.len() as u16, command.y + 1));
+        }
+        frame.render_widget(Paragraph::new(self.message.as_str()).wrap(ratatui::widgets::Wrap { trim: true }), status);
+    }
+
+    fn registers(&mut self) -> Vec<Line<'static>> {
+        let cpu = &self.runtime.cpu;
+        let flag = |set: bool, name: char| if set { name } else { '-' };
+        let f = (cpu.Z as u8) << 7 | (cpu.N as u8) << 6 | (cpu.H as u8) << 5 | (cpu.C as u8) << 4;
+        let mut lines = vec![
+            format!("AF {:02X}{:02X}   flags {}{}{}{}", cpu.A, f, flag(cpu.Z, 'Z'), flag(cpu.N, 'N'), flag(cpu.H, 'H'), flag(cpu.C, 'C')),
+            format!("BC {:04X}   DE {:04X}", cpu.BC.val(), cpu.DE.val()),
+            format!("HL {:04X}   SP {:04X}", cpu.HL.val(), cpu.SP),
+            format!("PC {:04X}   IME {} HALT {}", cpu.PC.val(), cpu.IME as u8, cpu.HALT as u8),
+        ];
+        let mapper = self.runtime.state.mmu.mapper.debug_state();
+        let cycles = self.runtime.cpu_cycles();
+        let mmu = &self.runtime.state.mmu;
+        lines.push(format!("LCDC {:02X} STAT {:02X} LY {:02X}", mmu.peek(ioregs::LCDC), mmu.peek(ioregs::STAT), mmu.peek(ioregs::LY)));
+        lines.push(format!("IE {:02X}   IF {:02X}", mmu.peek(ioregs::IE), mmu.peek(ioregs::IF)));
+        lines.push(mapper.to_string());
+        lines.push(format!("frame {}, cycle {}", self.runtime.state.gpu.frames(), cycles));
+        lines.into_iter().map(Line::from).collect()
+    }
+
+    fn breakpoints(&mut self) -> Vec<Line<'static>> {
+        let breakpoints = self.debugger().breakpoints().to_vec();
+        breakpoints
+            .iter()
+            .map(|(id, breakpoint)| match breakpoint {
+                Breakpoint::Pc(addr) => format!("{} PC {}", id, self.location(*addr)),
+                Breakpoint::BankedPc { bank, addr } => {
+                    let label = self.runtime.state.symbols.as_ref().and_then(|symbols| symbols.symbolize(*bank, *addr));
+                    match label {
+                        Some(label) => format!("{} {}({})", id, breakpoint, label),
+                        None => format!("{} {}", id, breakpoint),
+                    }
+                }
+                _ => format!("{} {}", id, breakpoint),
+            })
+            .map(Line::from)
+            .collect()
+    }
+
+    fn backtrace(&self) -> Vec<Line<'static>> {
+        self.runtime
+            .cpu
+            .backtrace()
+            .iter()
+            .map(|frame| Line::from(self.runtime.state.describe_frame(frame)))
+            .collect()
+    }
+
+    /* Instructions from PC on, with labels above ones they point at */
+    fn disassembly(&mut self, area: Rect) -> Vec<Line<'static>> {
+        let rows = area.height.saturating_sub(2) as usize;
+        let pc = self.runtime.cpu.PC.val();
+        let bank = self.runtime.state.mmu.mapper.current_rom_bank();
+        let breakpoints = self.debugger().breakpoints().to_vec();
+        let state = &self.runtime.state;
+        let mut lines = Vec::new();
+        let mut addr = pc;
+        while lines.len() < rows {
+            if let Some(label) = state.symbols.as_ref().and_then(|symbols| symbols.label(bank, addr)) {
+                lines.push(Line::styled(format!("{}:", label), Style::new().fg(Color::Yellow)));
+            }
+            let bytes = state.mmu.peek_range(addr, 3);
+            let (text, size) = disassemble(addr, &bytes).unwrap_or_else(|| (format!("DB ${:02X}", bytes[0]), 1));
+            let text = match state.symbols.as_ref() {
+                Some(symbols) => symbols.annotate(&text, bank),
+                None => text,
+            };
+            let marker = if breakpoints.iter().any(|(_, breakpoint)| breakpoint.stops_at(bank, addr)) { "●" } else { " " };
+            let cursor = if addr == pc { ">" } else { " " };
+            // Switchable ROM is shown as bank mapped now, bank 0 area and RAM have no bank
+            let at = match Breakpoint::code(bank, addr) {
+                Breakpoint::BankedPc { .. } => format!("{:02X}:{:04X}", bank, addr),
+                _ => format!("   {:04X}", addr),
+            };
+            let hex = bytes[..size as usize].iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+            let style = if addr == pc { Style::new().add_modifier(Modifier::REVERSED) } else { Style::new() };
+            lines.push(Line::from(vec![
+                Span::styled(marker, Style::new().fg(Color::Red)),
+                Span::styled(format!("{}{}  {:<8}  {}", cursor, at, hex, text), style),
+            ]));
+            addr = addr.wrapping_add(size as u16);
+        }
+        lines
+    }
+
+    fn memory(&mut self, area: Rect) -> Vec<Line<'static>> {
+        let rows = area.height.saturating_sub(2);
+        let first = self.memory;
+        let mmu = &self.runtime.state.mmu;
+        (0..rows)
+            .map(|row| {
+                let start = first.wrapping_add(row * MEMORY_ROW);
+                let bytes = mmu.peek_range(start, MEMORY_ROW as usize);
+                let hex = bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<String>>().join(" ");
+                let ascii = bytes
+                    .iter()
+                    .map(|b| if b.is_ascii_graphic() { *b as char } else { '.' })
+                    .collect::<String>();
+                Line::from(format!("{:04X}  {}  {}", start, hex, ascii))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::super::cpu::CPU;
+use super::super::mem::{BankController, IO_REGS_ADDR};
+use super::super::state::State;
+
+/* FNV-1a, so hashes are the same on every platform and build, unlike std's Hasher */
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/* Hash of CPU registers, WRAM, VRAM and IO registers. Cart RAM, OAM and device internals aren't in it. */
+pub fn state_hash<T: BankController>(cpu: &CPU, state: &State<T>) -> u64 {
+    let mut regs = vec![cpu.A, cpu.F(), cpu.IME as u8, cpu.HALT as u8, cpu.STOP as u8];
+    for word in [cpu.BC.val(), cpu.DE.val(), cpu.HL.val(), cpu.SP, cpu.PC.val()] {
+        regs.extend_from_slice(&word.to_le_bytes());
+    }
+    let mmu = &state.mmu;
+    let io = (0..0x100).map(|i| mmu.ioregs.get(IO_REGS_ADDR + i)).collect::<Vec<_>>();
+    [&regs[..], &mmu.ram, &mmu.vram, &io].iter().fold(FNV_OFFSET, |hash, bytes| fnv(hash, bytes))
+}
+
+/*
+ * Determinism audit, kept by Runtime(see Runtime::set_audit). After each frame it hashes emulated state and
+ * chains it with hashes of all frames before, so two runs(or two netplay peers) fed the same inputs have the
+ * same hash as long as they stay in sync, and differ from the first frame they don't. Log is text, one
+ * "<frame> <hash>" line per frame, compared with first_divergence.
+ */
+pub struct Audit {
+    hashes: Vec<u64>,
+    log: Option<BufWriter<File>>,
+}
+
+impl Audit {
+    pub fn new() -> Self {
+        Self { hashes: Vec::new(), log: None }
+    }
+
+    /* Audit which also writes each hash to the file as soon as frame is done */
+    pub fn logging(path: &Path) -> io::Result<Self> {
+        Ok(Self { hashes: Vec::new(), log: Some(BufWriter::new(File::create(path)?)) })
+    }
+
+    pub fn record<T: BankController>(&mut self, cpu: &CPU, state: &State<T>) {
+        let previous = self.last().unwrap_or(FNV_OFFSET);
+        let hash = fnv(previous, &state_hash(cpu, state).to_le_bytes());
+        if let Some(out) = self.log.as_mut() {
+            // Broken log shouldn't stop the game, the rest of it is just lost
+            if let Err(e) = writeln!(out, "{} {:016X}", self.hashes.len(), hash) {
+                log::warn!(target: "audit", "log not written: {}", e);
+                self.log = None;
+            }
+        }
+        self.hashes.push(hash);
+    }
+
+    /* Rolling hash after each frame recorded so far */
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    pub fn last(&self) -> Option<u64> {
+        self.hashes.last().copied()
+    }
+
+    pub fn to_text(&self) -> String {
+        self.hashes.iter().enumerate().map(|(frame, hash)| format!("{} {:016X}\n", frame, hash)).collect()
+    }
+}
+
+impl Default for Audit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* Hashes out of audit log, frames have to be numbered from 0 with none left out */
+pub fn parse_audit(text: &str) -> Result<Vec<u64>, String> {
+    let mut hashes = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let invalid = || format!("line {}: expected \"<frame> <hash>\", got \"{}\"", i + 1, line);
+        let (frame, hash) = line.trim().split_once(' ').ok_or_else(invalid)?;
+        if frame.parse::<usize>().map_err(|_| invalid())? != hashes.len() {
+            return Err(format!("line {}: frame {} out of order", i + 1, frame));
+        }
+        hashes.push(u64::from_str_radix(hash.trim(), 16).map_err(|_| invalid())?);
+    }
+    Ok(hashes)
+}
+
+pub fn load_audit(path: &Path) -> Result<Vec<u64>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_audit(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/*
+ * First frame two runs differ at. Hashes are rolling, so they differ on every frame after it too. Frames only
+ * one of them got to don't count, so run cut short is still in sync.
+ */
+pub fn first_divergence(a: &[u64], b: &[u64]) -> Option<u64> {
+    a.iter().zip(b).position(|(a, b)| a != b).map(|frame| frame as u64)
+}
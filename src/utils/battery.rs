@@ -0,0 +1,57 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::super::mem::{BankController, RAM_BANK_SIZE};
+
+/* Smallest cart RAM map_battery maps to file, smaller saves are cheap to copy */
+pub const MMAP_MIN_RAM: usize = 4 * RAM_BANK_SIZE;
+
+/* Battery saves are kept in save directory, named after the ROM, e.g. tetris.sav */
+pub fn battery_path(save_dir: &Path, rom: &Path) -> PathBuf {
+    let name = rom.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    save_dir.join(format!("{}.sav", name))
+}
+
+/*
+ * Fills cart RAM with battery save. False when there's none yet, like on the first run. Save of other size than
+ * cart RAM is refused, it belongs to other game or other emulator's format.
+ */
+pub fn load_battery<T: BankController>(mapper: &mut T, path: &Path) -> Result<bool, String> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let ram = mapper.ram();
+    if data.len() != ram.len() {
+        return Err(format!("{}: save has {} bytes, cart RAM {}", path.display(), data.len(), ram.len()));
+    }
+    ram.copy_from_slice(&data);
+    Ok(true)
+}
+
+/*
+ * Like load_battery, but cart RAM of at least MMAP_MIN_RAM is mapped to the save file(see CartRam::map_file), so
+ * game's writes persist as they happen. Smaller RAM, or mapper which can't map it, is loaded the usual way.
+ */
+pub fn map_battery<T: BankController>(mapper: &mut T, path: &Path) -> Result<bool, String> {
+    match mapper.cart_ram() {
+        Some(ram) if ram.len() >= MMAP_MIN_RAM => ram.map_file(path),
+        _ => load_battery(mapper, path),
+    }
+}
+
+/* Writes whole cart RAM, RAM mapped to the file is just flushed. False, with nothing written, when cart has no RAM. */
+pub fn save_battery<T: BankController>(mapper: &mut T, path: &Path) -> Result<bool, String> {
+    if let Some(ram) = mapper.cart_ram().filter(|ram| ram.path() == Some(path)) {
+        ram.flush()?;
+        return Ok(true);
+    }
+    let ram = mapper.ram();
+    if ram.is_empty() {
+        return Ok(false);
+    }
+    fs::write(path, &*ram).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(true)
+}
@@ -0,0 +1,48 @@
+use super::super::gpu::Color;
+
+/* Highest weight of previous frame, with more of it the picture would hardly change at all */
+pub const MAX_BLEND: f32 = 0.9;
+
+/*
+ * Mixes each frame with the one before it, like slow DMG LCD does. Games flickering sprites at 30 Hz count on it,
+ * without blending such sprites blink. Weight is how much of the previous frame goes into the result, 0 turns it off.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameBlend {
+    weight: f32,
+    previous: Vec<Color>,
+}
+
+impl FrameBlend {
+    /* Weight outside 0-MAX_BLEND is clamped */
+    pub fn new(weight: f32) -> Self {
+        Self { weight: if weight.is_nan() { 0.0 } else { weight.clamp(0.0, MAX_BLEND) }, previous: Vec::new() }
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /*
+     * Blends pixels with previous frame given here, in place. Previous frame is the original one, not blended,
+     * so fast moving sprites don't leave trails. Frame of other size(e.g. SGB border turned on) isn't blended.
+     */
+    pub fn apply(&mut self, pixels: &mut [Color]) {
+        if self.weight == 0.0 {
+            return;
+        }
+        let current = pixels.to_vec();
+        if self.previous.len() == pixels.len() {
+            let mix = |now: u8, before: u8| (now as f32 * (1.0 - self.weight) + before as f32 * self.weight).round() as u8;
+            for (pixel, before) in pixels.iter_mut().zip(self.previous.iter()) {
+                *pixel = (mix(pixel.0, before.0), mix(pixel.1, before.1), mix(pixel.2, before.2));
+            }
+        }
+        self.previous = current;
+    }
+
+    /* Next frame is shown as it is, e.g. after loading other ROM */
+    pub fn clear(&mut self) {
+        self.previous.clear();
+    }
+}
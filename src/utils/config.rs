@@ -0,0 +1,452 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::de::{Deserializer, Error};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use super::super::apu::{APUQuirks, PLAYBACK_FREQUENCY};
+use super::super::gpu::{Color, GPUQuirks, PaletteLayer, BLACK, DARK_GRAY, LIGHT_GRAY, WHITE};
+use super::super::joypad::DEFAULT_TURBO_FRAMES;
+use super::super::mem::BusPolicy;
+use super::model::HardwareModel;
+use super::pacer::Pacing;
+use super::palette::Palette;
+
+/* Config file looked up in working directory when no other is given */
+pub const DEFAULT_CONFIG_FILE: &str = "gameboy.toml";
+
+/*
+ * Everything configurable about the emulator. Can be read from TOML file, every missing field is
+ * left with its default value. Example:
+ *
+ * scale = 4
+ * sample_rate = 48000
+ * palette = ["#E0F8D0", "#88C070", "#346856", "#081820"]
+ * bootrom = "dmg_boot.bin"
+ * fast_boot = true
+ * model = "MGB"
+ * turbo_frames = 3
+ * pause_unfocused = false
+ * autosave = true
+ * mmap_saves = true
+ * integer_scale = true
+ * frame_blend = 0.5
+ * dot_matrix = true
+ * pacing = "exact"
+ *
+ * [keys]
+ * a = ["Z", "J"]
+ *
+ * [player2_keys]
+ * a = ["N"]
+ *
+ * [strict]
+ * header = true
+ * bus = "trap"
+ *
+ * [quirks]
+ * stat_write_bug = true
+ * zombie_mode = true
+ *
+ * [layer_palettes]
+ * obj0 = ["#FFFFFF", "#FF8484", "#943A3A", "#000000"]
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(default, deny_unknown_fields))]
+pub struct EmuConfig {
+    /* RGB values for 4 DMG shades, from lightest to darkest */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_palette"))]
+    pub palette: [Color; 4],
+    /* Own colors of background and sprite palettes, in place of the 4 shades */
+    pub layer_palettes: LayerPalettes,
+    /* Window size multiplier. Window can be resized later, picture keeps its aspect ratio. */
+    pub scale: u32,
+    /* Start in fullscreen */
+    pub fullscreen: bool,
+    /* Picture is scaled only by whole multiples, so all pixels have the same size */
+    pub integer_scale: bool,
+    /* How much of previous frame is mixed into each one, like slow DMG LCD does. 0 turns it off. */
+    pub frame_blend: f32,
+    /* Each GB pixel is drawn as LCD dot with gaps around it, see DotMatrix */
+    pub dot_matrix: bool,
+    /* What times the frames: audio, exact(59.7275 Hz by host clock), vsync or uncapped, see Pacing */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_pacing"))]
+    pub pacing: Pacing,
+    /* Audio playback rate in Hz */
+    pub sample_rate: u32,
+    /* Boot ROM to execute before the cart. None skips straight to 0x100. */
+    pub bootrom: Option<PathBuf>,
+    /* Boot ROM isn't executed even when given, GB starts from the state it would leave, see Runtime::skip_bootrom */
+    pub fast_boot: bool,
+    /* Emulated hardware. None picks one based on cart header. */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_model"))]
+    pub model: Option<HardwareModel>,
+    /* Frames turbo buttons stay pressed, then as many released */
+    pub turbo_frames: u8,
+    /* Emulation pauses while window isn't focused */
+    pub pause_unfocused: bool,
+    /* Directory for battery saves and save states */
+    pub save_dir: PathBuf,
+    /* State is saved when game is closed and restored next time it's opened */
+    pub autosave: bool,
+    /* Cart RAM of 32KB and more is mapped to battery save file, see map_battery */
+    pub mmap_saves: bool,
+    pub strict: Strictness,
+    pub quirks: Quirks,
+    pub keys: KeyBindings,
+    /* Second GB in local link mode. Only buttons are used. */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_player2_keys"))]
+    pub player2_keys: KeyBindings,
+}
+
+/*
+ * Accuracy checks which by default only warn. When enabled they stop the emulation instead.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(default, deny_unknown_fields))]
+pub struct Strictness {
+    /* Refuse to run carts with invalid header */
+    pub header: bool,
+    /* What illegal memory accesses(writes to ROM, accesses to missing cart RAM) do: ignore, log, trap or panic */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_bus_policy"))]
+    pub bus: BusPolicy,
+}
+
+/*
+ * Hardware bugs and differences between models. Each GPU one left unset comes from emulated model, APU ones are off
+ * unless set.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(default, deny_unknown_fields))]
+pub struct Quirks {
+    /* Writing LY resets it, instead of being ignored */
+    pub ly_write_resets: Option<bool>,
+    /* DMG STAT write bug, some games rely on it */
+    pub stat_write_bug: Option<bool>,
+    /* DMG OAM corruption by 16-bit INC/DEC, checked by hardware test ROMs */
+    pub oam_bug: Option<bool>,
+    /* NRx2 writes change volume of playing channel, games setting volume mid-note need it. Off on every model. */
+    pub zombie_mode: bool,
+}
+
+impl Quirks {
+    pub fn gpu(&self, model: HardwareModel) -> GPUQuirks {
+        let defaults = model.quirks();
+        GPUQuirks {
+            ly_write_resets: self.ly_write_resets.unwrap_or(defaults.ly_write_resets),
+            stat_write_bug: self.stat_write_bug.unwrap_or(defaults.stat_write_bug),
+            oam_bug: self.oam_bug.unwrap_or(defaults.oam_bug),
+        }
+    }
+
+    pub fn apu(&self) -> APUQuirks {
+        APUQuirks { zombie_mode: self.zombie_mode }
+    }
+}
+
+/*
+ * Colors for pixels that went through given palette register, like GBC compatibility palettes of DMG games. Layers
+ * without them use the palette of 4 shades.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(default, deny_unknown_fields))]
+pub struct LayerPalettes {
+    /* BGP, background and window */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_layer_palette"))]
+    pub bg: Option<[Color; 4]>,
+    /* OBP0 and OBP1, sprites */
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_layer_palette"))]
+    pub obj0: Option<[Color; 4]>,
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_layer_palette"))]
+    pub obj1: Option<[Color; 4]>,
+}
+
+/*
+ * Keyboard keys bound to each action, by SDL scancode names. Any of listed keys triggers the action.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(default, deny_unknown_fields))]
+pub struct KeyBindings {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub a: Vec<String>,
+    pub b: Vec<String>,
+    pub select: Vec<String>,
+    pub start: Vec<String>,
+    /* A and B pressed repeatedly while held */
+    pub turbo_a: Vec<String>,
+    pub turbo_b: Vec<String>,
+    /* Unlocked speed while held */
+    pub fast_forward: Vec<String>,
+    pub slower: Vec<String>,
+    pub faster: Vec<String>,
+    /* Soft reset, with shift held hard reset */
+    pub reset: Vec<String>,
+    pub pause: Vec<String>,
+    pub fullscreen: Vec<String>,
+    /* Debug overlay and layers shown by GPU */
+    pub overlay: Vec<String>,
+    pub toggle_background: Vec<String>,
+    pub toggle_window: Vec<String>,
+    pub toggle_sprites: Vec<String>,
+    /* Picture tinted by what drew each pixel: background, window, sprite or sprite behind background */
+    pub toggle_sources: Vec<String>,
+    /* Sound channels 1-4 muted, with shift held soloed */
+    pub toggle_channel1: Vec<String>,
+    pub toggle_channel2: Vec<String>,
+    pub toggle_channel3: Vec<String>,
+    pub toggle_channel4: Vec<String>,
+}
+
+impl Default for EmuConfig {
+    fn default() -> Self {
+        Self {
+            palette: [WHITE, LIGHT_GRAY, DARK_GRAY, BLACK],
+            layer_palettes: LayerPalettes::default(),
+            scale: 3,
+            fullscreen: false,
+            integer_scale: false,
+            frame_blend: 0.0,
+            dot_matrix: false,
+            pacing: Pacing::default(),
+            sample_rate: PLAYBACK_FREQUENCY,
+            bootrom: None,
+            fast_boot: false,
+            model: None,
+            turbo_frames: DEFAULT_TURBO_FRAMES,
+            pause_unfocused: true,
+            save_dir: PathBuf::from("."),
+            autosave: false,
+            mmap_saves: false,
+            strict: Strictness::default(),
+            quirks: Quirks::default(),
+            keys: KeyBindings::default(),
+            player2_keys: KeyBindings::player2(),
+        }
+    }
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| String::from(*name)).collect()
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: keys(&["W", "Up"]),
+            down: keys(&["S", "Down"]),
+            left: keys(&["A", "Left"]),
+            right: keys(&["D", "Right"]),
+            a: keys(&["Z"]),
+            b: keys(&["X"]),
+            select: keys(&["Space"]),
+            start: keys(&["Return"]),
+            turbo_a: keys(&["C"]),
+            turbo_b: keys(&["V"]),
+            fast_forward: keys(&["Tab"]),
+            slower: keys(&["-"]),
+            faster: keys(&["="]),
+            reset: keys(&["F5"]),
+            pause: keys(&["P"]),
+            fullscreen: keys(&["F11"]),
+            overlay: keys(&["F12"]),
+            toggle_background: keys(&["1"]),
+            toggle_window: keys(&["2"]),
+            toggle_sprites: keys(&["3"]),
+            toggle_sources: keys(&["4"]),
+            toggle_channel1: keys(&["5"]),
+            toggle_channel2: keys(&["6"]),
+            toggle_channel3: keys(&["7"]),
+            toggle_channel4: keys(&["8"]),
+        }
+    }
+}
+
+/* Names of actions, as used in config file */
+#[cfg(feature = "serde")]
+const ACTIONS: &[&str] = &[
+    "up", "down", "left", "right", "a", "b", "select", "start", "turbo_a", "turbo_b", "fast_forward", "slower",
+    "faster", "reset", "pause", "fullscreen", "overlay", "toggle_background", "toggle_window", "toggle_sprites",
+    "toggle_sources", "toggle_channel1", "toggle_channel2", "toggle_channel3", "toggle_channel4",
+];
+
+impl KeyBindings {
+    pub fn action_mut(&mut self, name: &str) -> Option<&mut Vec<String>> {
+        match name {
+            "up" => Some(&mut self.up),
+            "down" => Some(&mut self.down),
+            "left" => Some(&mut self.left),
+            "right" => Some(&mut self.right),
+            "a" => Some(&mut self.a),
+            "b" => Some(&mut self.b),
+            "select" => Some(&mut self.select),
+            "start" => Some(&mut self.start),
+            "turbo_a" => Some(&mut self.turbo_a),
+            "turbo_b" => Some(&mut self.turbo_b),
+            "fast_forward" => Some(&mut self.fast_forward),
+            "slower" => Some(&mut self.slower),
+            "faster" => Some(&mut self.faster),
+            "reset" => Some(&mut self.reset),
+            "pause" => Some(&mut self.pause),
+            "fullscreen" => Some(&mut self.fullscreen),
+            "overlay" => Some(&mut self.overlay),
+            "toggle_background" => Some(&mut self.toggle_background),
+            "toggle_window" => Some(&mut self.toggle_window),
+            "toggle_sprites" => Some(&mut self.toggle_sprites),
+            "toggle_sources" => Some(&mut self.toggle_sources),
+            "toggle_channel1" => Some(&mut self.toggle_channel1),
+            "toggle_channel2" => Some(&mut self.toggle_channel2),
+            "toggle_channel3" => Some(&mut self.toggle_channel3),
+            "toggle_channel4" => Some(&mut self.toggle_channel4),
+            _ => None,
+        }
+    }
+
+    /* Defaults for second player, away from keys used by the first one */
+    pub fn player2() -> Self {
+        Self {
+            up: keys(&["I"]),
+            down: keys(&["K"]),
+            left: keys(&["J"]),
+            right: keys(&["L"]),
+            a: keys(&["N"]),
+            b: keys(&["M"]),
+            select: keys(&["Right Shift"]),
+            start: keys(&["Backspace"]),
+            turbo_a: keys(&[","]),
+            turbo_b: keys(&["."]),
+            fast_forward: Vec::new(),
+            slower: Vec::new(),
+            faster: Vec::new(),
+            reset: Vec::new(),
+            pause: Vec::new(),
+            fullscreen: Vec::new(),
+            overlay: Vec::new(),
+            toggle_background: Vec::new(),
+            toggle_window: Vec::new(),
+            toggle_sprites: Vec::new(),
+            toggle_sources: Vec::new(),
+            toggle_channel1: Vec::new(),
+            toggle_channel2: Vec::new(),
+            toggle_channel3: Vec::new(),
+            toggle_channel4: Vec::new(),
+        }
+    }
+}
+
+impl EmuConfig {
+    #[cfg(feature = "serde")]
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /* Config file is read with serde, builds without it refuse one instead of running with other settings */
+    #[cfg(not(feature = "serde"))]
+    pub fn from_toml(_text: &str) -> Result<Self, String> {
+        Err(String::from("config files need serde feature"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        EmuConfig::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /* GPU renders with default DMG shades, this maps them to configured palette */
+    pub fn colorize(&self, color: Color) -> Color {
+        match color {
+            WHITE => self.palette[0],
+            LIGHT_GRAY => self.palette[1],
+            DARK_GRAY => self.palette[2],
+            BLACK => self.palette[3],
+            other => other,
+        }
+    }
+
+    /* Palette of 4 shades with layer colors put in, what frames are shown with */
+    pub fn colors(&self) -> Palette {
+        let mut palette = Palette::new(self.palette);
+        palette.set_layer(PaletteLayer::Background, self.layer_palettes.bg);
+        palette.set_layer(PaletteLayer::Object0, self.layer_palettes.obj0);
+        palette.set_layer(PaletteLayer::Object1, self.layer_palettes.obj1);
+        palette
+    }
+
+    /* Shows frames with given palette, each layer with colors it has */
+    pub fn set_colors(&mut self, palette: &Palette) {
+        self.palette = palette.shades();
+        self.layer_palettes = LayerPalettes {
+            bg: Some(palette.layer(PaletteLayer::Background)),
+            obj0: Some(palette.layer(PaletteLayer::Object0)),
+            obj1: Some(palette.layer(PaletteLayer::Object1)),
+        };
+    }
+}
+
+/* Parses color written as "#RRGGBB" (hash is optional) */
+pub fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+/* Parses palette written as 4 comma separated colors */
+pub fn parse_palette(text: &str) -> Option<[Color; 4]> {
+    let colors = text
+        .split(',')
+        .map(|color| parse_color(color.trim()))
+        .collect::<Option<Vec<Color>>>()?;
+    if colors.len() != 4 {
+        return None;
+    }
+    Some([colors[0], colors[1], colors[2], colors[3]])
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[Color; 4], D::Error> {
+    let colors: Vec<String> = Deserialize::deserialize(deserializer)?;
+    parse_palette(&colors.join(","))
+        .ok_or_else(|| D::Error::custom("palette must be 4 colors in #RRGGBB format"))
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_layer_palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<[Color; 4]>, D::Error> {
+    deserialize_palette(deserializer).map(Some)
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_model<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<HardwareModel>, D::Error> {
+    let name: String = Deserialize::deserialize(deserializer)?;
+    name.parse().map(Some).map_err(D::Error::custom)
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_bus_policy<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BusPolicy, D::Error> {
+    let name: String = Deserialize::deserialize(deserializer)?;
+    name.parse().map_err(D::Error::custom)
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_pacing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pacing, D::Error> {
+    let name: String = Deserialize::deserialize(deserializer)?;
+    name.parse().map_err(D::Error::custom)
+}
+
+/* Actions missing in [player2_keys] keep player 2 defaults, not the ones of player 1 */
+#[cfg(feature = "serde")]
+fn deserialize_player2_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyBindings, D::Error> {
+    let given: BTreeMap<String, Vec<String>> = Deserialize::deserialize(deserializer)?;
+    let mut keys = KeyBindings::player2();
+    for (action, names) in given {
+        *keys.action_mut(&action).ok_or_else(|| D::Error::unknown_field(&action, ACTIONS))? = names;
+    }
+    Ok(keys)
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::super::mem::{AccessKind, Addr, Byte, IllegalAccess, ROM_SWITCHABLE_ADDR, VRAM_ADDR};
+
+/* Interrupt sources, in order of priority. Each has its bit in IF and IE, VBlank is bit 0. */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] =
+        [Interrupt::VBlank, Interrupt::Stat, Interrupt::Timer, Interrupt::Serial, Interrupt::Joypad];
+
+    /* Address of the handler */
+    pub fn vector(self) -> Addr {
+        0x40 + 8 * self as Addr
+    }
+
+    /* Bit in IF and IE */
+    pub fn bit(self) -> u8 {
+        self as u8
+    }
+
+    pub fn mask(self) -> Byte {
+        1 << self.bit()
+    }
+
+    pub fn from_vector(addr: Addr) -> Option<Self> {
+        match addr {
+            0x40 => Some(Interrupt::VBlank),
+            0x48 => Some(Interrupt::Stat),
+            0x50 => Some(Interrupt::Timer),
+            0x58 => Some(Interrupt::Serial),
+            0x60 => Some(Interrupt::Joypad),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Interrupt {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "vblank" => Ok(Interrupt::VBlank),
+            "stat" | "lcd" => Ok(Interrupt::Stat),
+            "timer" => Ok(Interrupt::Timer),
+            "serial" => Ok(Interrupt::Serial),
+            "joypad" => Ok(Interrupt::Joypad),
+            _ => Err(format!("unknown interrupt {}, expected one of: vblank, stat, timer, serial, joypad", text)),
+        }
+    }
+}
+
+/* Test on value read or written */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Any,
+    Equal(Byte),
+    NotEqual(Byte),
+    /* Bits in mask have to be as in value, e.g. LCD being turned off is { mask: 0x80, value: 0x00 } */
+    Masked { mask: Byte, value: Byte },
+}
+
+impl Condition {
+    pub fn matches(self, value: Byte) -> bool {
+        match self {
+            Condition::Any => true,
+            Condition::Equal(expected) => value == expected,
+            Condition::NotEqual(expected) => value != expected,
+            Condition::Masked { mask, value: expected } => value & mask == expected & mask,
+        }
+    }
+}
+
+/* Parses "==XX", "!=XX" or "&MM==XX", values in hex. Empty text is any value. */
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let byte = |text: &str| {
+            let digits = text.trim_start_matches("0x").trim_start_matches('$');
+            Byte::from_str_radix(digits, 16).map_err(|_| format!("invalid value {}", text))
+        };
+        let text = text.trim();
+        if text.is_empty() {
+            Ok(Condition::Any)
+        } else if let Some(value) = text.strip_prefix("==") {
+            Ok(Condition::Equal(byte(value)?))
+        } else if let Some(value) = text.strip_prefix("!=") {
+            Ok(Condition::NotEqual(byte(value)?))
+        } else if let Some((mask, value)) = text.strip_prefix('&').and_then(|rest| rest.split_once("==")) {
+            Ok(Condition::Masked { mask: byte(mask)?, value: byte(value)? })
+        } else {
+            Err(format!("invalid condition {}, expected ==XX, !=XX or &MM==XX", text))
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Any => write!(f, "any value"),
+            Condition::Equal(value) => write!(f, "value == 0x{:02X}", value),
+            Condition::NotEqual(value) => write!(f, "value != 0x{:02X}", value),
+            Condition::Masked { mask, value } => write!(f, "value & 0x{:02X} == 0x{:02X}", mask, value & mask),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breakpoint {
+    /* Instruction at address is about to be executed */
+    Pc(Addr),
+    /* Instruction at address in switchable ROM is about to be executed, with given ROM bank mapped */
+    BankedPc { bank: usize, addr: Addr },
+    /* Handler of interrupt was entered */
+    Interrupt(Interrupt),
+    /* Instruction read or wrote IO register(or any other address) and value meets condition */
+    Io { addr: Addr, kind: AccessKind, condition: Condition },
+    /* Illegal memory access under BusPolicy::Trap. Never added, debugger makes hits of it on its own. */
+    Trap(IllegalAccess),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(addr) => write!(f, "PC 0x{:04X}", addr),
+            Breakpoint::BankedPc { bank, addr } => write!(f, "PC {:02X}:{:04X}", bank, addr),
+            Breakpoint::Interrupt(interrupt) => write!(f, "{:?} interrupt", interrupt),
+            Breakpoint::Io { addr, kind, condition } => {
+                let kind = match kind {
+                    AccessKind::Read => "read of",
+                    AccessKind::Write => "write to",
+                };
+                write!(f, "{} 0x{:04X}, {}", kind, addr, condition)
+            }
+            Breakpoint::Trap(access) => write!(f, "{}", access),
+        }
+    }
+}
+
+impl Breakpoint {
+    /* Breakpoint on code at bank and address, bank only matters in switchable ROM like in symbol files */
+    pub fn code(bank: usize, addr: Addr) -> Self {
+        if (ROM_SWITCHABLE_ADDR..VRAM_ADDR).contains(&addr) {
+            Breakpoint::BankedPc { bank, addr }
+        } else {
+            Breakpoint::Pc(addr)
+        }
+    }
+
+    /* Whether it's PC breakpoint on instruction at pc, with given ROM bank mapped */
+    pub fn stops_at(&self, bank: usize, pc: Addr) -> bool {
+        match self {
+            Breakpoint::Pc(addr) => *addr == pc,
+            Breakpoint::BankedPc { bank: expected, addr } => *addr == pc && *expected == bank,
+            _ => false,
+        }
+    }
+}
+
+pub type BreakpointId = usize;
+
+/* Id of hits made by traps, not by any added breakpoint */
+pub const TRAP_ID: BreakpointId = BreakpointId::MAX;
+
+/* Breakpoint which stopped execution */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub id: BreakpointId,
+    pub breakpoint: Breakpoint,
+    /* Instruction about to run for PC and interrupt breakpoints, one which made the access for IO ones */
+    pub pc: Addr,
+    pub cycle: u64,
+    /* Value read or written, for IO breakpoints */
+    pub value: Option<Byte>,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Breakpoint::Trap(access) = self.breakpoint {
+            return write!(f, "trapped {} at PC=0x{:04X}, cycle {}", access, self.pc, self.cycle);
+        }
+        write!(f, "breakpoint {}: {} at PC=0x{:04X}, cycle {}", self.id, self.breakpoint, self.pc, self.cycle)?;
+        match self.value {
+            Some(value) => write!(f, ", value 0x{:02X}", value),
+            None => Ok(()),
+        }
+    }
+}
+
+/*
+ * Breakpoints, checked by Runtime::step when set in State. PC and interrupt breakpoints stop before
+ * instruction runs, IO ones after the instruction making the access finished. Execution goes on once
+ * hit is taken, instruction stopped at isn't broken on again.
+ */
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+    next_id: BreakpointId,
+    hit: Option<Hit>,
+    /* Stopped before instruction at this address, it runs on next step */
+    resume: Option<Addr>,
+    /* Context of the current instruction. IO accesses outside instructions(interrupt dispatch) don't count. */
+    executing: bool,
+    pc: Addr,
+    cycle: u64,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /* Returns false when there was no such breakpoint */
+    pub fn remove(&mut self, id: BreakpointId) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|(other, _)| *other != id);
+        self.breakpoints.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &[(BreakpointId, Breakpoint)] {
+        &self.breakpoints
+    }
+
+    /* Breakpoint execution stopped on, if not taken yet */
+    pub fn hit(&self) -> Option<&Hit> {
+        self.hit.as_ref()
+    }
+
+    pub fn take_hit(&mut self) -> Option<Hit> {
+        self.hit.take()
+    }
+
+    /*
+     * Runs before each instruction, bank is ROM bank mapped at 0x4000-0x7FFF, interrupt is the one which handler
+     * was just entered. Returns false when execution has to stop before the instruction.
+     */
+    pub fn before_instruction(&mut self, pc: Addr, bank: usize, interrupt: Option<Interrupt>, cycle: u64) -> bool {
+        self.pc = pc;
+        self.cycle = cycle;
+        if self.resume.take() != Some(pc) {
+            let hit = self.breakpoints.iter().find(|(_, breakpoint)| match breakpoint {
+                Breakpoint::Pc(_) | Breakpoint::BankedPc { .. } => breakpoint.stops_at(bank, pc),
+                Breakpoint::Interrupt(expected) => interrupt == Some(*expected),
+                Breakpoint::Io { .. } | Breakpoint::Trap(_) => false,
+            });
+            if let Some((id, breakpoint)) = hit {
+                self.hit = Some(Hit { id: *id, breakpoint: *breakpoint, pc, cycle, value: None });
+                self.resume = Some(pc);
+                return false;
+            }
+        }
+        self.executing = true;
+        true
+    }
+
+    pub fn after_instruction(&mut self) {
+        self.executing = false;
+    }
+
+    /* Runs on each CPU memory access */
+    pub fn check_access(&mut self, kind: AccessKind, addr: Addr, value: Byte) {
+        if !self.executing {
+            return;
+        }
+        let hit = self.breakpoints.iter().find(|(_, breakpoint)| match breakpoint {
+            Breakpoint::Io { addr: expected, kind: expected_kind, condition } => {
+                *expected == addr && *expected_kind == kind && condition.matches(value)
+            }
+            _ => false,
+        });
+        if let Some((id, breakpoint)) = hit {
+            self.hit = Some(Hit { id: *id, breakpoint: *breakpoint, pc: self.pc, cycle: self.cycle, value: Some(value) });
+        }
+    }
+
+    /* Runs on illegal memory access made under BusPolicy::Trap, stops after the instruction like IO breakpoints */
+    pub fn trap(&mut self, access: IllegalAccess) {
+        if !self.executing || self.hit.is_some() {
+            return;
+        }
+        let breakpoint = Breakpoint::Trap(access);
+        self.hit = Some(Hit { id: TRAP_ID, breakpoint, pc: self.pc, cycle: self.cycle, value: None });
+    }
+}
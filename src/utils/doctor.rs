@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::mem::{discriminant, Discriminant};
+
+use super::super::cpu::{disassemble, CPU};
+use super::super::mem::{ioregs, Addr, BankController, Byte, HRAM_ADDR, HRAM_SIZE, MMU};
+
+/* Something real game is very unlikely to do on purpose, usually a sign of emulation bug */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /* PC in memory which can't hold code: echo RAM, OAM, unusable area, IO registers or missing cart RAM */
+    UnmappedExecution,
+    /* Executing HRAM which was never written since power on */
+    UninitializedHram,
+    /* Write ignored by mapper, or one that would hit boot ROM */
+    RomWrite { addr: Addr, value: Byte },
+    /* POP or RET past the top of memory */
+    StackUnderflow,
+    /* HALT with all interrupts disabled in IE, CPU never wakes up */
+    DeadHalt,
+    /* Opcode which doesn't exist, real hardware locks up */
+    IllegalOpcode(Byte),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::UnmappedExecution => write!(f, "executing memory which can't hold code"),
+            Fault::UninitializedHram => write!(f, "executing HRAM which was never written"),
+            Fault::RomWrite { addr, value } => write!(f, "write of 0x{:02X} to ROM at 0x{:04X}", value, addr),
+            Fault::StackUnderflow => write!(f, "stack underflow past 0xFFFE"),
+            Fault::DeadHalt => write!(f, "HALT with no interrupt enabled, CPU won't wake up"),
+            Fault::IllegalOpcode(op) => write!(f, "illegal opcode 0x{:02X}, CPU locked up", op),
+        }
+    }
+}
+
+/* Fault together with what CPU was doing */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub fault: Fault,
+    /* Address of instruction which caused it */
+    pub pc: Addr,
+    /* SP before the instruction */
+    pub sp: u16,
+    pub cycle: u64,
+    /* Instruction at PC, disassembled */
+    pub instruction: String,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at PC=0x{:04X}({}), SP=0x{:04X}, cycle {}",
+            self.fault, self.pc, self.instruction, self.sp, self.cycle
+        )
+    }
+}
+
+/*
+ * Doctor mode. Watches CPU for faults(see Fault) and reports them, instead of letting emulation silently go
+ * wrong or panic. Each kind of fault is reported once per instruction address. Reports are also logged as
+ * warnings, with "doctor" target.
+ */
+pub struct Doctor {
+    reports: Vec<Diagnosis>,
+    seen: HashSet<(Discriminant<Fault>, Addr)>,
+    hram_written: Vec<bool>,
+    /* Illegal opcode was hit, CPU doesn't execute anything until reset */
+    locked: bool,
+    /* Context of the current instruction */
+    pc: Addr,
+    sp: u16,
+    cycle: u64,
+}
+
+impl Default for Doctor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Doctor {
+    pub fn new() -> Self {
+        Self {
+            reports: Vec::new(),
+            seen: HashSet::new(),
+            hram_written: vec![false; HRAM_SIZE],
+            locked: false,
+            pc: 0,
+            sp: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn reports(&self) -> &[Diagnosis] {
+        &self.reports
+    }
+
+    /* Returns reports made so far and forgets them, faults already seen stay silent */
+    pub fn take_reports(&mut self) -> Vec<Diagnosis> {
+        std::mem::take(&mut self.reports)
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /* Called on reset, memory written before it doesn't count anymore */
+    pub fn reset(&mut self) {
+        self.hram_written.iter_mut().for_each(|written| *written = false);
+        self.locked = false;
+    }
+
+    /* Runs before each instruction. Returns false when instruction can't be executed. */
+    pub fn before_instruction<T: BankController>(&mut self, cpu: &CPU, mmu: &MMU<T>, cycle: u64) -> bool {
+        self.pc = cpu.PC.val();
+        self.sp = cpu.SP;
+        self.cycle = cycle;
+        // Halted CPU doesn't execute anything
+        if cpu.HALT {
+            return true;
+        }
+        let unmapped = match self.pc {
+            0xA000..=0xBFFF => mmu.mapper.peek_ram(self.pc).is_none(),
+            0xE000..=0xFF7F | 0xFFFF => true,
+            _ => false,
+        };
+        if unmapped {
+            self.report(mmu, Fault::UnmappedExecution);
+        }
+        if (0xFF80..=0xFFFE).contains(&self.pc) && !self.hram_written[(self.pc - HRAM_ADDR) as usize] {
+            self.report(mmu, Fault::UninitializedHram);
+        }
+        let op = mmu.peek(self.pc);
+        if disassemble(self.pc, &[op]).is_none() {
+            self.report(mmu, Fault::IllegalOpcode(op));
+            self.locked = true;
+        }
+        !self.locked
+    }
+
+    /* Runs after each instruction */
+    pub fn after_instruction<T: BankController>(&mut self, cpu: &CPU, mmu: &MMU<T>) {
+        // Checked right after HALT, nothing could wake CPU up later either
+        if cpu.HALT && mmu.peek(ioregs::IE) & 0x1F == 0 {
+            self.report(mmu, Fault::DeadHalt);
+        }
+        // POP and RET move SP up by 2. Going past 0xFFFE wraps it around or leaves it at IE.
+        let moved = cpu.SP.wrapping_sub(self.sp);
+        if moved == 2 && (cpu.SP < self.sp || cpu.SP == 0xFFFF) {
+            self.report(mmu, Fault::StackUnderflow);
+        }
+    }
+
+    /* Runs before each CPU write */
+    pub fn check_write(&mut self, addr: Addr) {
+        if (0xFF80..=0xFFFE).contains(&addr) {
+            self.hram_written[(addr - HRAM_ADDR) as usize] = true;
+        }
+    }
+
+    /* Runs after CPU write which MMU dropped: one ignored by mapper or hitting boot ROM */
+    pub fn rom_write<T: BankController>(&mut self, mmu: &MMU<T>, addr: Addr, value: Byte) {
+        self.report(mmu, Fault::RomWrite { addr, value });
+    }
+
+    fn report<T: BankController>(&mut self, mmu: &MMU<T>, fault: Fault) {
+        if !self.seen.insert((discriminant(&fault), self.pc)) {
+            return;
+        }
+        let bytes = mmu.peek_range(self.pc, 3);
+        let instruction = disassemble(self.pc, &bytes)
+            .map(|(text, _)| text)
+            .unwrap_or_else(|| format!("DB ${:02X}", bytes[0]));
+        let diagnosis = Diagnosis { fault, pc: self.pc, sp: self.sp, cycle: self.cycle, instruction };
+        log::warn!(target: "doctor", "{}", diagnosis);
+        self.reports.push(diagnosis);
+    }
+}
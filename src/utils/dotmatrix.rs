@@ -0,0 +1,80 @@
+use super::super::gpu::Color;
+
+/* Output pixels per side of each GB pixel, last row and column of them is the gap */
+pub const DOT_SIZE: usize = 4;
+/* Gap shows screen background with a bit of the dot's color, LCD cells bleed into each other */
+const GAP_BLEED: f32 = 0.35;
+/* Dots are up to this much brighter or darker than they should be, the same ones each frame like uneven LCD */
+const VARIATION: f32 = 0.04;
+
+/*
+ * Renders picture the way DMG LCD looks up close: each GB pixel becomes a square dot, with a thin gap of screen
+ * background around it. Output is larger, dot x dot pixels per GB pixel, meant for screenshots and video which
+ * look like real screen.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DotMatrix {
+    dot: usize,
+}
+
+impl DotMatrix {
+    /* Dot smaller than 2 has no room for the gap, it's made 2 */
+    pub fn new(dot: usize) -> Self {
+        Self { dot: dot.max(2) }
+    }
+
+    pub fn dot(&self) -> usize {
+        self.dot
+    }
+
+    /*
+     * Picture of given width, dot times larger each way. Background is the color of unlit LCD, usually lightest
+     * color of the palette.
+     */
+    pub fn render(&self, pixels: &[Color], width: usize, background: Color) -> Vec<Color> {
+        if width == 0 {
+            return Vec::new();
+        }
+        let height = pixels.len() / width;
+        let out_width = width * self.dot;
+        let mut out = vec![background; out_width * height * self.dot];
+        for (i, pixel) in pixels.iter().take(width * height).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let dot = shade(*pixel, 1.0 + VARIATION * noise(x, y));
+            let gap = mix(background, *pixel, GAP_BLEED);
+            for row in 0..self.dot {
+                let start = (y * self.dot + row) * out_width + x * self.dot;
+                for (col, out) in out[start..start + self.dot].iter_mut().enumerate() {
+                    *out = if row == self.dot - 1 || col == self.dot - 1 { gap } else { dot };
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Default for DotMatrix {
+    fn default() -> Self {
+        Self::new(DOT_SIZE)
+    }
+}
+
+/* Fixed value in -1..1 for each position */
+fn noise(x: usize, y: usize) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x9E37_79B1) ^ (y as u32).wrapping_mul(0x85EB_CA77);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0xC2B2_AE3D);
+    hash ^= hash >> 13;
+    (hash & 0xFFFF) as f32 / 0x7FFF as f32 - 1.0
+}
+
+fn shade(color: Color, factor: f32) -> Color {
+    let scale = |c: u8| (c as f32 * factor).round().clamp(0.0, 255.0) as u8;
+    (scale(color.0), scale(color.1), scale(color.2))
+}
+
+/* Weight is how much of b goes into the result */
+fn mix(a: Color, b: Color, weight: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 * (1.0 - weight) + b as f32 * weight).round() as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
@@ -0,0 +1,196 @@
+use std::ops::Range;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::super::gpu::Color;
+use super::super::joypad::{Button, Joypad};
+use super::super::mem::{Addr, BankController, Byte};
+use super::super::state::{Runtime, State, CPU_CYCLES_PER_FRAME};
+
+/* Bits of action bitmask, one per button. Set bit means button is held during the step. */
+pub const BUTTON_RIGHT: u8 = Button::Right.mask();
+pub const BUTTON_LEFT: u8 = Button::Left.mask();
+pub const BUTTON_UP: u8 = Button::Up.mask();
+pub const BUTTON_DOWN: u8 = Button::Down.mask();
+pub const BUTTON_A: u8 = Button::A.mask();
+pub const BUTTON_B: u8 = Button::B.mask();
+pub const BUTTON_SELECT: u8 = Button::Select.mask();
+pub const BUTTON_START: u8 = Button::Start.mask();
+
+/* Memory returned in observations when nothing else was asked for: whole WRAM */
+pub const DEFAULT_WATCH: Range<Addr> = 0xC000..0xE000;
+
+/* Holds buttons set in action bitmask, releases the rest */
+pub fn set_buttons(joypad: &mut Joypad, action: u8) {
+    for button in Button::ALL {
+        joypad.set(button, action & button.mask() != 0);
+    }
+}
+
+/* Emulates one frame without anybody watching: audio is thrown away. Stopped runtime stays where it was. */
+pub fn run_frame<T: BankController>(runtime: &mut Runtime<T>) {
+    while runtime.cpu_cycles() < CPU_CYCLES_PER_FRAME && !runtime.stopped() {
+        runtime.step();
+    }
+    end_frame(runtime);
+}
+
+/* Housekeeping after frame was emulated by other means, e.g. stepping in debugger */
+pub fn end_frame<T: BankController>(runtime: &mut Runtime<T>) {
+    runtime.reset_cycles();
+    runtime.state.serial.frame();
+    // Don't let sample buffers grow
+    runtime.state.apu.left_samples().clear();
+    runtime.state.apu.right_samples().clear();
+}
+
+pub type RewardHook<T> = Box<dyn FnMut(&mut State<T>) -> f32 + Send>;
+pub type DoneHook<T> = Box<dyn FnMut(&mut State<T>) -> bool + Send>;
+
+/* What agent gets back after each step */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /* GB screen, SCREEN_WIDTH x SCREEN_HEIGHT */
+    pub framebuffer: Vec<Color>,
+    /* Watched memory, see Env::set_watch */
+    pub wram: Vec<Byte>,
+    /* Returned by reward hook, 0 without one */
+    pub reward: f32,
+    /* Returned by done hook, false without one */
+    pub done: bool,
+    /* Frames emulated since last reset */
+    pub frame: u64,
+}
+
+/*
+ * Gym-like environment around single GB, for training agents. Nothing is drawn or played, so it runs as
+ * fast as host allows. Same seed and same actions give the same observations every time.
+ */
+pub struct Env<T: BankController> {
+    pub runtime: Runtime<T>,
+    watch: Range<Addr>,
+    /* Frames emulated per step, action is held for all of them */
+    frame_skip: u32,
+    reward: Option<RewardHook<T>>,
+    done: Option<DoneHook<T>>,
+    frame: u64,
+}
+
+impl<T: BankController> Env<T> {
+    /* Runtime should be set up already: boot ROM or model, quirks etc. They survive resets. */
+    pub fn new(runtime: Runtime<T>) -> Self {
+        Self {
+            runtime,
+            watch: DEFAULT_WATCH,
+            frame_skip: 1,
+            reward: None,
+            done: None,
+            frame: 0,
+        }
+    }
+
+    pub fn set_watch(&mut self, watch: Range<Addr>) {
+        self.watch = watch;
+    }
+
+    /* At least one frame is emulated per step */
+    pub fn set_frame_skip(&mut self, frames: u32) {
+        self.frame_skip = frames.max(1);
+    }
+
+    /* Called after each step, usually reads score or lives from memory */
+    pub fn set_reward_hook(&mut self, hook: RewardHook<T>) {
+        self.reward = Some(hook);
+    }
+
+    /* Called after each step, tells when episode is over */
+    pub fn set_done_hook(&mut self, hook: DoneHook<T>) {
+        self.done = Some(hook);
+    }
+
+    /*
+     * Powers GB on again. Real hardware starts with garbage in RAM, here it's generated from seed, so games
+     * seeding their RNG from RAM differ between seeds but not between runs.
+     */
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.runtime.reset(true);
+        let mut rng = StdRng::seed_from_u64(seed);
+        rng.fill(&mut self.runtime.state.mmu.ram[..]);
+        rng.fill(&mut self.runtime.state.mmu.hram[..]);
+        self.frame = 0;
+        self.observe(0.0, false)
+    }
+
+    pub fn step(&mut self, action: u8) -> Observation {
+        set_buttons(&mut self.runtime.state.joypad, action);
+        for _ in 0..self.frame_skip {
+            run_frame(&mut self.runtime);
+            self.frame += 1;
+        }
+        let state = &mut self.runtime.state;
+        let reward = self.reward.as_mut().map_or(0.0, |hook| hook(state));
+        let done = self.done.as_mut().is_some_and(|hook| hook(state));
+        self.observe(reward, done)
+    }
+
+    fn observe(&mut self, reward: f32, done: bool) -> Observation {
+        let mmu = &self.runtime.state.mmu;
+        Observation {
+            framebuffer: self.runtime.state.gpu.completed_frame().to_vec(),
+            wram: self.watch.clone().map(|addr| mmu.peek(addr)).collect(),
+            reward,
+            done,
+            frame: self.frame,
+        }
+    }
+}
+
+/*
+ * Many environments stepped together, spread over all CPU cores. Environment i gets i-th action and
+ * is reset with seed + i.
+ */
+pub struct BatchEnv<T: BankController> {
+    pub envs: Vec<Env<T>>,
+}
+
+impl<T: BankController + Send> BatchEnv<T> {
+    pub fn new(envs: Vec<Env<T>>) -> Self {
+        Self { envs }
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.run(|i, env| env.reset(seed.wrapping_add(i as u64)))
+    }
+
+    /* Environments without action get none(0) */
+    pub fn step(&mut self, actions: &[u8]) -> Vec<Observation> {
+        self.run(|i, env| env.step(actions.get(i).cloned().unwrap_or(0)))
+    }
+
+    fn run<F>(&mut self, f: F) -> Vec<Observation>
+    where
+        F: Fn(usize, &mut Env<T>) -> Observation + Sync,
+    {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = self.envs.len().div_ceil(threads).max(1);
+        let f = &f;
+        thread::scope(|scope| {
+            let handles = self
+                .envs
+                .chunks_mut(chunk)
+                .enumerate()
+                .map(|(c, envs)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .enumerate()
+                            .map(|(i, env)| f(c * chunk + i, env))
+                            .collect::<Vec<Observation>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::super::gpu::GPUMode;
+use super::debugger::Interrupt;
+
+/* Events kept by default, older ones are dropped. PPU alone makes ~37000 a second. */
+pub const EVENT_LOG_CAPACITY: usize = 1 << 18;
+/* Machine cycles per microsecond, Chrome trace timestamps are in microseconds */
+const CYCLES_PER_US: f64 = (1 << 20) as f64 / 1_000_000.0;
+
+/* What part of GB an event comes from, each one is a separate row in the trace */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventCategory {
+    Ppu,
+    Bank,
+    Interrupt,
+    Dma,
+    Apu,
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 5] =
+        [EventCategory::Ppu, EventCategory::Bank, EventCategory::Interrupt, EventCategory::Dma, EventCategory::Apu];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EventCategory::Ppu => "ppu",
+            EventCategory::Bank => "bank",
+            EventCategory::Interrupt => "interrupt",
+            EventCategory::Dma => "dma",
+            EventCategory::Apu => "apu",
+        }
+    }
+}
+
+impl FromStr for EventCategory {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text = text.trim().to_ascii_lowercase();
+        EventCategory::ALL.iter().copied().find(|category| category.name() == text).ok_or_else(|| {
+            format!("unknown event category {}, expected one of: ppu, bank, interrupt, dma, apu", text)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /* PPU entered the mode */
+    Mode(GPUMode),
+    /* Bank now mapped at 0x4000-0x7FFF, and at 0xA000-0xBFFF(None when RAM isn't mapped) */
+    RomBank(usize),
+    RamBank(Option<usize>),
+    /* CPU jumped to the handler */
+    Interrupt(Interrupt),
+    /* OAM DMA transfer */
+    DmaStart,
+    DmaEnd,
+    /* Sound channel 1-4 triggered by NRx4 write */
+    ChannelTrigger(u8),
+}
+
+impl EventKind {
+    pub fn category(&self) -> EventCategory {
+        match self {
+            EventKind::Mode(_) => EventCategory::Ppu,
+            EventKind::RomBank(_) | EventKind::RamBank(_) => EventCategory::Bank,
+            EventKind::Interrupt(_) => EventCategory::Interrupt,
+            EventKind::DmaStart | EventKind::DmaEnd => EventCategory::Dma,
+            EventKind::ChannelTrigger(_) => EventCategory::Apu,
+        }
+    }
+
+    /* Payload as JSON object, for trace args */
+    fn args(&self) -> String {
+        match self {
+            EventKind::RomBank(bank) => format!("{{\"bank\":{}}}", bank),
+            EventKind::RamBank(Some(bank)) => format!("{{\"bank\":{}}}", bank),
+            EventKind::ChannelTrigger(chan) => format!("{{\"channel\":{}}}", chan),
+            _ => String::from("{}"),
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Mode(GPUMode::HBLANK) => write!(f, "HBLANK"),
+            EventKind::Mode(GPUMode::VBLANK) => write!(f, "VBLANK"),
+            EventKind::Mode(GPUMode::OAM_SEARCH) => write!(f, "OAM"),
+            EventKind::Mode(GPUMode::LCD_TRANSFER) => write!(f, "DRAW"),
+            EventKind::RomBank(bank) => write!(f, "ROM bank {}", bank),
+            EventKind::RamBank(Some(bank)) => write!(f, "RAM bank {}", bank),
+            EventKind::RamBank(None) => write!(f, "RAM unmapped"),
+            EventKind::Interrupt(interrupt) => write!(f, "{:?} interrupt", interrupt),
+            EventKind::DmaStart => write!(f, "DMA start"),
+            EventKind::DmaEnd => write!(f, "DMA end"),
+            EventKind::ChannelTrigger(chan) => write!(f, "CH{} trigger", chan),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /* Machine cycles since log was started */
+    pub cycle: u64,
+    pub kind: EventKind,
+}
+
+/*
+ * Log of what happened when, kept by Runtime(see Runtime::set_event_log) in a ring buffer of the latest events.
+ * Devices are caught up after each instruction, so their events have cycle of the instruction end. Dumped as
+ * Chrome trace JSON, it shows as timeline in chrome://tracing or Perfetto.
+ */
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+    categories: Vec<EventCategory>,
+    /* Runtime counts cycles from 0 each frame, this is where current frame started */
+    base: u64,
+    dropped: u64,
+}
+
+impl EventLog {
+    /* Log of all categories, capacity of at least 1 */
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity: capacity.max(1),
+            categories: EventCategory::ALL.to_vec(),
+            base: 0,
+            dropped: 0,
+        }
+    }
+
+    /* Only events of these categories are recorded */
+    pub fn with_categories(mut self, categories: &[EventCategory]) -> Self {
+        self.categories = categories.to_vec();
+        self
+    }
+
+    pub fn enabled(&self, category: EventCategory) -> bool {
+        self.categories.contains(&category)
+    }
+
+    /* Cycle is the one of Runtime, counted from start of the frame */
+    pub fn record(&mut self, cycle: u64, kind: EventKind) {
+        if !self.enabled(kind.category()) {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(Event { cycle: self.base + cycle, kind });
+    }
+
+    /* Runtime starts counting cycles over, after given number of them */
+    pub(crate) fn restart_cycles(&mut self, cycles: u64) {
+        self.base += cycles;
+    }
+
+    /* Oldest first */
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /* Events which didn't fit and were dropped */
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.dropped = 0;
+    }
+
+    /*
+     * Chrome trace event format, one row per category. PPU modes are spans lasting until next mode, everything
+     * else is an instant.
+     */
+    pub fn to_chrome_trace(&self) -> String {
+        let us = |cycle: u64| cycle as f64 / CYCLES_PER_US;
+        // Rows are named after categories
+        let mut entries = EventCategory::ALL
+            .iter()
+            .enumerate()
+            .map(|(tid, category)| {
+                let args = format!("{{\"name\":\"{}\"}}", category.name());
+                format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{}}}", tid, args)
+            })
+            .collect::<Vec<String>>();
+        let modes = self.events.iter().filter(|event| event.kind.category() == EventCategory::Ppu).collect::<Vec<_>>();
+        let mut next_mode = modes.iter().skip(1);
+        for event in &self.events {
+            let category = event.kind.category();
+            let tid = EventCategory::ALL.iter().position(|c| *c == category).unwrap_or(0);
+            let common = format!(
+                "\"name\":\"{}\",\"cat\":\"{}\",\"pid\":0,\"tid\":{},\"ts\":{:.3},\"args\":{}",
+                event.kind,
+                category.name(),
+                tid,
+                us(event.cycle),
+                event.kind.args()
+            );
+            let end = match category {
+                EventCategory::Ppu => next_mode.next().map(|next| next.cycle),
+                _ => None,
+            };
+            entries.push(match end {
+                Some(end) => format!("{{{},\"ph\":\"X\",\"dur\":{:.3}}}", common, us(end) - us(event.cycle)),
+                None => format!("{{{},\"ph\":\"i\",\"s\":\"t\"}}", common),
+            });
+        }
+        format!("{{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n{}\n]}}\n", entries.join(",\n"))
+    }
+
+    pub fn save_chrome_trace(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_chrome_trace()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAPACITY)
+    }
+}
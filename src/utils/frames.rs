@@ -0,0 +1,73 @@
+use super::super::gpu::Color;
+use super::super::mem::BankController;
+use super::super::state::{Runtime, CPU_CYCLES_PER_FRAME};
+use super::env::{end_frame, set_buttons};
+
+/* GPU keeps counting VBlanks with LCD off, this only guards against waiting for one forever */
+const MAX_FRAME_CYCLES: u64 = 2 * CPU_CYCLES_PER_FRAME;
+
+/* Frame emulated by Runtime::frames */
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmulatedFrame {
+    /* Frames yielded before this one */
+    pub number: u64,
+    /* GB screen as of VBlank, SCREEN_WIDTH x SCREEN_HEIGHT, before any palette is applied */
+    pub framebuffer: Vec<Color>,
+    /* Interleaved stereo samples made during the frame: L, R, L, R... */
+    pub audio: Vec<i16>,
+    /* Buttons held while the frame was emulated, as action bitmask(see BUTTON_*) */
+    pub input: u8,
+}
+
+/* Gets number of frame about to be emulated, returns buttons to hold during it as action bitmask */
+pub type InputSource<'a> = Box<dyn FnMut(u64) -> u8 + 'a>;
+
+/*
+ * Iterator emulating one frame per item, from one VBlank to the next, so each picture is whole. First one ends at
+ * the first VBlank, it's shorter when runtime was left mid-frame. Iteration ends once runtime is stopped.
+ */
+pub struct Frames<'a, T: BankController> {
+    runtime: &'a mut Runtime<T>,
+    input: Option<InputSource<'a>>,
+    number: u64,
+}
+
+impl<'a, T: BankController> Frames<'a, T> {
+    pub fn new(runtime: &'a mut Runtime<T>) -> Self {
+        Self { runtime, input: None, number: 0 }
+    }
+
+    /* Buttons are asked for before each frame. Without input source joypad is left as it is. */
+    pub fn with_input(mut self, input: impl FnMut(u64) -> u8 + 'a) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+}
+
+impl<T: BankController> Iterator for Frames<'_, T> {
+    type Item = EmulatedFrame;
+
+    fn next(&mut self) -> Option<EmulatedFrame> {
+        if self.runtime.stopped() {
+            return None;
+        }
+        if let Some(input) = self.input.as_mut() {
+            set_buttons(&mut self.runtime.state.joypad, input(self.number));
+        }
+        let input = self.runtime.state.joypad.held();
+        let (vblanks, start) = (self.runtime.state.gpu.vblanks(), self.runtime.cpu_cycles());
+        while self.runtime.state.gpu.vblanks() == vblanks
+            && self.runtime.cpu_cycles() - start < MAX_FRAME_CYCLES
+            && !self.runtime.stopped()
+        {
+            self.runtime.step();
+        }
+        let apu = &mut self.runtime.state.apu;
+        let mut audio = vec![0; apu.available_samples()];
+        apu.pull_samples(&mut audio);
+        let framebuffer = self.runtime.state.gpu.completed_frame().to_vec();
+        end_frame(self.runtime);
+        self.number += 1;
+        Some(EmulatedFrame { number: self.number - 1, framebuffer, audio, input })
+    }
+}
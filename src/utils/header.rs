@@ -5,6 +5,25 @@ use std::str;
 
 use super::super::{ROM_BANK_SIZE, RAM_BANK_SIZE};
 
+/* Cart header location in ROM */
+pub const HEADER_START: usize = 0x100;
+pub const HEADER_END: usize = 0x150;
+/* Nintendo logo, boot ROM copies it to VRAM */
+pub const LOGO_START: usize = 0x104;
+/* Checksum of 0x134-0x14C, verified by boot ROM */
+pub const HEADER_CHECKSUM: usize = 0x14D;
+
+/* Global checksum is stored at 0x14E-0x14F and isn't part of the sum itself */
+const GLOBAL_CHECKSUM_START: usize = 0x14E;
+const GLOBAL_CHECKSUM_END: usize = 0x150;
+
+/* Bitmap boot ROM compares with cart before running it */
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
 /* Data stored in cart ROM at 0x100-0x14F */
 #[repr(packed)]
 pub struct CartHeader {
@@ -26,11 +45,32 @@ pub struct CartHeader {
 #[derive(Debug)]
 pub enum CartType {
     RomOnly(),
-    Mbc1(), Mbc2(), Mbc3(),
+    Mbc1(), Mbc2(), Mbc3(), Mbc5(),
+    HuC3(), Tama5(),
     Unknown(u8),
 }
 
-#[derive(Debug)]
+/* Reasons for header to be considered invalid */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeaderProblem {
+    Logo,
+    HeaderChecksum { stored: u8, calculated: u8 },
+    GlobalChecksum { stored: u16, calculated: u16 },
+}
+
+impl Display for HeaderProblem {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            HeaderProblem::Logo => write!(f, "Nintendo logo doesn't match"),
+            HeaderProblem::HeaderChecksum { stored, calculated } =>
+                write!(f, "Header checksum is 0x{:02X}, but should be 0x{:02X}", stored, calculated),
+            HeaderProblem::GlobalChecksum { stored, calculated } =>
+                write!(f, "Global checksum is 0x{:04X}, but should be 0x{:04X}", stored, calculated),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CGBRequirement {
     Unsupported,
     Optional,
@@ -46,22 +86,38 @@ impl CartHeader {
         unsafe { std::ptr::read(rom.as_ptr() as *const _) }
     }
 
+    /* Reads header from whole ROM image. None if ROM is too small to contain one. */
+    pub fn from_rom(rom: &[u8]) -> Option<Self> {
+        rom.get(HEADER_START..HEADER_END).map(|bytes| CartHeader::new(bytes.to_vec()))
+    }
+
+    /* Title is padded with zeros. Newer carts reuse its last bytes for other data, so only printable ASCII is kept. */
     pub fn title(&self) -> String {
-        let slice = str::from_utf8(if self.license_old == 0x33 {
-                &self.title[..11] 
-            } else {
-                &self.title[..16] 
-            }
-        ).unwrap();
-        String::from(slice)
+        let bytes = if self.license_old == 0x33 {
+            &self.title[..11]
+        } else {
+            &self.title[..16]
+        };
+        bytes.iter()
+            .take_while(|b| **b != 0)
+            .filter(|b| b.is_ascii_graphic() || **b == b' ')
+            .map(|b| *b as char)
+            .collect()
+    }
+
+    /* All 16 title bytes, with CGB flag and whatever else newer carts keep there */
+    pub fn raw_title(&self) -> [u8; 16] {
+        self.title
     }
 
+    /* Invalid new license code is reported as 0 */
     pub fn license(&self) -> u8 {
         if self.license_old != 0x33 {
             self.license_old
         } else {
-            let string = str::from_utf8(&self.license_new).unwrap();
-            u8::from_str_radix(string, 16).unwrap()
+            str::from_utf8(&self.license_new).ok()
+                .and_then(|string| u8::from_str_radix(string, 16).ok())
+                .unwrap_or(0)
         }
     }
 
@@ -69,12 +125,12 @@ impl CartHeader {
         self.sgb == 0x003
     }
 
+    /* CGB flag is the last title byte. Old carts have there ASCII char, which never matches. */
     pub fn cgb_support(&self) -> CGBRequirement {
-        match (self.license_old, self.title[15]) {
-            (0x33, _) => CGBRequirement::Unsupported,
-            (_, 0x80) => CGBRequirement::Optional,
-            (_, 0xC0) => CGBRequirement::Required,
-            (_, _) => CGBRequirement::Unsupported,
+        match self.title[15] {
+            0x80 => CGBRequirement::Optional,
+            0xC0 => CGBRequirement::Required,
+            _ => CGBRequirement::Unsupported,
         }
     }
 
@@ -84,10 +140,21 @@ impl CartHeader {
             0x01 | 0x02 | 0x03 => CartType::Mbc1(),
             0x05 | 0x06 => CartType::Mbc2(),
             0x0F | 0x10 | 0x11 | 0x12 | 0x13 => CartType::Mbc3(),
+            0x19..=0x1E => CartType::Mbc5(),
+            0xFD => CartType::Tama5(),
+            0xFE => CartType::HuC3(),
             other => CartType::Unknown(other),
         }
     }
 
+    /* Cart RAM is kept powered by a battery, so game expects it to survive power off */
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.cart_type,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFD | 0xFE | 0xFF
+        )
+    }
+
     pub fn rom_size(&self) -> usize {
         // Calculated as 32KB shl N
         ((1 << 15) << self.rom_size) as usize
@@ -120,6 +187,54 @@ impl CartHeader {
     pub fn checksum(&self) -> u8 {
         self.header_checksum
     }
+
+    /* Checksum of 0x134-0x14C, as calculated by boot ROM */
+    pub fn calc_header_checksum(&self) -> u8 {
+        let tail = [self.sgb, self.cart_type, self.rom_size, self.ram_size, self.destination, self.license_old, self.version];
+        self.title.iter().chain(self.license_new.iter()).chain(tail.iter())
+            .fold(0u8, |acc, b| acc.wrapping_sub(*b).wrapping_sub(1))
+    }
+
+    /* Boot ROM locks up when this fails */
+    pub fn verify_header_checksum(&self) -> bool {
+        self.calc_header_checksum() == self.header_checksum
+    }
+
+    pub fn global_checksum(&self) -> u16 {
+        ((self.global_checksum[0] as u16) << 8) | self.global_checksum[1] as u16
+    }
+
+    /* Sum of all ROM bytes, except for the checksum itself */
+    pub fn calc_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter().enumerate()
+            .filter(|(i, _)| !(GLOBAL_CHECKSUM_START..GLOBAL_CHECKSUM_END).contains(i))
+            .fold(0u16, |acc, (_, b)| acc.wrapping_add(*b as u16))
+    }
+
+    /* Real hardware never checks it, so plenty of working ROMs fail this */
+    pub fn verify_global_checksum(&self, rom: &[u8]) -> bool {
+        CartHeader::calc_global_checksum(rom) == self.global_checksum()
+    }
+
+    /* Boot ROM locks up when logo doesn't match */
+    pub fn verify_logo(&self) -> bool {
+        self.logo == NINTENDO_LOGO
+    }
+
+    /* Runs all checks. Empty result means header is valid. */
+    pub fn problems(&self, rom: &[u8]) -> Vec<HeaderProblem> {
+        let mut problems = Vec::new();
+        if !self.verify_logo() {
+            problems.push(HeaderProblem::Logo);
+        }
+        if !self.verify_header_checksum() {
+            problems.push(HeaderProblem::HeaderChecksum { stored: self.header_checksum, calculated: self.calc_header_checksum() });
+        }
+        if !self.verify_global_checksum(rom) {
+            problems.push(HeaderProblem::GlobalChecksum { stored: self.global_checksum(), calculated: CartHeader::calc_global_checksum(rom) });
+        }
+        problems
+    }
 }
 
 impl Display for CartHeader {
@@ -0,0 +1,112 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::super::mem::{AccessCallback, AccessKind, Addr};
+use super::png::write_gray_png;
+
+/* PNG map has one pixel per address, 256 addresses per row, so 0xC000 is at row 0xC0 */
+pub const HEATMAP_WIDTH: usize = 256;
+const ADDRESS_SPACE: usize = 0x10000;
+
+struct Counters {
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+/*
+ * Counts CPU reads and writes of each address. Subscribe recorder() on MMU, then export result as CSV or PNG.
+ * Clones share counters, so map can be saved from other thread than the one running emulation.
+ */
+#[derive(Clone)]
+pub struct Heatmap {
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            counters: Arc::new(Mutex::new(Counters {
+                reads: vec![0; ADDRESS_SPACE],
+                writes: vec![0; ADDRESS_SPACE],
+            })),
+        }
+    }
+
+    /* Callback to subscribe on MMU, usually for whole address space */
+    pub fn recorder(&self) -> AccessCallback {
+        let counters = self.counters.clone();
+        Box::new(move |access| {
+            let mut counters = counters.lock().unwrap();
+            let counts = match access.kind {
+                AccessKind::Read => &mut counters.reads,
+                AccessKind::Write => &mut counters.writes,
+            };
+            let count = &mut counts[access.addr as usize];
+            *count = count.saturating_add(1);
+        })
+    }
+
+    pub fn reads(&self, addr: Addr) -> u32 {
+        self.counters.lock().unwrap().reads[addr as usize]
+    }
+
+    pub fn writes(&self, addr: Addr) -> u32 {
+        self.counters.lock().unwrap().writes[addr as usize]
+    }
+
+    /* One line per address that was touched at all */
+    pub fn to_csv(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut csv = String::from("address,reads,writes\n");
+        for (addr, (reads, writes)) in counters.reads.iter().zip(counters.writes.iter()).enumerate() {
+            if *reads > 0 || *writes > 0 {
+                writeln!(csv, "0x{:04X},{},{}", addr, reads, writes).unwrap();
+            }
+        }
+        csv
+    }
+
+    /*
+     * Grayscale map of whole address space, HEATMAP_WIDTH addresses per row. Black is never touched, white is
+     * the most accessed address. Scale is logarithmic, otherwise few hot addresses(stack, HRAM) hide the rest.
+     * Kind None counts both reads and writes.
+     */
+    pub fn render(&self, kind: Option<AccessKind>) -> Vec<u8> {
+        let counters = self.counters.lock().unwrap();
+        let counts = (0..ADDRESS_SPACE)
+            .map(|addr| match kind {
+                Some(AccessKind::Read) => counters.reads[addr] as u64,
+                Some(AccessKind::Write) => counters.writes[addr] as u64,
+                None => counters.reads[addr] as u64 + counters.writes[addr] as u64,
+            })
+            .collect::<Vec<u64>>();
+        let max = (*counts.iter().max().unwrap_or(&0) as f64).ln_1p();
+        counts
+            .iter()
+            .map(|count| match count {
+                0 => 0,
+                // Touched once still has to be visible
+                _ => (1.0 + 254.0 * (*count as f64).ln_1p() / max).round() as u8,
+            })
+            .collect()
+    }
+
+    /* Format is picked by extension: .png saves map of all accesses, anything else CSV */
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        if png {
+            write_gray_png(path, HEATMAP_WIDTH, ADDRESS_SPACE / HEATMAP_WIDTH, &self.render(None))
+        } else {
+            fs::write(path, self.to_csv())
+        }
+    }
+}
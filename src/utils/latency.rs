@@ -0,0 +1,101 @@
+use std::fmt;
+
+use super::super::mem::Byte;
+use super::debugger::Interrupt;
+
+/* Cycles between interrupt request and its handler being entered, over all requests of one interrupt */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencyStats {
+    /* Handlers entered */
+    pub count: u64,
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl LatencyStats {
+    fn record(&mut self, cycles: u64) {
+        self.min = if self.count == 0 { cycles } else { self.min.min(cycles) };
+        self.max = self.max.max(cycles);
+        self.total += cycles;
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.total as f64 / self.count as f64)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mean() {
+            Some(mean) => write!(f, "{} taken, {:.1} cycles avg, {}-{}", self.count, mean, self.min, self.max),
+            None => write!(f, "none taken"),
+        }
+    }
+}
+
+/*
+ * Latency of each interrupt, kept by Runtime, see Runtime::interrupt_latency. Request is seen once IF bit gets
+ * set, so its time is known with instruction precision. Handler is entered when jump to it is done, so even
+ * interrupt taken right away has dispatch cycles counted. Requests dropped by clearing IF don't count.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterruptLatency {
+    /* Cycle each pending interrupt was requested at */
+    requested: [Option<u64>; 5],
+    stats: [LatencyStats; 5],
+}
+
+impl InterruptLatency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self, interrupt: Interrupt) -> LatencyStats {
+        self.stats[interrupt as usize]
+    }
+
+    /* Picks up requests from IF register, as it is at given cycle */
+    pub fn observe(&mut self, flags: Byte, cycle: u64) {
+        for interrupt in Interrupt::ALL {
+            let requested = &mut self.requested[interrupt as usize];
+            match (flags & interrupt.mask() != 0, *requested) {
+                (true, None) => *requested = Some(cycle),
+                (false, Some(_)) => *requested = None,
+                _ => {}
+            }
+        }
+    }
+
+    /* Handler of the interrupt was entered at given cycle */
+    pub fn entered(&mut self, interrupt: Interrupt, cycle: u64) {
+        if let Some(requested) = self.requested[interrupt as usize].take() {
+            self.stats[interrupt as usize].record(cycle.saturating_sub(requested));
+        }
+    }
+
+    /* Forgets pending requests, for when cycle count starts over. Stats are kept. */
+    pub fn restart(&mut self) {
+        self.requested = [None; 5];
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl fmt::Display for InterruptLatency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, interrupt) in Interrupt::ALL.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:?}: {}", interrupt, self.stats(*interrupt))?;
+        }
+        Ok(())
+    }
+}
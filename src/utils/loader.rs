@@ -0,0 +1,90 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use zip::ZipArchive;
+
+use super::header::{CGBRequirement, CartHeader, HeaderProblem};
+
+/* Extensions recognized as ROM images, also inside zip archives */
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+/* Hardware the ROM is meant to run on */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HardwareMode {
+    DMG,
+    CGB,
+}
+
+/* ROM image read from disk, together with its parsed header */
+pub struct LoadedRom {
+    pub rom: Vec<u8>,
+    pub header: CartHeader,
+    pub mode: HardwareMode,
+    /* Problems found in header. Only filled when loaded in non-strict mode. */
+    pub warnings: Vec<HeaderProblem>,
+}
+
+/*
+ * Reads ROM and verifies its header. Path can point to .gb/.gbc file or to .zip archive containing one.
+ * In strict mode any header problem fails the load, otherwise problems are returned as warnings and
+ * it's up to the caller to report them.
+ */
+pub fn load_rom(path: &Path, strict: bool) -> Result<LoadedRom, String> {
+    let (rom, extension) = read_image(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let header = CartHeader::from_rom(&rom)
+        .ok_or_else(|| format!("{}: ROM is too small to contain cart header", path.display()))?;
+    let warnings = header.problems(&rom);
+    if strict && !warnings.is_empty() {
+        let reasons = warnings.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+        return Err(format!("{}: invalid header. {}", path.display(), reasons.join(". ")));
+    }
+    let mode = detect_mode(&header, &extension);
+    Ok(LoadedRom { rom, header, mode, warnings })
+}
+
+/*
+ * Header decides if cart can run on CGB at all. Extension only picks the mode for carts supporting both.
+ */
+pub fn detect_mode(header: &CartHeader, extension: &str) -> HardwareMode {
+    match header.cgb_support() {
+        CGBRequirement::Required => HardwareMode::CGB,
+        CGBRequirement::Optional if extension == "gbc" => HardwareMode::CGB,
+        _ => HardwareMode::DMG,
+    }
+}
+
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/* Returns ROM bytes and extension of the ROM file, which for archives is the one of file inside */
+fn read_image(path: &Path) -> Result<(Vec<u8>, String), String> {
+    let ext = extension(&path.to_string_lossy());
+    if ext != "zip" {
+        let rom = fs::read(path).map_err(|e| e.to_string())?;
+        return Ok((rom, ext));
+    }
+
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let roms = (0..archive.len())
+        .filter(|i| {
+            archive.by_index(*i).map(|entry| {
+                entry.is_file() && ROM_EXTENSIONS.contains(&extension(entry.name()).as_str())
+            }).unwrap_or(false)
+        })
+        .collect::<Vec<usize>>();
+    if roms.len() != 1 {
+        return Err(format!("archive must contain exactly one ROM, found {}", roms.len()));
+    }
+
+    let mut entry = archive.by_index(roms[0]).map_err(|e| e.to_string())?;
+    let mut rom = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut rom).map_err(|e| e.to_string())?;
+    Ok((rom, extension(entry.name())))
+}
@@ -1,2 +1,85 @@
 pub mod header;
-pub use header::*;
\ No newline at end of file
+pub use header::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod loader;
+pub use loader::*;
+
+pub mod png;
+pub use png::*;
+
+pub mod netlink;
+pub use netlink::*;
+
+pub mod model;
+pub use model::*;
+
+pub mod heatmap;
+pub use heatmap::*;
+
+pub mod blend;
+pub use blend::*;
+
+pub mod palette;
+pub use palette::*;
+
+pub mod compat;
+pub use compat::*;
+
+pub mod dotmatrix;
+pub use dotmatrix::*;
+
+pub mod env;
+pub use env::*;
+
+pub mod runner;
+pub use runner::*;
+
+pub mod doctor;
+pub use doctor::*;
+
+pub mod symbols;
+pub use symbols::*;
+
+pub mod debugger;
+pub use debugger::*;
+
+pub mod watchdog;
+pub use watchdog::*;
+
+pub mod latency;
+pub use latency::*;
+
+pub mod session;
+pub use session::*;
+
+pub mod battery;
+pub use battery::*;
+
+pub mod frames;
+pub use frames::*;
+
+#[cfg(feature = "serde")]
+pub mod serde_array;
+
+#[cfg(feature = "serde")]
+pub mod savestate;
+#[cfg(feature = "serde")]
+pub use savestate::*;
+
+pub mod audit;
+pub use audit::*;
+
+pub mod events;
+pub use events::*;
+
+pub mod timeline;
+pub use timeline::*;
+
+pub mod summary;
+pub use summary::*;
+
+pub mod pacer;
+pub use pacer::*;
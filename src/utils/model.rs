@@ -0,0 +1,160 @@
+#![allow(non_snake_case)]
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::super::gpu::GPUQuirks;
+use super::super::mem::{Byte, BOOSTRAP_SIZE, CGB_BOOTROM_SIZE};
+use super::header::CartHeader;
+
+/* Where in VRAM DMG boot ROM puts logo tiles, the ® tile and the map showing them */
+const LOGO_TILES_OFFSET: usize = 0x0010;
+const REGISTERED_TILE_OFFSET: usize = 0x0190;
+const LOGO_MAP_OFFSET: usize = 0x1904;
+const REGISTERED_MAP_OFFSET: usize = 0x1910;
+/* Logo is 12 tiles wide, in 2 rows */
+const LOGO_WIDTH: usize = 12;
+const REGISTERED_TILE: Byte = 0x19;
+/* ® bitmap from DMG boot ROM, one byte per row */
+const REGISTERED: [Byte; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+/*
+ * Hardware revision being emulated. It decides what boot ROM leaves in registers, which boot ROM fits
+ * and which hardware bugs are present.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HardwareModel {
+    /* Early DMG revision, with different boot ROM */
+    DMG0,
+    DMG,
+    /* Game Boy Pocket. Same as DMG, except for A register, which games use to tell them apart. */
+    MGB,
+    CGB,
+    SGB,
+}
+
+/* Register values left by boot ROM, right before jumping to 0x100 */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BootRegisters {
+    pub A: u8,
+    pub F: u8,
+    pub BC: u16,
+    pub DE: u16,
+    pub HL: u16,
+    pub SP: u16,
+    /* Whole system counter, DIV is its upper byte */
+    pub counter: u16,
+}
+
+pub const MODELS: [HardwareModel; 5] = [
+    HardwareModel::DMG0,
+    HardwareModel::DMG,
+    HardwareModel::MGB,
+    HardwareModel::CGB,
+    HardwareModel::SGB,
+];
+
+impl HardwareModel {
+    /*
+     * Model used when none was chosen. CGB isn't emulated yet, so CGB carts still get DMG, and SGB only
+     * when it's compiled in.
+     */
+    pub fn detect(header: &CartHeader) -> Self {
+        if cfg!(feature = "sgb") && header.sgb_support() {
+            HardwareModel::SGB
+        } else {
+            HardwareModel::DMG
+        }
+    }
+
+    /*
+     * Header checksum matters only on DMG and MGB, their boot ROM leaves H and C flags set when it's not 0.
+     */
+    pub fn boot_registers(self, header_checksum: u8) -> BootRegisters {
+        let HC = if header_checksum != 0 { 0x30 } else { 0x00 };
+        let (A, F, BC, DE, HL, counter) = match self {
+            HardwareModel::DMG0 => (0x01, 0x00, 0xFF13, 0x00C1, 0x8403, 0x1800),
+            HardwareModel::DMG => (0x01, 0x80 | HC, 0x0013, 0x00D8, 0x014D, 0xABCC),
+            HardwareModel::MGB => (0xFF, 0x80 | HC, 0x0013, 0x00D8, 0x014D, 0xABCC),
+            // DIV left by CGB and SGB boot ROMs varies, it depends on how long the logo animation took
+            HardwareModel::CGB => (0x11, 0x80, 0x0000, 0xFF56, 0x000D, 0x0000),
+            HardwareModel::SGB => (0x01, 0x00, 0x0014, 0x0000, 0xC060, 0x0000),
+        };
+        BootRegisters { A, F, BC, DE, HL, SP: 0xFFFE, counter }
+    }
+
+    pub fn bootrom_size(self) -> usize {
+        match self {
+            HardwareModel::CGB => CGB_BOOTROM_SIZE,
+            _ => BOOSTRAP_SIZE,
+        }
+    }
+
+    /*
+     * Leaves VRAM the way boot ROM does when it's done scrolling the logo: logo from cart header scaled up 2x,
+     * as tiles 1-24, followed by ®. Only DMG family boot ROMs leave the logo in VRAM, with others it's untouched.
+     */
+    pub fn draw_boot_logo(self, logo: &[Byte], vram: &mut [Byte]) {
+        if !matches!(self, HardwareModel::DMG0 | HardwareModel::DMG | HardwareModel::MGB) {
+            return;
+        }
+        // Each nibble is a row of 4 pixels, each pixel and row is doubled. Only lower bit plane is set.
+        let double = |nibble: Byte| (0..4).fold(0, |row, bit| row | (((nibble >> bit) & 1) * 0b11) << (bit * 2));
+        let mut offset = LOGO_TILES_OFFSET;
+        for byte in logo {
+            for nibble in [byte >> 4, byte & 0x0F] {
+                vram[offset] = double(nibble);
+                vram[offset + 2] = double(nibble);
+                offset += 4;
+            }
+        }
+        for (i, row) in REGISTERED.iter().enumerate() {
+            vram[REGISTERED_TILE_OFFSET + i * 2] = *row;
+        }
+        for i in 0..LOGO_WIDTH {
+            vram[LOGO_MAP_OFFSET + i] = (i + 1) as Byte;
+            vram[LOGO_MAP_OFFSET + 0x20 + i] = (LOGO_WIDTH + i + 1) as Byte;
+        }
+        vram[REGISTERED_MAP_OFFSET] = REGISTERED_TILE;
+    }
+
+    pub fn sgb(self) -> bool {
+        self == HardwareModel::SGB
+    }
+
+    pub fn cgb(self) -> bool {
+        self == HardwareModel::CGB
+    }
+
+    /* Bugs fixed in CGB. LY write behaviour isn't a difference between models. */
+    pub fn quirks(self) -> GPUQuirks {
+        GPUQuirks {
+            ly_write_resets: false,
+            stat_write_bug: !self.cgb(),
+            oam_bug: !self.cgb(),
+        }
+    }
+}
+
+impl fmt::Display for HardwareModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/* Case doesn't matter, so both "mgb" and "MGB" work */
+impl FromStr for HardwareModel {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        MODELS
+            .iter()
+            .find(|model| model.to_string().eq_ignore_ascii_case(text.trim()))
+            .cloned()
+            .ok_or_else(|| {
+                let names = MODELS.iter().map(|model| model.to_string()).collect::<Vec<String>>();
+                format!("unknown model {}, expected one of: {}", text, names.join(", "))
+            })
+    }
+}
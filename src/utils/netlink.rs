@@ -0,0 +1,222 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::super::dev::{SerialLink, DISCONNECTED};
+
+/* Sent by both sides right after connecting */
+const HELLO: [u8; 4] = *b"GBLK";
+const PROTOCOL_VERSION: u8 = 1;
+/* How long GB driving the clock waits for the other side before giving up on transfer */
+const TRANSFER_TIMEOUT: Duration = Duration::from_millis(100);
+
+const MSG_READY: u8 = 0x01;
+const MSG_TRANSFER: u8 = 0x02;
+const MSG_SYNC: u8 = 0x03;
+
+/* Every message is tag and one byte of payload */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Message {
+    /* Other side waits for transfer with this byte in SB */
+    Ready(u8),
+    /* Other side clocked transfer, sending this byte */
+    Transfer(u8),
+    /* Other side finished emulating a frame(lockstep only) */
+    Sync,
+}
+
+impl Message {
+    fn encode(self) -> [u8; 2] {
+        match self {
+            Message::Ready(byte) => [MSG_READY, byte],
+            Message::Transfer(byte) => [MSG_TRANSFER, byte],
+            Message::Sync => [MSG_SYNC, 0],
+        }
+    }
+
+    fn decode(bytes: [u8; 2]) -> Option<Self> {
+        match bytes[0] {
+            MSG_READY => Some(Message::Ready(bytes[1])),
+            MSG_TRANSFER => Some(Message::Transfer(bytes[1])),
+            MSG_SYNC => Some(Message::Sync),
+            _ => None,
+        }
+    }
+}
+
+/*
+ * Link cable over TCP. Either side can drive the clock, whichever GB sets SC bit 0 is the master for that transfer.
+ *
+ * To hide latency, GB waiting for transfer announces its SB right away(Ready), so when the other side clocks
+ * the transfer, reply is usually already there. If both sides drive the clock at once, they just swap bytes.
+ *
+ * In lockstep mode messages are applied only at frame boundaries, after both sides finished the same frame.
+ * What each GB sees then doesn't depend on network timing, so runs are deterministic, at cost of waiting
+ * for the other side every frame.
+ */
+pub struct NetLink {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+    lockstep: bool,
+    connected: bool,
+    /* Byte other side waits with */
+    peer_ready: Option<u8>,
+    /* Transfer clocked by other side, not yet seen by GB */
+    peer_transfer: Option<u8>,
+    /* Byte we've announced as ready, so it's not sent on every poll */
+    announced: Option<u8>,
+}
+
+impl NetLink {
+    /* Waits for the other side to connect */
+    pub fn listen(addr: impl ToSocketAddrs, lockstep: bool) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        NetLink::from_stream(stream, lockstep)
+    }
+
+    pub fn connect(addr: impl ToSocketAddrs, lockstep: bool) -> io::Result<Self> {
+        NetLink::from_stream(TcpStream::connect(addr)?, lockstep)
+    }
+
+    /* Exchanges hello with the other side. Both have to agree on protocol version and lockstep mode. */
+    pub fn from_stream(mut stream: TcpStream, lockstep: bool) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut hello = HELLO.to_vec();
+        hello.extend_from_slice(&[PROTOCOL_VERSION, lockstep as u8]);
+        stream.write_all(&hello)?;
+
+        let mut peer = [0; 6];
+        stream.read_exact(&mut peer)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if peer[..4] != HELLO || peer[4] != PROTOCOL_VERSION {
+            return Err(invalid("other side isn't a compatible emulator"));
+        }
+        if (peer[5] != 0) != lockstep {
+            return Err(invalid("both sides have to use lockstep mode, or neither"));
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut bytes = [0; 2];
+            while reader.read_exact(&mut bytes).is_ok() {
+                match Message::decode(bytes) {
+                    Some(msg) if tx.send(msg).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            incoming: rx,
+            lockstep,
+            connected: true,
+            peer_ready: None,
+            peer_transfer: None,
+            announced: None,
+        })
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    fn send(&mut self, msg: Message) {
+        if self.connected && self.stream.write_all(&msg.encode()).is_err() {
+            self.disconnected();
+        }
+    }
+
+    fn disconnected(&mut self) {
+        if self.connected {
+            log::warn!(target: "link", "other side disconnected");
+        }
+        self.connected = false;
+    }
+
+    fn apply(&mut self, msg: Message) {
+        match msg {
+            Message::Ready(byte) => self.peer_ready = Some(byte),
+            Message::Transfer(byte) => self.peer_transfer = Some(byte),
+            Message::Sync => {}
+        }
+    }
+
+    /* Applies everything received so far. In lockstep it's done only in frame(). */
+    fn poll(&mut self) {
+        if self.lockstep {
+            return;
+        }
+        loop {
+            match self.incoming.try_recv() {
+                Ok(msg) => self.apply(msg),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnected();
+                    break;
+                }
+            }
+        }
+    }
+
+    /* Blocks until other side sends its byte, or timeout passes */
+    fn wait_for_peer(&mut self) {
+        let deadline = Instant::now() + TRANSFER_TIMEOUT;
+        while self.connected && self.peer_ready.is_none() && self.peer_transfer.is_none() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.incoming.recv_timeout(left) {
+                Ok(msg) => self.apply(msg),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => self.disconnected(),
+            }
+        }
+    }
+}
+
+impl SerialLink for NetLink {
+    fn exchange(&mut self, out: u8) -> u8 {
+        self.send(Message::Transfer(out));
+        self.poll();
+        if !self.lockstep {
+            self.wait_for_peer();
+        }
+        // When both sides drive the clock, their transfers are replies to each other
+        self.peer_ready
+            .take()
+            .or_else(|| self.peer_transfer.take())
+            .unwrap_or(DISCONNECTED)
+    }
+
+    fn respond(&mut self, out: u8) -> Option<u8> {
+        if self.announced != Some(out) {
+            self.send(Message::Ready(out));
+            self.announced = Some(out);
+        }
+        self.poll();
+        let received = self.peer_transfer.take()?;
+        self.announced = None;
+        Some(received)
+    }
+
+    /* Lockstep: tell other side this frame is done, then apply what it sent during the same frame */
+    fn frame(&mut self) {
+        if !self.lockstep || !self.connected {
+            return;
+        }
+        self.send(Message::Sync);
+        loop {
+            match self.incoming.recv() {
+                Ok(Message::Sync) => break,
+                Ok(msg) => self.apply(msg),
+                Err(_) => {
+                    self.disconnected();
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::super::gpu::FRAME_CYCLES;
+
+/* CPU cycles per second of real hardware */
+const CYCLES_PER_SECOND: f64 = (1 << 20) as f64;
+/* Frames real DMG shows per second, ~59.7275 */
+pub const REFRESH_RATE: f64 = CYCLES_PER_SECOND / FRAME_CYCLES as f64;
+/* Falling behind by more frames than that(slow host, window dragged...) starts counting over, instead of rushing */
+const MAX_FRAMES_BEHIND: f64 = 4.0;
+
+/* What decides when frontend shows next frame */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Pacing {
+    /* Frames are pulled as audio device plays them, at whatever rate sound card clock has */
+    #[default]
+    Audio,
+    /* Exactly REFRESH_RATE by host clock, see FramePacer */
+    Exact,
+    /* One frame per host display refresh, tear free, but a bit too fast on 60 Hz displays */
+    Vsync,
+    /* As fast as possible, without sound, for benchmarking */
+    Uncapped,
+}
+
+impl FromStr for Pacing {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "audio" => Ok(Pacing::Audio),
+            "exact" => Ok(Pacing::Exact),
+            "vsync" => Ok(Pacing::Vsync),
+            "uncapped" => Ok(Pacing::Uncapped),
+            _ => Err(format!("unknown pacing {}, expected one of: audio, exact, vsync, uncapped", text)),
+        }
+    }
+}
+
+impl fmt::Display for Pacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Pacing::Audio => "audio",
+            Pacing::Exact => "exact",
+            Pacing::Vsync => "vsync",
+            Pacing::Uncapped => "uncapped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/*
+ * Paces frames by host clock. Deadline of each frame is counted from the start, not from the previous frame,
+ * so fraction of millisecond every frame takes over whole ones never adds up to drift.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct FramePacer {
+    start: Instant,
+    /* Frames done since start */
+    frames: u64,
+    /* Seconds per frame */
+    period: f64,
+}
+
+impl FramePacer {
+    pub fn new(rate: f64, now: Instant) -> Self {
+        Self { start: now, frames: 0, period: 1.0 / rate }
+    }
+
+    /* When frame after the ones done is due */
+    pub fn deadline(&self) -> Instant {
+        self.start + Duration::from_secs_f64(self.period * (self.frames + 1) as f64)
+    }
+
+    /* Time left until next frame is due, zero if it's late already */
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline().saturating_duration_since(now)
+    }
+
+    pub fn frame_done(&mut self, now: Instant) {
+        self.frames += 1;
+        let late = now.saturating_duration_since(self.deadline()).as_secs_f64();
+        if late > MAX_FRAMES_BEHIND * self.period {
+            self.restart(now);
+        }
+    }
+
+    /* Counts from now on, e.g. after pause */
+    pub fn restart(&mut self, now: Instant) {
+        self.start = now;
+        self.frames = 0;
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
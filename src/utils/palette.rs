@@ -0,0 +1,63 @@
+use super::super::gpu::{Color, PaletteLayer, BLACK, DARK_GRAY, LIGHT_GRAY, WHITE};
+
+/* Default DMG shades, from lightest to darkest, as GPU renders them */
+const SHADES: [Color; 4] = [WHITE, LIGHT_GRAY, DARK_GRAY, BLACK];
+
+/*
+ * Colors GB shades are shown with. Each of BGP, OBP0 and OBP1 layers uses the 4 shades, unless it has own colors
+ * set, the way GBC colorizes DMG games. Can be changed any time, next frame mapped through it shows the change.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    shades: [Color; 4],
+    layers: [Option<[Color; 4]>; 3],
+}
+
+impl Palette {
+    /* Same shades for every layer, from lightest to darkest */
+    pub fn new(shades: [Color; 4]) -> Self {
+        Self { shades, layers: [None; 3] }
+    }
+
+    pub fn shades(&self) -> [Color; 4] {
+        self.shades
+    }
+
+    pub fn set_shades(&mut self, shades: [Color; 4]) {
+        self.shades = shades;
+    }
+
+    /* Shade 0-3, lightest is 0. Panics on other shades. */
+    pub fn set_shade(&mut self, shade: usize, color: Color) {
+        self.shades[shade] = color;
+    }
+
+    /* Colors layer is shown with, its own or the shared shades */
+    pub fn layer(&self, layer: PaletteLayer) -> [Color; 4] {
+        self.layers[layer as usize].unwrap_or(self.shades)
+    }
+
+    /* Own colors of layer, None makes it use the shared shades again */
+    pub fn set_layer(&mut self, layer: PaletteLayer, colors: Option<[Color; 4]>) {
+        self.layers[layer as usize] = colors;
+    }
+
+    /* Maps color GPU rendered with default shades, other colors are left as they are */
+    pub fn colorize(&self, color: Color, layer: PaletteLayer) -> Color {
+        match SHADES.iter().position(|shade| *shade == color) {
+            Some(shade) => self.layer(layer)[shade],
+            None => color,
+        }
+    }
+
+    /* Picture with each pixel mapped by the layer it was drawn on, see GPU::completed_layers */
+    pub fn apply(&self, pixels: &[Color], layers: &[PaletteLayer]) -> Vec<Color> {
+        pixels.iter().zip(layers).map(|(color, layer)| self.colorize(*color, *layer)).collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new(SHADES)
+    }
+}
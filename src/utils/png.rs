@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+/* IHDR color type */
+const COLOR_GRAYSCALE: u8 = 0;
+const COLOR_RGB: u8 = 2;
+
+/* Chunk is length, type, data and CRC of type+data */
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/* Encodes 8 bit image, pixels are rows of width*channels bytes */
+fn encode(width: usize, height: usize, color_type: u8, channels: usize, pixels: &[u8]) -> io::Result<Vec<u8>> {
+    let stride = width * channels;
+    if pixels.len() != stride * height {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "pixel buffer doesn't match image size"));
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, color type, compression, filter, interlace
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    // Each row starts with filter type, 0 is no filtering
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in pixels.chunks(stride.max(1)).take(height) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let data = encoder.finish()?;
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &data);
+    chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+/* 8 bit grayscale image, one byte per pixel */
+pub fn encode_gray_png(width: usize, height: usize, pixels: &[u8]) -> io::Result<Vec<u8>> {
+    encode(width, height, COLOR_GRAYSCALE, 1, pixels)
+}
+
+/* 8 bit RGB image, three bytes per pixel */
+pub fn encode_rgb_png(width: usize, height: usize, pixels: &[u8]) -> io::Result<Vec<u8>> {
+    encode(width, height, COLOR_RGB, 3, pixels)
+}
+
+pub fn write_gray_png(path: &Path, width: usize, height: usize, pixels: &[u8]) -> io::Result<()> {
+    fs::write(path, encode_gray_png(width, height, pixels)?)
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::super::gpu::Color;
+use super::super::mem::BankController;
+use super::super::serial::{SerialLink, DISCONNECTED};
+use super::super::state::Runtime;
+use super::env::{run_frame, set_buttons};
+
+/* Action bitmask(see BUTTON_*) held from given frame on, until next entry. Entries are sorted by frame. */
+pub type InputScript = Vec<(u64, u8)>;
+
+/* Single GB to run, already set up the way it should start */
+pub struct Job<T: BankController> {
+    pub runtime: Runtime<T>,
+    pub script: InputScript,
+}
+
+/* What job left behind */
+pub struct JobResult<T: BankController> {
+    /* For anything else worth checking, like memory */
+    pub runtime: Runtime<T>,
+    /* Screen after the last frame */
+    pub framebuffer: Vec<Color>,
+    /* Bytes sent through link cable, test ROMs report results this way. Only collected when nothing was connected. */
+    pub serial: Vec<u8>,
+}
+
+/* Behaves like disconnected cable, but remembers what was sent */
+struct SerialCapture {
+    sent: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SerialLink for SerialCapture {
+    fn exchange(&mut self, out: u8) -> u8 {
+        self.sent.lock().unwrap().push(out);
+        DISCONNECTED
+    }
+
+    fn respond(&mut self, _: u8) -> Option<u8> {
+        None
+    }
+}
+
+/*
+ * Runs many independent GBs for fixed number of frames, spread over a pool of threads. Meant for fuzzing,
+ * training agents and running test ROMs in bulk. Jobs don't share anything, so results don't depend on
+ * number of threads. Panic inside a job is passed on to caller of run().
+ */
+pub struct Runner {
+    threads: usize,
+}
+
+impl Runner {
+    /* 0 threads means one per CPU core */
+    pub fn new(threads: usize) -> Self {
+        let threads = match threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        Self { threads }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /* Results are in the same order as jobs */
+    pub fn run<T: BankController + Send>(&self, jobs: Vec<Job<T>>, frames: u64) -> Vec<JobResult<T>> {
+        let count = jobs.len();
+        let queue = Mutex::new(jobs.into_iter().enumerate().collect::<VecDeque<(usize, Job<T>)>>());
+        let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<JobResult<T>>>>());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(count) {
+                scope.spawn(|| loop {
+                    // Lock is dropped before running the job, so others can take theirs meanwhile
+                    let next = queue.lock().unwrap().pop_front();
+                    match next {
+                        Some((i, job)) => {
+                            let result = run_job(job, frames);
+                            results.lock().unwrap()[i] = Some(result);
+                        }
+                        None => break,
+                    }
+                });
+            }
+        });
+        results.into_inner().unwrap().into_iter().map(|result| result.unwrap()).collect()
+    }
+}
+
+fn run_job<T: BankController>(mut job: Job<T>, frames: u64) -> JobResult<T> {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let capture = !job.runtime.state.serial.connected();
+    if capture {
+        job.runtime.state.serial.connect(Box::new(SerialCapture { sent: sent.clone() }));
+    }
+    let mut script = job.script.iter().peekable();
+    for frame in 0..frames {
+        while let Some((_, action)) = script.next_if(|(at, _)| *at <= frame) {
+            set_buttons(&mut job.runtime.state.joypad, *action);
+        }
+        run_frame(&mut job.runtime);
+    }
+    if capture {
+        job.runtime.state.serial.disconnect();
+    }
+    let serial = std::mem::take(&mut *sent.lock().unwrap());
+    JobResult {
+        framebuffer: job.runtime.state.gpu.completed_frame().to_vec(),
+        serial,
+        runtime: job.runtime,
+    }
+}
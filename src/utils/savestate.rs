@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::super::gpu::{Color, SCREEN_HEIGHT, SCREEN_WIDTH};
+use super::super::mem::BankController;
+use super::super::state::Runtime;
+use super::header::{HEADER_END, HEADER_START};
+use super::png::encode_rgb_png;
+
+pub const SAVESTATE_MAGIC: [u8; 4] = *b"GBST";
+/* Bumped whenever serialized state changes shape, older states can't be loaded then */
+pub const SAVESTATE_VERSION: u16 = 14;
+/* Thumbnail is GB screen scaled down by half */
+pub const THUMBNAIL_WIDTH: usize = SCREEN_WIDTH / 2;
+pub const THUMBNAIL_HEIGHT: usize = SCREEN_HEIGHT / 2;
+
+/*
+ * Save state file. All numbers are little endian:
+ * MAGIC(4) | VERSION(2) | HEADER HASH(4) | THUMBNAIL LENGTH(4) | THUMBNAIL(PNG) | STATE LENGTH(4) | STATE
+ *
+ * State is Runtime serialized with bincode and compressed with zlib. Header hash is CRC32 of cart header,
+ * so state isn't loaded into other game.
+ */
+pub struct SaveState {
+    pub header_hash: u32,
+    /* Screen at the moment of saving, as PNG */
+    pub thumbnail: Vec<u8>,
+    state: Vec<u8>,
+}
+
+/* CRC32 of cart header(0x100-0x14F), as mapped in bank 0 */
+pub fn header_hash(runtime: &mut Runtime<impl BankController>) -> u32 {
+    let mut crc = crc32fast::Hasher::new();
+    if let Some(header) = runtime.state.mmu.mapper.rom().get(HEADER_START..HEADER_END) {
+        crc.update(header);
+    }
+    crc.finalize()
+}
+
+fn invalid(msg: &str) -> String {
+    format!("invalid save state: {}", msg)
+}
+
+/* Lengths come from the file, so they're checked before allocating anything */
+fn read_bytes(bytes: &mut &[u8], len: usize) -> Result<Vec<u8>, String> {
+    if bytes.len() < len {
+        return Err(invalid("file is truncated"));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken.to_vec())
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, String> {
+    let b = read_bytes(bytes, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+impl SaveState {
+    /* Screen is SCREEN_WIDTH x SCREEN_HEIGHT pixels, with final colors */
+    pub fn capture<T: BankController + Serialize>(runtime: &mut Runtime<T>, screen: &[Color]) -> Result<Self, String> {
+        let serialized = bincode::serialize(runtime).map_err(|e| e.to_string())?;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&serialized).map_err(|e| e.to_string())?;
+        let state = encoder.finish().map_err(|e| e.to_string())?;
+        Ok(Self {
+            header_hash: header_hash(runtime),
+            thumbnail: SaveState::thumbnail(screen).map_err(|e| e.to_string())?,
+            state,
+        })
+    }
+
+    fn thumbnail(screen: &[Color]) -> io::Result<Vec<u8>> {
+        let mut pixels = Vec::with_capacity(THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 3);
+        for y in 0..THUMBNAIL_HEIGHT {
+            for x in 0..THUMBNAIL_WIDTH {
+                let (r, g, b) = screen.get(2 * y * SCREEN_WIDTH + 2 * x).cloned().unwrap_or((0, 0, 0));
+                pixels.extend_from_slice(&[r, g, b]);
+            }
+        }
+        encode_rgb_png(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, &pixels)
+    }
+
+    /*
+     * Replaces emulated state of runtime. Things which aren't part of the state(link cable, memory observers, bus
+     * policy and stats, interrupt latency, event log, frame callbacks, doctor, debugger, watchdog, symbols, call
+     * trace setting, GPU layer flags, pause, speed and sample rate) are kept.
+     * Call trace starts over empty and watchdog is armed again. Fails without touching runtime when state belongs
+     * to other game.
+     */
+    pub fn restore<T: BankController + DeserializeOwned>(&self, runtime: &mut Runtime<T>) -> Result<(), String> {
+        if self.header_hash != header_hash(runtime) {
+            return Err(String::from("save state was made with other ROM"));
+        }
+        let mut serialized = Vec::new();
+        ZlibDecoder::new(&self.state[..])
+            .read_to_end(&mut serialized)
+            .map_err(|e| invalid(&e.to_string()))?;
+        let mut restored: Runtime<T> = bincode::deserialize(&serialized).map_err(|e| invalid(&e.to_string()))?;
+        if let Some(link) = runtime.state.serial.disconnect() {
+            restored.state.serial.connect(link);
+        }
+        restored.state.mmu.observers = std::mem::take(&mut runtime.state.mmu.observers);
+        restored.state.mmu.policy = runtime.state.mmu.policy;
+        restored.state.mmu.stats = runtime.state.mmu.stats;
+        // Battery save file stays mapped, restored RAM is written into it
+        if let (Some(ram), Some(mapped)) = (restored.state.mmu.mapper.cart_ram(), runtime.state.mmu.mapper.cart_ram()) {
+            ram.take_mapping(mapped);
+        }
+        restored.bus = runtime.bus;
+        // Pending requests were timed on the other timeline
+        restored.latency = runtime.latency;
+        restored.latency.restart();
+        // Loaded state is just another frame of the run being audited
+        restored.audit = runtime.audit.take();
+        restored.events = runtime.events.take();
+        restored.state.doctor = runtime.state.doctor.take();
+        restored.state.debugger = runtime.state.debugger.take();
+        // Program goes on from somewhere else, so watchdog is armed again
+        restored.state.watchdog = runtime.state.watchdog.take();
+        if let Some(watchdog) = restored.state.watchdog.as_mut() {
+            watchdog.reset();
+        }
+        restored.state.symbols = runtime.state.symbols.take();
+        restored.state.gpu.show_background = runtime.state.gpu.show_background;
+        restored.state.gpu.show_window = runtime.state.gpu.show_window;
+        restored.state.gpu.show_sprites = runtime.state.gpu.show_sprites;
+        restored.state.apu.keep_mixer(&mut runtime.state.apu);
+        // Audio device was opened with it
+        restored.state.apu.set_sample_rate(runtime.state.apu.sample_rate());
+        restored.state.joypad.set_turbo_frames(runtime.state.joypad.turbo_frames());
+        restored.callbacks = std::mem::take(&mut runtime.callbacks);
+        restored.cpu.set_call_trace(runtime.cpu.call_trace());
+        restored.set_paused(runtime.paused());
+        restored.set_speed(runtime.speed());
+        *runtime = restored;
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = SAVESTATE_MAGIC.to_vec();
+        out.extend_from_slice(&SAVESTATE_VERSION.to_le_bytes());
+        out.extend_from_slice(&self.header_hash.to_le_bytes());
+        out.extend_from_slice(&(self.thumbnail.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.thumbnail);
+        out.extend_from_slice(&(self.state.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.state);
+        out
+    }
+
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, String> {
+        if read_bytes(&mut bytes, 4)? != SAVESTATE_MAGIC {
+            return Err(invalid("not a save state"));
+        }
+        let version = read_bytes(&mut bytes, 2)?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version != SAVESTATE_VERSION {
+            return Err(format!(
+                "save state version {} isn't supported, expected {}",
+                version, SAVESTATE_VERSION
+            ));
+        }
+        let header_hash = read_u32(&mut bytes)?;
+        let len = read_u32(&mut bytes)? as usize;
+        let thumbnail = read_bytes(&mut bytes, len)?;
+        let len = read_u32(&mut bytes)? as usize;
+        let state = read_bytes(&mut bytes, len)?;
+        Ok(Self { header_hash, thumbnail, state })
+    }
+
+    /* Written to temporary file first, so crash while saving doesn't destroy previous state */
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        let tmp = path.with_file_name(name);
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&self.encode())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        SaveState::decode(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,24 @@
+use std::convert::TryInto;
+
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+/*
+ * Serde implements arrays only up to 32 elements. Longer ones are (de)serialized as sequence with this,
+ * through #[serde(with = "crate::utils::serde_array")].
+ */
+pub fn serialize<S: Serializer, T: Serialize, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(array.iter())
+}
+
+pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let items: Vec<T> = Deserialize::deserialize(deserializer)?;
+    let len = items.len();
+    items
+        .try_into()
+        .map_err(|_| D::Error::invalid_length(len, &format!("array of {} elements", N).as_str()))
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::de::{Deserializer, Error};
+#[cfg(feature = "serde")]
+use serde::ser::Serializer;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::super::gpu::Color;
+#[cfg(feature = "serde")]
+use super::config::parse_palette;
+use super::config::EmuConfig;
+use super::header::CartHeader;
+
+/* Session file, kept in save directory next to battery saves and save states */
+pub const SESSION_FILE: &str = "session.toml";
+
+/*
+ * What emulator remembers between runs: last ROM, window size and settings of each game played. Written back on
+ * exit, games are keyed by their header(see Session::key). Example:
+ *
+ * last_rom = "/home/user/roms/tetris.gb"
+ * window = [640, 576]
+ *
+ * [games."TETRIS 0BF6"]
+ * palette = ["#E0F8D0", "#88C070", "#346856", "#081820"]
+ * slot = 2
+ *
+ * [games."TETRIS 0BF6".keys]
+ * a = ["J"]
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(default, deny_unknown_fields))]
+pub struct Session {
+    pub last_rom: Option<PathBuf>,
+    /* Window size in host pixels, while it wasn't fullscreen */
+    pub window: Option<(u32, u32)>,
+    pub games: BTreeMap<String, GameSettings>,
+}
+
+/*
+ * Settings of one game, applied on top of config each time it's opened.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize), serde(default, deny_unknown_fields))]
+pub struct GameSettings {
+    /* Palette picked with --palette last time the game was run with one */
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "serialize_palette", deserialize_with = "deserialize_palette")
+    )]
+    pub palette: Option<[Color; 4]>,
+    /* Save state slot last saved to or loaded from */
+    pub slot: Option<usize>,
+    /* Keys bound to actions, by the same names as in [keys]. Actions left out keep keys from config. */
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+impl Session {
+    #[cfg(feature = "serde")]
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn from_toml(_text: &str) -> Result<Self, String> {
+        Err(String::from("session files need serde feature"))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn to_toml(&self) -> Result<String, String> {
+        Err(String::from("session files need serde feature"))
+    }
+
+    /* Missing file is an empty session, like on the first run. Builds without serde always start with one. */
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() || cfg!(not(feature = "serde")) {
+            return Ok(Session::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Session::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /* Nothing is written without serde, session isn't kept then */
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if cfg!(not(feature = "serde")) {
+            return Ok(());
+        }
+        fs::write(path, self.to_toml()?).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /* Title alone isn't unique(hacks, revisions, homebrew), so global checksum is part of the key */
+    pub fn key(header: &CartHeader) -> String {
+        format!("{} {:04X}", header.title().trim(), header.global_checksum())
+    }
+
+    /* Settings remembered for the game with given key, empty if it wasn't played before */
+    pub fn game(&self, key: &str) -> GameSettings {
+        self.games.get(key).cloned().unwrap_or_default()
+    }
+
+    pub fn game_mut(&mut self, key: &str) -> &mut GameSettings {
+        self.games.entry(String::from(key)).or_default()
+    }
+}
+
+impl GameSettings {
+    /* Config with game's palette and keys put in. Fails on keys of unknown action. */
+    pub fn apply(&self, config: &EmuConfig) -> Result<EmuConfig, String> {
+        let mut config = config.clone();
+        if let Some(palette) = self.palette {
+            config.palette = palette;
+        }
+        for (action, names) in &self.keys {
+            *config.keys.action_mut(action).ok_or_else(|| format!("unknown action {}", action))? = names.clone();
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_palette<S: Serializer>(palette: &Option<[Color; 4]>, serializer: S) -> Result<S::Ok, S::Error> {
+    palette
+        .map(|colors| colors.iter().map(|(r, g, b)| format!("#{:02X}{:02X}{:02X}", r, g, b)).collect::<Vec<_>>())
+        .serialize(serializer)
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<[Color; 4]>, D::Error> {
+    let colors: Vec<String> = Deserialize::deserialize(deserializer)?;
+    parse_palette(&colors.join(","))
+        .map(Some)
+        .ok_or_else(|| D::Error::custom("palette must be 4 colors in #RRGGBB format"))
+}
@@ -0,0 +1,66 @@
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+
+use super::super::gpu::{Color, SCREEN_HEIGHT, SCREEN_WIDTH};
+use super::super::mem::BankController;
+use super::super::state::Runtime;
+use super::env::run_frame;
+use super::header::CartHeader;
+use super::loader::load_rom;
+use super::model::HardwareModel;
+use super::png::encode_rgb_png;
+
+/* About 5 seconds, most games get past logos and intros to the title screen by then */
+pub const SUMMARY_FRAMES: u64 = 300;
+
+/* What a game library shows about a ROM, see summarize */
+pub struct RomSummary {
+    pub header: CartHeader,
+    /* Model the game was run on, picked based on header */
+    pub model: HardwareModel,
+    /* Screen after the frames were run, DMG shades without any palette */
+    pub screenshot: Vec<Color>,
+}
+
+impl RomSummary {
+    pub fn title(&self) -> String {
+        self.header.title().trim().to_string()
+    }
+
+    /* Screenshot as PNG, SCREEN_WIDTH x SCREEN_HEIGHT */
+    pub fn screenshot_png(&self) -> io::Result<Vec<u8>> {
+        let pixels = self.screenshot.iter().flat_map(|(r, g, b)| [*r, *g, *b]).collect::<Vec<u8>>();
+        encode_rgb_png(SCREEN_WIDTH, SCREEN_HEIGHT, &pixels)
+    }
+}
+
+/*
+ * Runs ROM headlessly, without boot ROM and with no buttons pressed, for given number of frames(SUMMARY_FRAMES
+ * is a good start) and takes a picture of where it got. Nothing is written anywhere, so many ROMs can be summed
+ * up at once, e.g. with one thread each.
+ */
+pub fn summarize<T>(rom: Vec<u8>, frames: u64) -> Result<RomSummary, String>
+where
+    T: BankController + TryFrom<Vec<u8>, Error = String>,
+{
+    let header = CartHeader::from_rom(&rom).ok_or_else(|| String::from("ROM is too small to contain cart header"))?;
+    let model = HardwareModel::detect(&header);
+    let mut runtime = Runtime::new(T::try_from(rom)?);
+    runtime.state.gpu.quirks = model.quirks();
+    runtime.skip_bootrom(model);
+    for _ in 0..frames {
+        run_frame(&mut runtime);
+    }
+    let screenshot = runtime.state.gpu.completed_frame().to_vec();
+    Ok(RomSummary { header, model, screenshot })
+}
+
+/* Same as summarize, for ROM file or zip archive with one. Header problems don't stop it, browsers list any ROM. */
+pub fn summarize_file<T>(path: &Path, frames: u64) -> Result<RomSummary, String>
+where
+    T: BankController + TryFrom<Vec<u8>, Error = String>,
+{
+    let loaded = load_rom(path, false)?;
+    summarize::<T>(loaded.rom, frames).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use super::super::mem::Addr;
+
+/*
+ * Labels from symbol file made by assembler: RGBDS(rgblink -n) or WLA-DX(-S). Both list them as "BANK:ADDR name",
+ * WLA-DX keeps them in [labels] section, next to other sections which are skipped. Bank only matters in switchable
+ * ROM, labels anywhere else are looked up by address alone.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    labels: BTreeMap<(usize, Addr), String>,
+    addresses: HashMap<String, (usize, Addr)>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Symbols::new();
+        // Before any section header it's RGBDS file, which has labels only
+        let mut in_labels = true;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_labels = line == "[labels]";
+                continue;
+            }
+            if !in_labels {
+                continue;
+            }
+            let invalid = || format!("line {}: expected BANK:ADDR label, got '{}'", n + 1, line);
+            let mut parts = line.split_whitespace();
+            let (bank, addr) = parts.next().and_then(|at| at.split_once(':')).ok_or_else(invalid)?;
+            let name = parts.next().ok_or_else(invalid)?;
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            let addr = Addr::from_str_radix(addr, 16).map_err(|_| invalid())?;
+            symbols.insert(bank, addr, name);
+        }
+        Ok(symbols)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Symbols::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /* First label at an address is the one shown, others can still be looked up by name */
+    pub fn insert(&mut self, bank: usize, addr: Addr, name: &str) {
+        let key = (bank_key(bank, addr), addr);
+        self.labels.entry(key).or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /* Label placed exactly at addr, with given ROM bank mapped */
+    pub fn label(&self, bank: usize, addr: Addr) -> Option<&str> {
+        self.labels.get(&(bank_key(bank, addr), addr)).map(|name| name.as_str())
+    }
+
+    /* Bank and address of label */
+    pub fn address(&self, name: &str) -> Option<(usize, Addr)> {
+        self.addresses.get(name).cloned()
+    }
+
+    /* Closest label at or before addr, in the same memory area, as "label" or "label+$offset" */
+    pub fn symbolize(&self, bank: usize, addr: Addr) -> Option<String> {
+        let bank = bank_key(bank, addr);
+        let (&(_, start), name) = self.labels.range((bank, area_start(addr))..=(bank, addr)).next_back()?;
+        Some(match addr - start {
+            0 => name.clone(),
+            offset => format!("{}+${:X}", name, offset),
+        })
+    }
+
+    /* Replaces addresses($XXXX) in disassembled instruction with labels placed at them */
+    pub fn annotate(&self, text: &str, bank: usize) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            let digits = rest[i + 1..].chars().take_while(|c| c.is_ascii_hexdigit()).count();
+            let operand = &rest[i..i + 1 + digits];
+            let label = match digits {
+                4 => Addr::from_str_radix(&operand[1..], 16).ok().and_then(|addr| self.label(bank, addr)),
+                _ => None,
+            };
+            out.push_str(label.unwrap_or(operand));
+            rest = &rest[i + 1 + digits..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/* Only switchable ROM has banks which can be told apart, no CGB RAM banking yet */
+fn bank_key(bank: usize, addr: Addr) -> usize {
+    if (0x4000..0x8000).contains(&addr) {
+        bank
+    } else {
+        0
+    }
+}
+
+/* Labels don't reach past the memory area they're in */
+fn area_start(addr: Addr) -> Addr {
+    match addr {
+        0x0000..=0x3FFF => 0x0000,
+        0x4000..=0x7FFF => 0x4000,
+        0x8000..=0x9FFF => 0x8000,
+        0xA000..=0xBFFF => 0xA000,
+        0xC000..=0xDFFF => 0xC000,
+        0xE000..=0xFDFF => 0xE000,
+        0xFE00..=0xFEFF => 0xFE00,
+        0xFF00..=0xFF7F => 0xFF00,
+        _ => 0xFF80,
+    }
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::Path;
+
+use super::super::gpu::{GPUMode, SCANLINE_CYCLES, SCREEN_HEIGHT, VBLANK_HEIGHT};
+use super::debugger::Interrupt;
+use super::events::{EventKind, EventLog};
+use super::png::encode_rgb_png;
+
+/* Lines of a frame, LY goes 0 to 153 */
+pub const TIMELINE_LINES: usize = SCREEN_HEIGHT + VBLANK_HEIGHT;
+const LINE_CYCLES: usize = SCANLINE_CYCLES as usize;
+/* PNG pixels per machine cycle across and per line down, so line is 456 pixels, one per dot */
+const CYCLE_WIDTH: usize = 4;
+const LINE_HEIGHT: usize = 3;
+
+const INTERRUPT_COLORS: [[u8; 3]; 5] =
+    [[0xFF, 0x00, 0x00], [0xFF, 0x00, 0xFF], [0x00, 0xFF, 0xFF], [0xFF, 0xFF, 0xFF], [0xFF, 0x80, 0x00]];
+
+/* Interrupt entered at given line and machine cycle within it */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterruptMark {
+    pub line: usize,
+    pub cycle: usize,
+    pub interrupt: Interrupt,
+}
+
+/*
+ * One frame of the event log laid out as 154 scanlines, with PPU mode of each machine cycle and interrupts CPU
+ * jumped to. Visible lines start where the log has their OAM search, VBlank ones follow each SCANLINE_CYCLES after
+ * VBlank starts. Saved as text or PNG, it shows at a glance where raster effects land and which interrupts come
+ * late. Log needs ppu category, interrupt one adds the marks.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameTimeline {
+    /* Cycle of the log frame started at */
+    start: u64,
+    /* Row of LINE_CYCLES per line, None past the end of line */
+    modes: Vec<Option<GPUMode>>,
+    interrupts: Vec<InterruptMark>,
+}
+
+impl FrameTimeline {
+    /* Frame with n-th VBlank of the log, the oldest is 0. None when log doesn't have all its visible lines. */
+    pub fn frame(log: &EventLog, n: usize) -> Option<Self> {
+        Self::build(log, n).map(|(timeline, _)| timeline)
+    }
+
+    /* Latest frame the log has all of, None before any */
+    pub fn last(log: &EventLog) -> Option<Self> {
+        let vblanks = log.events().filter(|event| event.kind == EventKind::Mode(GPUMode::VBLANK)).count();
+        (0..vblanks).rev().filter_map(|n| Self::build(log, n)).find(|(_, complete)| *complete).map(|(t, _)| t)
+    }
+
+    /* Frame and whether the log goes on past its end */
+    fn build(log: &EventLog, n: usize) -> Option<(Self, bool)> {
+        let changes = log
+            .events()
+            .filter_map(|event| match event.kind {
+                EventKind::Mode(mode) => Some((event.cycle, mode)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let vblank = changes.iter().enumerate().filter(|(_, (_, mode))| *mode == GPUMode::VBLANK).nth(n)?.0;
+        let oam_starts = |changes: &[(u64, GPUMode)]| {
+            changes.iter().filter(|(_, mode)| *mode == GPUMode::OAM_SEARCH).map(|(cycle, _)| *cycle).collect::<Vec<_>>()
+        };
+        // Visible lines are the ones since previous VBlank
+        let previous = changes[..vblank].iter().rposition(|(_, mode)| *mode == GPUMode::VBLANK).map_or(0, |i| i + 1);
+        let visible = oam_starts(&changes[previous..vblank]);
+        if visible.len() < SCREEN_HEIGHT {
+            return None;
+        }
+        let vblank_start = changes[vblank].0;
+        let next_frame = oam_starts(&changes[vblank..]).first().copied();
+        let mut starts = visible[visible.len() - SCREEN_HEIGHT..].to_vec();
+        starts.extend((0..VBLANK_HEIGHT as u64).map(|line| vblank_start + line * SCANLINE_CYCLES));
+        let end = next_frame.unwrap_or(vblank_start + VBLANK_HEIGHT as u64 * SCANLINE_CYCLES);
+
+        let mut modes = vec![None; TIMELINE_LINES * LINE_CYCLES];
+        let mut mode = None;
+        let mut next_change = changes.iter().peekable();
+        for (line, start) in starts.iter().enumerate() {
+            let line_end = starts.get(line + 1).copied().unwrap_or(end).min(start + SCANLINE_CYCLES);
+            for cycle in *start..line_end {
+                while let Some((_, next)) = next_change.next_if(|(at, _)| *at <= cycle) {
+                    mode = Some(*next);
+                }
+                modes[line * LINE_CYCLES + (cycle - start) as usize] = mode;
+            }
+        }
+        let interrupts = log
+            .events()
+            .filter(|event| (starts[0]..end).contains(&event.cycle))
+            .filter_map(|event| match event.kind {
+                EventKind::Interrupt(interrupt) => {
+                    let line = starts.iter().rposition(|start| *start <= event.cycle).unwrap_or(0);
+                    Some(InterruptMark { line, cycle: (event.cycle - starts[line]) as usize, interrupt })
+                }
+                _ => None,
+            })
+            .collect();
+        Some((Self { start: starts[0], modes, interrupts }, next_frame.is_some()))
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /* Mode during machine cycle of the line, None when not known or line is shorter */
+    pub fn mode(&self, line: usize, cycle: usize) -> Option<GPUMode> {
+        if line >= TIMELINE_LINES || cycle >= LINE_CYCLES {
+            return None;
+        }
+        self.modes[line * LINE_CYCLES + cycle]
+    }
+
+    pub fn interrupts(&self) -> &[InterruptMark] {
+        &self.interrupts
+    }
+
+    /*
+     * One row per line, one character per machine cycle: O OAM search, D drawing, H HBlank, V VBlank, . none.
+     * Interrupt is its IF bit over the cycle it was entered at(or the last one of the row), and is named at the end
+     * of the row with the actual cycle.
+     */
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Frame at cycle {}\n", self.start);
+        for line in 0..TIMELINE_LINES {
+            let mut row = (0..LINE_CYCLES).map(|cycle| mode_char(self.mode(line, cycle))).collect::<Vec<char>>();
+            let marks = self.interrupts.iter().filter(|mark| mark.line == line).collect::<Vec<_>>();
+            for mark in &marks {
+                row[mark.cycle.min(LINE_CYCLES - 1)] = (b'0' + mark.interrupt.bit()) as char;
+            }
+            let names = marks.iter().map(|mark| format!("{:?}@{}", mark.interrupt, mark.cycle)).collect::<Vec<_>>();
+            text += format!("{:3} |{}| {}", line, row.into_iter().collect::<String>(), names.join(" ")).trim_end();
+            text.push('\n');
+        }
+        text += "O OAM search, D drawing, H HBlank, V VBlank, . none, \
+                 0 VBlank, 1 Stat, 2 Timer, 3 Serial, 4 Joypad interrupt\n";
+        text
+    }
+
+    /* Picture of the frame, interrupts are bright lines across the cycle they were entered at */
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let (width, height) = (LINE_CYCLES * CYCLE_WIDTH, TIMELINE_LINES * LINE_HEIGHT);
+        let mut pixels = vec![0; width * height * 3];
+        let mut paint = |line: usize, cycle: usize, color: [u8; 3]| {
+            for y in line * LINE_HEIGHT..(line + 1) * LINE_HEIGHT {
+                let start = (y * width + cycle * CYCLE_WIDTH) * 3;
+                pixels[start..start + CYCLE_WIDTH * 3].chunks_mut(3).for_each(|pixel| pixel.copy_from_slice(&color));
+            }
+        };
+        for line in 0..TIMELINE_LINES {
+            for cycle in 0..LINE_CYCLES {
+                paint(line, cycle, mode_color(self.mode(line, cycle)));
+            }
+        }
+        for mark in &self.interrupts {
+            paint(mark.line, mark.cycle.min(LINE_CYCLES - 1), INTERRUPT_COLORS[mark.interrupt.bit() as usize]);
+        }
+        encode_rgb_png(width, height, &pixels).map_err(|e| e.to_string())
+    }
+
+    /* As PNG when path ends with .png, as text otherwise */
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            self.to_png()?
+        } else {
+            self.to_text().into_bytes()
+        };
+        fs::write(path, data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+fn mode_char(mode: Option<GPUMode>) -> char {
+    match mode {
+        Some(GPUMode::OAM_SEARCH) => 'O',
+        Some(GPUMode::LCD_TRANSFER) => 'D',
+        Some(GPUMode::HBLANK) => 'H',
+        Some(GPUMode::VBLANK) => 'V',
+        None => '.',
+    }
+}
+
+fn mode_color(mode: Option<GPUMode>) -> [u8; 3] {
+    match mode {
+        Some(GPUMode::OAM_SEARCH) => [0xE0, 0xB0, 0x30],
+        Some(GPUMode::LCD_TRANSFER) => [0x30, 0xA0, 0x50],
+        Some(GPUMode::HBLANK) => [0x30, 0x50, 0xA0],
+        Some(GPUMode::VBLANK) => [0x70, 0x70, 0x70],
+        // No mode event yet, e.g. LCD was off
+        None => [0x20, 0x20, 0x20],
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::super::mem::Addr;
+
+/* Sign that program isn't getting anywhere */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Runaway {
+    /* CPU ran this many cycles without VBlank: LCD left off, or game hung before turning it on */
+    NoVblank { cycles: u64 },
+    /* Instruction at pc ran this many times in a row with interrupts disabled, like JR -2 after DI */
+    Stuck { pc: Addr, instructions: u64 },
+}
+
+impl fmt::Display for Runaway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Runaway::NoVblank { cycles } => write!(f, "no VBlank for {} cycles", cycles),
+            Runaway::Stuck { pc, instructions } => {
+                write!(f, "PC stuck at 0x{:04X} with interrupts disabled for {} instructions", pc, instructions)
+            }
+        }
+    }
+}
+
+/* What happens once watchdog fires */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchdogAction {
+    /* Logged as warning, emulation goes on */
+    Report,
+    /* Runtime stops executing anything, as if paused, until reset */
+    Abort,
+}
+
+/*
+ * Watchdog. Fires when emulated program looks hung, so unattended runs(test ROMs in CI, Runner jobs) don't spin
+ * forever. Checked by Runtime::step after each instruction when set in State. It fires once, reset arms it again.
+ * Reports are logged with "watchdog" target.
+ */
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    /* Limits, None turns the check off */
+    max_cycles: Option<u64>,
+    max_stuck: Option<u64>,
+    action: WatchdogAction,
+    fired: Option<Runaway>,
+    /* Cycles since last VBlank */
+    cycles: u64,
+    /* Instruction run last and how many times in a row it ran with interrupts disabled */
+    pc: Addr,
+    stuck: u64,
+}
+
+impl Watchdog {
+    /* max_cycles - CPU cycles allowed without VBlank, max_stuck - runs of the same instruction with IME off */
+    pub fn new(max_cycles: Option<u64>, max_stuck: Option<u64>, action: WatchdogAction) -> Self {
+        Self { max_cycles, max_stuck, action, fired: None, cycles: 0, pc: 0, stuck: 0 }
+    }
+
+    pub fn action(&self) -> WatchdogAction {
+        self.action
+    }
+
+    /* What made watchdog fire, None while all is fine */
+    pub fn fired(&self) -> Option<Runaway> {
+        self.fired
+    }
+
+    /* Fired with Abort action, runtime doesn't execute anything */
+    pub fn aborted(&self) -> bool {
+        self.fired.is_some() && self.action == WatchdogAction::Abort
+    }
+
+    /* Called on reset, program starts over */
+    pub fn reset(&mut self) {
+        self.fired = None;
+        self.cycles = 0;
+        self.stuck = 0;
+    }
+
+    /*
+     * Runs after each instruction at pc, which took given cycles. vblank is whether GPU entered VBlank meanwhile.
+     * Halted CPU isn't stuck, DI; HALT waits for interrupt flag without handling it.
+     */
+    pub fn after_instruction(&mut self, pc: Addr, ime: bool, halted: bool, cycles: u64, vblank: bool) {
+        if self.fired.is_some() {
+            return;
+        }
+        self.cycles = if vblank { 0 } else { self.cycles + cycles };
+        self.stuck = match (ime || halted, pc == self.pc) {
+            (true, _) => 0,
+            (false, true) => self.stuck + 1,
+            (false, false) => 1,
+        };
+        self.pc = pc;
+        let runaway = if self.max_cycles.is_some_and(|max| self.cycles > max) {
+            Runaway::NoVblank { cycles: self.cycles }
+        } else if self.max_stuck.is_some_and(|max| self.stuck >= max) {
+            Runaway::Stuck { pc, instructions: self.stuck }
+        } else {
+            return;
+        };
+        log::warn!(target: "watchdog", "{}", runaway);
+        self.fired = Some(runaway);
+    }
+}
@@ -0,0 +1,133 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod alutest {
+    use gameboy::*;
+    use proptest::prelude::*;
+
+    const N: u8 = 1 << 6;
+    const H: u8 = 1 << 5;
+    const C: u8 = 1 << 4;
+
+    /* Runs given number of instructions from 0x100 with A, F and B set, returns A and F after them */
+    fn exec(program: &[u8], steps: usize, a: u8, f: u8, b: u8) -> (u8, u8) {
+        let mut runtime = Runtime::with_code(0x100, program);
+        runtime.cpu.A = a;
+        runtime.cpu.set_F(f);
+        runtime.cpu.BC.set((b as u16) << 8);
+        for _ in 0..steps {
+            runtime.step();
+        }
+        (runtime.cpu.A, runtime.cpu.F())
+    }
+
+    fn run(program: &[u8], a: u8, f: u8, b: u8) -> (u8, u8) {
+        exec(program, 1, a, f, b)
+    }
+
+    fn flags(z: bool, n: bool, h: bool, c: bool) -> u8 {
+        (z as u8) << 7 | (n as u8) << 6 | (h as u8) << 5 | (c as u8) << 4
+    }
+
+    /* ADD/ADC model, result and flags */
+    fn add(a: u8, b: u8, carry: bool) -> (u8, u8) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        let half = (a & 0xF) + (b & 0xF) + carry as u8 > 0xF;
+        (sum as u8, flags(sum as u8 == 0, false, half, sum > 0xFF))
+    }
+
+    /* SUB/SBC/CP model, result and flags */
+    fn sub(a: u8, b: u8, carry: bool) -> (u8, u8) {
+        let diff = a as i16 - b as i16 - carry as i16;
+        let half = ((a & 0xF) as i16) - ((b & 0xF) as i16) - (carry as i16) < 0;
+        (diff as u8, flags(diff as u8 == 0, true, half, diff < 0))
+    }
+
+    /* DAA model, as described in Pan Docs */
+    fn daa(a: u8, f: u8) -> (u8, u8) {
+        let (n, h, mut c) = (f & N != 0, f & H != 0, f & C != 0);
+        let mut a = a;
+        if !n {
+            if c || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                c = true;
+            }
+            if h || a & 0x0F > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
+        } else {
+            if c {
+                a = a.wrapping_sub(0x60);
+            }
+            if h {
+                a = a.wrapping_sub(0x06);
+            }
+        }
+        (a, flags(a == 0, n, false, c))
+    }
+
+    fn bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    proptest! {
+        #[test]
+        fn add_flags(a: u8, b: u8, f: u8) {
+            // ADD A, B
+            prop_assert_eq!(run(&[0x80], a, f, b), add(a, b, false));
+        }
+
+        #[test]
+        fn adc_flags(a: u8, b: u8, f: u8) {
+            // ADC A, B
+            prop_assert_eq!(run(&[0x88], a, f, b), add(a, b, f & C != 0));
+        }
+
+        #[test]
+        fn sub_flags(a: u8, b: u8, f: u8) {
+            // SUB A, B
+            prop_assert_eq!(run(&[0x90], a, f, b), sub(a, b, false));
+        }
+
+        #[test]
+        fn sbc_flags(a: u8, b: u8, f: u8) {
+            // SBC A, B
+            prop_assert_eq!(run(&[0x98], a, f, b), sub(a, b, f & C != 0));
+        }
+
+        #[test]
+        fn cp_flags(a: u8, b: u8, f: u8) {
+            // CP A, B leaves A alone
+            prop_assert_eq!(run(&[0xB8], a, f, b), (a, sub(a, b, false).1));
+        }
+
+        #[test]
+        fn immediate_same_as_register(a: u8, b: u8, f: u8, op in 0u8..8) {
+            // ADD/ADC/SUB/SBC/AND/XOR/OR/CP A, d8 against A, B
+            prop_assert_eq!(run(&[0xC6 | op << 3, b], a, f, b), run(&[0x80 | op << 3], a, f, b));
+        }
+
+        #[test]
+        fn daa_flags(a: u8, f: u8) {
+            // DAA
+            prop_assert_eq!(run(&[0x27], a, f, 0), daa(a, f));
+        }
+
+        #[test]
+        fn daa_after_add(x in 0u8..100, y in 0u8..100, f: u8) {
+            // ADD A, B; DAA gives decimal sum, carry is the hundreds digit
+            let sum = x as u16 + y as u16;
+            let expected = bcd((sum % 100) as u8);
+            let expected = (expected, flags(expected == 0, false, false, sum >= 100));
+            prop_assert_eq!(exec(&[0x80, 0x27], 2, bcd(x), f, bcd(y)), expected);
+        }
+
+        #[test]
+        fn daa_after_sub(x in 0u8..100, y in 0u8..100, f: u8) {
+            // SUB A, B; DAA gives decimal difference, carry is the borrow
+            let expected = bcd((x as i16 - y as i16).rem_euclid(100) as u8);
+            let expected = (expected, flags(expected == 0, true, false, x < y));
+            prop_assert_eq!(exec(&[0x90, 0x27], 2, bcd(x), f, bcd(y)), expected);
+        }
+    }
+}
@@ -0,0 +1,551 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod aputest {
+    use gameboy::*;
+
+    fn gen() -> (MMU<mbc::MBC1>, APU) {
+        let mut mmu = mem::MMU::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        let apu = APU::new(&mut mmu);
+        (mmu, apu)
+    }
+
+    #[test]
+    fn sample_rate() {
+        let (mut mmu, mut apu) = gen();
+
+        // One second of emulated time
+        for _ in 0..(1 << 20) {
+            apu.step(&mut mmu);
+        }
+
+        assert_eq!(apu.left_samples().len(), apu::PLAYBACK_FREQUENCY as usize);
+        assert_eq!(apu.right_samples().len(), apu::PLAYBACK_FREQUENCY as usize);
+    }
+
+    #[test]
+    fn pull_samples() {
+        let (mut mmu, mut apu) = gen();
+        apu.left_samples().extend_from_slice(&[1, 2, 3]);
+        apu.right_samples().extend_from_slice(&[-1, -2, -3]);
+        assert_eq!(apu.available_samples(), 6);
+
+        // Odd space left over isn't filled
+        let mut out = [0; 5];
+        assert_eq!(apu.pull_samples(&mut out), 4);
+        assert_eq!(out, [1, -1, 2, -2, 0]);
+        assert_eq!(apu.available_samples(), 2);
+
+        let mut out = [0; 8];
+        assert_eq!(apu.pull_samples(&mut out), 2);
+        assert_eq!(&out[..2], &[3, -3]);
+        assert_eq!(apu.pull_samples(&mut out), 0);
+
+        // Whatever APU produced comes out
+        for _ in 0..(1 << 12) {
+            apu.step(&mut mmu);
+        }
+        let mut out = vec![0; apu.available_samples()];
+        assert_eq!(apu.pull_samples(&mut out), out.len());
+        assert!(!out.is_empty());
+    }
+
+    #[test]
+    fn levels() {
+        let (mut mmu, mut apu) = gen();
+        assert_eq!(apu.take_levels(), [0; 4]);
+
+        // Channel 2 at full volume, 50% duty
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_21, 0x80);
+        mmu.write(ioregs::NR_22, 0xF0);
+        mmu.write(ioregs::NR_24, 0x87);
+        apu.trigger(&mut mmu, 2);
+        for _ in 0..(1 << 14) {
+            apu.step(&mut mmu);
+        }
+
+        let levels = apu.take_levels();
+        assert!(levels[1] > 0);
+        assert_eq!(levels[0], 0);
+        // Taking them starts over
+        assert_eq!(apu.take_levels(), [0; 4]);
+    }
+
+    /* Plays channel 2 at full volume for a while, returns loudest left sample */
+    fn play_chan2(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU) -> i16 {
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_51, 0xFF);
+        mmu.write(ioregs::NR_21, 0x80);
+        mmu.write(ioregs::NR_22, 0xF0);
+        mmu.write(ioregs::NR_24, 0x87);
+        apu.trigger(mmu, 2);
+        apu.left_samples().clear();
+        for _ in 0..(1 << 14) {
+            apu.step(mmu);
+        }
+        apu.left_samples().iter().cloned().max().unwrap_or(0)
+    }
+
+    #[test]
+    fn scope() {
+        let (mut mmu, mut apu) = gen();
+        assert!(apu.scope(2).is_empty());
+        apu.set_muted(2, true);
+        play_chan2(&mut mmu, &mut apu);
+        play_chan2(&mut mmu, &mut apu);
+
+        // Only the last samples are kept, square wave swings between silence and full volume
+        let scope = apu.scope(2);
+        assert_eq!(scope.len(), apu::SCOPE_SAMPLES);
+        assert!(scope.iter().any(|sample| *sample > 0));
+        assert!(scope.contains(&0));
+        // Stopped channels are flat
+        assert_eq!(apu.scope(1).len(), apu::SCOPE_SAMPLES);
+        assert!(apu.scope(1).iter().all(|sample| *sample == 0));
+        assert!(apu.scope(5).is_empty());
+    }
+
+    #[test]
+    fn mute() {
+        let (mut mmu, mut apu) = gen();
+        assert!(play_chan2(&mut mmu, &mut apu) > 0);
+
+        apu.set_muted(2, true);
+        assert!(apu.muted(2));
+        assert_eq!(play_chan2(&mut mmu, &mut apu), 0);
+        // Game still sees it playing, meters still show it
+        assert!(mmu.read_bit(ioregs::NR_52, 1));
+        assert!(apu.take_levels()[1] > 0);
+
+        apu.set_muted(2, false);
+        assert!(play_chan2(&mut mmu, &mut apu) > 0);
+        // Out of range channels are ignored
+        apu.set_muted(0, true);
+        apu.set_muted(5, true);
+        assert!(!apu.muted(5));
+    }
+
+    #[test]
+    fn solo() {
+        let (mut mmu, mut apu) = gen();
+        apu.set_solo(Some(1));
+        assert!(apu.audible(1));
+        assert!(!apu.audible(2));
+        assert_eq!(play_chan2(&mut mmu, &mut apu), 0);
+
+        apu.set_solo(Some(2));
+        assert!(play_chan2(&mut mmu, &mut apu) > 0);
+        apu.set_solo(Some(7));
+        assert_eq!(apu.solo(), None);
+    }
+
+    #[test]
+    fn master_volume() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_50, 0x77);
+        let full = play_chan2(&mut mmu, &mut apu);
+        assert_eq!(APU::master_volume(&mut mmu), (7, 7));
+
+        mmu.write(ioregs::NR_50, 0x73);
+        assert_eq!(APU::master_volume(&mut mmu), (3, 7));
+        assert_eq!(play_chan2(&mut mmu, &mut apu), full / 2);
+        assert_eq!(apu.right_samples().iter().cloned().max(), Some(full));
+        // Volume 0 is the quietest, not silence
+        mmu.write(ioregs::NR_50, 0x00);
+        assert_eq!(play_chan2(&mut mmu, &mut apu), full / 8);
+    }
+
+    #[test]
+    fn vin() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_52, 0x80);
+        // Nothing on VIN by default, even when NR50 takes it
+        mmu.write(ioregs::NR_50, 0xFF);
+        run(&mut mmu, &mut apu, 100);
+        assert!(apu.left_samples().iter().all(|sample| *sample == 0));
+
+        apu.set_vin(Some(Box::new(|| 1000)));
+        assert!(apu.has_vin());
+        // Left terminal only
+        mmu.write(ioregs::NR_50, 0x7F);
+        apu.left_samples().clear();
+        apu.right_samples().clear();
+        run(&mut mmu, &mut apu, 100);
+        assert!(!apu.left_samples().is_empty());
+        assert!(apu.left_samples().iter().all(|sample| *sample == 1000));
+        assert!(apu.right_samples().iter().all(|sample| *sample == 0));
+        // Scaled by master volume like channels
+        mmu.write(ioregs::NR_50, 0x7B);
+        apu.left_samples().clear();
+        run(&mut mmu, &mut apu, 100);
+        assert!(apu.left_samples().iter().all(|sample| *sample == 1000 * 4 / 8));
+    }
+
+    #[test]
+    fn vin_survives_reset() {
+        let mut state = State::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        state.apu.set_vin(Some(Box::new(|| 0)));
+        state.reset(true);
+        assert!(state.apu.has_vin());
+    }
+
+    #[test]
+    fn dac_off() {
+        let (mut mmu, mut apu) = gen();
+        // Channel doesn't start with DAC off
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_22, 0x00);
+        apu.trigger(&mut mmu, 2);
+        assert!(!mmu.read_bit(ioregs::NR_52, 1));
+
+        mmu.write(ioregs::NR_22, 0x08);
+        apu.trigger(&mut mmu, 2);
+        assert!(mmu.read_bit(ioregs::NR_52, 1));
+        // Turning it off stops the channel
+        mmu.write(ioregs::NR_22, 0x07);
+        apu.step(&mut mmu);
+        assert!(!mmu.read_bit(ioregs::NR_52, 1));
+
+        // Wave channel DAC is NR30 bit 7
+        mmu.write(ioregs::NR_30, 0x80);
+        apu.trigger(&mut mmu, 3);
+        assert!(mmu.read_bit(ioregs::NR_52, 2));
+        mmu.write(ioregs::NR_30, 0x00);
+        apu.step(&mut mmu);
+        assert!(!mmu.read_bit(ioregs::NR_52, 2));
+    }
+
+    #[test]
+    fn power() {
+        let mut state = State::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        // Sound starts off, registers ignore writes
+        state.safe_write(ioregs::NR_50, 0x77);
+        assert_eq!(state.safe_read(ioregs::NR_50), 0);
+
+        // Channel status bits can't be written
+        state.safe_write(ioregs::NR_52, 0x8F);
+        assert_eq!(state.safe_read(ioregs::NR_52), 0x80);
+        state.safe_write(ioregs::NR_50, 0x77);
+        state.safe_write(ioregs::NR_22, 0xF0);
+        state.safe_write(ioregs::NR_24, 0x80);
+        state.safe_write(0xFF30, 0x12);
+        state.apu.step(&mut state.mmu);
+        assert_eq!(state.safe_read(ioregs::NR_52), 0x82);
+
+        // Turning it off clears registers and stops channels, but not wave RAM
+        state.safe_write(ioregs::NR_52, 0x00);
+        assert_eq!(state.safe_read(ioregs::NR_52), 0x00);
+        assert_eq!(state.safe_read(ioregs::NR_50), 0);
+        assert_eq!(state.safe_read(ioregs::NR_22), 0);
+        assert_eq!(state.safe_read(0xFF30), 0x12);
+        state.safe_write(ioregs::NR_22, 0xF0);
+        assert_eq!(state.safe_read(ioregs::NR_22), 0);
+
+        state.safe_write(ioregs::NR_52, 0x80);
+        state.safe_write(ioregs::NR_22, 0xF0);
+        assert_eq!(state.safe_read(ioregs::NR_22), 0xF0);
+    }
+
+    /* Writes register like CPU does, with length counters seeing it first */
+    fn write(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU, addr: u16, value: u8) {
+        apu.write_length(mmu, addr, value);
+        mmu.write(addr, value);
+    }
+
+    fn run(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU, cycles: usize) {
+        for _ in 0..cycles {
+            apu.step(mmu);
+        }
+    }
+
+    #[test]
+    fn length_load() {
+        let (mut mmu, mut apu) = gen();
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x3E);
+        assert_eq!(apu.length(2), 2);
+        // Duty bits don't count
+        write(&mut mmu, &mut apu, ioregs::NR_11, 0x80);
+        assert_eq!(apu.length(1), 64);
+        write(&mut mmu, &mut apu, ioregs::NR_41, 0xFF);
+        assert_eq!(apu.length(4), 1);
+        // Wave channel has 8 bit length
+        write(&mut mmu, &mut apu, ioregs::NR_31, 0xFF);
+        assert_eq!(apu.length(3), 1);
+        write(&mut mmu, &mut apu, ioregs::NR_31, 0x00);
+        assert_eq!(apu.length(3), 256);
+    }
+
+    #[test]
+    fn length_stops_channel() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_22, 0xF0);
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x3C);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0xC7);
+
+        // Length is clocked every other sequencer step, 2048 cycles each
+        run(&mut mmu, &mut apu, 3 * 4096);
+        assert_eq!(apu.length(2), 1);
+        assert!(mmu.read_bit(ioregs::NR_52, 1));
+        run(&mut mmu, &mut apu, 4096);
+        assert_eq!(apu.length(2), 0);
+        assert!(!mmu.read_bit(ioregs::NR_52, 1));
+
+        // Stays at 0 until triggered again, then starts from max
+        run(&mut mmu, &mut apu, 8192);
+        assert_eq!(apu.length(2), 0);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0xC7);
+        apu.step(&mut mmu);
+        assert_eq!(apu.length(2), 64);
+        assert!(mmu.read_bit(ioregs::NR_52, 1));
+    }
+
+    #[test]
+    fn length_trigger_skipped_step() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_22, 0xF0);
+        mmu.write(ioregs::NR_30, 0x80);
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x3F);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0xC7);
+        write(&mut mmu, &mut apu, ioregs::NR_31, 0xFF);
+        write(&mut mmu, &mut apu, ioregs::NR_34, 0xC7);
+        run(&mut mmu, &mut apu, 2048);
+        assert_eq!(apu.length(2), 0);
+        assert_eq!(apu.length(3), 0);
+
+        // Next sequencer step doesn't clock length, so reloaded counter is one short
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0xC7);
+        write(&mut mmu, &mut apu, ioregs::NR_34, 0xC7);
+        apu.step(&mut mmu);
+        assert_eq!(apu.length(2), 63);
+        assert_eq!(apu.length(3), 255);
+    }
+
+    #[test]
+    fn length_extra_clock() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_22, 0xF0);
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x36);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x87);
+        run(&mut mmu, &mut apu, 2048);
+        assert_eq!(apu.length(2), 10);
+
+        // Enabling length when next step doesn't clock it clocks it once
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x47);
+        assert_eq!(apu.length(2), 9);
+        // Only when it was disabled before
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x47);
+        assert_eq!(apu.length(2), 9);
+        // Nothing extra when next step clocks it anyway
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x07);
+        run(&mut mmu, &mut apu, 2048);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x47);
+        assert_eq!(apu.length(2), 9);
+
+        // Extra clock can stop the channel
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x3F);
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x07);
+        run(&mut mmu, &mut apu, 2048);
+        assert!(mmu.read_bit(ioregs::NR_52, 1));
+        write(&mut mmu, &mut apu, ioregs::NR_24, 0x47);
+        assert_eq!(apu.length(2), 0);
+        assert!(!mmu.read_bit(ioregs::NR_52, 1));
+    }
+
+    /* Envelope is clocked on last of 8 sequencer steps */
+    const ENVELOPE_CYCLES: usize = 8 * 2048;
+
+    fn trigger_chan2(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU, nr_22: u8) {
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_22, nr_22);
+        mmu.write(ioregs::NR_24, 0x87);
+        apu.trigger(mmu, 2);
+    }
+
+    #[test]
+    fn envelope_period() {
+        let (mut mmu, mut apu) = gen();
+        trigger_chan2(&mut mmu, &mut apu, 0xF3);
+        assert_eq!(apu.volume(2), 15);
+        run(&mut mmu, &mut apu, 2 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 15);
+        run(&mut mmu, &mut apu, ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 14);
+        run(&mut mmu, &mut apu, 3 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 13);
+
+        // Period 0 keeps volume
+        trigger_chan2(&mut mmu, &mut apu, 0xF0);
+        run(&mut mmu, &mut apu, 4 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 15);
+    }
+
+    #[test]
+    fn envelope_direction() {
+        let (mut mmu, mut apu) = gen();
+        // Goes up from silence and stops at 15
+        trigger_chan2(&mut mmu, &mut apu, 0x09);
+        assert_eq!(apu.volume(2), 0);
+        run(&mut mmu, &mut apu, 3 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 3);
+        run(&mut mmu, &mut apu, 16 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 15);
+
+        // Goes down and stops at 0
+        trigger_chan2(&mut mmu, &mut apu, 0x21);
+        run(&mut mmu, &mut apu, 4 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(2), 0);
+    }
+
+    #[test]
+    fn envelope_registers() {
+        let (mut mmu, mut apu) = gen();
+        // Channel 1 and 4 go by their own NRx2, not channel 2 one
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_12, 0x09);
+        mmu.write(ioregs::NR_22, 0xF1);
+        mmu.write(ioregs::NR_42, 0x71);
+        apu.trigger(&mut mmu, 1);
+        apu.trigger(&mut mmu, 4);
+        run(&mut mmu, &mut apu, 2 * ENVELOPE_CYCLES);
+        assert_eq!(apu.volume(1), 2);
+        assert_eq!(apu.volume(4), 5);
+    }
+
+    #[test]
+    fn channel_debug() {
+        let (mut mmu, mut apu) = gen();
+        write(&mut mmu, &mut apu, ioregs::NR_21, 0x80 | 0x3C);
+        mmu.write(ioregs::NR_23, 0x00);
+        trigger_chan2(&mut mmu, &mut apu, 0xA0);
+        let info = apu.channel_debug(&mmu, 2);
+        assert!(info.enabled && info.dac_on);
+        assert_eq!(info.frequency, 512.0);
+        assert_eq!(info.volume, 10);
+        assert_eq!(info.length, 4);
+        assert_eq!(info.duty, Some(2));
+
+        // Wave channel, stopped, with DAC off
+        mmu.write(ioregs::NR_30, 0x00);
+        mmu.write(ioregs::NR_33, 0x00);
+        mmu.write(ioregs::NR_34, 0x07);
+        let info = apu.channel_debug(&mmu, 3);
+        assert!(!info.enabled && !info.dac_on);
+        assert_eq!(info.duty, None);
+
+        // Noise shifts 2^20/8 times a second at its fastest
+        mmu.write(ioregs::NR_43, 0x00);
+        assert_eq!(apu.channel_debug(&mmu, 4).frequency, 131072.0);
+        assert_eq!(apu.channel_debug(&mmu, 5), ChannelDebugInfo::default());
+    }
+
+    /* NRx2 write the way CPU does it */
+    fn write_nrx2(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU, addr: u16, value: u8) {
+        apu.write_envelope(mmu, addr, value);
+        mmu.write(addr, value);
+    }
+
+    #[test]
+    fn zombie_mode() {
+        let (mut mmu, mut apu) = gen();
+        apu.quirks.zombie_mode = true;
+        // Period 0, going up: each write adds 1
+        trigger_chan2(&mut mmu, &mut apu, 0x58);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0x58);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0x58);
+        assert_eq!(apu.volume(2), 7);
+        // Going down adds 2, wrapping around
+        trigger_chan2(&mut mmu, &mut apu, 0xF1);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0xF1);
+        assert_eq!(apu.volume(2), 1);
+        // Turning direction around
+        trigger_chan2(&mut mmu, &mut apu, 0x38);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0x30);
+        assert_eq!(apu.volume(2), 12);
+        // Going down from 15 and turning around, 17 wraps before it's flipped
+        trigger_chan2(&mut mmu, &mut apu, 0xF3);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0xFB);
+        assert_eq!(apu.volume(2), 15);
+        // Stopped channel isn't touched
+        mmu.write(ioregs::NR_12, 0x58);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_12, 0x58);
+        assert_eq!(apu.volume(1), 0);
+    }
+
+    #[test]
+    fn zombie_mode_off() {
+        let (mut mmu, mut apu) = gen();
+        trigger_chan2(&mut mmu, &mut apu, 0x58);
+        write_nrx2(&mut mmu, &mut apu, ioregs::NR_22, 0x58);
+        assert_eq!(apu.volume(2), 5);
+    }
+
+    #[test]
+    fn zombie_mode_survives_reset() {
+        let mut state = State::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        state.apu.quirks.zombie_mode = true;
+        state.reset(true);
+        assert!(state.apu.quirks.zombie_mode);
+    }
+
+    /* Starts noise channel shifting its LFSR every 8 cycles */
+    fn trigger_noise(mmu: &mut MMU<mbc::MBC1>, short: bool) {
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_42, 0xF0);
+        mmu.write(ioregs::NR_43, if short { 0x08 } else { 0x00 });
+        mmu.write(ioregs::NR_44, 0x80);
+    }
+
+    fn shift_noise(mmu: &mut MMU<mbc::MBC1>, apu: &mut APU, shifts: usize) -> u16 {
+        run(mmu, apu, 8 * shifts);
+        apu.noise_lfsr()
+    }
+
+    #[test]
+    fn noise_lfsr() {
+        let (mut mmu, mut apu) = gen();
+        trigger_noise(&mut mmu, false);
+        apu.step(&mut mmu);
+        assert_eq!(apu.noise_lfsr(), 0x7FFF);
+        run(&mut mmu, &mut apu, 7);
+        // Equal bits 0 and 1 shift in 0, until they differ
+        assert_eq!(apu.noise_lfsr(), 0x3FFF);
+        assert_eq!(shift_noise(&mut mmu, &mut apu, 13), 0x0001);
+        assert_eq!(shift_noise(&mut mmu, &mut apu, 1), 0x4000);
+
+        // Full 15 bit sequence repeats after 2^15-1 shifts
+        let start = apu.noise_lfsr();
+        assert_ne!(shift_noise(&mut mmu, &mut apu, 1000), start);
+        assert_eq!(shift_noise(&mut mmu, &mut apu, 32767 - 1000), start);
+    }
+
+    #[test]
+    fn noise_lfsr_short() {
+        let (mut mmu, mut apu) = gen();
+        trigger_noise(&mut mmu, true);
+        // Shifted in bit lands in bit 6 too
+        assert_eq!(shift_noise(&mut mmu, &mut apu, 1), 0x3FBF);
+
+        // Once bits above 6 are filled from it, sequence repeats after 127 shifts
+        let start = shift_noise(&mut mmu, &mut apu, 20);
+        assert_ne!(shift_noise(&mut mmu, &mut apu, 50), start);
+        assert_eq!(shift_noise(&mut mmu, &mut apu, 77), start);
+    }
+
+    #[test]
+    fn noise_output() {
+        let (mut mmu, mut apu) = gen();
+        mmu.write(ioregs::NR_51, 0xFF);
+        // Slowest shifting, register stays at 0x7FFF for the whole run: bit 0 set means silence
+        mmu.write(ioregs::NR_52, 0x80);
+        mmu.write(ioregs::NR_42, 0xF0);
+        mmu.write(ioregs::NR_43, 0x97);
+        mmu.write(ioregs::NR_44, 0x80);
+        run(&mut mmu, &mut apu, 1 << 12);
+        assert_eq!(apu.noise_lfsr(), 0x7FFF);
+        assert_eq!(apu.take_levels()[3], 0);
+    }
+}
@@ -0,0 +1,114 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod audittest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    fn gen() -> Runtime<mbc::MBC1> {
+        let mut runtime = Runtime::new(mbc::MBC1::new(ROM.to_vec()));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_audit(Some(Audit::new()));
+        runtime
+    }
+
+    fn hashes(runtime: &Runtime<mbc::MBC1>) -> Vec<u64> {
+        runtime.audit().unwrap().hashes().to_vec()
+    }
+
+    #[test]
+    fn hash_per_frame() {
+        let mut runtime = gen();
+        for _ in 0..20 {
+            run_frame(&mut runtime);
+        }
+        let audit = runtime.audit().unwrap();
+        assert_eq!(audit.hashes().len() as u64, runtime.state.gpu.frames());
+        assert_eq!(audit.last(), audit.hashes().last().copied());
+
+        runtime.set_audit(None);
+        run_frame(&mut runtime);
+        assert!(runtime.audit().is_none());
+    }
+
+    #[test]
+    fn same_runs_match() {
+        let (mut a, mut b) = (gen(), gen());
+        for _ in 0..30 {
+            run_frame(&mut a);
+            run_frame(&mut b);
+        }
+        assert_eq!(hashes(&a), hashes(&b));
+        assert_eq!(first_divergence(&hashes(&a), &hashes(&b)), None);
+        assert_eq!(state_hash(&a.cpu, &a.state), state_hash(&b.cpu, &b.state));
+    }
+
+    #[test]
+    fn divergence_found() {
+        let (mut a, mut b) = (gen(), gen());
+        for _ in 0..10 {
+            run_frame(&mut a);
+            run_frame(&mut b);
+        }
+        let poked = hashes(&b).len() as u64;
+        // Tetris doesn't use the end of WRAM
+        let last = b.state.mmu.ram.len() - 0x10;
+        b.state.mmu.ram[last] ^= 0xFF;
+        for _ in 0..10 {
+            run_frame(&mut a);
+            run_frame(&mut b);
+        }
+        assert_eq!(first_divergence(&hashes(&a), &hashes(&b)), Some(poked));
+        // Rolling hash stays different, even once state is the same again
+        b.state.mmu.ram[last] ^= 0xFF;
+        run_frame(&mut a);
+        run_frame(&mut b);
+        assert_ne!(hashes(&a).last(), hashes(&b).last());
+    }
+
+    #[test]
+    fn shorter_run_in_sync() {
+        assert_eq!(first_divergence(&[1, 2, 3], &[1, 2]), None);
+        assert_eq!(first_divergence(&[1, 2, 3], &[1, 5, 6, 7]), Some(1));
+        assert_eq!(first_divergence(&[], &[1]), None);
+    }
+
+    #[test]
+    fn log_round_trip() {
+        let path = std::env::temp_dir().join("gameboy-audittest.log");
+        let mut runtime = gen();
+        runtime.set_audit(Some(Audit::logging(&path).unwrap()));
+        for _ in 0..5 {
+            run_frame(&mut runtime);
+        }
+        let recorded = hashes(&runtime);
+        assert_eq!(parse_audit(&runtime.audit().unwrap().to_text()).unwrap(), recorded);
+        // Log is complete once audit is dropped
+        runtime.set_audit(None);
+        assert_eq!(load_audit(&path).unwrap(), recorded);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_log() {
+        assert_eq!(parse_audit("0 00000000000000FF\n1 10\n\n").unwrap(), vec![0xFF, 0x10]);
+        assert!(parse_audit("0 00000000000000FF\n2 10").is_err());
+        assert!(parse_audit("0").is_err());
+        assert!(parse_audit("0 XYZ").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn kept_on_savestate() {
+        let mut runtime = gen();
+        for _ in 0..3 {
+            run_frame(&mut runtime);
+        }
+        let screen = runtime.state.gpu.completed_frame().to_vec();
+        let state = SaveState::capture(&mut runtime, &screen).unwrap();
+        run_frame(&mut runtime);
+        state.restore(&mut runtime).unwrap();
+        assert_eq!(hashes(&runtime).len(), 4);
+    }
+}
@@ -0,0 +1,132 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod batterytest {
+    use gameboy::*;
+    use std::path::{Path, PathBuf};
+
+    /* MBC1+RAM+BATTERY with 8KB of RAM */
+    fn gen_cart() -> mbc::MBC1 {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x02;
+        mbc::MBC1::new(rom)
+    }
+
+    /* MBC5+RAM+BATTERY with 128KB of RAM */
+    fn gen_big_cart() -> mbc::MBC5 {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x1B;
+        rom[0x149] = 0x04;
+        mbc::MBC5::new(rom)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gameboy-batterytest-{}.sav", name))
+    }
+
+    #[test]
+    fn path_named_after_rom() {
+        let path = battery_path(Path::new("saves"), Path::new("roms/tetris.gb"));
+        assert_eq!(path, Path::new("saves").join("tetris.sav"));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = temp_path("round-trip");
+        let mut cart = gen_cart();
+        cart.ram()[0x123] = 0x45;
+        cart.ram()[0x1FFF] = 0x67;
+        assert!(save_battery(&mut cart, &path).unwrap());
+
+        let mut other = gen_cart();
+        assert!(load_battery(&mut other, &path).unwrap());
+        assert_eq!(other.ram()[0x123], 0x45);
+        assert_eq!(other.ram()[0x1FFF], 0x67);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn first_run_has_no_save() {
+        let path = temp_path("missing");
+        let _ = std::fs::remove_file(&path);
+        let mut cart = gen_cart();
+        assert!(!load_battery(&mut cart, &path).unwrap());
+        assert!(cart.ram().iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn size_mismatch_refused() {
+        let path = temp_path("size");
+        std::fs::write(&path, vec![0xAA; 0x800]).unwrap();
+        let mut cart = gen_cart();
+        assert!(load_battery(&mut cart, &path).is_err());
+        assert!(cart.ram().iter().all(|b| *b == 0));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nothing_written_without_ram() {
+        let path = temp_path("no-ram");
+        let _ = std::fs::remove_file(&path);
+        let mut cart = mbc::MBC1::new(vec![0; 0x8000]);
+        assert!(!save_battery(&mut cart, &path).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn mapped_ram_persists() {
+        let path = temp_path("mapped");
+        let _ = std::fs::remove_file(&path);
+        let mut cart = gen_big_cart();
+        cart.ram()[0x100] = 0x11;
+        // New save gets RAM as it was
+        assert!(!map_battery(&mut cart, &path).unwrap());
+        assert_eq!(cart.ram.path(), Some(path.as_path()));
+        assert_eq!(std::fs::read(&path).unwrap().len(), 0x20000);
+        // Writes land in the file without saving
+        cart.ram()[0x1FFFF] = 0x22;
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!((data[0x100], data[0x1FFFF]), (0x11, 0x22));
+        assert!(save_battery(&mut cart, &path).unwrap());
+        drop(cart);
+
+        let mut other = gen_big_cart();
+        assert!(map_battery(&mut other, &path).unwrap());
+        assert_eq!((other.ram()[0x100], other.ram()[0x1FFFF]), (0x11, 0x22));
+        drop(other);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn small_ram_loaded() {
+        let path = temp_path("small");
+        std::fs::write(&path, vec![0x33; 0x2000]).unwrap();
+        let mut cart = gen_cart();
+        assert!(map_battery(&mut cart, &path).unwrap());
+        assert!(cart.ram.path().is_none());
+        assert_eq!(cart.ram()[0x1000], 0x33);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mapped_size_mismatch_refused() {
+        let path = temp_path("mapped-size");
+        std::fs::write(&path, vec![0xAA; 0x2000]).unwrap();
+        let mut cart = gen_big_cart();
+        assert!(map_battery(&mut cart, &path).is_err());
+        assert!(cart.ram.path().is_none());
+        assert_eq!(std::fs::read(&path).unwrap().len(), 0x2000);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn header_battery_flag() {
+        let mut rom = vec![0; 0x150];
+        let types = [(0x01, false), (0x03, true), (0x13, true), (0x1B, true), (0x1C, false), (0xFE, true)];
+        for (cart_type, battery) in types {
+            rom[0x147] = cart_type;
+            assert_eq!(CartHeader::from_rom(&rom).unwrap().has_battery(), battery, "cart type 0x{:02X}", cart_type);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod blendtest {
+    use gameboy::*;
+
+    #[test]
+    fn off() {
+        let mut blend = FrameBlend::new(0.0);
+        let mut first = vec![WHITE; 4];
+        blend.apply(&mut first);
+        let mut second = vec![BLACK; 4];
+        blend.apply(&mut second);
+        assert_eq!(second, vec![BLACK; 4]);
+    }
+
+    #[test]
+    fn mixes_previous_frame() {
+        let mut blend = FrameBlend::new(0.5);
+        // First frame has nothing to mix with
+        let mut first = vec![(255, 255, 255), (0, 0, 0)];
+        blend.apply(&mut first);
+        assert_eq!(first, vec![(255, 255, 255), (0, 0, 0)]);
+
+        let mut second = vec![(0, 0, 0), (0, 0, 0)];
+        blend.apply(&mut second);
+        assert_eq!(second, vec![(128, 128, 128), (0, 0, 0)]);
+
+        // Previous frame is the original one, not blended
+        let mut third = vec![(0, 0, 0), (0, 0, 0)];
+        blend.apply(&mut third);
+        assert_eq!(third, vec![(0, 0, 0), (0, 0, 0)]);
+    }
+
+    #[test]
+    fn size_change_and_clear() {
+        let mut blend = FrameBlend::new(0.25);
+        blend.apply(&mut [(200, 200, 200); 2]);
+        let mut other_size = vec![(0, 0, 0); 3];
+        blend.apply(&mut other_size);
+        assert_eq!(other_size, vec![(0, 0, 0); 3]);
+
+        let mut next = vec![(100, 100, 100); 3];
+        blend.apply(&mut next);
+        assert_eq!(next, vec![(75, 75, 75); 3]);
+
+        blend.clear();
+        let mut after_clear = vec![(8, 8, 8); 3];
+        blend.apply(&mut after_clear);
+        assert_eq!(after_clear, vec![(8, 8, 8); 3]);
+    }
+
+    #[test]
+    fn weight_clamped() {
+        assert_eq!(FrameBlend::new(2.0).weight(), MAX_BLEND);
+        assert_eq!(FrameBlend::new(-1.0).weight(), 0.0);
+        assert_eq!(FrameBlend::new(f32::NAN).weight(), 0.0);
+    }
+}
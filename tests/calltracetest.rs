@@ -0,0 +1,110 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod calltracetest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Program at 0x100, extra routines at given addresses, call trace on */
+    fn gen(program: &[u8], routines: &[(usize, &[u8])]) -> Runtime<mbc::RomOnly> {
+        let mut runtime = program_runtime(program, routines);
+        runtime.cpu.set_call_trace(true);
+        runtime
+    }
+
+    fn run(runtime: &mut Runtime<mbc::RomOnly>, steps: usize) {
+        for _ in 0..steps {
+            runtime.step();
+        }
+    }
+
+    const CALLER: [u8; 5] = [
+        0xCD, 0x00, 0x02, // CALL 0x0200
+        0x18, 0xFE, // JR -2
+    ];
+    const LOOP: [u8; 2] = [0x18, 0xFE]; // JR -2
+
+    #[test]
+    fn off_by_default() {
+        let mut runtime = gen(&CALLER, &[(0x200, &LOOP)]);
+        runtime.cpu.set_call_trace(false);
+        run(&mut runtime, 10);
+        assert!(!runtime.cpu.call_trace());
+        assert!(runtime.cpu.backtrace().is_empty());
+    }
+
+    #[test]
+    fn nested_calls() {
+        let mut runtime = gen(&CALLER, &[(0x200, &[0xCF, 0xC9]), (0x08, &LOOP)]);
+        run(&mut runtime, 10);
+        let backtrace = runtime.cpu.backtrace();
+        assert_eq!(backtrace, vec![
+            CallFrame { kind: CallKind::Rst, target: 0x0008, ret: 0x0201, sp: 0xFFFA },
+            CallFrame { kind: CallKind::Call, target: 0x0200, ret: 0x0103, sp: 0xFFFC },
+        ]);
+        assert_eq!(backtrace[0].to_string(), "RST 0x0008, returns to 0x0201, SP=0xFFFA");
+    }
+
+    #[test]
+    fn returns() {
+        // RST 08 returns right away, then CALL returns too
+        let mut runtime = gen(&CALLER, &[(0x200, &[0xCF, 0xC9]), (0x08, &[0xC9])]);
+        run(&mut runtime, 10);
+        assert_eq!(runtime.cpu.PC.val(), 0x103);
+        assert!(runtime.cpu.backtrace().is_empty());
+    }
+
+    #[test]
+    fn unwound_stack() {
+        // Return address popped instead of RET
+        let mut runtime = gen(&CALLER, &[(0x200, &[0xE1, 0x18, 0xFE])]);
+        run(&mut runtime, 10);
+        assert_eq!(runtime.cpu.PC.val(), 0x201);
+        assert!(runtime.cpu.backtrace().is_empty());
+    }
+
+    #[test]
+    fn interrupt() {
+        let program = [
+            0x3E, 0x01, // LD A, 0x01
+            0xE0, 0xFF, // LDH (IE), A
+            0xFB, // EI
+            0x18, 0xFE, // JR -2
+        ];
+        let mut runtime = gen(&program, &[(0x40, &LOOP)]);
+        run(&mut runtime, 20000);
+        let backtrace = runtime.cpu.backtrace();
+        assert_eq!(backtrace.len(), 1);
+        assert_eq!(backtrace[0].kind, CallKind::Interrupt);
+        assert_eq!(backtrace[0].target, 0x40);
+        assert_eq!(backtrace[0].ret, 0x105);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let program = [
+            0x31, 0xF0, 0xDF, // LD SP, 0xDFF0
+            0xCD, 0x00, 0x02, // CALL 0x0200
+        ];
+        // Calls itself forever
+        let mut runtime = gen(&program, &[(0x200, &[0xCD, 0x00, 0x02])]);
+        run(&mut runtime, CALL_TRACE_DEPTH + 100);
+        let backtrace = runtime.cpu.backtrace();
+        assert_eq!(backtrace.len(), CALL_TRACE_DEPTH);
+        assert_eq!(backtrace[0].sp, runtime.cpu.SP);
+    }
+
+    #[test]
+    fn survives_reset() {
+        let mut runtime = gen(&CALLER, &[(0x200, &LOOP)]);
+        run(&mut runtime, 5);
+        assert_eq!(runtime.cpu.backtrace().len(), 1);
+        runtime.reset(false);
+        assert!(runtime.cpu.call_trace());
+        assert!(runtime.cpu.backtrace().is_empty());
+        run(&mut runtime, 5);
+        assert_eq!(runtime.cpu.backtrace().len(), 1);
+    }
+}
@@ -0,0 +1,13 @@
+use gameboy::*;
+
+/* 32KB ROM with program at 0x100 and routines at given addresses, on DMG right after boot ROM */
+pub fn program_runtime(program: &[u8], routines: &[(usize, &[u8])]) -> Runtime<mbc::RomOnly> {
+    let mut rom = vec![0; 1 << 15];
+    rom[0x100..0x100 + program.len()].copy_from_slice(program);
+    for (addr, routine) in routines {
+        rom[*addr..*addr + routine.len()].copy_from_slice(routine);
+    }
+    let mut runtime = Runtime::new(mbc::RomOnly::new(rom));
+    runtime.skip_bootrom(HardwareModel::DMG);
+    runtime
+}
@@ -0,0 +1,68 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod compattest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    // Header of a cart with given title and old licensee code
+    fn header(title: &str, licensee: u8) -> CartHeader {
+        let mut rom = vec![0; 0x8000];
+        rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x14B] = licensee;
+        CartHeader::from_rom(&rom).unwrap()
+    }
+
+    #[test]
+    fn tetris() {
+        let header = CartHeader::from_rom(ROM).unwrap();
+        assert_eq!(compat_combination(&header), 3);
+        let palette = compat_palette(&header);
+        // White, yellow, red and black for everything
+        let colors = [(0xFF, 0xFF, 0xFF), (0xFF, 0xFF, 0x00), (0xFF, 0x00, 0x00), (0x00, 0x00, 0x00)];
+        for layer in PaletteLayer::ALL.iter() {
+            assert_eq!(palette.layer(*layer), colors);
+        }
+    }
+
+    #[test]
+    fn default_palette() {
+        // Green background and red sprites, for unknown games and the ones not licensed by Nintendo
+        for header in [header("PUZZLE", 0x01), header("TETRIS", 0x33)].iter() {
+            assert_eq!(compat_combination(header), 0);
+            let palette = compat_palette(header);
+            assert_eq!(palette.layer(PaletteLayer::Background)[1], (0x7B, 0xFF, 0x31));
+            assert_eq!(palette.layer(PaletteLayer::Background)[2], (0x00, 0x63, 0xC6));
+            assert_eq!(palette.layer(PaletteLayer::Object0)[1], (0xFF, 0x84, 0x84));
+            assert_eq!(palette.layer(PaletteLayer::Object1)[2], (0x94, 0x39, 0x39));
+        }
+    }
+
+    #[test]
+    fn different_layers() {
+        // Red background and green sprites in OBP0
+        let palette = compat_palette(&header("POKEMON RED", 0x01));
+        assert_eq!(palette.layer(PaletteLayer::Background)[1], (0xFF, 0x84, 0x84));
+        assert_eq!(palette.layer(PaletteLayer::Object0)[1], (0x7B, 0xFF, 0x31));
+        assert_eq!(palette.colorize(LIGHT_GRAY, PaletteLayer::Object0), (0x7B, 0xFF, 0x31));
+    }
+
+    #[test]
+    fn fourth_letter() {
+        // Same checksum, told apart by 4th letter
+        assert_eq!(compat_combination(&header("SUPER MARIOLAND", 0x01)), 22);
+        assert_eq!(compat_combination(&header("POKEMON BLUE", 0x01)), 11);
+        // Checksum of SUPER MARIOLAND, but 4th letter of no game having it
+        assert_eq!(compat_combination(&header("SUPFR MARIOLANC", 0x01)), 0);
+    }
+
+    #[test]
+    fn new_licensee() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x134..0x13A].copy_from_slice(b"TETRIS");
+        rom[0x144..0x146].copy_from_slice(b"01");
+        rom[0x14B] = 0x33;
+        assert_eq!(compat_combination(&CartHeader::from_rom(&rom).unwrap()), 3);
+    }
+}
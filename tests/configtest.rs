@@ -0,0 +1,145 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod configtest {
+    use gameboy::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn empty_is_default() {
+        assert_eq!(EmuConfig::from_toml("").unwrap(), EmuConfig::default());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn partial_file() {
+        let config = EmuConfig::from_toml(
+            r##"
+            scale = 5
+            fast_boot = true
+            autosave = true
+            dot_matrix = true
+            pacing = "Exact"
+            palette = ["#E0F8D0", "88C070", "#346856", "#081820"]
+
+            [keys]
+            a = ["J"]
+
+            [strict]
+            header = true
+            bus = "panic"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.scale, 5);
+        assert!(config.fast_boot);
+        assert!(config.autosave);
+        assert!(config.dot_matrix);
+        assert_eq!(config.pacing, Pacing::Exact);
+        assert_eq!(config.palette[0], (0xE0, 0xF8, 0xD0));
+        assert_eq!(config.palette[1], (0x88, 0xC0, 0x70));
+        assert_eq!(config.keys.a, vec![String::from("J")]);
+        assert!(config.strict.header);
+        assert_eq!(config.strict.bus, BusPolicy::Panic);
+        // Untouched fields keep defaults
+        assert_eq!(config.sample_rate, apu::PLAYBACK_FREQUENCY);
+        assert_eq!(config.keys.b, KeyBindings::default().b);
+    }
+
+    #[test]
+    fn invalid_file() {
+        assert!(EmuConfig::from_toml("scael = 5").is_err());
+        assert!(EmuConfig::from_toml("[strict]\nbus = \"crash\"").is_err());
+        assert!(EmuConfig::from_toml("pacing = \"60hz\"").is_err());
+        assert!(EmuConfig::from_toml("palette = [\"#FFFFFF\"]").is_err());
+        assert!(EmuConfig::from_toml("palette = [\"#FFFFFF\", \"#FFFFFF\", \"#FFFFFF\", \"#GGGGGG\"]").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn player2_keys() {
+        let config = EmuConfig::from_toml("[player2_keys]\na = [\"Q\"]").unwrap();
+        assert_eq!(config.player2_keys.a, vec![String::from("Q")]);
+        // Missing ones are player 2 defaults, not player 1 ones
+        assert_eq!(config.player2_keys.b, KeyBindings::player2().b);
+        assert_eq!(config.keys, KeyBindings::default());
+        assert!(EmuConfig::from_toml("[player2_keys]\njump = [\"Q\"]").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn turbo() {
+        let config = EmuConfig::from_toml("turbo_frames = 4\n[player2_keys]\nturbo_b = [\"P\"]").unwrap();
+        assert_eq!(config.turbo_frames, 4);
+        assert_eq!(config.player2_keys.turbo_b, vec![String::from("P")]);
+        assert_eq!(config.player2_keys.turbo_a, KeyBindings::player2().turbo_a);
+        assert_eq!(EmuConfig::default().turbo_frames, joypad::DEFAULT_TURBO_FRAMES);
+    }
+
+    #[test]
+    fn colorize() {
+        let config = EmuConfig {
+            palette: parse_palette("#111111, #222222, #333333, #444444").unwrap(),
+            ..EmuConfig::default()
+        };
+        assert_eq!(config.colorize(gpu::WHITE), (0x11, 0x11, 0x11));
+        assert_eq!(config.colorize(gpu::BLACK), (0x44, 0x44, 0x44));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn layer_palettes() {
+        let config = EmuConfig::from_toml(
+            r##"
+            palette = ["#111111", "#222222", "#333333", "#444444"]
+
+            [layer_palettes]
+            obj1 = ["#FFFFFF", "#FF8484", "#943A3A", "#000000"]
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.layer_palettes.obj1.unwrap()[1], (0xFF, 0x84, 0x84));
+        assert_eq!(config.layer_palettes.bg, None);
+        let colors = config.colors();
+        assert_eq!(colors.colorize(gpu::LIGHT_GRAY, PaletteLayer::Object1), (0xFF, 0x84, 0x84));
+        assert_eq!(colors.colorize(gpu::LIGHT_GRAY, PaletteLayer::Object0), (0x22, 0x22, 0x22));
+        assert_eq!(EmuConfig::default().colors(), Palette::default());
+
+        let mut other = EmuConfig::default();
+        other.set_colors(&colors);
+        assert_eq!(other.palette, config.palette);
+        assert_eq!(other.colors().layer(PaletteLayer::Object1), colors.layer(PaletteLayer::Object1));
+
+        assert!(EmuConfig::from_toml("[layer_palettes]
+obj0 = [\"#FFFFFF\"]").is_err());
+        assert!(EmuConfig::from_toml("[layer_palettes]
+obj2 = []").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn model_and_quirks() {
+        let config = EmuConfig::from_toml(
+            r##"
+            model = "cgb"
+
+            [quirks]
+            stat_write_bug = true
+            zombie_mode = true
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.model, Some(HardwareModel::CGB));
+        let quirks = config.quirks.gpu(HardwareModel::CGB);
+        // Set quirk overrides model, others come from it
+        assert!(quirks.stat_write_bug);
+        assert!(!quirks.oam_bug);
+        assert!(!quirks.ly_write_resets);
+        assert!(config.quirks.apu().zombie_mode);
+        assert!(!EmuConfig::default().quirks.apu().zombie_mode);
+
+        assert_eq!(EmuConfig::default().model, None);
+        assert!(EmuConfig::from_toml("model = \"gbc\"").is_err());
+    }
+}
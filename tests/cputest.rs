@@ -6,20 +6,80 @@ mod cputest {
 
     const NOP: u8 = 0x00;
 
-    fn gen() -> Runtime<mbc::MBC3> { 
-        Runtime::new(mbc::MBC3::new(vec![0; 1 << 21]))
+    /*
+     * Machine cycles of each opcode as (not taken, taken), same for both unless instruction is conditional.
+     * Illegal opcodes and 0xCB prefix are 0, CB ones take 2, 3 for BIT on (HL) and 4 for the rest on (HL).
+     */
+    const CYCLES: [(u64, u64); 256] = [
+        // 0x00
+        (1, 1), (3, 3), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        (5, 5), (2, 2), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x10
+        (1, 1), (3, 3), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        (3, 3), (2, 2), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x20
+        (2, 3), (3, 3), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        (2, 3), (2, 2), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x30
+        (2, 3), (3, 3), (2, 2), (2, 2), (3, 3), (3, 3), (3, 3), (1, 1),
+        (2, 3), (2, 2), (2, 2), (2, 2), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x40
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x50
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x60
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x70
+        (2, 2), (2, 2), (2, 2), (2, 2), (2, 2), (2, 2), (1, 1), (2, 2),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x80
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0x90
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0xA0
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0xB0
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (1, 1), (2, 2), (1, 1),
+        // 0xC0
+        (2, 5), (3, 3), (3, 4), (4, 4), (3, 6), (4, 4), (2, 2), (4, 4),
+        (2, 5), (4, 4), (3, 4), (0, 0), (3, 6), (6, 6), (2, 2), (4, 4),
+        // 0xD0
+        (2, 5), (3, 3), (3, 4), (0, 0), (3, 6), (4, 4), (2, 2), (4, 4),
+        (2, 5), (4, 4), (3, 4), (0, 0), (3, 6), (0, 0), (2, 2), (4, 4),
+        // 0xE0
+        (3, 3), (3, 3), (2, 2), (0, 0), (0, 0), (4, 4), (2, 2), (4, 4),
+        (4, 4), (1, 1), (4, 4), (0, 0), (0, 0), (0, 0), (2, 2), (4, 4),
+        // 0xF0
+        (3, 3), (3, 3), (2, 2), (1, 1), (0, 0), (4, 4), (2, 2), (4, 4),
+        (3, 3), (2, 2), (4, 4), (1, 1), (0, 0), (0, 0), (2, 2), (4, 4),
+    ];
+
+    // Cycles of single instruction at 0xC000, with all flags set or cleared and pointers into WRAM
+    fn cycles_of(code: &[u8], flags: bool) -> u64 {
+        let mut runtime = Runtime::with_code(0xC000, code);
+        runtime.cpu.set_F(if flags { 0xF0 } else { 0x00 });
+        runtime.cpu.BC.set(0xC100);
+        runtime.cpu.DE.set(0xC100);
+        runtime.cpu.HL.set(0xC100);
+        runtime.cpu.SP = 0xD000;
+        runtime.step();
+        runtime.cpu_cycles()
     }
 
-    fn gen_with_code(code: Vec<u8>) -> Runtime<mbc::MBC3> {
-        let mut bytes = vec![0; 1 << 21];
-        for (i, b) in code.into_iter().enumerate() { bytes[i] = b; }
-        let mut res = Runtime::new(mbc::MBC3::new(bytes));
-        
-        res.state.mmu.disable_bootrom(); // We're testing instructions so bootrom should be disabled
-        res.cpu.STOP = false;
-        res.cpu.HALT = false;
+    fn gen() -> Runtime<mbc::RamOnly> { 
+        Runtime::new(mbc::RamOnly::new(Vec::new()))
+    }
 
-        res
+    // We're testing instructions so bootrom is disabled
+    fn gen_with_code(code: Vec<u8>) -> Runtime<mbc::RamOnly> {
+        Runtime::with_code(0x0000, &code)
     }
 
     #[test]
@@ -53,6 +113,252 @@ mod cputest {
         }
     }
 
+    #[test]
+    fn sp_plus_r8_flags() {
+        let mut runtime = Runtime::with_code(0x0100, &[
+            0xE8, 0x00, // ADD SP, r8
+            0xF8, 0x00, // LD HL, SP+r8
+        ]);
+        let sps = (0..=0xFFu16).chain([0x0F00, 0x7FFF, 0x8000, 0xFF80, 0xFFF8, 0xFFFF]);
+        for sp in sps {
+            for offset in 0..=0xFFu8 {
+                // Flags come from unsigned addition of offset to low byte of SP
+                let expected = sp.wrapping_add(offset as i8 as u16);
+                let half = (sp & 0xF) + (offset & 0xF) as u16 > 0xF;
+                let carry = (sp & 0xFF) + offset as u16 > 0xFF;
+                runtime.state.mmu.load(0x0101, &[offset]);
+                runtime.state.mmu.load(0x0103, &[offset]);
+
+                runtime.cpu.PC.set(0x0102);
+                runtime.cpu.SP = sp;
+                runtime.cpu.Z = true;
+                runtime.cpu.N = true;
+                runtime.step();
+                assert_eq!(runtime.cpu.HL.val(), expected, "LD HL, SP+0x{:02X} with SP 0x{:04X}", offset, sp);
+                assert_eq!(runtime.cpu.SP, sp);
+                assert_eq!((runtime.cpu.Z, runtime.cpu.N, runtime.cpu.H, runtime.cpu.C), (false, false, half, carry));
+
+                runtime.cpu.PC.set(0x0100);
+                runtime.cpu.Z = true;
+                runtime.cpu.N = true;
+                runtime.step();
+                assert_eq!(runtime.cpu.SP, expected, "ADD SP, 0x{:02X} with SP 0x{:04X}", offset, sp);
+                assert_eq!((runtime.cpu.Z, runtime.cpu.N, runtime.cpu.H, runtime.cpu.C), (false, false, half, carry));
+            }
+        }
+    }
+
+    #[test]
+    fn jr_offsets() {
+        for offset in 0..=0xFFu8 {
+            let mut runtime = Runtime::with_code(0x8000, &[0x18, offset]); // JR r8
+            runtime.step();
+            assert_eq!(runtime.cpu.PC.val(), 0x8002u16.wrapping_add(offset as i8 as u16));
+            assert_eq!(runtime.cpu_cycles(), 3);
+        }
+        // Wraps around address space both ways
+        let mut runtime = Runtime::with_code(0xFFF0, &[0x18, 0x7F]);
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), 0x0071);
+        let mut runtime = Runtime::with_code(0x0000, &[0x18, 0x80]);
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), 0xFF82);
+    }
+
+    #[test]
+    fn code_anywhere() {
+        let mut runtime = Runtime::with_code(0xC000, &[
+            0x3E, 0x42, // LD A, 0x42
+            0xC3, 0x00, 0x40, // JP 0x4000
+        ]);
+        runtime.state.mmu.load(0x4000, &[
+            0x3C, // INC A
+            0xEA, 0x00, 0x40, // LD (0x4000), A
+        ]);
+        assert_eq!(runtime.cpu.PC.val(), 0xC000);
+
+        for _ in 0..4 {
+            runtime.step();
+        }
+        assert_eq!(runtime.cpu.A, 0x43);
+        assert_eq!(runtime.cpu.PC.val(), 0x4004);
+        assert_eq!(runtime.state.mmu.read(0x4000), 0x43);
+    }
+
+    #[test]
+    fn fetch_wraps_around() {
+        // Opcode in last HRAM byte, operand is IE
+        let mut runtime = Runtime::with_code(0xFFFE, &[
+            0x3E, 0x1F, // LD A, 0x1F
+        ]);
+        runtime.step();
+        assert_eq!(runtime.cpu.A, 0x1F);
+        assert_eq!(runtime.cpu.PC.val(), 0x0000);
+
+        // Opcode is IE, operands come from 0x0000
+        let mut runtime = Runtime::with_code(0xFFFF, &[
+            0xC3, 0x00, 0xC0, // JP 0xC000
+        ]);
+        assert_eq!(runtime.state.mmu.read(0x0001), 0xC0);
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), 0xC000);
+
+        // Return address wraps as well
+        let mut runtime = Runtime::with_code(0xFFFD, &[
+            0xCD, 0x00, 0xC0, // CALL 0xC000
+        ]);
+        runtime.cpu.SP = 0xD000;
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), 0xC000);
+        assert_eq!(runtime.state.read_word(0xCFFE), 0x0000);
+    }
+
+    #[test]
+    fn code_in_io_and_oam() {
+        // Serial data register is readable and writable, so it holds an instruction
+        let mut runtime = Runtime::with_code(ioregs::SB, &[0x3C]); // INC A
+        runtime.cpu.A = 0x41;
+        runtime.step();
+        assert_eq!(runtime.cpu.A, 0x42);
+        assert_eq!(runtime.cpu.PC.val(), ioregs::SB + 1);
+
+        let mut runtime = Runtime::with_code(0xFE00, &[
+            0x3C, // INC A
+            0xC3, 0x00, 0xC0, // JP 0xC000
+        ]);
+        runtime.cpu.A = 0x00;
+        runtime.step();
+        runtime.step();
+        assert_eq!(runtime.cpu.A, 0x01);
+        assert_eq!(runtime.cpu.PC.val(), 0xC000);
+    }
+
+    #[test]
+    fn pop_af_masks_flags() {
+        for f in 0..=0xFFu8 {
+            let mut runtime = gen_with_code(vec![
+                0xF1, // POP AF
+                0xF5, // PUSH AF
+                0xC1, // POP BC
+            ]);
+            runtime.cpu.SP = 0xD000;
+            runtime.state.mmu.load(0xD000, &[f, 0x5A]);
+
+            runtime.step();
+            assert_eq!(runtime.cpu.A, 0x5A);
+            assert_eq!(runtime.cpu.F(), f & 0xF0);
+            assert_eq!(runtime.cpu.C, f & 0x10 != 0);
+
+            // What's pushed back has lower nibble cleared too
+            runtime.step();
+            runtime.step();
+            assert_eq!(runtime.cpu.BC.val(), 0x5A00 | (f & 0xF0) as u16);
+            assert_eq!(runtime.cpu.SP, 0xD002);
+        }
+
+        let mut runtime = gen();
+        runtime.cpu.set_F(0xFF);
+        assert_eq!(runtime.cpu.F(), 0xF0);
+    }
+
+    #[test]
+    fn push_pop_af_round_trip() {
+        let mut runtime = gen_with_code(vec![
+            0xF5, // PUSH AF
+            0xAF, // XOR A, A
+            0x37, // SCF
+            0xF1, // POP AF
+        ]);
+        runtime.cpu.SP = 0xD000;
+        runtime.cpu.A = 0xC3;
+        runtime.cpu.set_F(0b1010_0000);
+
+        for _ in 0..4 {
+            runtime.step();
+        }
+        assert_eq!(runtime.cpu.A, 0xC3);
+        assert_eq!(runtime.cpu.F(), 0b1010_0000);
+        assert_eq!(runtime.cpu.SP, 0xD000);
+    }
+
+    #[test]
+    fn push_pop_across_region_boundary() {
+        let mut runtime = gen_with_code(vec![
+            0xC5, // PUSH BC
+            0xD1, // POP DE
+        ]);
+        // Low byte lands at 0xCFFF, high one at 0xD000
+        runtime.cpu.SP = 0xD001;
+        runtime.cpu.BC.set(0x1234);
+
+        runtime.step();
+        assert_eq!(runtime.cpu.SP, 0xCFFF);
+        assert_eq!(runtime.state.mmu.read(0xCFFF), 0x34);
+        assert_eq!(runtime.state.mmu.read(0xD000), 0x12);
+        runtime.step();
+        assert_eq!(runtime.cpu.DE.val(), 0x1234);
+        assert_eq!(runtime.cpu.SP, 0xD001);
+    }
+
+    #[test]
+    fn ld_a16_sp() {
+        let mut runtime = Runtime::with_code(0x0100, &[
+            0x08, 0x00, 0xC1, // LD (0xC100), SP
+            0x08, 0xFF, 0xFF, // LD (0xFFFF), SP
+        ]);
+        runtime.cpu.SP = 0xBEEF;
+
+        runtime.step();
+        assert_eq!(runtime.cpu_cycles(), 5);
+        assert_eq!(runtime.state.mmu.read(0xC100), 0xEF);
+        assert_eq!(runtime.state.mmu.read(0xC101), 0xBE);
+
+        // Upper byte wraps around to 0x0000
+        runtime.step();
+        assert_eq!(runtime.state.mmu.read(ioregs::IE), 0xEF);
+        assert_eq!(runtime.state.mmu.read(0x0000), 0xBE);
+    }
+
+    // Runs op with A, B and carry set, returns A and Z, N, H, C
+    fn with_carry(op: u8, a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+        let mut runtime = Runtime::with_code(0x0100, &[op]);
+        runtime.cpu.A = a;
+        runtime.cpu.BC.set((b as u16) << 8);
+        runtime.cpu.C = carry;
+        runtime.step();
+        let cpu = &runtime.cpu;
+        (cpu.A, cpu.Z, cpu.N, cpu.H, cpu.C)
+    }
+
+    #[test]
+    fn adc_carry_vectors() {
+        // ADC A, B
+        assert_eq!(with_carry(0x88, 0xFF, 0x00, true), (0x00, true, false, true, true));
+        assert_eq!(with_carry(0x88, 0x00, 0xFF, true), (0x00, true, false, true, true));
+        assert_eq!(with_carry(0x88, 0x0F, 0x00, true), (0x10, false, false, true, false));
+        assert_eq!(with_carry(0x88, 0x80, 0x7F, true), (0x00, true, false, true, true));
+        assert_eq!(with_carry(0x88, 0x08, 0x07, true), (0x10, false, false, true, false));
+        assert_eq!(with_carry(0x88, 0xE1, 0x0F, true), (0xF1, false, false, true, false));
+        assert_eq!(with_carry(0x88, 0xE1, 0x3B, true), (0x1D, false, false, false, true));
+        assert_eq!(with_carry(0x88, 0xE1, 0x1E, true), (0x00, true, false, true, true));
+        assert_eq!(with_carry(0x88, 0xFF, 0xFF, true), (0xFF, false, false, true, true));
+        assert_eq!(with_carry(0x88, 0x00, 0x00, false), (0x00, true, false, false, false));
+    }
+
+    #[test]
+    fn sbc_carry_vectors() {
+        // SBC A, B
+        assert_eq!(with_carry(0x98, 0x00, 0xFF, true), (0x00, true, true, true, true));
+        assert_eq!(with_carry(0x98, 0x00, 0x00, true), (0xFF, false, true, true, true));
+        assert_eq!(with_carry(0x98, 0x10, 0x0F, true), (0x00, true, true, true, false));
+        assert_eq!(with_carry(0x98, 0x10, 0x00, true), (0x0F, false, true, true, false));
+        assert_eq!(with_carry(0x98, 0x3B, 0x2A, true), (0x10, false, true, false, false));
+        assert_eq!(with_carry(0x98, 0x3B, 0x3A, true), (0x00, true, true, false, false));
+        assert_eq!(with_carry(0x98, 0x3B, 0x4F, true), (0xEB, false, true, true, true));
+        assert_eq!(with_carry(0x98, 0xFF, 0xFF, true), (0xFF, false, true, true, true));
+        assert_eq!(with_carry(0x98, 0x01, 0x01, false), (0x00, true, true, false, false));
+    }
+
     #[test]
     fn cb_instructions() {
         let mut runtime = gen_with_code(vec![
@@ -66,10 +372,86 @@ mod cputest {
 
         runtime.step();
         assert_eq!(runtime.cpu.A, 1 << 2);
-        runtime.cpu.Z = false;
+        runtime.cpu.Z = true;
 
+        // Z is set when tested bit is 0
         runtime.step();
-        assert_eq!(runtime.cpu.Z, true);
+        assert!(!runtime.cpu.Z);
+    }
+
+    /* B, C, D, E, H, L, (HL), A, same order as in opcodes. HL is the address from before the instruction. */
+    fn cb_operands(runtime: &mut Runtime<mbc::RamOnly>, hl: u16) -> [u8; 8] {
+        let cpu = &runtime.cpu;
+        let mut operands = [cpu.BC.up(), cpu.BC.low(), cpu.DE.up(), cpu.DE.low(), cpu.HL.up(), cpu.HL.low(), 0, cpu.A];
+        operands[6] = runtime.state.mmu.read(hl);
+        operands
+    }
+
+    #[test]
+    fn opcode_cycles() {
+        for op in 0..=0xFFu8 {
+            let (not_taken, taken) = CYCLES[op as usize];
+            if not_taken == 0 {
+                continue;
+            }
+            // Operands point at 0xC000, so jumps and stores stay in WRAM
+            let code = [op, 0x00, 0xC0];
+            let cycles = [cycles_of(&code, false), cycles_of(&code, true)];
+            let (min, max) = (cycles[0].min(cycles[1]), cycles[0].max(cycles[1]));
+            let text = disassemble(0xC000, &code).map(|(text, _)| text);
+            assert_eq!((min, max), (not_taken, taken), "cycles of 0x{:02X} {:?}", op, text);
+        }
+        for op in 0..=0xFFu8 {
+            let expected = match (op & 0x7, op >> 6) {
+                (6, 1) => 3,
+                (6, _) => 4,
+                _ => 2,
+            };
+            assert_eq!(cycles_of(&[0xCB, op], false), expected, "cycles of CB 0x{:02X}", op);
+        }
+    }
+
+    #[test]
+    fn cb_every_opcode() {
+        // Every bit is seen both set and cleared
+        for &mask in [0x00, 0xFF].iter() {
+            for cb in 0..=0xFFu8 {
+                let mut runtime = gen_with_code(vec![0xCB, cb]);
+                let before = [0x12, 0x34, 0x56, 0x78, 0xC0, 0x01, 0xBC, 0x9A].map(|value: u8| value ^ mask);
+                runtime.cpu.BC.set(u16::from_be_bytes([before[0], before[1]]));
+                runtime.cpu.DE.set(u16::from_be_bytes([before[2], before[3]]));
+                runtime.cpu.HL.set(u16::from_be_bytes([before[4], before[5]]));
+                runtime.cpu.A = before[7];
+                runtime.state.mmu.write(runtime.cpu.HL.val(), before[6]);
+                runtime.cpu.C = false;
+
+                runtime.step();
+                assert_eq!(runtime.cpu.PC.val(), 0x0002, "CB {:02X}", cb);
+                let after = cb_operands(&mut runtime, u16::from_be_bytes([before[4], before[5]]));
+                let (target, bit) = ((cb & 0x7) as usize, (cb >> 3) & 0x7);
+                let value = before[target];
+                let expected = match cb >> 6 {
+                    0 => match bit {
+                        0 => value.rotate_left(1),
+                        1 => value.rotate_right(1),
+                        2 | 4 => value << 1,
+                        3 | 7 => value >> 1,
+                        5 => value >> 1 | (value & 0x80),
+                        _ => value.rotate_left(4),
+                    },
+                    1 => {
+                        assert_eq!(runtime.cpu.Z, value & (1 << bit) == 0, "CB {:02X}", cb);
+                        value
+                    }
+                    2 => value & !(1 << bit),
+                    _ => value | (1 << bit),
+                };
+                for (i, (&old, &new)) in before.iter().zip(after.iter()).enumerate() {
+                    let wanted = if i == target { expected } else { old };
+                    assert_eq!(new, wanted, "CB {:02X}, operand {}", cb, i);
+                }
+            }
+        }
     }
 
     #[test]
@@ -266,4 +648,98 @@ mod cputest {
         assert_eq!(runtime.cpu.IME, false);
         assert_eq!(runtime.cpu.PC.val(), 0x0048);
     }
+
+    /* CPU with interrupts enabled at 0x1234, stack in WRAM, IE and IF set */
+    fn gen_interrupts(ie: u8, iflag: u8) -> Runtime<mbc::RamOnly> {
+        let mut runtime = Runtime::with_code(0x1234, &[]);
+        runtime.cpu.IME = true;
+        runtime.cpu.SP = 0xD000;
+        runtime.state.safe_write(ioregs::IE, ie);
+        runtime.state.safe_write(ioregs::IF, iflag);
+        runtime
+    }
+
+    #[test]
+    fn interrupt_priority() {
+        let mut runtime = gen_interrupts(0x1F, 0x1F);
+        // VBlank, STAT, timer, serial, joypad
+        for (bit, vector) in [0x40, 0x48, 0x50, 0x58, 0x60].iter().enumerate() {
+            runtime.cpu.IME = true;
+            // 5 machine cycles, 20 clocks
+            assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 5);
+            assert_eq!(runtime.cpu.PC.val(), *vector);
+            assert!(!runtime.cpu.IME);
+            assert_eq!(runtime.state.mmu.read(ioregs::IF), 0xE0 | (0x1F << (bit + 1)) & 0x1F);
+        }
+        runtime.cpu.IME = true;
+        assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 0);
+
+        // Only enabled ones count, highest priority first
+        let mut runtime = gen_interrupts(0b10100, 0b10110);
+        assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 5);
+        assert_eq!(runtime.cpu.PC.val(), 0x50);
+        assert_eq!(runtime.cpu.SP, 0xCFFE);
+        assert_eq!(runtime.state.mmu.read(0xCFFE), 0x34);
+        assert_eq!(runtime.state.mmu.read(0xCFFF), 0x12);
+        assert_eq!(runtime.state.mmu.read(ioregs::IF), 0xE0 | 0b10010);
+    }
+
+    #[test]
+    fn interrupt_flag_upper_bits() {
+        let mut runtime = gen_interrupts(0x00, 0x00);
+        assert_eq!(runtime.state.safe_read(ioregs::IF), 0xE0);
+        runtime.state.safe_write(ioregs::IF, 0x05);
+        assert_eq!(runtime.state.safe_read(ioregs::IF), 0xE5);
+        // IE keeps all 8 bits
+        runtime.state.safe_write(ioregs::IE, 0xFF);
+        assert_eq!(runtime.state.safe_read(ioregs::IE), 0xFF);
+    }
+
+    #[test]
+    fn interrupt_cancelled_by_ie_push() {
+        // Upper byte of PC(0x12) lands on IE and disables VBlank, nothing else is pending
+        let mut runtime = gen_interrupts(0x01, 0x01);
+        runtime.cpu.SP = 0x0000;
+        assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 5);
+        assert_eq!(runtime.cpu.PC.val(), 0x0000);
+        assert!(!runtime.cpu.IME);
+        assert_eq!(runtime.cpu.SP, 0xFFFE);
+        assert_eq!(runtime.state.mmu.read(ioregs::IE), 0x12);
+        assert_eq!(runtime.state.mmu.read(0xFFFE), 0x34);
+        // Request stays, it wasn't serviced
+        assert_eq!(runtime.state.mmu.read(ioregs::IF), 0xE1);
+
+        // Push enables STAT instead, which is taken in place of VBlank
+        let mut runtime = gen_interrupts(0x01, 0x03);
+        runtime.cpu.PC.set(0x0234);
+        runtime.cpu.SP = 0x0000;
+        assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 5);
+        assert_eq!(runtime.cpu.PC.val(), 0x0048);
+        assert_eq!(runtime.state.mmu.read(ioregs::IF), 0xE1);
+
+        // Lower byte lands on IE after interrupt was picked, it doesn't matter
+        let mut runtime = gen_interrupts(0x01, 0x01);
+        runtime.cpu.SP = 0x0001;
+        assert_eq!(runtime.cpu.interrupts(&mut runtime.state), 5);
+        assert_eq!(runtime.cpu.PC.val(), 0x0040);
+        assert_eq!(runtime.state.mmu.read(ioregs::IE), 0x34);
+    }
+
+    #[test]
+    fn disassembly() {
+        assert_eq!(disassemble(0x100, &[0x00]), Some((String::from("NOP"), 1)));
+        assert_eq!(disassemble(0x100, &[0xC3, 0x50, 0x01]), Some((String::from("JP $0150"), 3)));
+        assert_eq!(disassemble(0x100, &[0x3E, 0x42]), Some((String::from("LD A, $42"), 2)));
+        assert_eq!(disassemble(0x100, &[0x46]), Some((String::from("LD B, (HL)"), 1)));
+        assert_eq!(disassemble(0x100, &[0x3C]), Some((String::from("INC A"), 1)));
+        assert_eq!(disassemble(0x100, &[0xE0, 0x40]), Some((String::from("LDH ($FF40), A"), 2)));
+        assert_eq!(disassemble(0x100, &[0x18, 0xFE]), Some((String::from("JR $0100"), 2)));
+        assert_eq!(disassemble(0x100, &[0xE8, 0xFF]), Some((String::from("ADD SP, -1"), 2)));
+        assert_eq!(disassemble(0x100, &[0xCB, 0x7C]), Some((String::from("BIT 7, H"), 2)));
+        assert_eq!(disassemble(0x100, &[0xCB, 0x37]), Some((String::from("SWAP A"), 2)));
+        assert_eq!(disassemble(0x100, &[0xCB, 0x86]), Some((String::from("RES 0, (HL)"), 2)));
+        assert_eq!(disassemble(0x100, &[0xCB, 0xFF]), Some((String::from("SET 7, A"), 2)));
+        assert_eq!(disassemble(0x100, &[0xCB, 0x1A]), Some((String::from("RR D"), 2)));
+        assert_eq!(disassemble(0x100, &[0xD3]), None);
+    }
 }
\ No newline at end of file
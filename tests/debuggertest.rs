@@ -0,0 +1,229 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod debuggertest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Program at 0x100, extra routines at given addresses, debugger on */
+    fn gen(program: &[u8], routines: &[(usize, &[u8])]) -> Runtime<mbc::RomOnly> {
+        let mut runtime = program_runtime(program, routines);
+        runtime.state.debugger = Some(Debugger::new());
+        runtime
+    }
+
+    fn add(runtime: &mut Runtime<mbc::RomOnly>, breakpoint: Breakpoint) -> BreakpointId {
+        runtime.state.debugger.as_mut().unwrap().add(breakpoint)
+    }
+
+    /* Turns LCD off, then writes 0x91 to LCDC over and over */
+    const LCDC_LOOP: [u8; 10] = [
+        0xAF, // XOR A
+        0xE0, 0x40, // LDH (LCDC), A
+        0x3E, 0x91, // LD A, 0x91
+        0xE0, 0x40, // LDH (LCDC), A
+        0x18, 0xFA, // JR -6
+        0x00,
+    ];
+
+    #[test]
+    fn pc() {
+        let mut runtime = gen(&LCDC_LOOP, &[]);
+        let id = add(&mut runtime, Breakpoint::Pc(0x103));
+        let hit = runtime.run_until_break(1000).unwrap();
+        assert_eq!(hit.id, id);
+        assert_eq!(hit.pc, 0x103);
+        assert_eq!(hit.value, None);
+        // Stops before the instruction
+        assert_eq!(runtime.cpu.PC.val(), 0x103);
+        assert_eq!(runtime.cpu.A, 0x00);
+
+        // Goes on from there and stops again on next loop
+        let hit = runtime.run_until_break(1000).unwrap();
+        assert_eq!(hit.pc, 0x103);
+        assert_eq!(runtime.cpu.A, 0x91);
+
+        assert!(runtime.state.debugger.as_mut().unwrap().remove(id));
+        assert!(runtime.run_until_break(1000).is_none());
+    }
+
+    #[test]
+    fn banked_pc() {
+        // Four banks, both 2 and 3 have RET at 0x4000
+        let mut rom = vec![0; 1 << 16];
+        rom[0x148] = 0x01;
+        rom[2 * 0x4000] = 0xC9;
+        rom[3 * 0x4000] = 0xC9;
+        let program = [
+            0x3E, 0x02, // LD A, 2
+            0xEA, 0x00, 0x20, // LD (0x2000), A
+            0xCD, 0x00, 0x40, // CALL 0x4000
+            0x3E, 0x03, // LD A, 3
+            0xEA, 0x00, 0x20, // LD (0x2000), A
+            0xCD, 0x00, 0x40, // CALL 0x4000
+            0x18, 0xFE, // JR -2
+        ];
+        rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.state.debugger = Some(Debugger::new());
+        let id = runtime.state.debugger.as_mut().unwrap().add(Breakpoint::code(3, 0x4000));
+
+        // Same address in bank 2 doesn't stop
+        let hit = runtime.run_until_break(1000).unwrap();
+        assert_eq!(hit.id, id);
+        assert_eq!(hit.pc, 0x4000);
+        assert_eq!(runtime.state.mmu.mapper.current_rom_bank(), 3);
+        assert_eq!(runtime.cpu.SP, 0xFFFC);
+        assert_eq!(hit.to_string(), format!("breakpoint {}: PC 03:4000 at PC=0x4000, cycle {}", id, hit.cycle));
+    }
+
+    #[test]
+    fn code_breakpoint_bank() {
+        assert_eq!(Breakpoint::code(3, 0x4000), Breakpoint::BankedPc { bank: 3, addr: 0x4000 });
+        // Bank doesn't matter outside switchable ROM
+        assert_eq!(Breakpoint::code(3, 0x3FFF), Breakpoint::Pc(0x3FFF));
+        assert_eq!(Breakpoint::code(3, 0xC000), Breakpoint::Pc(0xC000));
+
+        let banked = Breakpoint::code(3, 0x4000);
+        assert!(banked.stops_at(3, 0x4000));
+        assert!(!banked.stops_at(2, 0x4000));
+        assert!(Breakpoint::Pc(0x150).stops_at(7, 0x150));
+        assert!(!Breakpoint::Interrupt(Interrupt::VBlank).stops_at(1, 0x40));
+    }
+
+    #[test]
+    fn io_write_condition() {
+        let mut runtime = gen(&LCDC_LOOP, &[]);
+        add(&mut runtime, Breakpoint::Io {
+            addr: ioregs::LCDC,
+            kind: AccessKind::Write,
+            condition: Condition::Masked { mask: 0x80, value: 0x80 },
+        });
+        let hit = runtime.run_until_break(1000).unwrap();
+        assert_eq!(hit.pc, 0x105);
+        assert_eq!(hit.value, Some(0x91));
+        // Stops after the instruction making the access
+        assert_eq!(runtime.cpu.PC.val(), 0x107);
+        assert_eq!(runtime.state.mmu.read(ioregs::LCDC), 0x91);
+        assert_eq!(hit.to_string(), format!(
+            "breakpoint 0: write to 0xFF40, value & 0x80 == 0x80 at PC=0x0105, cycle {}, value 0x91", hit.cycle
+        ));
+    }
+
+    #[test]
+    fn io_read() {
+        let program = [
+            0xF0, 0x00, // LDH A, (P1)
+            0x18, 0xFC, // JR -4
+        ];
+        let mut runtime = gen(&program, &[]);
+        add(&mut runtime, Breakpoint::Io { addr: ioregs::P1, kind: AccessKind::Write, condition: Condition::Any });
+        assert!(runtime.run_until_break(100).is_none());
+        add(&mut runtime, Breakpoint::Io { addr: ioregs::P1, kind: AccessKind::Read, condition: Condition::NotEqual(0) });
+        let hit = runtime.run_until_break(100).unwrap();
+        assert_eq!(hit.id, 1);
+        assert_eq!(hit.pc, 0x100);
+    }
+
+    #[test]
+    fn conditions() {
+        assert!(Condition::Any.matches(0x12));
+        assert!(Condition::Equal(0x12).matches(0x12));
+        assert!(!Condition::Equal(0x12).matches(0x13));
+        assert!(Condition::NotEqual(0x12).matches(0x13));
+        assert!(Condition::Masked { mask: 0x80, value: 0x00 }.matches(0x7F));
+        assert!(!Condition::Masked { mask: 0x80, value: 0x00 }.matches(0x80));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("".parse(), Ok(Condition::Any));
+        assert_eq!("==91".parse(), Ok(Condition::Equal(0x91)));
+        assert_eq!("!=0x00".parse(), Ok(Condition::NotEqual(0x00)));
+        assert_eq!("&80==$00".parse(), Ok(Condition::Masked { mask: 0x80, value: 0x00 }));
+        assert!("=91".parse::<Condition>().is_err());
+        assert!("==G1".parse::<Condition>().is_err());
+
+        assert_eq!("VBlank".parse(), Ok(Interrupt::VBlank));
+        assert_eq!("lcd".parse(), Ok(Interrupt::Stat));
+        assert!("nmi".parse::<Interrupt>().is_err());
+
+        assert_eq!(ioregs::by_name("nr52"), Some(ioregs::NR_52));
+        assert_eq!(ioregs::by_name("LCDC"), Some(ioregs::LCDC));
+        assert_eq!(ioregs::by_name("R2D2"), None);
+    }
+
+    #[test]
+    fn interrupt() {
+        let program = [
+            0x3E, 0x04, // LD A, 0x04
+            0xE0, 0xFF, // LDH (IE), A
+            0x3E, 0x05, // LD A, 0x05
+            0xE0, 0x07, // LDH (TAC), A
+            0xFB, // EI
+            0x18, 0xFE, // JR -2
+        ];
+        let handler = [0xD9]; // RETI
+        let mut runtime = gen(&program, &[(0x40, &handler), (0x50, &handler)]);
+        add(&mut runtime, Breakpoint::Interrupt(Interrupt::VBlank));
+        add(&mut runtime, Breakpoint::Interrupt(Interrupt::Timer));
+        let hit = runtime.run_until_break(CPU_CYCLES_PER_FRAME).unwrap();
+        assert_eq!(hit.breakpoint, Breakpoint::Interrupt(Interrupt::Timer));
+        assert_eq!(hit.pc, 0x50);
+        assert_eq!(runtime.cpu.PC.val(), 0x50);
+        // Handler runs once resumed
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), 0x109);
+        assert_eq!(Interrupt::Timer.vector(), 0x50);
+        assert_eq!(Interrupt::from_vector(0x60), Some(Interrupt::Joypad));
+    }
+
+    #[test]
+    fn trap_illegal_access() {
+        let program = [
+            0x3E, 0x01, // LD A, 0x01
+            0xEA, 0x00, 0x20, // LD (0x2000), A
+            0x18, 0xF9, // JR -7
+        ];
+        let mut runtime = gen(&program, &[]);
+        runtime.state.mmu.policy = BusPolicy::Trap;
+        let hit = runtime.run_until_break(1000).unwrap();
+        assert_eq!(hit.id, TRAP_ID);
+        assert_eq!(hit.breakpoint, Breakpoint::Trap(IllegalAccess::RomWrite { addr: 0x2000, value: 0x01 }));
+        assert_eq!(hit.pc, 0x102);
+        // Stops after the instruction, write itself is dropped
+        assert_eq!(runtime.cpu.PC.val(), 0x105);
+        assert_eq!(runtime.state.mmu.read(0x2000), 0x00);
+        assert_eq!(hit.to_string(), "trapped write of 0x01 to ROM at 0x2000 at PC=0x0102, cycle 2");
+        // Goes on until the next one
+        assert_eq!(runtime.run_until_break(1000).unwrap().pc, 0x102);
+
+        // Other policies don't stop
+        runtime.state.mmu.policy = BusPolicy::Log;
+        assert!(runtime.run_until_break(1000).is_none());
+    }
+
+    #[test]
+    fn trap_ignores_debugger_reads() {
+        let mut runtime = gen(&LCDC_LOOP, &[]);
+        runtime.state.mmu.policy = BusPolicy::Trap;
+        // Like memory view of debugger, outside of any instruction
+        runtime.state.mmu.read(RAM_SWITCHABLE_ADDR);
+        assert!(runtime.run_until_break(1000).is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn kept_on_savestate() {
+        let mut runtime = gen(&LCDC_LOOP, &[]);
+        add(&mut runtime, Breakpoint::Pc(0x103));
+        let screen = runtime.state.gpu.framebuff.clone();
+        let state = SaveState::capture(&mut runtime, &screen).unwrap();
+        state.restore(&mut runtime).unwrap();
+        assert_eq!(runtime.state.debugger.as_ref().unwrap().breakpoints().len(), 1);
+        assert!(runtime.run_until_break(1000).is_some());
+    }
+}
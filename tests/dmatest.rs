@@ -0,0 +1,95 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod dmatest {
+    use gameboy::*;
+
+    const OAM_TABLE: usize = 0xA0;
+
+    fn gen_state() -> State<mbc::MBC1> {
+        // Every ROM bank is filled with its number, header asks for 32KB of RAM
+        let mut rom: Vec<u8> = (0..1 << 21).map(|i| (i / ROM_BANK_SIZE) as u8).collect();
+        rom[0x149] = 0x03;
+        let mut state = State::new(mbc::MBC1::new(rom));
+        state.mmu.disable_bootrom();
+        state
+    }
+
+    fn transfer(state: &mut State<mbc::MBC1>, page: u8) {
+        state.safe_write(ioregs::DMA, page);
+        assert!(state.dma.active());
+        state.dma.step(&mut state.mmu);
+        assert!(!state.dma.active());
+    }
+
+    fn fill(state: &mut State<mbc::MBC1>, addr: Addr, value: u8) {
+        for i in 0..OAM_TABLE as Addr {
+            state.mmu.write(addr + i, value.wrapping_add(i as u8));
+        }
+    }
+
+    fn assert_oam(state: &State<mbc::MBC1>, value: u8) {
+        for i in 0..OAM_TABLE {
+            assert_eq!(state.mmu.oam[i], value.wrapping_add(i as u8), "OAM byte {}", i);
+        }
+    }
+
+    #[test]
+    fn whole_table() {
+        let mut state = gen_state();
+        fill(&mut state, 0xC100, 0x10);
+        transfer(&mut state, 0xC1);
+        assert_oam(&state, 0x10);
+        // Unusable area past the table isn't touched
+        assert_eq!(state.mmu.oam[OAM_TABLE], 0x00);
+    }
+
+    #[test]
+    fn from_rom_banks() {
+        let mut state = gen_state();
+        transfer(&mut state, 0x02);
+        assert!(state.mmu.oam[..OAM_TABLE].iter().all(|byte| *byte == 0));
+
+        // Switchable area is read with bank selected now
+        state.mmu.write(0x2000, 0x05);
+        transfer(&mut state, 0x40);
+        assert!(state.mmu.oam[..OAM_TABLE].iter().all(|byte| *byte == 5));
+    }
+
+    #[test]
+    fn from_cart_ram() {
+        let mut state = gen_state();
+        state.mmu.write(0x0000, 0x0A);
+        fill(&mut state, 0xA000, 0x30);
+        transfer(&mut state, 0xA0);
+        assert_oam(&state, 0x30);
+    }
+
+    #[test]
+    fn from_vram_and_wram() {
+        let mut state = gen_state();
+        fill(&mut state, 0x8800, 0x50);
+        transfer(&mut state, 0x88);
+        assert_oam(&state, 0x50);
+
+        fill(&mut state, 0xDF00, 0x70);
+        transfer(&mut state, 0xDF);
+        assert_oam(&state, 0x70);
+    }
+
+    #[test]
+    fn high_pages_clamped_to_wram() {
+        let mut state = gen_state();
+        fill(&mut state, 0xC000, 0x01);
+        transfer(&mut state, 0xE0);
+        assert_oam(&state, 0x01);
+
+        // OAM and IO are never read, WRAM is instead
+        fill(&mut state, 0xDE00, 0x90);
+        transfer(&mut state, 0xFE);
+        assert_oam(&state, 0x90);
+        fill(&mut state, 0xDF00, 0xA0);
+        transfer(&mut state, 0xFF);
+        assert_oam(&state, 0xA0);
+    }
+}
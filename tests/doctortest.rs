@@ -0,0 +1,105 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod doctortest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Cart without MBC, so writes to ROM are ignored, with program at 0x100 */
+    fn gen(program: &[u8]) -> Runtime<mbc::RomOnly> {
+        let mut runtime = program_runtime(program, &[]);
+        runtime.state.doctor = Some(Doctor::new());
+        runtime
+    }
+
+    fn run(runtime: &mut Runtime<mbc::RomOnly>, steps: usize) -> Vec<Diagnosis> {
+        for _ in 0..steps {
+            runtime.step();
+        }
+        runtime.state.doctor.as_mut().unwrap().take_reports()
+    }
+
+    #[test]
+    fn illegal_opcode_locks_up() {
+        let mut runtime = gen(&[0x00, 0xD3]);
+        let reports = run(&mut runtime, 10);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].fault, Fault::IllegalOpcode(0xD3));
+        assert_eq!(reports[0].pc, 0x101);
+        assert_eq!(reports[0].instruction, "DB $D3");
+        assert!(runtime.state.doctor.as_ref().unwrap().locked());
+        assert_eq!(runtime.cpu.PC.val(), 0x101);
+
+        runtime.reset(true);
+        assert!(!runtime.state.doctor.as_ref().unwrap().locked());
+    }
+
+    #[test]
+    fn rom_write() {
+        // LD (0x2000), A
+        let mut runtime = gen(&[0xEA, 0x00, 0x20]);
+        let reports = run(&mut runtime, 1);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].fault, Fault::RomWrite { addr: 0x2000, value: 0x01 });
+        assert_eq!(reports[0].instruction, "LD ($2000), A");
+    }
+
+    #[test]
+    fn bootrom_write_dropped() {
+        let mut runtime = Runtime::new(mbc::RomOnly::new(vec![0; 1 << 15]));
+        runtime.state.doctor = Some(Doctor::new());
+        runtime.state.safe_write(0x0010, 0x42);
+        assert_eq!(runtime.state.doctor.unwrap().reports()[0].fault, Fault::RomWrite { addr: 0x10, value: 0x42 });
+    }
+
+    #[test]
+    fn stack_underflow() {
+        // POP BC
+        let mut runtime = gen(&[0xC1]);
+        let reports = run(&mut runtime, 1);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].fault, Fault::StackUnderflow);
+        assert_eq!(reports[0].sp, 0xFFFE);
+    }
+
+    #[test]
+    fn dead_halt() {
+        // LD A, 0; LDH (IE), A; HALT
+        let mut runtime = gen(&[0x3E, 0x00, 0xE0, 0xFF, 0x76]);
+        let reports = run(&mut runtime, 10);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].fault, Fault::DeadHalt);
+    }
+
+    #[test]
+    fn bad_execution() {
+        // JP 0xFF90, HRAM nobody wrote to
+        let mut runtime = gen(&[0xC3, 0x90, 0xFF]);
+        let reports = run(&mut runtime, 2);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].fault, Fault::UninitializedHram);
+
+        // JP 0xE000, echo RAM
+        let mut runtime = gen(&[0xC3, 0x00, 0xE0]);
+        let reports = run(&mut runtime, 2);
+        assert_eq!(reports[0].fault, Fault::UnmappedExecution);
+        assert_eq!(reports[0].pc, 0xE000);
+    }
+
+    #[test]
+    fn hram_routine() {
+        // LD A, 0xC9(RET); LDH (0x80), A; CALL 0xFF80
+        let mut runtime = gen(&[0x3E, 0xC9, 0xE0, 0x80, 0xCD, 0x80, 0xFF]);
+        assert!(run(&mut runtime, 4).is_empty());
+        assert_eq!(runtime.cpu.PC.val(), 0x107);
+    }
+
+    #[test]
+    fn reported_once() {
+        // Endless loop writing ROM: LD (0x2000), A; JR -5
+        let mut runtime = gen(&[0xEA, 0x00, 0x20, 0x18, 0xFB]);
+        assert_eq!(run(&mut runtime, 20).len(), 1);
+    }
+}
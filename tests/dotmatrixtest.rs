@@ -0,0 +1,56 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod dotmatrixtest {
+    use gameboy::*;
+
+    const GREEN: Color = (0xE0, 0xF8, 0xD0);
+
+    fn close(a: Color, b: Color) -> bool {
+        let near = |a: u8, b: u8| (a as i32 - b as i32).abs() <= 12;
+        near(a.0, b.0) && near(a.1, b.1) && near(a.2, b.2)
+    }
+
+    #[test]
+    fn larger_picture() {
+        let dots = DotMatrix::default();
+        assert_eq!(dots.dot(), DOT_SIZE);
+        let picture = vec![BLACK; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let out = dots.render(&picture, SCREEN_WIDTH, GREEN);
+        assert_eq!(out.len(), SCREEN_WIDTH * SCREEN_HEIGHT * DOT_SIZE * DOT_SIZE);
+        // Too small dot is made big enough for the gap
+        assert_eq!(DotMatrix::new(1).dot(), 2);
+        assert!(dots.render(&picture, 0, GREEN).is_empty());
+    }
+
+    #[test]
+    fn dots_and_gaps() {
+        let dots = DotMatrix::new(4);
+        let out = dots.render(&[BLACK, WHITE], 2, GREEN);
+        let width = 8;
+        // Inside of the dot is the pixel, with a bit of variation
+        for row in 0..3 {
+            for col in 0..3 {
+                assert!(close(out[row * width + col], BLACK));
+                assert!(close(out[row * width + 4 + col], WHITE));
+            }
+        }
+        // Gaps are the background, darker next to black dot
+        let gap = out[3 * width];
+        assert_eq!(out[3], gap);
+        assert!(gap.1 < GREEN.1 && gap.1 > BLACK.1);
+        assert_ne!(out[3 * width + 4], gap);
+    }
+
+    #[test]
+    fn same_every_frame() {
+        let dots = DotMatrix::default();
+        let shades = [WHITE, LIGHT_GRAY, DARK_GRAY];
+        let picture = (0..SCREEN_WIDTH * SCREEN_HEIGHT).map(|i| shades[i % 3]).collect::<Vec<_>>();
+        let first = dots.render(&picture, SCREEN_WIDTH, GREEN);
+        assert_eq!(dots.render(&picture, SCREEN_WIDTH, GREEN), first);
+        // Dots of the same color don't all look the same
+        let light = (0..SCREEN_WIDTH).step_by(3).map(|x| first[x * DOT_SIZE]).collect::<Vec<_>>();
+        assert!(light.iter().any(|color| *color != light[0]));
+    }
+}
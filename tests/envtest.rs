@@ -0,0 +1,77 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod envtest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Reads P1 with buttons selected, then loops forever */
+    const PROGRAM: [u8; 7] = [
+        0x3E, 0x10, // LD A, 0x10
+        0xE0, 0x00, // LDH (P1), A
+        0x00, // NOP
+        0x18, 0xFD, // JR -3
+    ];
+
+    fn gen() -> Env<mbc::RomOnly> {
+        Env::new(program_runtime(&PROGRAM, &[]))
+    }
+
+    #[test]
+    fn deterministic() {
+        let (mut first, mut second) = (gen(), gen());
+        assert_eq!(first.reset(7), second.reset(7));
+        for action in [0, BUTTON_A, BUTTON_START | BUTTON_UP, 0] {
+            assert_eq!(first.step(action), second.step(action));
+        }
+        // Other seed, other garbage in RAM
+        assert_ne!(first.reset(8).wram, second.reset(7).wram);
+    }
+
+    #[test]
+    fn step() {
+        let mut env = gen();
+        env.set_frame_skip(4);
+        env.set_watch(0xC000..0xC010);
+        let obs = env.reset(0);
+        assert_eq!(obs.frame, 0);
+        assert_eq!(obs.wram.len(), 0x10);
+
+        let obs = env.step(BUTTON_A | BUTTON_START);
+        assert_eq!(obs.frame, 4);
+        assert_eq!(obs.framebuffer.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(env.runtime.state.safe_read(ioregs::P1) & 0x0F, 0b0110);
+        env.step(0);
+        assert_eq!(env.runtime.state.safe_read(ioregs::P1) & 0x0F, 0b1111);
+    }
+
+    #[test]
+    fn hooks() {
+        let mut env = gen();
+        env.set_reward_hook(Box::new(|state| state.mmu.read(0xC000) as f32));
+        env.set_done_hook(Box::new(|state| state.mmu.read(0xC000) == 0x42));
+        env.reset(0);
+        env.runtime.state.mmu.write(0xC000, 0x10);
+        let obs = env.step(0);
+        assert_eq!(obs.reward, 16.0);
+        assert!(!obs.done);
+        env.runtime.state.mmu.write(0xC000, 0x42);
+        assert!(env.step(0).done);
+    }
+
+    #[test]
+    fn batch() {
+        let mut batch = BatchEnv::new((0..5).map(|_| gen()).collect());
+        let observations = batch.reset(100);
+        assert_eq!(observations.len(), 5);
+        assert_eq!(observations[3], gen().reset(103));
+
+        let observations = batch.step(&[BUTTON_A, BUTTON_B]);
+        let mut single = gen();
+        single.reset(101);
+        assert_eq!(observations[1], single.step(BUTTON_B));
+        assert_eq!(observations[4].frame, 1);
+    }
+}
@@ -0,0 +1,117 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod eventstest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    fn gen(events: EventLog) -> Runtime<mbc::MBC1> {
+        let mut runtime = Runtime::new(mbc::MBC1::new(ROM.to_vec()));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_event_log(Some(events));
+        runtime
+    }
+
+    fn kinds(runtime: &Runtime<mbc::MBC1>) -> Vec<EventKind> {
+        runtime.event_log().unwrap().events().map(|event| event.kind.clone()).collect()
+    }
+
+    #[test]
+    fn frame_events() {
+        let mut runtime = gen(EventLog::default());
+        for _ in 0..10 {
+            run_frame(&mut runtime);
+        }
+        let kinds = kinds(&runtime);
+        for kind in [
+            EventKind::Mode(GPUMode::VBLANK),
+            EventKind::Mode(GPUMode::LCD_TRANSFER),
+            EventKind::Interrupt(Interrupt::VBlank),
+            EventKind::DmaStart,
+            EventKind::DmaEnd,
+        ] {
+            assert!(kinds.contains(&kind), "no {}", kind);
+        }
+        // Cycles keep going up over frames, even though runtime counts them from 0 each frame
+        let log = runtime.event_log().unwrap();
+        let cycles = log.events().map(|event| event.cycle).collect::<Vec<_>>();
+        assert!(cycles.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(*cycles.last().unwrap() > 9 * CPU_CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn categories() {
+        let mut runtime = gen(EventLog::default().with_categories(&[EventCategory::Interrupt]));
+        for _ in 0..10 {
+            run_frame(&mut runtime);
+        }
+        let log = runtime.event_log().unwrap();
+        assert!(!log.is_empty());
+        assert!(log.events().all(|event| event.kind.category() == EventCategory::Interrupt));
+        assert!(log.enabled(EventCategory::Interrupt) && !log.enabled(EventCategory::Ppu));
+
+        assert_eq!("DMA".parse::<EventCategory>(), Ok(EventCategory::Dma));
+        assert!("video".parse::<EventCategory>().is_err());
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let mut log = EventLog::new(2);
+        log.record(1, EventKind::DmaStart);
+        log.record(2, EventKind::DmaEnd);
+        log.record(3, EventKind::ChannelTrigger(1));
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(log.events().next().unwrap().kind, EventKind::DmaEnd);
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(log.dropped(), 0);
+    }
+
+    #[test]
+    fn bank_switch() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x147] = 0x01; // MBC1
+        // LD A, 2; LD (0x2000), A; JR -2
+        rom[0x100..0x108].copy_from_slice(&[0x3E, 0x02, 0xEA, 0x00, 0x20, 0x18, 0xFE, 0x00]);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_event_log(Some(EventLog::default().with_categories(&[EventCategory::Bank])));
+        runtime.run_cycles(100);
+        assert_eq!(kinds(&runtime), vec![EventKind::RomBank(2)]);
+    }
+
+    #[test]
+    fn channel_trigger() {
+        let mut runtime = gen(EventLog::default().with_categories(&[EventCategory::Apu]));
+        runtime.state.safe_write(ioregs::NR_52, 0x80);
+        runtime.state.safe_write(ioregs::NR_22, 0xF0);
+        runtime.state.safe_write(ioregs::NR_24, 0x80);
+        runtime.step();
+        assert_eq!(kinds(&runtime), vec![EventKind::ChannelTrigger(2)]);
+        assert_eq!(runtime.state.apu.triggers(2), 1);
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let mut runtime = gen(EventLog::default());
+        for _ in 0..10 {
+            run_frame(&mut runtime);
+        }
+        let trace: serde_json::Value = serde_json::from_str(&runtime.event_log().unwrap().to_chrome_trace()).unwrap();
+        let entries = trace["traceEvents"].as_array().unwrap();
+        // Row names, then the events
+        assert_eq!(entries.len(), EventCategory::ALL.len() + runtime.event_log().unwrap().len());
+        let span = |entry: &serde_json::Value| entry["ph"] == "X" && entry["dur"].as_f64().unwrap() > 0.0;
+        assert!(entries.iter().any(|entry| entry["cat"] == "ppu" && span(entry)));
+        assert!(entries.iter().any(|entry| entry["ph"] == "i" && entry["name"] == "VBlank interrupt"));
+
+        let log = runtime.take_event_log().unwrap();
+        assert!(runtime.event_log().is_none());
+        let path = std::env::temp_dir().join("gameboy-eventstest.json");
+        log.save_chrome_trace(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), log.to_chrome_trace());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod framestest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Turns LCD on, then loops forever */
+    const PROGRAM: [u8; 6] = [
+        0x3E, 0x91, // LD A, 0x91
+        0xE0, 0x40, // LDH (LCDC), A
+        0x18, 0xFE, // JR -2
+    ];
+
+    fn gen() -> Runtime<mbc::RomOnly> {
+        program_runtime(&PROGRAM, &[])
+    }
+
+    #[test]
+    fn one_vblank_per_frame() {
+        let mut runtime = gen();
+        // First frame only gets to the nearest VBlank
+        runtime.frames().next().unwrap();
+        let vblanks = runtime.state.gpu.vblanks();
+        let frames = runtime.frames().take(3).collect::<Vec<EmulatedFrame>>();
+
+        assert_eq!(frames.iter().map(|frame| frame.number).collect::<Vec<u64>>(), vec![0, 1, 2]);
+        assert_eq!(runtime.state.gpu.vblanks(), vblanks + 3);
+        for frame in &frames {
+            assert_eq!(frame.framebuffer.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+            assert_eq!(frame.audio.len() % 2, 0);
+            assert!(!frame.audio.is_empty());
+        }
+        // Whole frame worth of audio(APU rounds sample period to whole cycles), nothing left behind in APU
+        let samples = frames[1].audio.len() as u64 / 2;
+        let expected = runtime.state.apu.sample_rate() as u64 * CPU_CYCLES_PER_FRAME / (1 << 20);
+        assert!(samples.abs_diff(expected) <= expected / 50, "{} samples, expected {}", samples, expected);
+        assert_eq!(runtime.state.apu.available_samples(), 0);
+    }
+
+    #[test]
+    fn input_per_frame() {
+        let mut runtime = gen();
+        let script = [0, BUTTON_START, BUTTON_A | BUTTON_LEFT];
+        let inputs = runtime
+            .frames()
+            .with_input(|frame| script[frame as usize])
+            .take(script.len())
+            .map(|frame| frame.input)
+            .collect::<Vec<u8>>();
+        assert_eq!(inputs, script);
+        // Last buttons stay held
+        assert_eq!(runtime.state.joypad.held(), BUTTON_A | BUTTON_LEFT);
+    }
+
+    #[test]
+    fn input_left_alone_without_source() {
+        let mut runtime = gen();
+        runtime.state.joypad.set(Button::B, true);
+        assert_eq!(runtime.frames().next().unwrap().input, BUTTON_B);
+    }
+
+    #[test]
+    fn ends_when_stopped() {
+        let mut runtime = gen();
+        runtime.set_paused(true);
+        assert!(runtime.frames().next().is_none());
+        runtime.set_paused(false);
+        assert_eq!(runtime.frames().take(2).count(), 2);
+    }
+}
@@ -265,17 +265,307 @@ mod gputest {
         assert_eq!(GPU::OBP0_COLOR_3_SHADE(&mut mmu), 0);
         assert_eq!(GPU::OBP0_COLOR_2_SHADE(&mut mmu), 1);
         assert_eq!(GPU::OBP0_COLOR_1_SHADE(&mut mmu), 2);
-        assert_eq!(GPU::obp0_color(&mut mmu, 3), gpu::WHITE);
-        assert_eq!(GPU::obp0_color(&mut mmu, 2), gpu::LIGHT_GRAY);
-        assert_eq!(GPU::obp0_color(&mut mmu, 1), gpu::DARK_GRAY);
-        assert_eq!(GPU::obp0_color(&mut mmu, 0), gpu::TRANSPARENT);
+        assert_eq!(GPU::obp0_color(&mut mmu, 3), Some(gpu::WHITE));
+        assert_eq!(GPU::obp0_color(&mut mmu, 2), Some(gpu::LIGHT_GRAY));
+        assert_eq!(GPU::obp0_color(&mut mmu, 1), Some(gpu::DARK_GRAY));
+        assert_eq!(GPU::obp0_color(&mut mmu, 0), None);
 
         assert_eq!(GPU::OBP1_COLOR_3_SHADE(&mut mmu), 3);
         assert_eq!(GPU::OBP1_COLOR_2_SHADE(&mut mmu), 0);
         assert_eq!(GPU::OBP1_COLOR_1_SHADE(&mut mmu), 2);
-        assert_eq!(GPU::obp1_color(&mut mmu, 3), gpu::BLACK);
-        assert_eq!(GPU::obp1_color(&mut mmu, 2), gpu::WHITE);
-        assert_eq!(GPU::obp1_color(&mut mmu, 1), gpu::DARK_GRAY);
-        assert_eq!(GPU::obp1_color(&mut mmu, 0), gpu::TRANSPARENT);
+        assert_eq!(GPU::obp1_color(&mut mmu, 3), Some(gpu::BLACK));
+        assert_eq!(GPU::obp1_color(&mut mmu, 2), Some(gpu::WHITE));
+        assert_eq!(GPU::obp1_color(&mut mmu, 1), Some(gpu::DARK_GRAY));
+        assert_eq!(GPU::obp1_color(&mut mmu, 0), None);
     }
-}
\ No newline at end of file
+    fn step_until(state: &mut State<mbc::MBC1>, mode: GPUMode) {
+        while GPU::MODE(&mut state.mmu) != mode {
+            state.gpu.step(&mut state.mmu);
+        }
+    }
+
+    fn stat_int(state: &mut State<mbc::MBC1>) -> bool {
+        let iflag = state.mmu.read(ioregs::IF);
+        state.mmu.write(ioregs::IF, iflag & !2);
+        iflag & 2 != 0
+    }
+
+    #[test]
+    fn stat_blocking() {
+        let mut state = gen_state();
+        state.safe_write(ioregs::STAT, 0b00101010);
+        stat_int(&mut state);
+
+        step_until(&mut state, GPUMode::HBLANK);
+        assert!(stat_int(&mut state));
+
+        // HBLANK -> OAM keeps the line high, so no new interrupt
+        step_until(&mut state, GPUMode::OAM_SEARCH);
+        assert!(state.gpu.stat_line());
+        assert!(!stat_int(&mut state));
+
+        // Line drops in LCD transfer and rises again in HBLANK
+        step_until(&mut state, GPUMode::LCD_TRANSFER);
+        assert!(!state.gpu.stat_line());
+        step_until(&mut state, GPUMode::HBLANK);
+        assert!(stat_int(&mut state));
+    }
+
+    #[test]
+    fn stat_lyc_blocked_by_hblank() {
+        let mut state = gen_state();
+        state.safe_write(ioregs::STAT, 0b01001010);
+        state.safe_write(LYC, 1);
+        stat_int(&mut state);
+
+        step_until(&mut state, GPUMode::HBLANK);
+        assert!(stat_int(&mut state));
+        // LY=LYC on next line comes while HBLANK still holds the line
+        step_until(&mut state, GPUMode::OAM_SEARCH);
+        assert_eq!(GPU::LY(&mut state.mmu), 1);
+        assert!(GPU::COINCIDENCE_FLAG(&mut state.mmu));
+        assert!(!stat_int(&mut state));
+    }
+
+    #[test]
+    fn stat_enable_raises_line() {
+        let mut state = gen_state();
+        stat_int(&mut state);
+        step_until(&mut state, GPUMode::HBLANK);
+        assert!(!stat_int(&mut state));
+
+        state.safe_write(ioregs::STAT, 0b00001000);
+        assert!(stat_int(&mut state));
+    }
+    #[test]
+    fn ly_read_only() {
+        let mut state = gen_state();
+        step_until(&mut state, GPUMode::HBLANK);
+        step_until(&mut state, GPUMode::OAM_SEARCH);
+        assert_eq!(GPU::LY(&mut state.mmu), 1);
+        state.safe_write(ioregs::LY, 0x42);
+        assert_eq!(GPU::LY(&mut state.mmu), 1);
+
+        state.gpu.quirks.ly_write_resets = true;
+        state.safe_write(ioregs::LY, 0x42);
+        assert_eq!(GPU::LY(&mut state.mmu), 0);
+    }
+
+    #[test]
+    fn stat_protected_bits() {
+        let mut state = gen_state();
+        let stat = state.mmu.read(ioregs::STAT);
+        state.safe_write(ioregs::STAT, !0x07);
+        assert_eq!(state.mmu.read(ioregs::STAT) & 0x07, stat & 0x07);
+        assert_eq!(GPU::MODE(&mut state.mmu), GPUMode::OAM_SEARCH);
+        state.safe_write(ioregs::STAT, 0x07);
+        assert_eq!(state.mmu.read(ioregs::STAT), stat & 0x07);
+    }
+
+    #[test]
+    fn stat_write_bug() {
+        let mut state = gen_state();
+        step_until(&mut state, GPUMode::HBLANK);
+        stat_int(&mut state);
+
+        // No sources enabled, so nothing happens without the bug
+        state.safe_write(ioregs::STAT, 0);
+        assert!(!stat_int(&mut state));
+
+        state.gpu.quirks.stat_write_bug = true;
+        state.safe_write(ioregs::STAT, 0);
+        assert!(stat_int(&mut state));
+
+        // Not in LCD transfer
+        step_until(&mut state, GPUMode::LCD_TRANSFER);
+        stat_int(&mut state);
+        state.safe_write(ioregs::STAT, 0);
+        assert!(!stat_int(&mut state));
+    }
+
+    #[test]
+    fn oam_bug() {
+        let mut state = gen_state();
+        step_until(&mut state, GPUMode::OAM_SEARCH);
+        for i in 0..0xA0 {
+            state.mmu.oam[i] = i as u8;
+        }
+        state.gpu_lag = 2;
+
+        // Disabled by default
+        state.oam_bug(0xFE10);
+        assert_eq!(state.mmu.oam[16], 16);
+
+        state.gpu.quirks.oam_bug = true;
+        // Outside of OAM
+        state.oam_bug(0xC000);
+        assert_eq!(state.mmu.oam[16], 16);
+
+        state.oam_bug(0xFE10);
+        // a = 0x1110, b = 0x0908, c = 0x0D0C
+        let mixed: u16 = ((0x1110 ^ 0x0D0C) & (0x0908 ^ 0x0D0C)) ^ 0x0D0C;
+        assert_eq!(state.mmu.oam[16], mixed as u8);
+        assert_eq!(state.mmu.oam[17], (mixed >> 8) as u8);
+        assert_eq!(state.mmu.oam[18..24], [10, 11, 12, 13, 14, 15]);
+        // Other rows untouched
+        assert_eq!(state.mmu.oam[8..16], [8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_eq!(state.mmu.oam[24], 24);
+    }
+
+    // Black tile 0 as background, sprite of black tile 1 in the top left corner, over white background
+    fn layers_frame(layers: (bool, bool, bool), background: Byte) -> Vec<Color> {
+        render(layers, background, 0, 0xE4).framebuff
+    }
+
+    fn render(layers: (bool, bool, bool), background: Byte, attributes: Byte, bgp: Byte) -> GPU {
+        let (mut mmu, mut gpu) = gen();
+        mmu.write(ioregs::LCDC, 0x93);
+        mmu.write(ioregs::BGP, bgp);
+        mmu.write(ioregs::OBP_0, 0xE4);
+        for i in 0..16 {
+            mmu.vram[i] = background;
+            mmu.vram[16 + i] = 0xFF;
+        }
+        mmu.oam[..4].copy_from_slice(&[16, 8, 1, attributes]);
+        (gpu.show_background, gpu.show_window, gpu.show_sprites) = layers;
+        while gpu.vblanks() == 0 {
+            gpu.step(&mut mmu);
+        }
+        gpu
+    }
+
+    #[test]
+    fn layers() {
+        let all = layers_frame((true, true, true), 0xFF);
+        assert!(all.iter().all(|color| *color == BLACK));
+
+        // Hidden background is drawn with color 0
+        let frame = layers_frame((false, true, true), 0xFF);
+        assert_eq!(frame[0], BLACK);
+        assert_eq!(frame[8], WHITE);
+        assert_eq!(frame[SCREEN_WIDTH * 8], WHITE);
+
+        let frame = layers_frame((true, true, false), 0x00);
+        assert!(frame.iter().all(|color| *color == WHITE));
+        let frame = layers_frame((true, true, true), 0x00);
+        assert_eq!(frame[0], BLACK);
+    }
+
+    #[test]
+    fn pixel_sources() {
+        let gpu = render((true, true, true), 0x00, 0, 0xE4);
+        assert_eq!(gpu.sources()[0], PixelSource::Sprite);
+        assert_eq!(gpu.sources()[8], PixelSource::Background);
+        // Frontends tint completed frame, with sources copied along with it
+        assert_eq!(gpu.completed_sources()[..9], gpu.sources()[..9]);
+
+        // BG priority flag: sprite shows over color 0 only
+        let gpu = render((true, true, true), 0x00, 0x80, 0xE4);
+        assert_eq!(gpu.sources()[0], PixelSource::SpriteBehind);
+        let gpu = render((true, true, true), 0xFF, 0x80, 0xE4);
+        assert_eq!(gpu.sources()[0], PixelSource::SpriteHidden);
+        assert_eq!(gpu.pixel(0, 0), BLACK);
+
+        let gpu = render((false, true, false), 0xFF, 0, 0xE4);
+        assert_eq!(gpu.sources()[0], PixelSource::Blank);
+
+        let tinted = gpu.tinted_framebuff();
+        assert_eq!(tinted[0], tint_pixel(gpu.pixel(0, 0), PixelSource::Blank));
+        assert_ne!(tinted[0], gpu.pixel(0, 0));
+    }
+
+    #[test]
+    fn palette_layers() {
+        let gpu = render((true, true, true), 0x00, 0, 0xE4);
+        assert_eq!(gpu.completed_layers()[0], PaletteLayer::Object0);
+        assert_eq!(gpu.completed_layers()[8], PaletteLayer::Background);
+        let gpu = render((true, true, true), 0x00, 0x10, 0xE4);
+        assert_eq!(gpu.completed_layers()[0], PaletteLayer::Object1);
+        // Sprite hidden behind background doesn't color the pixel
+        let gpu = render((true, true, true), 0xFF, 0x90, 0xE4);
+        assert_eq!(gpu.completed_layers()[0], PaletteLayer::Background);
+    }
+
+    #[test]
+    fn sprite_priority_uses_color_numbers() {
+        // Every background color is white, sprite behind background still hides behind colors 1-3
+        let gpu = render((true, true, true), 0xFF, 0x80, 0x00);
+        assert_eq!(gpu.pixel(0, 0), WHITE);
+        assert_eq!(gpu.sources()[0], PixelSource::SpriteHidden);
+        let gpu = render((true, true, true), 0x00, 0x80, 0x00);
+        assert_eq!(gpu.pixel(0, 0), BLACK);
+    }
+
+    #[test]
+    fn pixel_accessors() {
+        let (_, mut gpu) = gen();
+        gpu.framebuff[3 * SCREEN_WIDTH + 5] = (1, 2, 3);
+        gpu.framebuff[SCREEN_WIDTH * SCREEN_HEIGHT - 1] = BLACK;
+        assert_eq!(gpu.pixel(5, 3), (1, 2, 3));
+        assert_eq!(gpu.pixel(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), BLACK);
+        assert_eq!(gpu.row(3).len(), SCREEN_WIDTH);
+        assert_eq!(gpu.row(3)[5], (1, 2, 3));
+        assert_eq!(gpu.row(3)[4], WHITE);
+
+        // Buffer is reused, not appended to
+        let mut rgba = vec![7; 10];
+        gpu.as_rgba8(&mut rgba);
+        assert_eq!(rgba.len(), 4 * SCREEN_WIDTH * SCREEN_HEIGHT);
+        let at = 4 * (3 * SCREEN_WIDTH + 5);
+        assert_eq!(rgba[at..at + 4], [1, 2, 3, 0xFF]);
+        assert_eq!(rgba[..4], [WHITE.0, WHITE.1, WHITE.2, 0xFF]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn pixel_outside_screen() {
+        let (_, gpu) = gen();
+        gpu.pixel(SCREEN_WIDTH, 0);
+    }
+
+    #[test]
+    fn completed_frame_swapped_at_vblank() {
+        let (mut mmu, mut gpu) = gen();
+        mmu.write(ioregs::LCDC, 0x91);
+        mmu.write(ioregs::BGP, 0xE4);
+        for i in 0..16 {
+            mmu.vram[i] = 0xFF;
+        }
+        while gpu.vblanks() == 0 {
+            gpu.step(&mut mmu);
+        }
+        assert!(gpu.completed_frame().iter().all(|color| *color == BLACK));
+
+        // Halfway through next frame only back buffer has the new picture
+        for i in 0..16 {
+            mmu.vram[i] = 0x00;
+        }
+        let drawn = gpu.drawn_lines();
+        while gpu.drawn_lines() - drawn < SCREEN_HEIGHT as u64 / 2 {
+            gpu.step(&mut mmu);
+        }
+        assert_eq!(gpu.pixel(0, 0), WHITE);
+        assert_eq!(gpu.pixel(0, SCREEN_HEIGHT - 1), BLACK);
+        assert!(gpu.completed_frame().iter().all(|color| *color == BLACK));
+        assert_eq!(gpu.completed_pixel(0, 0), BLACK);
+        assert!(gpu.completed_row(0).iter().all(|color| *color == BLACK));
+        let mut rgba = Vec::new();
+        gpu.completed_rgba8(&mut rgba);
+        assert!(rgba.chunks(4).all(|pixel| pixel == [BLACK.0, BLACK.1, BLACK.2, 0xFF]));
+
+        while gpu.vblanks() == 1 {
+            gpu.step(&mut mmu);
+        }
+        assert!(gpu.completed_frame().iter().all(|color| *color == WHITE));
+        assert_eq!(gpu.completed_frame(), &gpu.framebuff[..]);
+    }
+
+    #[test]
+    fn layers_survive_reset() {
+        let mut state = gen_state();
+        state.gpu.show_window = false;
+        state.reset(true);
+        assert!(!state.gpu.show_window);
+        assert!(state.gpu.show_background);
+    }
+}
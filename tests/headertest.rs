@@ -0,0 +1,46 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod headertest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    #[test]
+    fn valid_header() {
+        let header = CartHeader::from_rom(ROM).unwrap();
+        assert!(header.verify_logo());
+        assert!(header.verify_header_checksum());
+        assert!(header.verify_global_checksum(ROM));
+        assert!(header.problems(ROM).is_empty());
+        assert_eq!(header.title(), "TETRIS");
+    }
+
+    #[test]
+    fn corrupted_header() {
+        let mut rom = ROM.to_vec();
+        rom[0x104] ^= 0xFF; // Logo
+        rom[0x134] ^= 0xFF; // Title, breaks header checksum
+        let header = CartHeader::from_rom(&rom).unwrap();
+        let problems = header.problems(&rom);
+
+        assert_eq!(problems.len(), 3);
+        assert_eq!(problems[0], HeaderProblem::Logo);
+        assert!(matches!(problems[1], HeaderProblem::HeaderChecksum { .. }));
+        assert!(matches!(problems[2], HeaderProblem::GlobalChecksum { .. }));
+    }
+
+    #[test]
+    fn non_ascii_title() {
+        let mut rom = ROM.to_vec();
+        rom[0x134..0x13A].copy_from_slice(&[b'T', 0xFF, b'E', 0x80, b'S', b'T']);
+        rom[0x13A] = 0x00;
+        let header = CartHeader::from_rom(&rom).unwrap();
+        assert_eq!(header.title(), "TEST");
+    }
+
+    #[test]
+    fn too_small() {
+        assert!(CartHeader::from_rom(&ROM[..0x120]).is_none());
+    }
+}
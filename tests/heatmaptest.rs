@@ -0,0 +1,86 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod heatmaptest {
+    use gameboy::*;
+    use std::fs;
+
+    fn gen() -> (Runtime<mbc::MBC1>, Heatmap) {
+        let mut runtime = Runtime::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let heatmap = Heatmap::new();
+        runtime.state.mmu.subscribe(0x0000..=0xFFFF, None, heatmap.recorder());
+        (runtime, heatmap)
+    }
+
+    #[test]
+    fn counts() {
+        let (mut runtime, heatmap) = gen();
+        runtime.state.safe_write(0xC000, 1);
+        runtime.state.safe_write(0xC000, 2);
+        runtime.state.safe_read(0xC000);
+        assert_eq!(heatmap.writes(0xC000), 2);
+        assert_eq!(heatmap.reads(0xC000), 1);
+        assert_eq!(heatmap.reads(0xC001), 0);
+
+        // Opcode fetches count as reads
+        runtime.step();
+        assert_eq!(heatmap.reads(0x100), 1);
+    }
+
+    #[test]
+    fn interrupt_poll_isnt_read() {
+        let mut runtime = Runtime::with_code(0x100, &[0x00; 10]);
+        let heatmap = Heatmap::new();
+        runtime.state.mmu.subscribe(0x0000..=0xFFFF, None, heatmap.recorder());
+        for _ in 0..10 {
+            runtime.step();
+        }
+        assert_eq!(heatmap.reads(0x109), 1);
+        assert_eq!(heatmap.reads(ioregs::IE), 0);
+        assert_eq!(heatmap.reads(ioregs::IF), 0);
+    }
+
+    #[test]
+    fn csv() {
+        let (mut runtime, heatmap) = gen();
+        runtime.state.safe_write(0x8010, 1);
+        runtime.state.safe_read(0xFF44);
+        assert_eq!(heatmap.to_csv(), "address,reads,writes\n0x8010,0,1\n0xFF44,1,0\n");
+    }
+
+    #[test]
+    fn render() {
+        let (mut runtime, heatmap) = gen();
+        for _ in 0..100 {
+            runtime.state.safe_write(0xC000, 0);
+        }
+        runtime.state.safe_write(0xC001, 0);
+
+        let pixels = heatmap.render(None);
+        assert_eq!(pixels.len(), 0x10000);
+        assert_eq!(pixels[0xC000], 0xFF);
+        // Rarely touched address is dim, but visible
+        assert!(pixels[0xC001] > 0 && pixels[0xC001] < 0x80);
+        assert_eq!(pixels[0xC002], 0);
+        assert!(heatmap.render(Some(AccessKind::Read)).iter().all(|p| *p == 0));
+    }
+
+    #[test]
+    fn save() {
+        let (mut runtime, heatmap) = gen();
+        runtime.state.safe_write(0xC000, 0);
+        let dir = std::env::temp_dir();
+
+        let png = dir.join("gameboy-heatmap.png");
+        heatmap.save(&png).unwrap();
+        let bytes = fs::read(&png).unwrap();
+        assert_eq!(bytes[1..4], *b"PNG");
+        // IHDR width and height
+        assert_eq!(bytes[16..24], [0, 0, 1, 0, 0, 0, 1, 0]);
+
+        let csv = dir.join("gameboy-heatmap.csv");
+        heatmap.save(&csv).unwrap();
+        assert_eq!(fs::read_to_string(&csv).unwrap(), heatmap.to_csv());
+    }
+}
@@ -0,0 +1,140 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod joypadtest {
+    use gameboy::*;
+
+    fn gen() -> State<mbc::MBC1> {
+        State::new(mbc::MBC1::new(vec![0; 1 << 21]))
+    }
+
+    fn interrupt(state: &mut State<mbc::MBC1>) -> bool {
+        state.joypad.step(&mut state.mmu);
+        let requested = state.mmu.read_bit(ioregs::IF, 4);
+        state.mmu.set_bit(ioregs::IF, 4, false);
+        requested
+    }
+
+    #[test]
+    fn columns() {
+        let mut state = gen();
+        state.joypad.set(Button::A, true);
+        state.joypad.set(Button::Down, true);
+
+        state.safe_write(ioregs::P1, 0x10);
+        assert_eq!(state.safe_read(ioregs::P1), 0xDE);
+        state.safe_write(ioregs::P1, 0x20);
+        assert_eq!(state.safe_read(ioregs::P1), 0xE7);
+        // Both columns selected, lines are ANDed
+        state.safe_write(ioregs::P1, 0x00);
+        assert_eq!(state.safe_read(ioregs::P1), 0xC6);
+        state.safe_write(ioregs::P1, 0x30);
+        assert_eq!(state.safe_read(ioregs::P1), 0xFF);
+    }
+
+    #[test]
+    fn interrupt_on_press() {
+        let mut state = gen();
+        state.safe_write(ioregs::P1, 0x10);
+        assert!(!interrupt(&mut state));
+
+        state.joypad.set(Button::Start, true);
+        assert!(interrupt(&mut state));
+        // Held button doesn't request it again
+        assert!(!interrupt(&mut state));
+        state.joypad.set(Button::Start, false);
+        assert!(!interrupt(&mut state));
+    }
+
+    #[test]
+    fn no_interrupt_for_unselected_column() {
+        let mut state = gen();
+        state.safe_write(ioregs::P1, 0x10);
+        interrupt(&mut state);
+
+        state.joypad.set(Button::Up, true);
+        assert!(!interrupt(&mut state));
+        // Selecting column with button held pulls the line low
+        state.safe_write(ioregs::P1, 0x20);
+        assert!(interrupt(&mut state));
+    }
+
+    #[test]
+    fn turbo() {
+        let mut state = gen();
+        state.joypad.set_turbo_frames(2);
+        state.safe_write(ioregs::P1, 0x10);
+
+        // Pressed for 2 frames, released for 2
+        state.joypad.turbo_a(true);
+        let mut pressed = Vec::new();
+        for _ in 0..6 {
+            pressed.push(state.safe_read(ioregs::P1) & 0x01 == 0);
+            state.joypad.frame();
+        }
+        assert_eq!(pressed, vec![true, true, false, false, true, true]);
+
+        // Held button stays pressed
+        state.joypad.set(Button::A, true);
+        state.joypad.frame();
+        state.joypad.frame();
+        assert_eq!(state.safe_read(ioregs::P1) & 0x01, 0);
+
+        state.joypad.set(Button::A, false);
+        state.joypad.turbo_a(false);
+        assert_eq!(state.safe_read(ioregs::P1) & 0x01, 0x01);
+        // Pressing it again starts a new period
+        state.joypad.turbo_b(true);
+        assert_eq!(state.safe_read(ioregs::P1) & 0x03, 0x01);
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut state = gen();
+        state.safe_write(ioregs::P1, 0x10);
+        state.joypad.press(Button::Select);
+        assert!(state.joypad.pressed(Button::Select));
+        assert_eq!(state.safe_read(ioregs::P1) & 0x0F, 0x0B);
+        state.joypad.release(Button::Select);
+        assert!(!state.joypad.pressed(Button::Select));
+        assert_eq!(state.safe_read(ioregs::P1) & 0x0F, 0x0F);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_setters() {
+        let mut state = gen();
+        state.joypad.left(true);
+        state.joypad.b(true);
+        assert_eq!(state.joypad.held(), BUTTON_LEFT | BUTTON_B);
+        state.joypad.left(false);
+        assert_eq!(state.joypad.held(), BUTTON_B);
+    }
+
+    #[test]
+    fn button_names() {
+        assert_eq!("Start".parse::<Button>(), Ok(Button::Start));
+        assert_eq!(" a ".parse::<Button>(), Ok(Button::A));
+        assert!("turbo".parse::<Button>().is_err());
+        for (i, button) in Button::ALL.iter().enumerate() {
+            assert_eq!(button.mask(), 1 << i);
+        }
+        assert_eq!(Button::Start.mask(), BUTTON_START);
+    }
+
+    #[test]
+    fn held_as_action_bitmask() {
+        let mut state = gen();
+        assert_eq!(state.joypad.held(), 0);
+        state.joypad.set(Button::Up, true);
+        state.joypad.set(Button::Start, true);
+        assert_eq!(state.joypad.held(), BUTTON_UP | BUTTON_START);
+
+        // Turbo counts only while it's pressed
+        state.joypad.set_turbo_frames(1);
+        state.joypad.turbo_b(true);
+        assert_eq!(state.joypad.held(), BUTTON_UP | BUTTON_START | BUTTON_B);
+        state.joypad.frame();
+        assert_eq!(state.joypad.held(), BUTTON_UP | BUTTON_START);
+    }
+}
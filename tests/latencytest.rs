@@ -0,0 +1,96 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod latencytest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    const VBLANK: u8 = 0x01;
+    const TIMER: u8 = 0x04;
+
+    /* Cart without MBC, with program at 0x100 and RETI as every handler */
+    fn gen(program: &[u8]) -> Runtime<mbc::RomOnly> {
+        let handlers: Vec<(usize, &[u8])> = Interrupt::ALL.iter()
+            .map(|interrupt| (interrupt.vector() as usize, &[0xD9][..]))
+            .collect();
+        program_runtime(program, &handlers)
+    }
+
+    #[test]
+    fn request_to_handler() {
+        let mut latency = InterruptLatency::new();
+        latency.observe(VBLANK, 100);
+        // Still pending, request time doesn't move
+        latency.observe(VBLANK | TIMER, 110);
+        latency.entered(Interrupt::VBlank, 120);
+        latency.entered(Interrupt::Timer, 150);
+
+        let vblank = latency.stats(Interrupt::VBlank);
+        assert_eq!((vblank.count, vblank.min, vblank.max), (1, 20, 20));
+        assert_eq!(latency.stats(Interrupt::Timer).mean(), Some(40.0));
+        assert_eq!(latency.stats(Interrupt::Serial).mean(), None);
+
+        latency.observe(VBLANK, 200);
+        latency.entered(Interrupt::VBlank, 210);
+        let vblank = latency.stats(Interrupt::VBlank);
+        assert_eq!((vblank.count, vblank.min, vblank.max, vblank.total), (2, 10, 20, 30));
+        assert_eq!(vblank.to_string(), "2 taken, 15.0 cycles avg, 10-20");
+    }
+
+    #[test]
+    fn cleared_request_dropped() {
+        let mut latency = InterruptLatency::new();
+        latency.observe(TIMER, 100);
+        latency.observe(0x00, 110);
+        latency.entered(Interrupt::Timer, 120);
+        assert_eq!(latency.stats(Interrupt::Timer).count, 0);
+
+        // Pending requests are forgotten when cycles start over, stats stay
+        latency.observe(TIMER, 130);
+        latency.entered(Interrupt::Timer, 140);
+        latency.observe(TIMER, 150);
+        latency.restart();
+        latency.entered(Interrupt::Timer, 10);
+        assert_eq!(latency.stats(Interrupt::Timer).count, 1);
+        latency.clear();
+        assert_eq!(latency, InterruptLatency::new());
+    }
+
+    #[test]
+    fn vblank_taken_right_away() {
+        // EI; JR -2
+        let mut runtime = gen(&[0xFB, 0x18, 0xFE]);
+        runtime.state.mmu.write(ioregs::IE, VBLANK);
+        for _ in 0..3 * CPU_CYCLES_PER_FRAME / 2 {
+            runtime.step();
+        }
+        let vblank = runtime.interrupt_latency().stats(Interrupt::VBlank);
+        assert!(vblank.count >= 2, "{}", vblank);
+        // Only dispatch itself
+        assert_eq!((vblank.min, vblank.max), (5, 5));
+        assert_eq!(runtime.interrupt_latency().stats(Interrupt::Timer).count, 0);
+
+        runtime.clear_interrupt_latency();
+        assert_eq!(runtime.interrupt_latency().stats(Interrupt::VBlank).count, 0);
+    }
+
+    #[test]
+    fn delayed_by_di() {
+        // DI; JR -2, interrupts are enabled once VBlank is pending
+        let mut runtime = gen(&[0xF3, 0x18, 0xFE]);
+        runtime.state.mmu.write(ioregs::IE, VBLANK);
+        while runtime.state.mmu.read(ioregs::IF) & VBLANK == 0 {
+            runtime.step();
+        }
+        for _ in 0..100 {
+            runtime.step();
+        }
+        runtime.cpu.IME = true;
+        runtime.step();
+        let vblank = runtime.interrupt_latency().stats(Interrupt::VBlank);
+        assert_eq!(vblank.count, 1);
+        assert!(vblank.min > 100 * 3, "{}", vblank);
+    }
+}
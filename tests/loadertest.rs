@@ -0,0 +1,92 @@
+extern crate gameboy;
+extern crate zip;
+
+#[cfg(test)]
+mod loadertest {
+    use gameboy::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use zip::write::{FileOptions, ZipWriter};
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gameboy-loadertest-{}", name))
+    }
+
+    fn write_zip(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let path = temp_path(name);
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (file, data) in files {
+            zip.start_file(*file, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    // Marks ROM as supporting both DMG and CGB, with header checksum fixed up.
+    fn dual_mode_rom() -> Vec<u8> {
+        let mut rom = ROM.to_vec();
+        rom[0x143] = 0x80;
+        let header = CartHeader::from_rom(&rom).unwrap();
+        rom[0x14D] = header.calc_header_checksum();
+        rom
+    }
+
+    #[test]
+    fn plain_rom() {
+        let path = temp_path("plain.gb");
+        fs::write(&path, ROM).unwrap();
+        let loaded = load_rom(&path, true).unwrap();
+        assert_eq!(loaded.rom, ROM);
+        assert_eq!(loaded.mode, HardwareMode::DMG);
+        assert!(loaded.warnings.is_empty());
+    }
+
+    #[test]
+    fn zipped_rom() {
+        let path = write_zip("single.zip", &[("readme.txt", b"hello"), ("Tetris.GB", ROM)]);
+        let loaded = load_rom(&path, true).unwrap();
+        assert_eq!(loaded.rom, ROM);
+        assert_eq!(loaded.header.title(), "TETRIS");
+    }
+
+    #[test]
+    fn zip_without_single_rom() {
+        let empty = write_zip("empty.zip", &[("readme.txt", b"hello")]);
+        assert!(load_rom(&empty, false).is_err());
+        let double = write_zip("double.zip", &[("a.gb", ROM), ("b.gbc", ROM)]);
+        assert!(load_rom(&double, false).is_err());
+    }
+
+    #[test]
+    fn mode_from_extension() {
+        let rom = dual_mode_rom();
+        let gb = temp_path("dual.gb");
+        fs::write(&gb, &rom).unwrap();
+        assert_eq!(load_rom(&gb, false).unwrap().mode, HardwareMode::DMG);
+
+        let gbc = write_zip("dual.zip", &[("dual.gbc", &rom)]);
+        assert_eq!(load_rom(&gbc, false).unwrap().mode, HardwareMode::CGB);
+
+        // DMG only cart stays DMG even with .gbc extension
+        let dmg = temp_path("dmg.gbc");
+        fs::write(&dmg, ROM).unwrap();
+        assert_eq!(load_rom(&dmg, false).unwrap().mode, HardwareMode::DMG);
+    }
+
+    #[test]
+    fn strict_header() {
+        let mut rom = ROM.to_vec();
+        rom[0x104] ^= 0xFF;
+        let path = temp_path("broken.gb");
+        fs::write(&path, &rom).unwrap();
+        assert!(load_rom(&path, true).is_err());
+        // Logo is also part of global checksum
+        let warnings = load_rom(&path, false).unwrap().warnings;
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0], HeaderProblem::Logo);
+    }
+}
@@ -6,6 +6,7 @@ mod mbctest {
 
     const SZ_32KB: usize = 1 << 15;
     const SZ_256KB: usize = 1 << 18;
+    const SZ_512KB: usize = 1 << 19;
     const SZ_2MB: usize = 1 << 21;
 
     fn gen_rom(size: usize) -> Vec<u8> { 
@@ -119,6 +120,7 @@ mod mbctest {
         #[should_panic]
         fn rtc_read_not_latched() {
             let mut memory = mock_memory(gen_mbc3());
+            memory.policy = BusPolicy::Panic;
             memory.write(0x4000, 0x8);
             memory.read(RAM_SWITCHABLE_ADDR);
         }
@@ -147,6 +149,56 @@ mod mbctest {
         }
     }
 
+    #[cfg(test)]
+    mod clock {
+        use super::*;
+        use chrono::{TimeZone, Utc};
+
+        fn gen_emulated() -> mbc::MBC3<mbc::EmulatedClock> {
+            let start = Utc.with_ymd_and_hms(2001, 3, 21, 10, 0, 0).unwrap();
+            mbc::MBC3::with_clock(gen_rom(SZ_2MB), mbc::EmulatedClock::new(start))
+        }
+
+        fn latch(memory: &mut MMU<mbc::MBC3<mbc::EmulatedClock>>) -> Vec<u8> {
+            memory.write(0x6000, 0x00);
+            memory.write(0x6000, 0x01);
+            (0x08..=0x0B).map(|reg| { memory.write(0x4000, reg); memory.read(RAM_SWITCHABLE_ADDR) }).collect()
+        }
+
+        #[test]
+        fn emulated_clock_follows_cycles() {
+            let mut memory = mock_memory(gen_emulated());
+            assert_eq!(latch(&mut memory), vec![0, 0, 10, 21]);
+
+            // Unhalt, then 90 seconds pass
+            memory.write(0x6000, 0x00);
+            memory.write(0x6000, 0x01);
+            memory.mapper.tick(90 << 20);
+            assert_eq!(latch(&mut memory), vec![30, 1, 10, 21]);
+        }
+
+        #[test]
+        fn same_cycles_same_time() {
+            let (mut first, mut second) = (gen_emulated(), gen_emulated());
+            for cycles in [5, 1 << 20, 17556] {
+                first.tick(cycles);
+                second.tick(cycles);
+            }
+            assert_eq!(first.clock.now(), second.clock.now());
+            assert_eq!(first.clock.cycles(), 5 + (1 << 20) + 17556);
+        }
+
+        #[test]
+        fn runtime_ticks_mapper() {
+            let mut runtime = Runtime::new(gen_emulated());
+            runtime.skip_bootrom(HardwareModel::DMG);
+            for _ in 0..100 {
+                runtime.step();
+            }
+            assert_eq!(runtime.state.mmu.mapper.clock.cycles(), runtime.cpu_cycles());
+        }
+    }
+
     #[cfg(test)]
     mod mbc2 {
         use super::*;
@@ -155,6 +207,7 @@ mod mbctest {
         #[should_panic]
         fn access_over_512_ram() {
             let mut memory = mock_memory(gen_mbc2());
+            memory.policy = BusPolicy::Panic;
             memory.read(RAM_SWITCHABLE_ADDR + 512);
         }
 
@@ -168,6 +221,7 @@ mod mbctest {
         #[should_panic]
         fn ram_access_when_disabled() {
             let mut memory = mock_memory(gen_mbc2());
+            memory.policy = BusPolicy::Panic;
 
             memory.write(0x0000, 0x00); // Disable RAM
 
@@ -195,6 +249,72 @@ mod mbctest {
         }
     }
 
+    #[cfg(test)]
+    mod mbc5 {
+        use super::*;
+        use mbc::BankController;
+
+        fn gen_mbc5(cart_type: u8) -> mbc::MBC5 {
+            let mut rom = gen_rom(1 << 23);
+            rom[0x147] = cart_type;
+            rom[0x148] = 0x08; // 8MB
+            rom[0x149] = 0x04; // 128KB RAM
+            mbc::MBC5::new(rom)
+        }
+
+        #[test]
+        fn rom_banks() {
+            let mut memory = mock_memory(gen_mbc5(0x19));
+            memory.mapper.rom[0] = 0x37;
+            memory.mapper.rom[ROM_BANK_SIZE * 0x1C] = 0x1C;
+            memory.mapper.rom[ROM_BANK_SIZE * 0x11C] = 0x11;
+
+            // Bank 0 can be mapped at 0x4000 too
+            memory.write(0x2000, 0x00);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x37);
+            memory.write(0x2000, 0x1C);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x1C);
+            // 9th bit
+            memory.write(0x3000, 0x01);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x11);
+            assert_eq!(memory.mapper.current_rom_bank(), 0x11C);
+            // Nothing at 0x6000-0x7FFF
+            assert!(!memory.mapper.write_rom(0x6000, 0x01));
+        }
+
+        #[test]
+        fn ram_banks() {
+            let mut memory = mock_memory(gen_mbc5(0x1B));
+            // Disabled at power on
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0xFF);
+
+            memory.write(0x0000, 0x0A);
+            memory.write(0x4000, 0x0F);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            assert_eq!(memory.mapper.ram[RAM_BANK_SIZE * 0x0F], 0x42);
+            assert!(!memory.mapper.rumble());
+        }
+
+        #[test]
+        fn rumble_bit() {
+            let mut memory = mock_memory(gen_mbc5(0x1E));
+            memory.write(0x0000, 0x0A);
+            memory.write(0x4000, 0x0B);
+            assert!(memory.mapper.rumble());
+            // Bit 3 doesn't select RAM bank, rumble carts have at most 8
+            assert_eq!(memory.mapper.current_ram_bank(), Some(3));
+            assert_eq!(memory.mapper.ram.len(), RAM_BANK_SIZE * 8);
+            assert_eq!(memory.mapper.debug_state().to_string(), "MBC5+RUMBLE ROM 01/200 RAM 03/08 rumble 01");
+
+            memory.write(0x4000, 0x03);
+            assert!(!memory.mapper.rumble());
+            memory.write(0x4000, 0x08);
+            memory.reset(false);
+            assert!(!memory.mapper.rumble());
+        }
+    }
+
     #[cfg(test)]
     mod mbc1 {
         use super::*;
@@ -346,6 +466,7 @@ mod mbctest {
         #[should_panic]
         fn write_rom() {
             let mut memory = mock_memory(gen_romonly());
+            memory.policy = BusPolicy::Panic;
 
             // Writing to ROM segment -> should panic
             memory.write(0x2137, 0x69);
@@ -368,9 +489,448 @@ mod mbctest {
         #[should_panic]
         fn accessing_switchable_ram() {
             let mut memory = mock_memory(gen_romonly());
+            memory.policy = BusPolicy::Panic;
 
             // Reading switchable RAM -> Rom only doesn't support it
             memory.read(RAM_SWITCHABLE_ADDR as u16);
         }
     }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod ramonly {
+        use super::*;
+
+        #[test]
+        fn write_everywhere() {
+            let mut memory = mock_memory(mbc::RamOnly::new(vec![0x37; 0x200]));
+            assert_eq!(memory.read(ROM_BASE_ADDR + 0x1FF), 0x37);
+            assert_eq!(memory.read(ROM_BASE_ADDR + 0x200), 0x00);
+
+            memory.write(ROM_BASE_ADDR + 0x100, 0x21);
+            memory.write(0x7FFF, 0x69);
+            memory.write(RAM_SWITCHABLE_ADDR + 0x1FFF, 0x42);
+
+            assert_eq!(memory.read(ROM_BASE_ADDR + 0x100), 0x21);
+            assert_eq!(memory.read(0x7FFF), 0x69);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR + 0x1FFF), 0x42);
+            assert_eq!(memory.mapper.code[0x100], 0x21);
+        }
+    }
+
+    #[cfg(test)]
+    mod huc3 {
+        use super::*;
+
+        fn gen_huc3() -> mbc::HuC3 { mbc::HuC3::new(gen_rom(SZ_2MB)) }
+
+        #[test]
+        fn banks_by_mode() {
+            let mut memory = mock_memory(gen_huc3());
+            memory.mapper.rom[ROM_BANK_SIZE * 0x05] = 0x05;
+            memory.write(0x2000, 0x05);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x05);
+
+            // RAM is written in mode 0xA only, read in 0x0 too
+            memory.write(0x4000, 0x02);
+            memory.write(0x0000, 0x0A);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            assert_eq!(memory.mapper.ram[RAM_BANK_SIZE * 2], 0x42);
+            memory.write(0x0000, 0x00);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x21);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0x42);
+
+            // Other modes map registers there
+            memory.write(0x0000, 0x0D);
+            assert_eq!(memory.mapper.current_ram_bank(), None);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0x01);
+        }
+
+        #[test]
+        fn rtc_memory() {
+            let mut memory = mock_memory(gen_huc3());
+            memory.write(0x0000, 0x0B);
+            // Address 0x23, write 7 there, go back and read it
+            for command in [0x43, 0x52, 0x37, 0x43, 0x10].iter() {
+                memory.write(RAM_SWITCHABLE_ADDR, *command);
+            }
+            memory.write(0x0000, 0x0C);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0x17);
+            assert!(memory.mapper.unsupported().is_empty());
+        }
+
+        #[test]
+        fn unsupported_features() {
+            let mut memory = mock_memory(gen_huc3());
+            memory.write(0x0000, 0x0E);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0xC0);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x01);
+            memory.write(0x0000, 0x0B);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x60);
+            assert_eq!(memory.mapper.unsupported(), &["infrared port", "RTC clock and speaker"]);
+        }
+    }
+
+    #[cfg(test)]
+    mod tama5 {
+        use super::*;
+
+        fn gen_tama5() -> mbc::TAMA5 { mbc::TAMA5::new(gen_rom(SZ_512KB)) }
+
+        // Selects register, then writes its nibble
+        fn set(memory: &mut MMU<mbc::TAMA5>, reg: u8, value: u8) {
+            memory.write(RAM_SWITCHABLE_ADDR + 1, reg);
+            memory.write(RAM_SWITCHABLE_ADDR, value);
+        }
+
+        fn get(memory: &mut MMU<mbc::TAMA5>, reg: u8) -> u8 {
+            memory.write(RAM_SWITCHABLE_ADDR + 1, reg);
+            memory.read(RAM_SWITCHABLE_ADDR)
+        }
+
+        #[test]
+        fn rom_bank() {
+            let mut memory = mock_memory(gen_tama5());
+            memory.mapper.rom[ROM_BANK_SIZE] = 0x01;
+            memory.mapper.rom[ROM_BANK_SIZE * 0x13] = 0x13;
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x01);
+            set(&mut memory, 0x0, 0x3);
+            set(&mut memory, 0x1, 0x1);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x13);
+            assert_eq!(memory.mapper.current_rom_bank(), 0x13);
+        }
+
+        #[test]
+        fn ram_commands() {
+            let mut memory = mock_memory(gen_tama5());
+            assert_eq!(get(&mut memory, 0xA), 0xF1);
+            // Write 0xAB at 0x12
+            set(&mut memory, 0x4, 0xB);
+            set(&mut memory, 0x5, 0xA);
+            set(&mut memory, 0x6, 0x1);
+            set(&mut memory, 0x7, 0x2);
+            assert_eq!(memory.mapper.ram[0x12], 0xAB);
+            // And read it back
+            set(&mut memory, 0x6, 0x3);
+            set(&mut memory, 0x7, 0x2);
+            assert_eq!(get(&mut memory, 0xC), 0xFB);
+            assert_eq!(get(&mut memory, 0xD), 0xFA);
+            assert!(memory.mapper.unsupported().is_empty());
+        }
+
+        #[test]
+        fn rtc_unsupported() {
+            let mut memory = mock_memory(gen_tama5());
+            set(&mut memory, 0x6, 0x4);
+            set(&mut memory, 0x7, 0x0);
+            get(&mut memory, 0xC);
+            assert_eq!(memory.mapper.unsupported(), &["RTC"]);
+        }
+    }
+
+    #[cfg(test)]
+    mod cart {
+        use super::*;
+        use mbc::BankController;
+        use std::convert::TryFrom;
+
+        fn gen_cart_rom(cart_type: u8, size: usize) -> Vec<u8> {
+            let mut rom = gen_rom(size);
+            rom[0x147] = cart_type;
+            rom
+        }
+
+        // Same way run command gets its GB: ROM file through loader, mapper picked from header
+        fn load_cart(cart_type: u8, size: usize) -> Runtime<mbc::Cart> {
+            let path = std::env::temp_dir().join(format!("gameboy-mbctest-{:02X}.gb", cart_type));
+            std::fs::write(&path, gen_cart_rom(cart_type, size)).unwrap();
+            let loaded = load_rom(&path, false).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            Runtime::new(mbc::Cart::try_from(loaded.rom).unwrap())
+        }
+
+        #[test]
+        fn huc3_and_tama5() {
+            let runtime = load_cart(0xFE, SZ_512KB);
+            assert!(matches!(runtime.state.mmu.mapper, mbc::Cart::HuC3(_)));
+            assert_eq!(runtime.state.mmu.mapper.debug_state().name, "HuC-3");
+            let runtime = load_cart(0xFD, SZ_512KB);
+            assert!(matches!(runtime.state.mmu.mapper, mbc::Cart::TAMA5(_)));
+            assert_eq!(runtime.state.mmu.mapper.debug_state().name, "TAMA5");
+        }
+
+        #[test]
+        fn mapper_of_cart_type() {
+            let carts = [(0x00, SZ_32KB, "ROM only"), (0x03, SZ_2MB, "MBC1"), (0x06, SZ_256KB, "MBC2"),
+                (0x13, SZ_2MB, "MBC3"), (0x1B, SZ_2MB, "MBC5")];
+            for (cart_type, size, name) in carts.iter() {
+                assert_eq!(load_cart(*cart_type, *size).state.mmu.mapper.debug_state().name, *name);
+            }
+            // Unknown ones run as MBC1
+            assert!(matches!(load_cart(0x20, SZ_2MB).state.mmu.mapper, mbc::Cart::MBC1(_)));
+        }
+
+        #[test]
+        fn swapped_cart() {
+            let mut runtime = load_cart(0x01, SZ_512KB);
+            runtime.load_rom(gen_cart_rom(0xFD, SZ_512KB)).unwrap();
+            assert!(matches!(runtime.state.mmu.mapper, mbc::Cart::TAMA5(_)));
+            // Too big for the cart type it says
+            assert!(runtime.load_rom(gen_cart_rom(0x00, SZ_512KB)).is_err());
+        }
+
+        // Latches clock and reads seconds, minutes, hours and day
+        fn latch(runtime: &mut Runtime<mbc::Cart>) -> Vec<u8> {
+            let mmu = &mut runtime.state.mmu;
+            mmu.write(0x6000, 0x00);
+            mmu.write(0x6000, 0x01);
+            (0x08..=0x0B).map(|reg| { mmu.write(0x4000, reg); mmu.read(RAM_SWITCHABLE_ADDR) }).collect()
+        }
+
+        #[test]
+        fn emulated_clock_in_lockstep() {
+            let mut rom = gen_cart_rom(0x10, SZ_2MB);
+            // JR -2
+            rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+            let mut runtimes = [0, 1].map(|_| {
+                let mut runtime = Runtime::new(mbc::Cart::with_emulated_clock(rom.clone()).unwrap());
+                runtime.skip_bootrom(HardwareModel::DMG);
+                runtime
+            });
+            assert!(matches!(runtimes[0].state.mmu.mapper, mbc::Cart::MBC3Emulated(_)));
+
+            // A bit over a second of emulated time, both GBs an instruction at a time
+            while runtimes[0].cpu_cycles() < 1 << 20 | 0x1000 {
+                runtimes.iter_mut().for_each(|runtime| runtime.step());
+            }
+            let [first, second] = &mut runtimes;
+            let time = latch(first);
+            assert_eq!(time, vec![1, 0, 0, 1]);
+            assert_eq!(latch(second), time);
+        }
+    }
+
+    #[cfg(test)]
+    mod sizes {
+        use super::*;
+
+        // ROM of given banks, first byte of each is its number, header asks for RAM of given code
+        fn gen_banked(banks: usize, ram_code: u8) -> Vec<u8> {
+            let mut rom = vec![0; banks * ROM_BANK_SIZE];
+            for bank in 0..banks {
+                rom[bank * ROM_BANK_SIZE] = bank as u8;
+            }
+            rom[0x149] = ram_code;
+            rom
+        }
+
+        #[test]
+        fn sized_from_header() {
+            let mbc = mbc::MBC1::new(gen_banked(4, 0x02));
+            assert_eq!(mbc.rom.len(), 4 * ROM_BANK_SIZE);
+            assert_eq!(mbc.ram.len(), RAM_BANK_SIZE);
+
+            // Header saying 256KB wins over shorter ROM
+            let mut rom = gen_banked(2, 0x00);
+            rom[0x148] = 0x03;
+            let mbc = mbc::MBC3::new(rom);
+            assert_eq!(mbc.rom.len(), 16 * ROM_BANK_SIZE);
+            assert!(mbc.ram.is_empty());
+
+            // Odd sizes are rounded up to power of two, 2KB RAM gets whole bank
+            let mut rom = vec![0; 3 * ROM_BANK_SIZE];
+            rom[0x149] = 0x01;
+            let mbc = mbc::MBC1::new(rom);
+            assert_eq!(mbc.rom.len(), 4 * ROM_BANK_SIZE);
+            assert_eq!(mbc.ram.len(), RAM_BANK_SIZE);
+
+            // Broken header leaves everything mapper supports
+            let mut rom = vec![0; 0x150];
+            rom[0x148] = 0x42;
+            rom[0x149] = 0x42;
+            let mbc = mbc::MBC3::new(rom);
+            assert_eq!(mbc.rom.len(), 2 * ROM_BANK_SIZE);
+            assert_eq!(mbc.ram.len(), 8 * RAM_BANK_SIZE);
+        }
+
+        #[test]
+        fn mbc1_high_banks_mirror() {
+            let mut memory = mock_memory(mbc::MBC1::new(gen_banked(4, 0x02)));
+            memory.write(0x2000, 0x05);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x01);
+            assert_eq!(memory.mapper.current_rom_bank(), 1);
+            // Bank 0 can't be picked directly, but 4 wraps around to it
+            memory.write(0x2000, 0x04);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x00);
+            memory.write(0x2000, 0x1F);
+            memory.write(0x4000, 0x03);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x03);
+
+            // Single RAM bank is there whatever bank is selected
+            memory.write(0x0000, 0x0A);
+            memory.write(0x6000, 0x01);
+            memory.write(0x4000, 0x00);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            memory.write(0x4000, 0x03);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0x42);
+        }
+
+        #[test]
+        fn mbc2_high_banks_mirror() {
+            let mut memory = mock_memory(mbc::MBC2::new(gen_banked(4, 0x00)));
+            memory.write(0x2100, 0x0F);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x03);
+            memory.write(0x2100, 0x06);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x02);
+        }
+
+        #[test]
+        fn mbc3_high_banks_mirror() {
+            let mut memory = mock_memory(mbc::MBC3::new(gen_banked(8, 0x03)));
+            memory.write(0x2000, 0x7F);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x07);
+            memory.write(0x2000, 0x09);
+            assert_eq!(memory.read(ROM_SWITCHABLE_ADDR), 0x01);
+
+            // 4 RAM banks, 5 is the same as 1
+            memory.write(0x4000, 0x01);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            memory.write(0x4000, 0x05);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0x42);
+        }
+
+        #[test]
+        fn missing_ram() {
+            let mut memory = mock_memory(mbc::MBC1::new(gen_banked(2, 0x00)));
+            memory.write(0x0000, 0x0A);
+            memory.write(RAM_SWITCHABLE_ADDR, 0x42);
+            assert_eq!(memory.read(RAM_SWITCHABLE_ADDR), 0xFF);
+        }
+    }
+
+    #[cfg(test)]
+    mod access {
+        use super::*;
+        use mbc::BankController;
+
+        #[test]
+        fn rom_writes_taken_by_registers_only() {
+            let mut romonly = gen_romonly();
+            assert!(!romonly.write_rom(0x2000, 0x01));
+            assert_eq!(romonly.read_rom(0x2000), 0x00);
+
+            let mut mbc1 = gen_mbc1();
+            assert!(mbc1.write_rom(0x2000, 0x02));
+            assert_eq!(mbc1.current_rom_bank(), 2);
+
+            // MBC2 registers are in lower half only
+            let mut mbc2 = gen_mbc2();
+            assert!(mbc2.write_rom(0x2100, 0x03));
+            assert!(!mbc2.write_rom(0x6000, 0x01));
+            assert_eq!(mbc2.current_rom_bank(), 3);
+        }
+
+        #[test]
+        fn mbc2_ram_holds_nibbles() {
+            let mut mbc2 = gen_mbc2();
+            assert!(mbc2.write_ram(RAM_SWITCHABLE_ADDR + 0x10, 0xAB));
+            assert_eq!(mbc2.read_ram(RAM_SWITCHABLE_ADDR + 0x10), Some(0x0B));
+            assert_eq!(mbc2.ram()[0x10], 0x0B);
+
+            assert!(!mbc2.write_ram(RAM_SWITCHABLE_ADDR + 512, 0x01));
+            assert_eq!(mbc2.read_ram(RAM_SWITCHABLE_ADDR + 512), None);
+
+            // Disabled RAM
+            mbc2.write_rom(0x0000, 0x00);
+            assert_eq!(mbc2.read_ram(RAM_SWITCHABLE_ADDR + 0x10), None);
+            assert!(!mbc2.write_ram(RAM_SWITCHABLE_ADDR + 0x10, 0x01));
+        }
+
+        #[test]
+        fn mbc3_rtc_register_over_whole_area() {
+            let mut mbc3 = gen_mbc3();
+            mbc3.write_rom(0x6000, 0x00);
+            mbc3.write_rom(0x6000, 0x01);
+            mbc3.write_rom(0x4000, 0x08);
+            assert!(mbc3.write_ram(RAM_SWITCHABLE_ADDR, 30));
+            assert_eq!(mbc3.rtc_reg[0], 30);
+            assert_eq!(mbc3.read_ram(0xBFFF), Some(30));
+
+            // No register past 0x0C
+            mbc3.write_rom(0x4000, 0x0D);
+            assert_eq!(mbc3.read_ram(RAM_SWITCHABLE_ADDR), None);
+            assert!(!mbc3.write_ram(RAM_SWITCHABLE_ADDR, 0x01));
+        }
+
+        #[test]
+        fn peek_same_as_read() {
+            let mut mbc3 = gen_mbc3();
+            mbc3.write_rom(0x2000, 0x05);
+            mbc3.write_rom(0x4000, 0x02);
+            mbc3.write_ram(RAM_SWITCHABLE_ADDR + 7, 0x42);
+            for addr in [0x0000, 0x3FFF, 0x4000, 0x7FFF] {
+                assert_eq!(mbc3.peek_rom(addr), mbc3.read_rom(addr));
+            }
+            assert_eq!(mbc3.peek_ram(RAM_SWITCHABLE_ADDR + 7), Some(0x42));
+
+            mbc3.write_rom(0x4000, 0x08);
+            assert_eq!(mbc3.peek_ram(0xBFFF), mbc3.read_ram(0xBFFF));
+            mbc3.write_rom(0x4000, 0x0D);
+            assert_eq!(mbc3.peek_ram(RAM_SWITCHABLE_ADDR), None);
+
+            let mut mbc2 = gen_mbc2();
+            mbc2.write_ram(RAM_SWITCHABLE_ADDR, 0xAB);
+            assert_eq!(mbc2.peek_ram(RAM_SWITCHABLE_ADDR), Some(0x0B));
+        }
+
+        #[test]
+        fn ram_accessor_is_whole_ram() {
+            let mut mbc3 = gen_mbc3();
+            mbc3.write_rom(0x4000, 0x03);
+            mbc3.write_ram(RAM_SWITCHABLE_ADDR + 5, 0x42);
+            assert_eq!(mbc3.ram()[3 * RAM_BANK_SIZE + 5], 0x42);
+            assert_eq!(mbc3.rom().len(), SZ_2MB);
+        }
+    }
+
+    #[cfg(test)]
+    mod debug {
+        use super::*;
+        use mbc::BankController;
+
+        #[test]
+        fn current_banks() {
+            let mut mbc1 = gen_mbc1();
+            mbc1.write_rom(0x2000, 0x1C);
+            mbc1.write_rom(0x6000, 0x01);
+            mbc1.write_rom(0x4000, 0x02);
+            assert_eq!(mbc1.current_rom_bank(), 0x1C);
+            assert_eq!(mbc1.current_ram_bank(), Some(2));
+
+            let mut mbc3 = gen_mbc3();
+            mbc3.write_rom(0x4000, 0x03);
+            assert_eq!(mbc3.current_ram_bank(), Some(3));
+            // RTC register mapped instead of RAM
+            mbc3.write_rom(0x4000, 0x08);
+            assert_eq!(mbc3.current_ram_bank(), None);
+
+            assert_eq!(gen_romonly().current_ram_bank(), None);
+        }
+
+        #[test]
+        fn debug_state() {
+            let mut mbc1 = gen_mbc1();
+            mbc1.write_rom(0x2000, 0x1C);
+            let info = mbc1.debug_state();
+            assert_eq!(info.name, "MBC1");
+            assert_eq!((info.rom_bank, info.rom_banks), (0x1C, 128));
+            assert_eq!((info.ram_bank, info.ram_banks), (Some(0), 4));
+            assert_eq!(info.to_string(), "MBC1 ROM 1C/80 RAM 00/04 off mode 00");
+
+            let mut mbc3 = gen_mbc3();
+            mbc3.write_rom(0x4000, 0x08);
+            assert_eq!(mbc3.debug_state().to_string(), "MBC3 ROM 01/80 RAM --/08 select 08 RTC halt 00");
+
+            assert_eq!(gen_romonly().debug_state().to_string(), "ROM only ROM 01/02 no RAM");
+        }
+    }
+}
@@ -19,10 +19,47 @@ mod memtest {
         #[should_panic]
         fn write_to_bootstrap() {
             let mut mmu = gen_mmu(SZ_2MB);
+            mmu.policy = BusPolicy::Panic;
             mmu.write(BOOT, 0);
             mmu.write(0x0000, 0x21);
         }
 
+        #[test]
+        fn cgb_bootrom_around_header() {
+            let mut mmu = MMU::new(mbc::MBC1::new(vec![0xAA; SZ_2MB]));
+            mmu.bootstrap = vec![0x11; CGB_BOOTROM_SIZE];
+            assert_eq!(mmu.read(0x00FF), 0x11);
+            // Cart header shows through
+            assert_eq!(mmu.read(0x0100), 0xAA);
+            assert_eq!(mmu.read(0x01FF), 0xAA);
+            assert_eq!(mmu.read(0x0200), 0x11);
+            assert_eq!(mmu.read(0x08FF), 0x11);
+            assert_eq!(mmu.read(0x0900), 0xAA);
+            mmu.write(0x0200, 0x00);
+            assert_eq!(mmu.take_illegal(), Some(IllegalAccess::BootromWrite { addr: 0x0200, value: 0x00 }));
+
+            mmu.disable_bootrom();
+            assert_eq!(mmu.read(0x0000), 0xAA);
+            assert_eq!(mmu.read(0x0200), 0xAA);
+        }
+
+        #[test]
+        fn dmg_bootrom_ends_at_100() {
+            let mut mmu = MMU::new(mbc::MBC1::new(vec![0xAA; SZ_2MB]));
+            assert_eq!(mmu.read(0x0000), mmu.bootstrap[0]);
+            assert_eq!(mmu.read(0x0200), 0xAA);
+        }
+
+        #[test]
+        fn bootstrap_write_ignored() {
+            let mut mmu = gen_mmu(SZ_2MB);
+            mmu.write(BOOT, 0);
+            mmu.write(0x0000, 0x21);
+            assert_eq!(mmu.read(0x0000), 0x31);
+            mmu.write(BOOT, 1);
+            assert_eq!(mmu.read(0x0000), 0x00);
+        }
+
         #[test]
         fn map_unmap() {
             let mut mmu = gen_mmu(SZ_2MB);
@@ -131,5 +168,286 @@ mod memtest {
             mmu.write(IE, ie | 0x0F);
             assert_eq!(mmu.read(IE), 0x0F);
         }
+
+        #[test]
+        fn cgb_registers_missing_on_dmg() {
+            let mut mmu = gen_mmu(SZ_2MB);
+
+            for addr in [KEY1, VBK, RP, SVBK, 0xFF72, 0xFF75].iter() {
+                mmu.write(*addr, 0x01);
+                assert_eq!(mmu.read(*addr), 0xFF);
+            }
+        }
+
+        #[test]
+        fn cgb_registers() {
+            let mut mmu = gen_mmu(SZ_2MB);
+            mmu.set_cgb(true);
+
+            // Normal speed, no switch prepared
+            assert_eq!(mmu.read(KEY1), 0x7E);
+            mmu.write(KEY1, 0xFF);
+            assert_eq!(mmu.read(KEY1), 0x7F);
+
+            mmu.write(VBK, 0xFF);
+            assert_eq!(mmu.read(VBK), 0xFF);
+            mmu.write(VBK, 0x00);
+            assert_eq!(mmu.read(VBK), 0xFE);
+
+            // No light received
+            assert_eq!(mmu.read(RP), 0x3E);
+            mmu.write(RP, 0xC1);
+            assert_eq!(mmu.read(RP), 0xFF);
+
+            mmu.write(SVBK, 0xFD);
+            assert_eq!(mmu.read(SVBK), 0xFD);
+
+            mmu.write(0xFF72, 0x5A);
+            assert_eq!(mmu.read(0xFF72), 0x5A);
+            mmu.write(0xFF75, 0xFF);
+            assert_eq!(mmu.read(0xFF75), 0xFF);
+            mmu.write(0xFF75, 0x00);
+            assert_eq!(mmu.read(0xFF75), 0x8F);
+        }
+    }
+
+    #[cfg(test)]
+    mod observers {
+        use super::*;
+        use std::sync::{Arc, Mutex};
+
+        // LD A, 0x42; LD (0xC000), A; LD A, (0xC000)
+        fn gen_runtime() -> Runtime<mbc::MBC1> {
+            let mut rom = vec![0; SZ_2MB];
+            rom[0x100..0x108].copy_from_slice(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0xFA, 0x00, 0xC0]);
+            let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+            runtime.skip_bootrom(HardwareModel::DMG);
+            runtime
+        }
+
+        #[test]
+        fn log_accesses() {
+            let mut runtime = gen_runtime();
+            let log = AccessLog::new(16);
+            runtime.state.mmu.subscribe(0xC000..=0xDFFF, None, log.recorder());
+            for _ in 0..3 {
+                runtime.step();
+            }
+            let entries = log.entries();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].kind, AccessKind::Write);
+            assert_eq!((entries[0].pc, entries[0].addr, entries[0].value), (0x102, 0xC000, 0x42));
+            assert_eq!(entries[1].kind, AccessKind::Read);
+            assert_eq!(entries[1].pc, 0x105);
+            assert!(entries[1].cycle > entries[0].cycle);
+        }
+
+        #[test]
+        fn filter_and_unsubscribe() {
+            let mut runtime = gen_runtime();
+            let writes = Arc::new(Mutex::new(0));
+            let counter = writes.clone();
+            let id = runtime.state.mmu.subscribe(
+                0x0000..=0xFFFF,
+                Some(AccessKind::Write),
+                Box::new(move |_| *counter.lock().unwrap() += 1),
+            );
+            runtime.step();
+            runtime.step();
+            // Opcode fetches are reads, so only the store is counted
+            assert_eq!(*writes.lock().unwrap(), 1);
+
+            assert!(runtime.state.mmu.unsubscribe(id));
+            assert!(!runtime.state.mmu.unsubscribe(id));
+            runtime.state.safe_write(0xC000, 0x00);
+            assert_eq!(*writes.lock().unwrap(), 1);
+        }
+
+        #[test]
+        fn log_capacity() {
+            let mut runtime = gen_runtime();
+            let log = AccessLog::new(2);
+            runtime.state.mmu.subscribe(0xC000..=0xC0FF, None, log.recorder());
+            for value in 0..5 {
+                runtime.state.safe_write(0xC000, value);
+            }
+            let values = log.entries().iter().map(|access| access.value).collect::<Vec<u8>>();
+            assert_eq!(values, vec![3, 4]);
+        }
+    }
+
+    #[cfg(test)]
+    mod bus {
+        use super::*;
+
+        fn gen_romonly(policy: BusPolicy) -> MMU<mbc::RomOnly> {
+            let mut mmu = MMU::new(mbc::RomOnly::new(vec![0x42; 1 << 15]));
+            mmu.disable_bootrom();
+            mmu.policy = policy;
+            mmu
+        }
+
+        #[test]
+        fn illegal_accesses_dropped() {
+            for policy in [BusPolicy::Ignore, BusPolicy::Log, BusPolicy::Trap] {
+                let mut mmu = gen_romonly(policy);
+                mmu.write(0x2000, 0x01);
+                mmu.write(RAM_SWITCHABLE_ADDR, 0x01);
+                assert_eq!(mmu.read(0x2000), 0x42);
+                assert_eq!(mmu.read(RAM_SWITCHABLE_ADDR), 0xFF);
+            }
+        }
+
+        #[test]
+        fn keeps_last_illegal() {
+            for policy in [BusPolicy::Ignore, BusPolicy::Log, BusPolicy::Trap] {
+                let mut mmu = gen_romonly(policy);
+                assert_eq!(mmu.take_illegal(), None);
+                mmu.write(0x2000, 0x01);
+                assert_eq!(mmu.take_illegal(), Some(IllegalAccess::RomWrite { addr: 0x2000, value: 0x01 }));
+                assert_eq!(mmu.take_illegal(), None);
+                mmu.read(RAM_SWITCHABLE_ADDR);
+                let missing = IllegalAccess::Missing { kind: AccessKind::Read, addr: RAM_SWITCHABLE_ADDR };
+                assert_eq!(mmu.take_illegal(), Some(missing));
+            }
+        }
+
+        #[test]
+        #[should_panic]
+        fn panic_on_missing_ram() {
+            let mut mmu = gen_romonly(BusPolicy::Panic);
+            mmu.write(RAM_SWITCHABLE_ADDR, 0x01);
+        }
+
+        #[test]
+        fn parse_policy() {
+            assert_eq!("trap".parse::<BusPolicy>(), Ok(BusPolicy::Trap));
+            assert_eq!(" Panic ".parse::<BusPolicy>(), Ok(BusPolicy::Panic));
+            assert!("strict".parse::<BusPolicy>().is_err());
+            assert_eq!(BusPolicy::default(), BusPolicy::Log);
+        }
+
+        #[test]
+        fn stats_per_region() {
+            let mut state = State::new(mbc::RomOnly::new(vec![0x42; 1 << 15]));
+            state.mmu.disable_bootrom();
+            state.mmu.policy = BusPolicy::Ignore;
+            // Devices reach MMU directly, only accesses made by CPU count
+            state.mmu.read(mem::ioregs::TAC);
+            state.mmu.write(mem::ioregs::TIMA, 0x01);
+            assert_eq!(state.mmu.stats, BusStats::default());
+            let before = state.mmu.stats;
+
+            state.safe_read(0x0150);
+            state.safe_read(0x7FFF);
+            state.safe_write(0xC000, 0x01);
+            state.safe_write(0xDFFF, 0x01);
+            state.safe_read(0xE000);
+            state.safe_read(0xFFFF);
+            state.safe_write(0xFF80, 0x01);
+            state.safe_write(0x2000, 0x01);
+
+            let delta = state.mmu.stats.since(&before);
+            assert_eq!(delta.reads(Region::Rom), 2);
+            assert_eq!(delta.writes(Region::Rom), 1);
+            assert_eq!(delta.writes(Region::Wram), 2);
+            assert_eq!(delta.reads(Region::Echo), 1);
+            assert_eq!(delta.reads(Region::Io), 1);
+            assert_eq!(delta.writes(Region::Hram), 1);
+            assert_eq!((delta.total_reads(), delta.total_writes()), (4, 4));
+            // ROM write is illegal even though it's ignored
+            assert_eq!(delta.illegal, 1);
+            assert_eq!(delta.to_string(), "4 reads, 4 writes, ROM 2/1, WRAM 0/2, ECHO 1/0, IO 1/0, HRAM 0/1, 1 illegal");
+        }
+
+        #[test]
+        fn peek_has_no_side_effects() {
+            let mut mmu = gen_romonly(BusPolicy::Panic);
+            mmu.write(0xC010, 0x42);
+            mmu.write(0xFF80, 0x24);
+            let before = mmu.stats;
+
+            assert_eq!(mmu.peek(0xC010), 0x42);
+            assert_eq!(mmu.peek(0xE010), 0x42);
+            assert_eq!(mmu.peek(0xFF80), 0x24);
+            // Missing cart RAM doesn't panic
+            assert_eq!(mmu.peek(RAM_SWITCHABLE_ADDR), 0xFF);
+            assert_eq!(mmu.peek_range(0xFFFF, 2), vec![mmu.peek(0xFFFF), mmu.peek(0x0000)]);
+
+            assert_eq!(mmu.stats, before);
+            assert_eq!(mmu.take_illegal(), None);
+            for addr in [0x0100, 0x8000, 0xC010, 0xFE00, mem::ioregs::IF, 0xFF80, 0xFFFF] {
+                assert_eq!(mmu.peek(addr), mmu.read(addr));
+            }
+        }
+
+        #[test]
+        fn peek_sees_bootrom() {
+            let mmu = MMU::new(mbc::RomOnly::new(vec![0xAA; 0x8000]));
+            assert_eq!(mmu.peek(0x0000), mmu.bootstrap[0]);
+            assert_eq!(mmu.peek(0x0100), 0xAA);
+        }
+
+        #[test]
+        fn regions() {
+            assert_eq!(Region::of(0x9FFF), Region::Vram);
+            assert_eq!(Region::of(0xA000), Region::CartRam);
+            assert_eq!(Region::of(0xFEA0), Region::Oam);
+            assert_eq!(Region::of(0xFF7F), Region::Io);
+            assert_eq!(Region::of(0xFFFE), Region::Hram);
+        }
+    }
+
+    #[cfg(test)]
+    mod words {
+        use super::*;
+
+        fn gen_ramonly() -> MMU<mbc::RamOnly> {
+            let mut mmu = MMU::new(mbc::RamOnly::new(vec![]));
+            mmu.disable_bootrom();
+            mmu
+        }
+
+        #[test]
+        fn little_endian() {
+            let mut mmu = gen_ramonly();
+            mmu.write_word(0xC000, 0x1234);
+            assert_eq!(mmu.read(0xC000), 0x34);
+            assert_eq!(mmu.read(0xC001), 0x12);
+            assert_eq!(mmu.read_word(0xC000), 0x1234);
+        }
+
+        #[test]
+        fn across_region_boundaries() {
+            let mut mmu = gen_ramonly();
+            // Both WRAM banks, WRAM and echo, ROM and VRAM, cart RAM and WRAM, IO and HRAM
+            for addr in [0xCFFF, 0xDFFF, 0x7FFF, 0xBFFF, 0xFF7F] {
+                mmu.write_word(addr, 0xBEEF);
+                assert_eq!(mmu.read(addr), 0xEF, "0x{:04X}", addr);
+                assert_eq!(mmu.read(addr + 1), 0xBE, "0x{:04X}", addr);
+                assert_eq!(mmu.read_word(addr), 0xBEEF, "0x{:04X}", addr);
+            }
+            // Echo of WRAM start got high byte of word at 0xDFFF
+            assert_eq!(mmu.read(0xC000), 0xBE);
+        }
+
+        #[test]
+        fn wraps_around() {
+            let mut mmu = gen_ramonly();
+            mmu.write_word(0xFFFF, 0x1F42);
+            assert_eq!(mmu.read(mem::ioregs::IE), 0x42);
+            assert_eq!(mmu.read(0x0000), 0x1F);
+            assert_eq!(mmu.read_word(0xFFFF), 0x1F42);
+        }
+
+        #[test]
+        fn two_accesses() {
+            let mut state = State::new(mbc::RamOnly::new(Vec::new()));
+            state.mmu.disable_bootrom();
+            state.write_word(0xCFFF, 0x0102);
+            state.read_word(0xCFFF);
+            let stats = state.mmu.stats;
+            assert_eq!((stats.reads(Region::Wram), stats.writes(Region::Wram)), (2, 2));
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,125 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod modeltest {
+    use gameboy::*;
+
+    fn gen(header_checksum: u8) -> Runtime<mbc::MBC1> {
+        let mut rom = vec![0; 1 << 21];
+        rom[HEADER_CHECKSUM] = header_checksum;
+        Runtime::new(mbc::MBC1::new(rom))
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("mgb".parse::<HardwareModel>(), Ok(HardwareModel::MGB));
+        assert_eq!("DMG0".parse::<HardwareModel>(), Ok(HardwareModel::DMG0));
+        assert!("gba".parse::<HardwareModel>().is_err());
+        for model in MODELS.iter() {
+            assert_eq!(model.to_string().parse::<HardwareModel>(), Ok(*model));
+        }
+    }
+
+    #[test]
+    fn skip_bootrom() {
+        let mut runtime = gen(0x42);
+        runtime.skip_bootrom(HardwareModel::DMG);
+        assert!(!runtime.state.mmu.bootrom_mapped());
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        assert_eq!(runtime.cpu.A, 0x01);
+        assert_eq!(runtime.cpu.F(), 0xB0);
+        assert_eq!(runtime.cpu.BC.val(), 0x0013);
+        assert_eq!(runtime.cpu.HL.val(), 0x014D);
+        assert_eq!(runtime.cpu.SP, 0xFFFE);
+        assert_eq!(runtime.state.mmu.read(ioregs::DIV), 0xAB);
+        // Sound is left on
+        assert!(runtime.state.mmu.read_bit(ioregs::NR_52, 7));
+    }
+
+    #[test]
+    fn skip_bootrom_leaves_logo() {
+        let mut rom = vec![0; 1 << 21];
+        rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let vram = &runtime.state.mmu.vram;
+        // First logo byte 0xCE: nibble 0xC is 4 pixels, 2 set, scaled to 8. Only lower bit plane is set.
+        assert_eq!(vram[0x10..0x14], [0xF0, 0x00, 0xF0, 0x00]);
+        assert_eq!(vram[0x14..0x18], [0xFC, 0x00, 0xFC, 0x00]);
+        // Logo tiles in the middle of the screen, ® right after upper row
+        assert_eq!(vram[0x1904..0x1911], [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0x19]);
+        assert_eq!(vram[0x1924..0x1930], [13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24]);
+        assert_eq!(vram[0x0190], 0x3C);
+
+        // CGB boot ROM leaves no such thing
+        let mut runtime = gen(0x42);
+        runtime.skip_bootrom(HardwareModel::CGB);
+        assert!(runtime.state.mmu.vram.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn cgb_registers_follow_model() {
+        let mut runtime = gen(0x42);
+        assert_eq!(runtime.state.mmu.read(ioregs::KEY1), 0xFF);
+        runtime.set_model(HardwareModel::CGB);
+        assert!(runtime.state.mmu.cgb());
+        assert_eq!(runtime.state.mmu.read(ioregs::KEY1), 0x7E);
+        runtime.set_model(HardwareModel::DMG);
+        assert_eq!(runtime.state.mmu.read(ioregs::KEY1), 0xFF);
+    }
+
+    #[test]
+    fn header_checksum_flags() {
+        let mut runtime = gen(0x00);
+        runtime.skip_bootrom(HardwareModel::DMG);
+        assert_eq!(runtime.cpu.F(), 0x80);
+    }
+
+    #[test]
+    fn models_differ() {
+        // Games tell models apart by A register
+        let a = |model| {
+            let mut runtime = gen(0x42);
+            runtime.skip_bootrom(model);
+            runtime.cpu.A
+        };
+        assert_eq!(a(HardwareModel::DMG0), 0x01);
+        assert_eq!(a(HardwareModel::MGB), 0xFF);
+        assert_eq!(a(HardwareModel::CGB), 0x11);
+        assert_eq!(a(HardwareModel::SGB), 0x01);
+
+        let mut runtime = gen(0x42);
+        runtime.skip_bootrom(HardwareModel::SGB);
+        assert_eq!(runtime.cpu.BC.val(), 0x0014);
+        assert_eq!(runtime.cpu.HL.val(), 0xC060);
+    }
+
+    #[test]
+    fn features() {
+        assert!(HardwareModel::SGB.sgb());
+        assert!(!HardwareModel::DMG.sgb());
+        assert!(HardwareModel::CGB.cgb());
+        assert_eq!(HardwareModel::DMG.bootrom_size(), BOOSTRAP_SIZE);
+        assert_eq!(HardwareModel::CGB.bootrom_size(), 0x900);
+    }
+
+    #[test]
+    fn quirks() {
+        assert!(HardwareModel::DMG.quirks().stat_write_bug);
+        assert!(HardwareModel::MGB.quirks().oam_bug);
+        assert!(!HardwareModel::CGB.quirks().stat_write_bug);
+        assert!(!HardwareModel::CGB.quirks().oam_bug);
+    }
+
+    #[test]
+    fn detect() {
+        let mut rom = vec![0; 0x8000];
+        let header = CartHeader::from_rom(&rom).unwrap();
+        assert_eq!(HardwareModel::detect(&header), HardwareModel::DMG);
+        // SGB flag
+        rom[0x146] = 0x03;
+        let header = CartHeader::from_rom(&rom).unwrap();
+        let expected = if cfg!(feature = "sgb") { HardwareModel::SGB } else { HardwareModel::DMG };
+        assert_eq!(HardwareModel::detect(&header), expected);
+    }
+}
@@ -0,0 +1,79 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod netlinktest {
+    use gameboy::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn pair(lockstep: bool) -> (NetLink, NetLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || NetLink::from_stream(TcpStream::connect(addr).unwrap(), lockstep));
+        let (stream, _) = listener.accept().unwrap();
+        let host = NetLink::from_stream(stream, lockstep).unwrap();
+        (host, client.join().unwrap().unwrap())
+    }
+
+    #[test]
+    fn master_and_slave() {
+        let (mut master, mut slave) = pair(false);
+        // Slave waits with its byte first, master clocks the transfer
+        assert_eq!(slave.respond(0x22), None);
+        assert_eq!(master.exchange(0x11), 0x22);
+        let mut received = None;
+        while received.is_none() {
+            received = slave.respond(0x22);
+        }
+        assert_eq!(received, Some(0x11));
+    }
+
+    #[test]
+    fn both_drive_clock() {
+        let (mut a, mut b) = pair(false);
+        let other = thread::spawn(move || b.exchange(0xBB));
+        assert_eq!(a.exchange(0xAA), 0xBB);
+        assert_eq!(other.join().unwrap(), 0xAA);
+    }
+
+    #[test]
+    fn lockstep_applies_on_frame() {
+        let (mut master, mut slave) = pair(true);
+        assert_eq!(slave.respond(0x22), None);
+        // Nothing is visible until both sides finish the frame
+        let other = thread::spawn(move || {
+            slave.frame();
+            slave
+        });
+        master.frame();
+        let mut slave = other.join().unwrap();
+
+        assert_eq!(master.exchange(0x11), 0x22);
+        assert_eq!(slave.respond(0x22), None);
+
+        let other = thread::spawn(move || {
+            slave.frame();
+            slave
+        });
+        master.frame();
+        let mut slave = other.join().unwrap();
+        assert_eq!(slave.respond(0x22), Some(0x11));
+    }
+
+    #[test]
+    fn lockstep_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || NetLink::from_stream(TcpStream::connect(addr).unwrap(), false));
+        let (stream, _) = listener.accept().unwrap();
+        assert!(NetLink::from_stream(stream, true).is_err());
+        assert!(client.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn peer_gone() {
+        let (mut link, other) = pair(false);
+        drop(other);
+        assert_eq!(link.exchange(0x11), DISCONNECTED);
+    }
+}
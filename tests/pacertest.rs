@@ -0,0 +1,71 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod pacertest {
+    use gameboy::*;
+    use std::time::{Duration, Instant};
+
+    fn period() -> Duration {
+        Duration::from_secs_f64(1.0 / REFRESH_RATE)
+    }
+
+    #[test]
+    fn dmg_refresh_rate() {
+        assert!((REFRESH_RATE - 59.7275).abs() < 0.0001);
+    }
+
+    #[test]
+    fn first_frame_due_after_period() {
+        let start = Instant::now();
+        let pacer = FramePacer::new(REFRESH_RATE, start);
+        assert_eq!(pacer.remaining(start), period());
+        assert!(pacer.remaining(start + period() * 2) < Duration::from_micros(1));
+    }
+
+    #[test]
+    fn no_drift() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(REFRESH_RATE, start);
+        // Host frames done right on time, a minute of them
+        for _ in 0..3600 {
+            let due = pacer.deadline();
+            pacer.frame_done(due);
+        }
+        assert_eq!(pacer.frames(), 3600);
+        let elapsed = pacer.deadline() - start;
+        let expected = Duration::from_secs_f64(3601.0 / REFRESH_RATE);
+        let error = elapsed.abs_diff(expected);
+        assert!(error < Duration::from_micros(10), "{:?}", error);
+    }
+
+    #[test]
+    fn late_frames_catch_up() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(REFRESH_RATE, start);
+        // Frame took 2 periods, next one is due right away to make up for it
+        pacer.frame_done(start + period() * 2);
+        assert!(pacer.remaining(start + period() * 2) < Duration::from_micros(1));
+        assert_eq!(pacer.frames(), 1);
+    }
+
+    #[test]
+    fn far_behind_restarts() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(REFRESH_RATE, start);
+        let resumed = start + Duration::from_secs(1);
+        pacer.frame_done(resumed);
+        assert_eq!(pacer.frames(), 0);
+        assert_eq!(pacer.remaining(resumed), period());
+    }
+
+    #[test]
+    fn pacing_names() {
+        assert_eq!("vsync".parse::<Pacing>().unwrap(), Pacing::Vsync);
+        assert_eq!(" Uncapped ".parse::<Pacing>().unwrap(), Pacing::Uncapped);
+        assert!("fast".parse::<Pacing>().is_err());
+        for pacing in [Pacing::Audio, Pacing::Exact, Pacing::Vsync, Pacing::Uncapped] {
+            assert_eq!(pacing.to_string().parse::<Pacing>().unwrap(), pacing);
+        }
+        assert_eq!(Pacing::default(), Pacing::Audio);
+    }
+}
@@ -0,0 +1,40 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod palettetest {
+    use gameboy::*;
+
+    const GREEN: [Color; 4] = [(0xE0, 0xF8, 0xD0), (0x88, 0xC0, 0x70), (0x34, 0x68, 0x56), (0x08, 0x18, 0x20)];
+    const RED: [Color; 4] = [(0xFF, 0xFF, 0xFF), (0xFF, 0x84, 0x84), (0x94, 0x3A, 0x3A), (0x00, 0x00, 0x00)];
+
+    #[test]
+    fn shades() {
+        let mut palette = Palette::default();
+        assert_eq!(palette.colorize(LIGHT_GRAY, PaletteLayer::Background), LIGHT_GRAY);
+        palette.set_shades(GREEN);
+        assert_eq!(palette.shades(), GREEN);
+        assert_eq!(palette.colorize(WHITE, PaletteLayer::Background), GREEN[0]);
+        assert_eq!(palette.colorize(BLACK, PaletteLayer::Object1), GREEN[3]);
+        palette.set_shade(2, (1, 2, 3));
+        assert_eq!(palette.colorize(DARK_GRAY, PaletteLayer::Object0), (1, 2, 3));
+        // Colors which aren't DMG shades stay
+        assert_eq!(palette.colorize((10, 20, 30), PaletteLayer::Background), (10, 20, 30));
+    }
+
+    #[test]
+    fn layers() {
+        let mut palette = Palette::new(GREEN);
+        palette.set_layer(PaletteLayer::Object0, Some(RED));
+        assert_eq!(palette.layer(PaletteLayer::Object0), RED);
+        assert_eq!(palette.layer(PaletteLayer::Object1), GREEN);
+        assert_eq!(palette.colorize(LIGHT_GRAY, PaletteLayer::Object0), RED[1]);
+        assert_eq!(palette.colorize(LIGHT_GRAY, PaletteLayer::Background), GREEN[1]);
+
+        let pixels = [BLACK, BLACK, WHITE];
+        let layers = [PaletteLayer::Background, PaletteLayer::Object0, PaletteLayer::Object1];
+        assert_eq!(palette.apply(&pixels, &layers), vec![GREEN[3], RED[3], GREEN[0]]);
+
+        palette.set_layer(PaletteLayer::Object0, None);
+        assert_eq!(palette.layer(PaletteLayer::Object0), GREEN);
+    }
+}
@@ -0,0 +1,74 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod runnertest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Sends 'O' through link cable, then keeps copying button state to 0xC000 */
+    const PROGRAM: [u8; 19] = [
+        0x3E, 0x4F, // LD A, 'O'
+        0xE0, 0x01, // LDH (SB), A
+        0x3E, 0x81, // LD A, 0x81
+        0xE0, 0x02, // LDH (SC), A
+        0x3E, 0x10, // LD A, 0x10
+        0xE0, 0x00, // LDH (P1), A
+        0xF0, 0x00, // LDH A, (P1)
+        0xEA, 0x00, 0xC0, // LD (0xC000), A
+        0x18, 0xF5, // JR -11
+    ];
+
+    fn job(script: InputScript) -> Job<mbc::RomOnly> {
+        Job { runtime: program_runtime(&PROGRAM, &[]), script }
+    }
+
+    fn buttons(result: &mut JobResult<mbc::RomOnly>) -> u8 {
+        result.runtime.state.mmu.read(0xC000) & 0x0F
+    }
+
+    #[test]
+    fn runs_scripts() {
+        let jobs = vec![
+            job(vec![]),
+            job(vec![(0, BUTTON_A)]),
+            job(vec![(0, BUTTON_A), (2, BUTTON_START)]),
+            job(vec![(5, BUTTON_B)]),
+        ];
+        let mut results = Runner::new(2).run(jobs, 3);
+        assert_eq!(results.len(), 4);
+        assert_eq!(buttons(&mut results[0]), 0b1111);
+        assert_eq!(buttons(&mut results[1]), 0b1110);
+        assert_eq!(buttons(&mut results[2]), 0b0111);
+        // Script entry past the last frame is never applied
+        assert_eq!(buttons(&mut results[3]), 0b1111);
+        for result in results.iter() {
+            assert_eq!(result.serial, b"O".to_vec());
+            assert_eq!(result.framebuffer.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+            assert!(!result.runtime.state.serial.connected());
+        }
+    }
+
+    #[test]
+    fn threads_dont_matter() {
+        let jobs = || (0..6).map(|i| job(vec![(i, BUTTON_UP), (i + 1, BUTTON_DOWN)])).collect::<Vec<_>>();
+        let mut single = Runner::new(1).run(jobs(), 4);
+        let mut many = Runner::new(0).run(jobs(), 4);
+        assert!(Runner::new(0).threads() >= 1);
+        for (a, b) in single.iter_mut().zip(many.iter_mut()) {
+            assert_eq!(a.framebuffer, b.framebuffer);
+            assert_eq!(a.runtime.cpu.PC.val(), b.runtime.cpu.PC.val());
+            assert_eq!(a.runtime.state.mmu.ram, b.runtime.state.mmu.ram);
+        }
+    }
+
+    #[test]
+    fn keeps_own_link() {
+        let mut job = job(vec![]);
+        job.runtime.state.serial.connect(Box::new(LocalLink::pair().0));
+        let results = Runner::new(1).run(vec![job], 1);
+        assert!(results[0].serial.is_empty());
+        assert!(results[0].runtime.state.serial.connected());
+    }
+}
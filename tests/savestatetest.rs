@@ -0,0 +1,153 @@
+#![cfg(feature = "serde")]
+extern crate gameboy;
+
+#[cfg(test)]
+mod savestatetest {
+    use gameboy::*;
+
+    // INC (HL); JR -3, with title in header
+    fn gen(title: &[u8]) -> Runtime<mbc::MBC1> {
+        let mut rom = vec![0; 1 << 16];
+        rom[0x100..0x103].copy_from_slice(&[0x34, 0x18, 0xFD]);
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.cpu.HL.set(0xC000);
+        runtime
+    }
+
+    fn run(runtime: &mut Runtime<mbc::MBC1>, steps: usize) {
+        for _ in 0..steps {
+            runtime.step();
+        }
+    }
+
+    fn screen() -> Vec<(u8, u8, u8)> {
+        vec![(0x10, 0x20, 0x30); SCREEN_WIDTH * SCREEN_HEIGHT]
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut runtime = gen(b"GAME");
+        run(&mut runtime, 1000);
+        let saved = runtime.state.mmu.read(0xC000);
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+
+        run(&mut runtime, 1000);
+        assert_ne!(runtime.state.mmu.read(0xC000), saved);
+        state.restore(&mut runtime).unwrap();
+        assert_eq!(runtime.state.mmu.read(0xC000), saved);
+    }
+
+    #[test]
+    fn battery_mapping_kept() {
+        // MBC1+RAM+BATTERY with 32KB of RAM
+        let mut rom = vec![0; 1 << 16];
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x03;
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        let path = std::env::temp_dir().join("gameboy-savestate-mapped.sav");
+        let _ = std::fs::remove_file(&path);
+        map_battery(&mut runtime.state.mmu.mapper, &path).unwrap();
+        runtime.state.mmu.mapper.ram[0x10] = 0x42;
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+
+        runtime.state.mmu.mapper.ram[0x10] = 0x99;
+        state.restore(&mut runtime).unwrap();
+        // Restored RAM went into the file, which is still mapped
+        assert_eq!(runtime.state.mmu.mapper.ram.path(), Some(path.as_path()));
+        assert_eq!(std::fs::read(&path).unwrap()[0x10], 0x42);
+        drop(runtime);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut runtime = gen(b"GAME");
+        run(&mut runtime, 1000);
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+        let path = std::env::temp_dir().join("gameboy-savestate.state1");
+        state.save(&path).unwrap();
+        let loaded = SaveState::load(&path).unwrap();
+        assert_eq!(loaded.encode(), state.encode());
+        assert_eq!(loaded.header_hash, header_hash(&mut runtime));
+
+        // Thumbnail is PNG with half of screen size
+        assert_eq!(loaded.thumbnail[1..4], *b"PNG");
+        assert_eq!(loaded.thumbnail[16..24], [0, 0, 0, 80, 0, 0, 0, 72]);
+    }
+
+    #[test]
+    fn other_rom_rejected() {
+        let mut runtime = gen(b"GAME");
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+        let mut other = gen(b"OTHER");
+        run(&mut other, 100);
+        let cycles = other.cpu_cycles();
+        assert!(state.restore(&mut other).is_err());
+        assert_eq!(other.cpu_cycles(), cycles);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let mut runtime = gen(b"GAME");
+        let bytes = SaveState::capture(&mut runtime, &screen()).unwrap().encode();
+        assert!(SaveState::decode(b"NOPE").is_err());
+        assert!(SaveState::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut newer = bytes.clone();
+        newer[4] = 0xFF;
+        let err = SaveState::decode(&newer).err().unwrap();
+        assert!(err.contains("version"));
+    }
+
+    #[test]
+    fn link_kept() {
+        struct Fixed;
+        impl SerialLink for Fixed {
+            fn exchange(&mut self, _: u8) -> u8 {
+                0x42
+            }
+            fn respond(&mut self, _: u8) -> Option<u8> {
+                None
+            }
+        }
+        let mut runtime = gen(b"GAME");
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+        runtime.state.serial.connect(Box::new(Fixed));
+        let log = AccessLog::new(4);
+        runtime.state.mmu.subscribe(0xC000..=0xC000, None, log.recorder());
+
+        state.restore(&mut runtime).unwrap();
+        assert!(runtime.state.serial.connected());
+        run(&mut runtime, 1);
+        assert!(!log.entries().is_empty());
+    }
+
+    #[test]
+    fn layer_flags_kept() {
+        let mut runtime = gen(b"GAME");
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+        runtime.state.gpu.show_sprites = false;
+
+        state.restore(&mut runtime).unwrap();
+        assert!(!runtime.state.gpu.show_sprites);
+        assert!(runtime.state.gpu.show_background);
+    }
+
+    #[test]
+    fn speed_and_sample_rate_kept() {
+        let mut runtime = gen(b"GAME");
+        runtime.set_speed(4.0);
+        runtime.state.apu.set_sample_rate(22050);
+        let state = SaveState::capture(&mut runtime, &screen()).unwrap();
+        runtime.set_speed(1.0);
+        runtime.state.apu.set_sample_rate(48000);
+        let cycles = runtime.cycles_per_frame();
+
+        state.restore(&mut runtime).unwrap();
+        assert_eq!(runtime.speed(), 1.0);
+        assert_eq!(runtime.cycles_per_frame(), cycles);
+        assert_eq!(runtime.state.apu.sample_rate(), 48000);
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "serde")]
+extern crate gameboy;
+
+#[cfg(test)]
+mod serdetest {
+    use gameboy::*;
+
+    // Loop incrementing counter in WRAM, with timer running: INC (HL); JR -3
+    fn gen() -> Runtime<mbc::RomOnly> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x103].copy_from_slice(&[0x34, 0x18, 0xFD]);
+        let mut runtime = Runtime::new(mbc::RomOnly::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.cpu.HL.set(0xC000);
+        runtime.state.safe_write(ioregs::TAC, 0x05);
+        runtime
+    }
+
+    fn run(runtime: &mut Runtime<mbc::RomOnly>, steps: usize) {
+        for _ in 0..steps {
+            runtime.step();
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut runtime = gen();
+        run(&mut runtime, 5000);
+        let json = serde_json::to_string(&runtime).unwrap();
+        let restored: Runtime<mbc::RomOnly> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.cpu.HL.val(), 0xC000);
+        assert_eq!(restored.cpu_cycles(), runtime.cpu_cycles());
+    }
+
+    #[test]
+    fn restored_runs_the_same() {
+        let mut runtime = gen();
+        run(&mut runtime, 3000);
+        let mut restored: Runtime<mbc::RomOnly> =
+            serde_json::from_str(&serde_json::to_string(&runtime).unwrap()).unwrap();
+        run(&mut runtime, 10000);
+        run(&mut restored, 10000);
+        assert_eq!(restored.state.mmu.read(0xC000), runtime.state.mmu.read(0xC000));
+        assert_eq!(restored.state.timer.counter(), runtime.state.timer.counter());
+        assert_eq!(serde_json::to_string(&restored).unwrap(), serde_json::to_string(&runtime).unwrap());
+    }
+
+    #[test]
+    fn long_arrays() {
+        let state = State::new(mbc::RomOnly::new(vec![0; 0x8000]));
+        let json = serde_json::to_value(&state.gpu).unwrap();
+        assert_eq!(json["sprites"].as_array().unwrap().len(), SPRITE_COUNT);
+
+        // Wrong length is rejected, not truncated
+        let mut broken = json.clone();
+        broken["sprites"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<GPU>(broken).is_err());
+    }
+
+    #[test]
+    fn mapper_state() {
+        let mut rom = vec![0; 1 << 16];
+        rom[0x149] = 0x02; // 8KB RAM
+        let mut mbc = mbc::MBC1::new(rom);
+        mbc.ram[0x10] = 0x42;
+        mbc.ram_enabled = true;
+        let restored: mbc::MBC1 = serde_json::from_str(&serde_json::to_string(&mbc).unwrap()).unwrap();
+        assert_eq!(restored.ram[0x10], 0x42);
+        assert!(restored.ram_enabled);
+        assert_eq!(restored.rom.len(), mbc.rom.len());
+    }
+}
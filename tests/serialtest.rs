@@ -0,0 +1,185 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod serialtest {
+    use gameboy::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    fn gen() -> Runtime<mbc::MBC1> {
+        let mut runtime = Runtime::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        // Endless NOPs
+        runtime.state.mmu.disable_bootrom();
+        runtime
+    }
+
+    /* Records what was sent and answers with fixed byte */
+    struct Echo {
+        sent: Arc<Mutex<Vec<u8>>>,
+        answer: u8,
+    }
+
+    impl SerialLink for Echo {
+        fn exchange(&mut self, out: u8) -> u8 {
+            self.sent.lock().unwrap().push(out);
+            self.answer
+        }
+
+        fn respond(&mut self, out: u8) -> Option<u8> {
+            Some(self.exchange(out))
+        }
+    }
+
+    fn run_cycles(runtime: &mut Runtime<mbc::MBC1>, cycles: u64) {
+        let end = runtime.cpu_cycles() + cycles;
+        while runtime.cpu_cycles() < end {
+            runtime.step();
+        }
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut runtime = gen();
+        runtime.state.safe_write(ioregs::SB, 0x42);
+        runtime.state.safe_write(ioregs::SC, 0x81);
+        run_cycles(&mut runtime, 8 * SERIAL_BIT_CYCLES + 1);
+        assert_eq!(runtime.state.mmu.read(ioregs::SB), 0xFF);
+        assert_eq!(runtime.state.mmu.read(ioregs::SC) & 0x80, 0);
+        assert!(runtime.state.mmu.read_bit(ioregs::IF, 3));
+    }
+
+    #[test]
+    fn transfer_takes_8_bits() {
+        let mut runtime = gen();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        runtime.state.serial.connect(Box::new(Echo { sent: sent.clone(), answer: 0x12 }));
+        runtime.state.safe_write(ioregs::SB, 0x42);
+        runtime.state.safe_write(ioregs::SC, 0x81);
+
+        run_cycles(&mut runtime, 4 * SERIAL_BIT_CYCLES);
+        assert_eq!(runtime.state.mmu.read(ioregs::SC) & 0x80, 0x80);
+        assert!(sent.lock().unwrap().is_empty());
+
+        run_cycles(&mut runtime, 4 * SERIAL_BIT_CYCLES + 1);
+        assert_eq!(runtime.state.mmu.read(ioregs::SB), 0x12);
+        assert_eq!(*sent.lock().unwrap(), vec![0x42]);
+    }
+
+    #[test]
+    fn external_clock() {
+        let mut runtime = gen();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        runtime.state.serial.connect(Box::new(Echo { sent: sent.clone(), answer: 0x34 }));
+        runtime.state.safe_write(ioregs::SB, 0x56);
+        runtime.state.safe_write(ioregs::SC, 0x80);
+        run_cycles(&mut runtime, SERIAL_BIT_CYCLES + 1);
+        assert_eq!(runtime.state.mmu.read(ioregs::SB), 0x34);
+        assert_eq!(*sent.lock().unwrap(), vec![0x56]);
+    }
+
+    #[test]
+    fn local_link() {
+        let (mut master, mut slave) = (gen(), gen());
+        let (cable1, cable2) = LocalLink::pair();
+        master.state.serial.connect(Box::new(cable1));
+        slave.state.serial.connect(Box::new(cable2));
+
+        slave.state.safe_write(ioregs::SB, 0x22);
+        slave.state.safe_write(ioregs::SC, 0x80);
+        run_cycles(&mut slave, SERIAL_BIT_CYCLES + 1);
+        master.state.safe_write(ioregs::SB, 0x11);
+        master.state.safe_write(ioregs::SC, 0x81);
+        run_cycles(&mut master, 8 * SERIAL_BIT_CYCLES + 1);
+        run_cycles(&mut slave, SERIAL_BIT_CYCLES + 1);
+
+        assert_eq!(master.state.mmu.read(ioregs::SB), 0x22);
+        assert_eq!(slave.state.mmu.read(ioregs::SB), 0x11);
+        assert_eq!(slave.state.mmu.read(ioregs::SC) & 0x80, 0);
+    }
+
+    fn packet(command: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x88, 0x33, command, 0x00, data.len() as u8, (data.len() >> 8) as u8];
+        bytes.extend_from_slice(data);
+        let checksum = bytes[2..].iter().fold(0u16, |acc, b| acc.wrapping_add(*b as u16));
+        bytes.push(checksum as u8);
+        bytes.push((checksum >> 8) as u8);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    fn send(printer: &mut Printer, bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().map(|b| printer.exchange(*b)).collect()
+    }
+
+    fn out_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gameboy-printer-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn printer_handshake() {
+        let mut printer = Printer::new(out_dir("handshake"));
+        let answer = send(&mut printer, &packet(0x01, &[]));
+        assert_eq!(answer[answer.len() - 2..], [0x81, 0x00]);
+
+        // Broken checksum is reported in next status
+        let mut broken = packet(0x0F, &[]);
+        broken[6] ^= 0xFF;
+        send(&mut printer, &broken);
+        let answer = send(&mut printer, &packet(0x0F, &[]));
+        assert_eq!(answer[answer.len() - 1] & 0x01, 0x01);
+    }
+
+    #[test]
+    fn printer_prints_png() {
+        let dir = out_dir("png");
+        let mut printer = Printer::new(dir.clone());
+        send(&mut printer, &packet(0x01, &[]));
+        // One band of black tiles
+        send(&mut printer, &packet(0x04, &[0xFF; 0x280]));
+        let answer = send(&mut printer, &packet(0x0F, &[]));
+        assert_eq!(answer[answer.len() - 1] & 0x08, 0x08);
+
+        send(&mut printer, &packet(0x02, &[0x01, 0x00, 0xE4, 0x40]));
+        assert_eq!(printer.printed().len(), 1);
+        let png = fs::read(&printer.printed()[0]).unwrap();
+        assert_eq!(png[1..4], *b"PNG");
+        // IHDR width and height
+        assert_eq!(png[16..24], [0, 0, 0, 160, 0, 0, 0, 16]);
+
+        // Busy until printing is done
+        let answer = send(&mut printer, &packet(0x0F, &[]));
+        assert_eq!(answer[answer.len() - 1] & 0x02, 0x02);
+    }
+
+    #[test]
+    fn printer_render() {
+        let mut printer = Printer::new(out_dir("render"));
+        send(&mut printer, &packet(0x01, &[]));
+        // Compressed: run of 0x280 bytes of 0xFF, split into runs of max 129
+        let mut data = Vec::new();
+        let mut left = 0x280;
+        while left > 0 {
+            let run = left.min(129);
+            data.push(0x80 | (run - 2) as u8);
+            data.push(0xFF);
+            left -= run;
+        }
+        let mut compressed = packet(0x04, &data);
+        compressed[3] = 0x01;
+        let checksum = compressed[2..compressed.len() - 4].iter().fold(0u16, |acc, b| acc.wrapping_add(*b as u16));
+        let len = compressed.len();
+        compressed[len - 4] = checksum as u8;
+        compressed[len - 3] = (checksum >> 8) as u8;
+        send(&mut printer, &compressed);
+
+        let pixels = printer.render(0xE4);
+        assert_eq!(pixels.len(), PRINTER_WIDTH * 16);
+        assert!(pixels.iter().all(|p| *p == 0x00));
+        // Inverted palette
+        assert!(printer.render(0x1B).iter().all(|p| *p == 0xFF));
+    }
+}
@@ -0,0 +1,77 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod sessiontest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    fn played() -> Session {
+        let mut session = Session {
+            last_rom: Some(std::path::PathBuf::from("/roms/tetris.gb")),
+            window: Some((640, 576)),
+            ..Session::default()
+        };
+        let game = session.game_mut("TETRIS 16BF");
+        game.palette = parse_palette("#E0F8D0,#88C070,#346856,#081820");
+        game.slot = Some(2);
+        game.keys.insert(String::from("a"), vec![String::from("J")]);
+        session
+    }
+
+    #[test]
+    fn key_of_header() {
+        let header = CartHeader::from_rom(ROM).unwrap();
+        assert_eq!(Session::key(&header), "TETRIS 16BF");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn toml_round_trip() {
+        let session = played();
+        let text = session.to_toml().unwrap();
+        assert!(text.contains("\"#E0F8D0\""));
+        assert_eq!(Session::from_toml(&text).unwrap(), session);
+        assert_eq!(Session::from_toml("").unwrap(), Session::default());
+        assert!(Session::from_toml("last_rom = 5").is_err());
+        assert!(Session::from_toml("[games.TETRIS]\npalette = [\"#FFFFFF\"]").is_err());
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let path = std::env::temp_dir().join("gameboy-sessiontest-missing.toml");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Session::load(&path).unwrap(), Session::default());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("gameboy-sessiontest.toml");
+        let session = played();
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path).unwrap(), session);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn game_settings_on_top_of_config() {
+        let session = played();
+        let config = EmuConfig::default();
+        let applied = session.game("TETRIS 16BF").apply(&config).unwrap();
+        assert_eq!(applied.palette[0], (0xE0, 0xF8, 0xD0));
+        assert_eq!(applied.keys.a, vec![String::from("J")]);
+        // Everything else comes from config
+        assert_eq!(applied.keys.b, config.keys.b);
+        assert_eq!(applied.scale, config.scale);
+        // Game played for the first time changes nothing
+        assert_eq!(session.game("OTHER 0000").apply(&config).unwrap(), config);
+    }
+
+    #[test]
+    fn unknown_action() {
+        let mut game = GameSettings::default();
+        game.keys.insert(String::from("jump"), vec![String::from("J")]);
+        assert!(game.apply(&EmuConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "sgb")]
+extern crate gameboy;
+
+#[cfg(test)]
+mod sgbtest {
+    use gameboy::*;
+
+    fn gen() -> State<mbc::MBC1> {
+        let mut state = State::new(mbc::MBC1::new(vec![0; 1 << 21]));
+        state.sgb.enable(true);
+        state
+    }
+
+    /* Sends packet bit by bit, the way carts do it */
+    fn send(state: &mut State<mbc::MBC1>, packet: &[u8; 16]) {
+        state.safe_write(ioregs::P1, 0x00);
+        state.safe_write(ioregs::P1, 0x30);
+        for byte in packet.iter() {
+            for bit in 0..8 {
+                let value = if byte & (1 << bit) != 0 { 0x10 } else { 0x20 };
+                state.safe_write(ioregs::P1, value);
+                state.safe_write(ioregs::P1, 0x30);
+            }
+        }
+        // Stop bit
+        state.safe_write(ioregs::P1, 0x20);
+        state.safe_write(ioregs::P1, 0x30);
+    }
+
+    #[test]
+    fn pal01() {
+        let mut state = gen();
+        let mut packet = [0; 16];
+        packet[0] = 0x01; // PAL01, 1 packet
+        // Color 0 - pure red
+        packet[1] = 0x1F;
+        // Palette 0 color 1 - pure green
+        packet[3] = 0xE0;
+        packet[4] = 0x03;
+        // Palette 1 color 3 - pure blue
+        packet[13] = 0x00;
+        packet[14] = 0x7C;
+        send(&mut state, &packet);
+
+        assert_eq!(state.sgb.palette(0)[0], (255, 0, 0));
+        assert_eq!(state.sgb.palette(0)[1], (0, 255, 0));
+        assert_eq!(state.sgb.palette(1)[0], (255, 0, 0));
+        assert_eq!(state.sgb.palette(1)[3], (0, 0, 255));
+        // Color 0 is shared
+        assert_eq!(state.sgb.palette(3)[0], (255, 0, 0));
+    }
+
+    #[test]
+    fn ignored_when_disabled() {
+        let mut state = gen();
+        state.sgb.enable(false);
+        let mut packet = [0; 16];
+        packet[0] = 0x01;
+        packet[1] = 0x1F;
+        send(&mut state, &packet);
+        assert_eq!(state.sgb.palette(0)[0], WHITE);
+    }
+
+    #[test]
+    fn mlt_req() {
+        let mut state = gen();
+        let mut packet = [0; 16];
+        packet[0] = 0x11 << 3 | 1;
+        packet[1] = 0x01; // 2 players
+        send(&mut state, &packet);
+
+        state.safe_write(ioregs::P1, 0x30);
+        assert_eq!(state.safe_read(ioregs::P1) & 0x0F, 0x0F);
+
+        // Reading buttons and releasing P15 switches controller
+        state.safe_write(ioregs::P1, 0x10);
+        state.safe_write(ioregs::P1, 0x30);
+        assert_eq!(state.safe_read(ioregs::P1) & 0x0F, 0x0E);
+
+        state.safe_write(ioregs::P1, 0x10);
+        state.safe_write(ioregs::P1, 0x30);
+        assert_eq!(state.safe_read(ioregs::P1) & 0x0F, 0x0F);
+    }
+
+    #[test]
+    fn attr_div() {
+        let mut state = gen();
+        let mut packet = [0; 16];
+        packet[0] = 0x06 << 3 | 1;
+        // Horizontal split at row 5: above - 1, line - 2, below - 3
+        packet[1] = 0x40 | (2 << 4) | (1 << 2) | 3;
+        packet[2] = 5;
+        send(&mut state, &packet);
+
+        assert_eq!(state.sgb.attribute(0, 0), 1);
+        assert_eq!(state.sgb.attribute(19, 5), 2);
+        assert_eq!(state.sgb.attribute(10, 17), 3);
+    }
+
+    #[test]
+    fn screen_colorized() {
+        let mut state = gen();
+        let mut packet = [0; 16];
+        packet[0] = 0x01;
+        packet[1] = 0x1F;
+        send(&mut state, &packet);
+
+        let screen = vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT];
+        state.sgb.render(&screen);
+        // Screen is centered, border is empty so backdrop shows everywhere
+        assert_eq!(state.sgb.framebuff.len(), SGB_WIDTH * SGB_HEIGHT);
+        assert_eq!(state.sgb.framebuff[40 * SGB_WIDTH + 48], (255, 0, 0));
+        assert_eq!(state.sgb.framebuff[0], (255, 0, 0));
+    }
+
+    #[test]
+    fn mask_en() {
+        let mut state = gen();
+        let mut packet = [0; 16];
+        packet[0] = 0x17 << 3 | 1;
+        packet[1] = 2;
+        send(&mut state, &packet);
+        assert_eq!(state.sgb.mask(), SGBMask::Black);
+
+        let screen = vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT];
+        state.sgb.render(&screen);
+        assert_eq!(state.sgb.framebuff[40 * SGB_WIDTH + 48], BLACK);
+    }
+}
@@ -0,0 +1,338 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod statetest {
+    use super::common::program_runtime;
+    use gameboy::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn gen() -> Runtime<mbc::MBC1> {
+        let mut rom = vec![0; 1 << 21];
+        rom[0x149] = 0x03; // 32KB RAM
+        Runtime::new(mbc::MBC1::new(rom))
+    }
+
+    #[test]
+    fn speed_scales_frame() {
+        let mut runtime = gen();
+        assert_eq!(runtime.cycles_per_frame(), CPU_CYCLES_PER_FRAME);
+
+        runtime.set_speed(2.0);
+        assert_eq!(runtime.cycles_per_frame(), 2 * CPU_CYCLES_PER_FRAME);
+
+        runtime.set_speed(0.5);
+        assert_eq!(runtime.cycles_per_frame(), CPU_CYCLES_PER_FRAME / 2);
+    }
+
+    #[test]
+    fn speed_clamped() {
+        let mut runtime = gen();
+        runtime.set_speed(100.0);
+        assert_eq!(runtime.speed(), MAX_SPEED);
+        runtime.set_speed(0.0);
+        assert_eq!(runtime.speed(), MIN_SPEED);
+        runtime.set_speed(f32::NAN);
+        assert_eq!(runtime.speed(), 1.0);
+    }
+
+    #[test]
+    fn pause() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_paused(true);
+        assert_eq!(runtime.cycles_per_frame(), 0);
+        runtime.step();
+        assert_eq!(runtime.cpu_cycles(), 0);
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        assert_eq!(runtime.run_until_break(1000), None);
+        assert_eq!(runtime.cpu_cycles(), 0);
+
+        // Reset doesn't unpause
+        runtime.reset(false);
+        assert!(runtime.paused());
+
+        runtime.set_paused(false);
+        runtime.step();
+        assert!(runtime.cpu_cycles() > 0);
+        assert_eq!(runtime.cycles_per_frame(), CPU_CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn run_cycles() {
+        let mut rom = vec![0; 1 << 21];
+        // JP 0x100, 4 machine cycles each
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x00, 0x01]);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        assert_eq!(runtime.run_cycles(8), 8);
+        // Instruction started before the end is finished
+        assert_eq!(runtime.run_cycles(10), 12);
+        assert_eq!(runtime.run_cycles(0), 0);
+        assert_eq!(runtime.cpu_cycles(), 20);
+
+        runtime.set_paused(true);
+        assert_eq!(runtime.run_cycles(100), 0);
+    }
+
+    #[test]
+    fn interrupt_helpers() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.state.safe_write(ioregs::IF, 0x00);
+        runtime.state.request_interrupt(Interrupt::Timer);
+        assert_eq!(runtime.state.safe_read(ioregs::IF) & 0x1F, Interrupt::Timer.mask());
+        assert_eq!(Interrupt::Timer.bit(), 2);
+
+        assert!(!runtime.state.interrupt_enabled(Interrupt::Timer));
+        runtime.state.safe_write(ioregs::IE, Interrupt::Timer.mask() | Interrupt::Joypad.mask());
+        assert!(runtime.state.interrupt_enabled(Interrupt::Timer));
+        assert!(runtime.state.interrupt_enabled(Interrupt::Joypad));
+        assert!(!runtime.state.interrupt_enabled(Interrupt::VBlank));
+
+        // CPU takes it like one raised by timer itself, and runs first instruction of the handler, NOP
+        runtime.cpu.IME = true;
+        runtime.step();
+        assert_eq!(runtime.cpu.PC.val(), Interrupt::Timer.vector() + 1);
+        assert_eq!(runtime.state.safe_read(ioregs::IF) & 0x1F, 0);
+    }
+
+    /* Runs a while, leaving something in WRAM and cart RAM */
+    fn dirty(runtime: &mut Runtime<mbc::MBC1>) {
+        for _ in 0..1000 {
+            runtime.step();
+        }
+        runtime.state.safe_write(0x1000, 0x0A);
+        runtime.state.safe_write(0xA000, 0x42);
+        runtime.state.safe_write(0xC000, 0x24);
+    }
+
+    #[test]
+    fn soft_reset() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::MGB);
+        dirty(&mut runtime);
+        runtime.reset(false);
+
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        assert_eq!(runtime.cpu.A, 0xFF);
+        assert_eq!(runtime.cpu_cycles(), 0);
+        assert!(!runtime.state.mmu.bootrom_mapped());
+        assert_eq!(runtime.state.mmu.read(0xC000), 0x24);
+        assert_eq!(runtime.state.mmu.mapper.ram[0], 0x42);
+        // MBC registers are reset, so cart RAM is disabled again
+        assert!(!runtime.state.mmu.mapper.ram_enabled);
+    }
+
+    #[test]
+    fn soft_reset_after_bootrom() {
+        let mut runtime = gen();
+        runtime.set_model(HardwareModel::DMG);
+        dirty(&mut runtime);
+        runtime.reset(false);
+
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        assert_eq!(runtime.cpu.A, 0x01);
+        assert!(!runtime.state.mmu.bootrom_mapped());
+    }
+
+    #[test]
+    fn hard_reset_reruns_bootrom() {
+        let mut runtime = gen();
+        dirty(&mut runtime);
+        runtime.reset(true);
+
+        assert_eq!(runtime.cpu.PC.val(), 0x0000);
+        assert!(runtime.state.mmu.bootrom_mapped());
+        assert_eq!(runtime.state.mmu.read(0xC000), 0x00);
+        assert_eq!(runtime.state.mmu.mapper.ram[0], 0x42);
+    }
+
+    #[test]
+    fn hard_reset_skips_bootrom_again() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        dirty(&mut runtime);
+        runtime.reset(true);
+
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        assert_eq!(runtime.cpu.SP, 0xFFFE);
+        assert!(!runtime.state.mmu.bootrom_mapped());
+        assert_eq!(runtime.state.mmu.read(0xC000), 0x00);
+    }
+
+    #[test]
+    fn reset_keeps_settings() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_speed(2.0);
+        runtime.state.apu.set_sample_rate(22050);
+        runtime.state.apu.set_muted(3, true);
+        runtime.state.apu.set_solo(Some(1));
+        runtime.state.gpu.quirks.oam_bug = true;
+        runtime.state.gpu.quirks.ly_write_resets = true;
+        let log = AccessLog::new(16);
+        runtime.state.mmu.subscribe(0xC000..=0xC000, None, log.recorder());
+        runtime.state.serial.connect(Box::new(LocalLink::pair().0));
+
+        for hard in [false, true] {
+            runtime.reset(hard);
+            assert_eq!(runtime.speed(), 2.0);
+            assert_eq!(runtime.model(), HardwareModel::DMG);
+            assert_eq!(runtime.state.apu.sample_rate(), 22050);
+            assert!(runtime.state.apu.muted(3));
+            assert_eq!(runtime.state.apu.solo(), Some(1));
+            assert!(runtime.state.gpu.quirks.oam_bug);
+            assert!(runtime.state.gpu.quirks.ly_write_resets);
+            assert!(runtime.state.serial.connected());
+        }
+        runtime.state.safe_write(0xC000, 0x01);
+        assert_eq!(log.entries().len(), 1);
+    }
+
+    #[test]
+    fn load_rom() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.set_speed(4.0);
+        runtime.state.serial.connect(Box::new(LocalLink::pair().0));
+        dirty(&mut runtime);
+
+        let mut rom = vec![0; 1 << 15];
+        rom[0x150] = 0x3C;
+        rom[HEADER_CHECKSUM] = 0x00;
+        runtime.load_rom(rom).unwrap();
+
+        assert_eq!(runtime.state.mmu.read(0x150), 0x3C);
+        // Sized for the new cart, which has no RAM
+        assert_eq!(runtime.state.mmu.mapper.rom.len(), 1 << 15);
+        // Cart RAM belongs to old cart, it's gone with it
+        assert!(runtime.state.mmu.mapper.ram.is_empty());
+        assert_eq!(runtime.state.mmu.read(0xC000), 0x00);
+        assert_eq!(runtime.cpu.PC.val(), 0x100);
+        // H and C depend on checksum of new header
+        assert_eq!(runtime.cpu.F(), 0x80);
+        assert_eq!(runtime.speed(), 4.0);
+        assert!(runtime.state.serial.connected());
+    }
+
+    fn run_frames<T: BankController>(runtime: &mut Runtime<T>, frames: u64) {
+        while runtime.cpu_cycles() < frames * CPU_CYCLES_PER_FRAME {
+            runtime.step();
+        }
+    }
+
+    #[test]
+    fn frame_callbacks() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let vblanks = Arc::new(Mutex::new(Vec::new()));
+        let frames = Arc::new(AtomicUsize::new(0));
+        let seen = vblanks.clone();
+        runtime.on_vblank(Box::new(move |state| seen.lock().unwrap().push(state.mmu.read(ioregs::LY))));
+        let counter = frames.clone();
+        let id = runtime.on_frame_complete(Box::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        run_frames(&mut runtime, 3);
+        assert_eq!(*vblanks.lock().unwrap(), vec![144, 144, 144]);
+        assert_eq!(frames.load(Ordering::SeqCst), 3);
+
+        assert!(runtime.remove_callback(id));
+        assert!(!runtime.remove_callback(id));
+        // Reset keeps callbacks
+        runtime.reset(true);
+        run_frames(&mut runtime, 2);
+        assert_eq!(vblanks.lock().unwrap().len(), 5);
+        assert_eq!(frames.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn scanline_callbacks() {
+        // Raster effect: SCX follows LY
+        let mut rom = vec![0; 1 << 21];
+        rom[0x100..0x106].copy_from_slice(&[0xF0, 0x44, 0xE0, 0x43, 0x18, 0xFA]);
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let seen = lines.clone();
+        let id = runtime.on_scanline(Box::new(move |line, pixels| {
+            assert_eq!(pixels.len(), SCREEN_WIDTH);
+            seen.lock().unwrap().push(*line);
+        }));
+
+        run_frames(&mut runtime, 2);
+        let lines = std::mem::take(&mut *lines.lock().unwrap());
+        // Emulation doesn't start at the top of the screen
+        let top = lines.iter().position(|line| line.ly == 0).unwrap();
+        let frame = &lines[top..top + SCREEN_HEIGHT];
+        assert!(frame.iter().enumerate().all(|(ly, line)| line.ly as usize == ly));
+        assert!(frame.windows(2).all(|pair| pair[1].scx > pair[0].scx));
+        assert!(frame.iter().all(|line| line.scx.abs_diff(line.ly) <= 1));
+
+        assert!(runtime.remove_callback(id));
+    }
+
+    #[test]
+    fn frame_bus_stats() {
+        // JR -2 fetches 2 bytes every 3 cycles, devices polling IO registers aren't counted
+        let mut runtime = program_runtime(&[0x18, 0xFE], &[]);
+        assert_eq!(runtime.frame_bus_stats(), BusStats::default());
+        run_frames(&mut runtime, 2);
+        let frame = runtime.frame_bus_stats();
+        assert!((CPU_CYCLES_PER_FRAME / 2..CPU_CYCLES_PER_FRAME).contains(&frame.reads(Region::Rom)), "{}", frame);
+        assert_eq!(frame.total_reads(), frame.reads(Region::Rom));
+        assert_eq!(frame.total_writes(), 0);
+        assert!(runtime.bus_stats().total_reads() > frame.total_reads());
+
+        // Report only logs
+        runtime.set_bus_report(Some(1));
+        run_frames(&mut runtime, 3);
+        assert!(runtime.frame_bus_stats().reads(Region::Rom).abs_diff(frame.reads(Region::Rom)) <= 3);
+    }
+
+    #[test]
+    fn rumble_callbacks() {
+        let mut rom = vec![0; 1 << 15];
+        rom[0x147] = 0x1C; // MBC5+RUMBLE
+        rom[0x100..0x10E].copy_from_slice(&[
+            0x3E, 0x08, // LD A, 0x08
+            0xEA, 0x00, 0x40, // LD (0x4000), A
+            0xAF, // XOR A
+            0xEA, 0x00, 0x40, // LD (0x4000), A
+            0x3E, 0x08, // LD A, 0x08
+            0xEA, 0x00, 0x40, // LD (0x4000), A
+        ]);
+        rom[0x10E..0x110].copy_from_slice(&[0x18, 0xFE]); // JR -2
+        let mut runtime = Runtime::new(mbc::MBC5::new(rom));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let id = runtime.on_rumble(Box::new(move |on| seen.lock().unwrap().push(on)));
+
+        for _ in 0..10 {
+            runtime.step();
+        }
+        assert_eq!(*events.lock().unwrap(), vec![true, false, true]);
+        // Reset stops the motor
+        runtime.reset(true);
+        assert_eq!(*events.lock().unwrap(), vec![true, false, true, false]);
+
+        assert!(runtime.remove_callback(id));
+    }
+
+    #[test]
+    fn load_rom_too_big() {
+        let mut runtime = gen();
+        runtime.skip_bootrom(HardwareModel::DMG);
+        dirty(&mut runtime);
+        let pc = runtime.cpu.PC.val();
+
+        assert!(runtime.load_rom(vec![0; (1 << 21) + 1]).is_err());
+        assert_eq!(runtime.cpu.PC.val(), pc);
+        assert_eq!(runtime.state.mmu.mapper.ram[0], 0x42);
+    }
+}
@@ -0,0 +1,48 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod summarytest {
+    use gameboy::*;
+    use std::path::Path;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    #[test]
+    fn title_screen() {
+        let summary = summarize::<mbc::MBC1>(ROM.to_vec(), SUMMARY_FRAMES).unwrap();
+        assert_eq!(summary.title(), "TETRIS");
+        assert_eq!(summary.model, HardwareModel::DMG);
+        assert_eq!(summary.screenshot.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert!(summary.screenshot.iter().any(|color| *color != WHITE));
+
+    }
+
+    #[test]
+    fn nothing_drawn_without_frames() {
+        let summary = summarize::<mbc::MBC1>(ROM.to_vec(), 0).unwrap();
+        assert!(summary.screenshot.iter().all(|color| *color == WHITE));
+    }
+
+    #[test]
+    fn screenshot_as_png() {
+        let summary = summarize::<mbc::MBC1>(ROM.to_vec(), 10).unwrap();
+        // Same ROM, same picture
+        let again = summarize::<mbc::MBC1>(ROM.to_vec(), 10).unwrap();
+        assert_eq!(again.screenshot, summary.screenshot);
+        let png = summary.screenshot_png().unwrap();
+        assert_eq!(png[..8], [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    }
+
+    #[test]
+    fn from_file() {
+        let summary = summarize_file::<mbc::MBC1>(Path::new("rom/tetris.gb"), 1).unwrap();
+        assert_eq!(summary.title(), "TETRIS");
+        let missing = summarize_file::<mbc::MBC1>(Path::new("rom/missing.gb"), 1);
+        assert!(missing.err().unwrap().contains("missing.gb"));
+    }
+
+    #[test]
+    fn too_small() {
+        assert!(summarize::<mbc::MBC1>(vec![0; 0x100], 1).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod symbolstest {
+    use gameboy::*;
+
+    const RGBDS: &str = "; File generated by rgblink
+00:0100 EntryPoint
+00:0150 Main
+00:0150 Start
+00:0158 Main.loop
+01:4000 BankedRoutine
+02:4000 OtherBank
+00:C000 wBuffer
+00:FF80 hCounter
+";
+
+    const WLA: &str = "[information]
+name GAME
+
+[labels]
+0000:0150 main
+0001:4010 banked
+
+[definitions]
+00000010 _sizeof_main
+";
+
+    #[test]
+    fn parse_rgbds() {
+        let symbols = Symbols::parse(RGBDS).unwrap();
+        assert_eq!(symbols.len(), 8);
+        assert_eq!(symbols.label(1, 0x0100), Some("EntryPoint"));
+        // First label at address wins, others can still be found by name
+        assert_eq!(symbols.label(1, 0x0150), Some("Main"));
+        assert_eq!(symbols.address("Start"), Some((0, 0x0150)));
+        assert_eq!(symbols.address("Main.loop"), Some((0, 0x0158)));
+        assert_eq!(symbols.label(1, 0xFF80), Some("hCounter"));
+        assert_eq!(symbols.address("Nothing"), None);
+    }
+
+    #[test]
+    fn parse_wla() {
+        let symbols = Symbols::parse(WLA).unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.label(1, 0x0150), Some("main"));
+        assert_eq!(symbols.label(1, 0x4010), Some("banked"));
+        assert_eq!(symbols.address("_sizeof_main"), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Symbols::parse("00:0150").is_err());
+        assert!(Symbols::parse("0150 Main").is_err());
+        let err = Symbols::parse("00:0100 Ok\nZZ:0150 Main").unwrap_err();
+        assert!(err.starts_with("line 2"), "{}", err);
+        assert!(Symbols::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn banks() {
+        let symbols = Symbols::parse(RGBDS).unwrap();
+        assert_eq!(symbols.label(1, 0x4000), Some("BankedRoutine"));
+        assert_eq!(symbols.label(2, 0x4000), Some("OtherBank"));
+        assert_eq!(symbols.label(3, 0x4000), None);
+        // Bank doesn't matter outside switchable ROM
+        assert_eq!(symbols.label(5, 0xC000), Some("wBuffer"));
+    }
+
+    #[test]
+    fn symbolize() {
+        let symbols = Symbols::parse(RGBDS).unwrap();
+        assert_eq!(symbols.symbolize(1, 0x0150), Some(String::from("Main")));
+        assert_eq!(symbols.symbolize(1, 0x0153), Some(String::from("Main+$3")));
+        assert_eq!(symbols.symbolize(1, 0x015A), Some(String::from("Main.loop+$2")));
+        assert_eq!(symbols.symbolize(2, 0x4100), Some(String::from("OtherBank+$100")));
+        // Labels don't reach other memory areas
+        assert_eq!(symbols.symbolize(1, 0x0050), None);
+        assert_eq!(symbols.symbolize(1, 0xD000), Some(String::from("wBuffer+$1000")));
+        assert_eq!(symbols.symbolize(1, 0xE000), None);
+    }
+
+    #[test]
+    fn annotate() {
+        let symbols = Symbols::parse(RGBDS).unwrap();
+        assert_eq!(symbols.annotate("CALL $0150", 1), "CALL Main");
+        assert_eq!(symbols.annotate("JP $4000", 2), "JP OtherBank");
+        assert_eq!(symbols.annotate("LD ($FF80), A", 1), "LD (hCounter), A");
+        assert_eq!(symbols.annotate("LD A, $42", 1), "LD A, $42");
+        assert_eq!(symbols.annotate("JP $0151", 1), "JP $0151");
+    }
+
+    #[test]
+    fn runtime() {
+        // 4 banks, so bank 2 doesn't mirror bank 0
+        let mut rom = vec![0; 1 << 16];
+        rom[0x100..0x103].copy_from_slice(&[0xCD, 0x50, 0x01]); // CALL 0x0150
+        rom[0x150..0x152].copy_from_slice(&[0x18, 0xFE]); // JR -2
+        let mut runtime = Runtime::new(mbc::MBC1::new(rom.clone()));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        runtime.cpu.set_call_trace(true);
+        assert_eq!(runtime.state.symbolize(0x0150), None);
+        runtime.state.symbols = Some(Symbols::parse(RGBDS).unwrap());
+        for _ in 0..5 {
+            runtime.step();
+        }
+        runtime.state.mmu.write(0x2000, 0x01);
+        assert_eq!(runtime.state.symbolize(0x4000), Some(String::from("BankedRoutine")));
+        runtime.state.mmu.write(0x2000, 0x02);
+        assert_eq!(runtime.state.symbolize(0x4000), Some(String::from("OtherBank")));
+        let frame = runtime.cpu.backtrace()[0];
+        assert_eq!(
+            runtime.state.describe_frame(&frame),
+            "CALL 0x0150, returns to 0x0103, SP=0xFFFC (Main, returns to EntryPoint+$3)"
+        );
+
+        // Symbols belong to the cart
+        runtime.load_rom(rom).unwrap();
+        assert!(runtime.state.symbols.is_none());
+    }
+}
@@ -0,0 +1,81 @@
+extern crate gameboy;
+
+#[cfg(test)]
+mod timelinetest {
+    use gameboy::*;
+
+    const ROM: &[u8] = include_bytes!("../rom/tetris.gb");
+
+    fn logged(frames: usize) -> EventLog {
+        let mut runtime = Runtime::new(mbc::MBC1::new(ROM.to_vec()));
+        runtime.skip_bootrom(HardwareModel::DMG);
+        let categories = [EventCategory::Ppu, EventCategory::Interrupt];
+        runtime.set_event_log(Some(EventLog::default().with_categories(&categories)));
+        for _ in 0..frames {
+            run_frame(&mut runtime);
+        }
+        runtime.take_event_log().unwrap()
+    }
+
+    #[test]
+    fn modes() {
+        let timeline = FrameTimeline::last(&logged(30)).unwrap();
+        // Visible lines go through all three modes, VBlank lines are VBlank only
+        for line in [0, 70, SCREEN_HEIGHT - 1].iter() {
+            let modes = (0..SCANLINE_CYCLES as usize).filter_map(|cycle| timeline.mode(*line, cycle));
+            let modes = modes.collect::<Vec<_>>();
+            for mode in [GPUMode::OAM_SEARCH, GPUMode::LCD_TRANSFER, GPUMode::HBLANK].iter() {
+                assert!(modes.contains(mode), "no {:?} on line {}", mode, line);
+            }
+        }
+        assert_eq!(timeline.mode(SCREEN_HEIGHT + 5, 50), Some(GPUMode::VBLANK));
+        assert_eq!(timeline.mode(SCREEN_HEIGHT, 10), Some(GPUMode::VBLANK));
+        assert_eq!(timeline.mode(TIMELINE_LINES, 0), None);
+
+        let vblank = timeline.interrupts().iter().find(|mark| mark.interrupt == Interrupt::VBlank).unwrap();
+        assert_eq!(vblank.line, SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn frames() {
+        let log = logged(10);
+        // Log starts during the first frame, without its OAM search
+        assert!(FrameTimeline::frame(&log, 0).is_none());
+        let first = FrameTimeline::frame(&log, 1).unwrap();
+        let second = FrameTimeline::frame(&log, 2).unwrap();
+        assert!(second.start() > first.start());
+        assert!(FrameTimeline::frame(&log, 100).is_none());
+        // Next frame has to have started, for the last one to be complete
+        let last = FrameTimeline::last(&log).unwrap();
+        assert!(last.start() > second.start() && last.start() < log.events().last().unwrap().cycle);
+
+        assert!(FrameTimeline::last(&EventLog::default()).is_none());
+    }
+
+    #[test]
+    fn text() {
+        let text = FrameTimeline::last(&logged(30)).unwrap().to_text();
+        let lines = text.lines().collect::<Vec<_>>();
+        // Header, lines, legend
+        assert_eq!(lines.len(), TIMELINE_LINES + 2);
+        assert!(lines[1].starts_with("  0 |"));
+        assert!(lines[1].contains('O') && lines[1].contains('D') && lines[1].contains('H'));
+        let vblank = lines[1 + SCREEN_HEIGHT];
+        assert!(vblank.contains("VBlank@"));
+        assert!(vblank.contains('0') && vblank.contains('V'));
+    }
+
+    #[test]
+    fn save() {
+        let timeline = FrameTimeline::last(&logged(10)).unwrap();
+        let png = std::env::temp_dir().join("gameboy-timelinetest.png");
+        timeline.save(&png).unwrap();
+        assert_eq!(std::fs::read(&png).unwrap(), timeline.to_png().unwrap());
+        std::fs::remove_file(&png).unwrap();
+
+        let txt = std::env::temp_dir().join("gameboy-timelinetest.txt");
+        timeline.save(&txt).unwrap();
+        assert_eq!(std::fs::read_to_string(&txt).unwrap(), timeline.to_text());
+        std::fs::remove_file(&txt).unwrap();
+    }
+}
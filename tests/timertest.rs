@@ -41,12 +41,14 @@ mod timertest {
 
         let steps = [timer::STEPS_4096HZ, timer::STEPS_16384HZ, timer::STEPS_65536HZ, timer::STEPS_262144HZ];
         let modes = [TimerMode::FQ_4096HZ, TimerMode::FQ_16384HZ, TimerMode::FQ_65536HZ, TimerMode::FQ_262144HZ];
-        let masks = [0, 3, 2, 1];
+        let masks = [0b100, 0b111, 0b110, 0b101];
 
         for ((steps, mode), mask) in steps.into_iter().zip(modes.into_iter()).zip(masks.into_iter()) {
             state.safe_write(ioregs::TAC, *mask);
             assert_eq!(Timer::ENABLED(&mut state.mmu), true);
             assert_eq!(Timer::MODE(&mut state.mmu), *mode);
+            // Start each mode with fresh system counter
+            state.safe_write(ioregs::DIV, 0);
 
             state.safe_write(ioregs::TIMA, 0);
             assert_eq!(Timer::TIMA(&mut state.mmu), 0);
@@ -56,13 +58,16 @@ mod timertest {
             state.safe_write(ioregs::TMA, tma);
             assert_eq!(Timer::TMA(&mut state.mmu), tma);
 
+            // After overflow TIMA reads 0 for one cycle, then it's reloaded with TMA
+            let mut reloading = false;
             for _ in 0..700 {
-                let overflow = Timer::TIMA(&mut state.mmu) == 0xFF;
-                for _ in 0..*steps {
-                    assert_eq!(Timer::TIMA(&mut state.mmu), count);
+                for i in 0..*steps {
+                    let expected = if reloading && i == 0 { 0 } else { count };
+                    assert_eq!(Timer::TIMA(&mut state.mmu), expected);
                     state.timer.step(&mut state.mmu);
                 }
-                count = if overflow { tma } else { count+1 };
+                reloading = count == 0xFF;
+                count = if reloading { tma } else { count+1 };
             }
         } 
     }
@@ -110,7 +115,7 @@ mod timertest {
         assert_eq!(Timer::TIMA(&mut state.mmu), 21);
         assert_eq!(state.safe_read(ioregs::TIMA), 21);
 
-        // IMPORTATNT: If internal clocks wouldn't be reset the time value would go to 23
+        // Writing TIMA doesn't touch system counter, so next increment comes on schedule
         for _ in 0..timer::STEPS_4096HZ { state.timer.step(&mut state.mmu); }
 
         assert_eq!(Timer::TIMA(&mut state.mmu), 22);
@@ -133,10 +138,97 @@ mod timertest {
         assert_eq!(Timer::DIV(&mut state.mmu), 0);
         assert_eq!(state.safe_read(ioregs::DIV), 0);
 
-        // IMPORTATNT: If internal clocks wouldn't be reset the time value would go to 23
+        // IMPORTATNT: If internal clocks wouldn't be reset the time value would go to 5
         for _ in 0..timer::STEPS_16384HZ { state.timer.step(&mut state.mmu); }
 
-        assert_eq!(Timer::TIMA(&mut state.mmu), 1);
-        assert_eq!(state.safe_read(ioregs::TIMA), 1);
+        assert_eq!(Timer::DIV(&mut state.mmu), 1);
+        assert_eq!(state.safe_read(ioregs::DIV), 1);
    }
+
+    fn step(state: &mut State<mbc::MBC1>, n: u64) {
+        for _ in 0..n { state.timer.step(&mut state.mmu); }
+    }
+
+    // Counter bit selected by TAC is high, so dropping it to 0 by DIV write is a falling edge
+    #[test]
+    fn div_write_increments_tima() {
+        let mut state = gen_state();
+        state.safe_write(ioregs::TAC, 0b100);
+        step(&mut state, timer::STEPS_4096HZ / 2);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0);
+
+        state.safe_write(ioregs::DIV, 0);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 1);
+        assert_eq!(state.timer.counter(), 0);
+
+        // With selected bit low nothing happens
+        step(&mut state, 1);
+        state.safe_write(ioregs::DIV, 0);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 1);
+    }
+
+    #[test]
+    fn tac_write_increments_tima() {
+        let mut state = gen_state();
+        state.safe_write(ioregs::TAC, 0b101);
+        step(&mut state, timer::STEPS_262144HZ / 2);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0);
+
+        // Disabling timer while signal is high
+        state.safe_write(ioregs::TAC, 0b001);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 1);
+
+        // Switching to mode which selects bit that's low
+        state.safe_write(ioregs::TAC, 0b101);
+        state.safe_write(ioregs::TAC, 0b100);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 2);
+    }
+
+    fn overflow_state() -> State<mbc::MBC1> {
+        let mut state = gen_state();
+        state.safe_write(ioregs::TMA, 0x42);
+        state.safe_write(ioregs::TAC, 0b101);
+        state.safe_write(ioregs::TIMA, 0xFF);
+        step(&mut state, timer::STEPS_262144HZ);
+        state
+    }
+
+    #[test]
+    fn overflow_reload_delay() {
+        let mut state = overflow_state();
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0);
+        assert!(!state.mmu.read_bit(ioregs::IF, 2));
+
+        step(&mut state, 1);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0x42);
+        assert!(state.mmu.read_bit(ioregs::IF, 2));
+    }
+
+    #[test]
+    fn write_cancels_reload() {
+        let mut state = overflow_state();
+        state.safe_write(ioregs::TIMA, 0x10);
+        step(&mut state, 1);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0x10);
+        assert!(!state.mmu.read_bit(ioregs::IF, 2));
+    }
+
+    #[test]
+    fn write_during_reload() {
+        let mut state = overflow_state();
+        step(&mut state, 1);
+
+        // TIMA writes are ignored in reload cycle
+        state.safe_write(ioregs::TIMA, 0x10);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0x42);
+
+        // TMA writes go to TIMA as well
+        state.safe_write(ioregs::TMA, 0x33);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0x33);
+
+        // Afterwards TIMA is writable again
+        step(&mut state, 1);
+        state.safe_write(ioregs::TIMA, 0x10);
+        assert_eq!(Timer::TIMA(&mut state.mmu), 0x10);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+extern crate gameboy;
+
+mod common;
+
+#[cfg(test)]
+mod watchdogtest {
+    use super::common::program_runtime;
+    use gameboy::*;
+
+    /* Cart without MBC, with program at 0x100 */
+    fn gen(program: &[u8], watchdog: Watchdog) -> Runtime<mbc::RomOnly> {
+        let mut runtime = program_runtime(program, &[]);
+        runtime.state.watchdog = Some(watchdog);
+        runtime
+    }
+
+    fn fired(runtime: &Runtime<mbc::RomOnly>) -> Option<Runaway> {
+        runtime.state.watchdog.as_ref().unwrap().fired()
+    }
+
+    #[test]
+    fn stuck_aborts() {
+        // DI; JR -2
+        let mut runtime = gen(&[0xF3, 0x18, 0xFE], Watchdog::new(None, Some(100), WatchdogAction::Abort));
+        for _ in 0..200 {
+            runtime.step();
+        }
+        assert_eq!(fired(&runtime), Some(Runaway::Stuck { pc: 0x101, instructions: 100 }));
+        assert!(runtime.stopped());
+        assert!(!runtime.paused());
+        assert_eq!(runtime.cycles_per_frame(), 0);
+
+        let cycles = runtime.cpu_cycles();
+        runtime.step();
+        assert_eq!(runtime.cpu_cycles(), cycles);
+
+        runtime.reset(true);
+        assert_eq!(fired(&runtime), None);
+        assert!(!runtime.stopped());
+    }
+
+    #[test]
+    fn interrupts_enabled_not_stuck() {
+        // EI; JR -2
+        let watchdog = Watchdog::new(Some(CPU_CYCLES_PER_FRAME * 2), Some(100), WatchdogAction::Abort);
+        let mut runtime = gen(&[0xFB, 0x18, 0xFE], watchdog);
+        for _ in 0..3 {
+            run_frame(&mut runtime);
+        }
+        assert_eq!(fired(&runtime), None);
+    }
+
+    #[test]
+    fn halt_not_stuck() {
+        // DI; HALT; JR -3
+        let mut runtime = gen(&[0xF3, 0x76, 0x18, 0xFD], Watchdog::new(None, Some(100), WatchdogAction::Abort));
+        runtime.state.safe_write(ioregs::IE, 0x01);
+        run_frame(&mut runtime);
+        run_frame(&mut runtime);
+        assert_eq!(fired(&runtime), None);
+    }
+
+    #[test]
+    fn no_vblank_reported() {
+        // EI; LD A, 0; LDH (LCDC), A; JR -2
+        let program = [0xFB, 0x3E, 0x00, 0xE0, 0x40, 0x18, 0xFE];
+        let mut runtime = gen(&program, Watchdog::new(Some(CPU_CYCLES_PER_FRAME * 2), None, WatchdogAction::Report));
+        for _ in 0..4 {
+            run_frame(&mut runtime);
+        }
+        match fired(&runtime) {
+            Some(Runaway::NoVblank { cycles }) => assert!(cycles > CPU_CYCLES_PER_FRAME * 2),
+            other => panic!("expected NoVblank, got {:?}", other),
+        }
+        // Only reported, emulation goes on
+        assert!(!runtime.stopped());
+        let cycles = runtime.cpu_cycles();
+        runtime.step();
+        assert!(runtime.cpu_cycles() > cycles);
+    }
+}